@@ -0,0 +1,42 @@
+/**
+ * sf2 파일을 읽어서 다시 저장했을 때 원래 파일과 같은지 확인함
+ * 사용법: sf2-roundtrip <입력.sf2> [출력.sf2]
+ * 모르는 INFO 청크, 홀수 크기 청크 등은 저장할 때 빠지거나 바뀌므로 완전히 같지 않을 수 있음
+ */
+
+use std::fs::File;
+use std::io::{ BufReader, Cursor };
+use whitesynth::soundbank::sf2::SF2;
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("Usage: {} <input.sf2> [output.sf2]", args[0]);
+    }
+    let original = std::fs::read(&args[1])?;
+    let sf2 = SF2::new(&mut BufReader::new(File::open(&args[1])?))?;
+
+    let mut stream = Cursor::new(vec![]);
+    sf2.write(&mut stream)?;
+    let written = stream.into_inner();
+    if args.len() >= 3 {
+        std::fs::write(&args[2], &written)?;
+    }
+
+    // 저장한 파일을 다시 읽어서 구조가 같은지 확인
+    let reread = SF2::new(&mut Cursor::new(&written))?;
+    println!(
+        "presets: {} -> {}, instruments: {} -> {}, samples: {} -> {}",
+        sf2.presets.len(), reread.presets.len(),
+        sf2.instruments.len(), reread.instruments.len(),
+        sf2.sample_headers.len(), reread.sample_headers.len()
+    );
+
+    if original == written {
+        println!("identical ({} bytes)", written.len());
+        return Ok(());
+    }
+    let first_diff = original.iter().zip(written.iter()).position(|(a, b)| a != b).unwrap_or(original.len().min(written.len()));
+    println!("different: {} bytes -> {} bytes, first difference at 0x{:x}", original.len(), written.len(), first_diff);
+    return Ok(());
+}
@@ -0,0 +1,214 @@
+/**
+ * 오디오 장치 출력(cpal 사용)
+ * 장치의 오디오 스레드가 콜백을 부름. 장치가 요구하는 샘플 형식과 채널 수에 맞춰서 바꿔 넣음
+ */
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use anyhow::Context;
+use cpal::{ FromSample, SizedSample, StreamInstant };
+use cpal::traits::{ DeviceTrait, HostTrait, StreamTrait };
+use super::{ AudioConfig, AudioSink, RenderCallback, SinkState };
+
+// 출력 장치 이름 목록
+pub fn output_device_names() -> anyhow::Result<Vec<String>> {
+    let host = cpal::default_host();
+    let mut names = vec![];
+    for device in host.output_devices()? {
+        names.push(device.name()?);
+    }
+    return Ok(names);
+}
+
+pub struct DeviceSink {
+    // 없으면 기본 장치
+    device_name: Option<String>,
+    state: Arc<SinkState>,
+    stream: Option<cpal::Stream>
+}
+
+impl Default for DeviceSink {
+    fn default() -> Self {
+        return Self {
+            device_name: None,
+            state: Arc::new(SinkState::default()),
+            stream: None
+        };
+    }
+}
+
+impl DeviceSink {
+    pub fn new() -> Self {
+        return Default::default();
+    }
+
+    // 이름에 name이 들어있는 장치를 씀(대소문자 구분 안 함)
+    pub fn with_device_name(name: &str) -> Self {
+        let mut sink = Self::new();
+        sink.device_name = Some(name.to_owned());
+        return sink;
+    }
+
+    fn find_device(&self) -> anyhow::Result<cpal::Device> {
+        let host = cpal::default_host();
+        let Some(name) = &self.device_name else {
+            return host.default_output_device().ok_or_else(|| anyhow::anyhow!("No audio output device found"));
+        };
+
+        let lower = name.to_lowercase();
+        for device in host.output_devices()? {
+            if device.name()?.to_lowercase().contains(&lower) {
+                return Ok(device);
+            }
+        }
+        anyhow::bail!("Audio output device not found: {}", name);
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f64>>(
+    device: &cpal::Device,
+    stream_config: &cpal::StreamConfig,
+    config: &AudioConfig,
+    state: Arc<SinkState>,
+    mut callback: RenderCallback
+) -> anyhow::Result<cpal::Stream> {
+    let channels = stream_config.channels as usize;
+    let mono = channels == 1;
+    let buffer_size = config.buffer_size;
+    let sample_rate = config.sample_rate as f64;
+    let mut left = vec![0.0; buffer_size];
+    let mut right = vec![0.0; buffer_size];
+    // 앞 콜백에서 넣은 소리가 다 재생되는 시각
+    let mut played_until: Option<StreamInstant> = None;
+
+    let data_state = state.clone();
+    let stream = device.build_output_stream(stream_config, move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+        let frames = data.len() / channels;
+        let timestamp = info.timestamp();
+        // 앞에서 넣은 소리가 다 재생된 뒤에야 불렸으면 끊긴 것임
+        if played_until.is_some_and(|until| timestamp.callback > until) {
+            data_state.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        played_until = timestamp.playback.add(Duration::from_secs_f64(frames as f64 / sample_rate));
+
+        for chunk in data.chunks_mut(buffer_size * channels) {
+            let len = chunk.len() / channels;
+            left[0..len].fill(0.0);
+            right[0..len].fill(0.0);
+            callback(&mut left[0..len], &mut right[0..len]);
+            for (i, frame) in chunk.chunks_mut(channels).enumerate() {
+                for (ch, sample) in frame.iter_mut().enumerate() {
+                    let value = match ch {
+                        // 모노 장치면 왼쪽과 오른쪽을 섞음
+                        0 if mono => (left[i] + right[i]) * 0.5,
+                        0 => left[i],
+                        1 => right[i],
+                        _ => 0.0
+                    };
+                    *sample = T::from_sample(value.max(-1.0).min(1.0));
+                }
+            }
+        }
+        data_state.rendered_frames.fetch_add(frames as u64, Ordering::Relaxed);
+    }, move |err| {
+        log::error!("Audio stream error: {}", err);
+        state.underruns.fetch_add(1, Ordering::Relaxed);
+    }, None)?;
+    return Ok(stream);
+}
+
+// 샘플 형식 우선순위(낮을수록 좋음). 못 쓰는 형식이면 None
+fn format_rank(format: cpal::SampleFormat) -> Option<u32> {
+    return match format {
+        cpal::SampleFormat::F32 => Some(0),
+        cpal::SampleFormat::F64 => Some(1),
+        cpal::SampleFormat::I32 => Some(2),
+        cpal::SampleFormat::I16 => Some(3),
+        cpal::SampleFormat::U16 => Some(4),
+        cpal::SampleFormat::I8 => Some(5),
+        cpal::SampleFormat::U8 => Some(6),
+        _ => None
+    };
+}
+
+// 채널 수 우선순위: 스테레오, 3채널 이상(앞 두 채널만 씀), 모노
+fn channels_rank(channels: u16) -> u32 {
+    return match channels {
+        2 => 0,
+        0 | 1 => 2,
+        _ => 1
+    };
+}
+
+/**
+ * 장치가 지원하는 설정 중에서 sample rate가 맞고 쓸 수 있는 샘플 형식인 것을 고름
+ * 맞는 설정이 없으면 오류
+ */
+fn choose_config(device: &cpal::Device, sample_rate: u32) -> anyhow::Result<cpal::SupportedStreamConfig> {
+    let rate = cpal::SampleRate(sample_rate);
+    let mut best: Option<((u32, u32), cpal::SupportedStreamConfigRange)> = None;
+    for range in device.supported_output_configs().context("Failed to get audio output configs")? {
+        if range.channels() == 0 || rate < range.min_sample_rate() || rate > range.max_sample_rate() {
+            continue;
+        }
+        let Some(format_rank) = format_rank(range.sample_format()) else {
+            continue;
+        };
+        let rank = (channels_rank(range.channels()), format_rank);
+        if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+            best = Some((rank, range));
+        }
+    }
+    let Some((_, range)) = best else {
+        anyhow::bail!("Audio output device does not support {} Hz with a usable sample format", sample_rate);
+    };
+    return Ok(range.with_sample_rate(rate));
+}
+
+impl AudioSink for DeviceSink {
+    fn start(&mut self, config: &AudioConfig, callback: RenderCallback) -> anyhow::Result<()> {
+        self.stop()?;
+
+        let device = self.find_device()?;
+        let supported = choose_config(&device, config.sample_rate)?;
+        let stream_config = cpal::StreamConfig {
+            channels: supported.channels(),
+            sample_rate: supported.sample_rate(),
+            buffer_size: cpal::BufferSize::Fixed(config.buffer_size as u32)
+        };
+        self.state.reset();
+        let state = self.state.clone();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::I8 => build_stream::<i8>(&device, &stream_config, config, state, callback),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, config, state, callback),
+            cpal::SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, config, state, callback),
+            cpal::SampleFormat::U8 => build_stream::<u8>(&device, &stream_config, config, state, callback),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, config, state, callback),
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, config, state, callback),
+            cpal::SampleFormat::F64 => build_stream::<f64>(&device, &stream_config, config, state, callback),
+            format => anyhow::bail!("Unsupported audio sample format: {}", format)
+        }.context("Failed to open audio output")?;
+        stream.play()?;
+        self.stream = Some(stream);
+        return Ok(());
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        self.state.running.store(false, Ordering::Release);
+        self.stream = None;
+        return Ok(());
+    }
+
+    fn is_running(&self) -> bool {
+        return self.stream.is_some();
+    }
+
+    fn underruns(&self) -> u64 {
+        return self.state.underruns.load(Ordering::Relaxed);
+    }
+
+    fn rendered_frames(&self) -> u64 {
+        return self.state.rendered_frames.load(Ordering::Relaxed);
+    }
+}
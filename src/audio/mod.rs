@@ -0,0 +1,182 @@
+/**
+ * 오디오 출력
+ *
+ * AudioSink는 정해진 버퍼 크기마다 렌더링 콜백을 불러서 나온 소리를 어딘가로 내보냄
+ * - NullSink: 소리를 버림(장치 없이 시험할 때)
+ * - WavSink: wav 파일로 씀
+ * - DeviceSink: 오디오 장치로 내보냄(cpal 기능을 켰을 때만 있음)
+ *
+ * synth는 synth_render_callback으로 콜백에 넣고, 미디 메세지는 MidiMessageSender로 보내면 됨
+ */
+
+pub mod null;
+pub mod wav;
+#[cfg(feature = "cpal")]
+pub mod device;
+
+use std::sync::atomic::{ AtomicBool, AtomicU64, Ordering };
+use std::time::{ Duration, Instant };
+use crate::synth::Synth;
+
+pub use null::NullSink;
+pub use wav::WavSink;
+#[cfg(feature = "cpal")]
+pub use device::DeviceSink;
+
+/**
+ * 렌더링 콜백
+ * 왼쪽, 오른쪽 버퍼를 채워야 함. 길이는 보통 buffer_size지만 더 짧을 수도 있음
+ * 오디오 스레드에서 불리므로 락을 기다리거나 메모리를 할당하지 않는 게 좋음
+ */
+pub type RenderCallback = Box<dyn FnMut(&mut [f64], &mut [f64]) + Send>;
+
+// synth를 렌더링하는 콜백을 만듦
+pub fn synth_render_callback(mut synth: Synth) -> RenderCallback {
+    return Box::new(move |left, right| synth.render(left, right));
+}
+
+/**
+ * 오디오 출력 설정
+ * 지연 시간은 buffer_size * buffer_count 샘플임
+ */
+#[derive(Clone, Debug)]
+pub struct AudioConfig {
+    pub(crate) sample_rate: u32, // 8000 - 192000 (기본값 = 48000)
+
+    // 콜백 한 번에 렌더링하는 샘플 수
+    pub(crate) buffer_size: usize, // 16 - 8192 (기본값 = 256)
+
+    // 미리 렌더링해서 쌓아두는 버퍼 수. 늘리면 끊김이 줄어드는 대신 지연 시간이 늘어남
+    // DeviceSink는 장치 드라이버가 정하므로 이 값은 참고만 함
+    pub(crate) buffer_count: usize // 1 - 16 (기본값 = 2)
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        return Self {
+            sample_rate: 48000,
+            buffer_size: 256,
+            buffer_count: 2
+        };
+    }
+}
+
+impl AudioConfig {
+    pub fn new() -> Self {
+        return Default::default();
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        return self.sample_rate;
+    }
+
+    pub fn set_sample_rate(&mut self, val: u32) {
+        self.sample_rate = val.max(8000).min(192000);
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        return self.buffer_size;
+    }
+
+    pub fn set_buffer_size(&mut self, val: usize) {
+        self.buffer_size = val.max(16).min(8192);
+    }
+
+    pub fn buffer_count(&self) -> usize {
+        return self.buffer_count;
+    }
+
+    pub fn set_buffer_count(&mut self, val: usize) {
+        self.buffer_count = val.max(1).min(16);
+    }
+
+    pub fn latency_frames(&self) -> usize {
+        return self.buffer_size * self.buffer_count;
+    }
+
+    pub fn latency_seconds(&self) -> f64 {
+        return self.latency_frames() as f64 / self.sample_rate as f64;
+    }
+}
+
+/**
+ * 오디오 출력 방식
+ * start로 콜백을 넘기면 stop할 때까지 별도 스레드(또는 장치의 오디오 스레드)에서 계속 불러줌
+ */
+pub trait AudioSink {
+    fn start(&mut self, config: &AudioConfig, callback: RenderCallback) -> anyhow::Result<()>;
+
+    // 콜백을 멈추고 없앰. 출력 중에 생긴 오류가 있으면 돌려줌
+    fn stop(&mut self) -> anyhow::Result<()>;
+
+    fn is_running(&self) -> bool;
+
+    // 렌더링이 늦어서 소리가 끊긴 횟수
+    fn underruns(&self) -> u64;
+
+    // 지금까지 렌더링한 샘플 수
+    fn rendered_frames(&self) -> u64;
+}
+
+// 오디오 스레드와 같이 쓰는 상태
+#[derive(Default)]
+pub(crate) struct SinkState {
+    pub running: AtomicBool,
+    pub underruns: AtomicU64,
+    pub rendered_frames: AtomicU64
+}
+
+impl SinkState {
+    pub fn reset(&self) {
+        self.running.store(true, Ordering::Release);
+        self.underruns.store(0, Ordering::Relaxed);
+        self.rendered_frames.store(0, Ordering::Relaxed);
+    }
+}
+
+/**
+ * 장치 없이 콜백을 계속 부르는 렌더링 루프(NullSink, WavSink에서 씀)
+ * realtime이면 실제 장치처럼 buffer_count개의 버퍼가 쌓일 때까지만 렌더링하고 재생 속도에 맞춰 기다림
+ * 재생 속도를 못 따라가서 쌓인 버퍼가 바닥나면 끊긴 것으로 셈
+ * realtime이 아니면 최대한 빠르게 렌더링함
+ */
+pub(crate) fn run_render_loop<F: FnMut(&[f64], &[f64]) -> anyhow::Result<()>>(
+    config: &AudioConfig,
+    state: &SinkState,
+    callback: &mut RenderCallback,
+    realtime: bool,
+    mut output: F
+) -> anyhow::Result<()> {
+    let mut left = vec![0.0; config.buffer_size];
+    let mut right = vec![0.0; config.buffer_size];
+    let sample_rate = config.sample_rate as f64;
+    let latency_frames = config.latency_frames() as f64;
+
+    // 재생을 시작한 시간과 그때부터 쌓은 샘플 수
+    let mut clock_start = Instant::now();
+    let mut queued_frames = 0.0;
+    while state.running.load(Ordering::Acquire) {
+        if realtime {
+            let played_frames = clock_start.elapsed().as_secs_f64() * sample_rate;
+            if played_frames > queued_frames {
+                if queued_frames > 0.0 {
+                    state.underruns.fetch_add(1, Ordering::Relaxed);
+                }
+                clock_start = Instant::now();
+                queued_frames = 0.0;
+            } else if queued_frames - played_frames >= latency_frames {
+                let wait = (queued_frames - played_frames - latency_frames + config.buffer_size as f64) / sample_rate;
+                std::thread::sleep(Duration::from_secs_f64(wait.min(0.1)));
+                continue;
+            }
+        }
+
+        left.fill(0.0);
+        right.fill(0.0);
+        callback(&mut left, &mut right);
+        output(&left, &right)?;
+        queued_frames += config.buffer_size as f64;
+        state.rendered_frames.fetch_add(config.buffer_size as u64, Ordering::Relaxed);
+    }
+    return Ok(());
+}
@@ -0,0 +1,80 @@
+/**
+ * 소리를 버리는 출력
+ * 오디오 장치가 없는 곳에서 실시간 동작(미디 입력, 끊김 횟수 등)을 시험할 때 씀
+ */
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::thread::JoinHandle;
+use super::{ AudioConfig, AudioSink, RenderCallback, SinkState, run_render_loop };
+
+pub struct NullSink {
+    realtime: bool,
+    state: Arc<SinkState>,
+    thread: Option<JoinHandle<anyhow::Result<()>>>
+}
+
+impl Default for NullSink {
+    fn default() -> Self {
+        return Self {
+            realtime: true,
+            state: Arc::new(SinkState::default()),
+            thread: None
+        };
+    }
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        return Default::default();
+    }
+
+    // false면 재생 속도를 맞추지 않고 최대한 빠르게 렌더링함(성능 측정용)
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+    }
+}
+
+impl AudioSink for NullSink {
+    fn start(&mut self, config: &AudioConfig, mut callback: RenderCallback) -> anyhow::Result<()> {
+        self.stop()?;
+        self.state.reset();
+
+        let config = config.clone();
+        let state = self.state.clone();
+        let realtime = self.realtime;
+        self.thread = Some(std::thread::Builder::new().name("whitesynth-null-sink".into()).spawn(move || {
+            return run_render_loop(&config, &state, &mut callback, realtime, |_, _| Ok(()));
+        })?);
+        return Ok(());
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        self.state.running.store(false, Ordering::Release);
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        return match thread.join() {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("Render thread panicked"))
+        };
+    }
+
+    fn is_running(&self) -> bool {
+        return self.thread.as_ref().is_some_and(|thread| !thread.is_finished());
+    }
+
+    fn underruns(&self) -> u64 {
+        return self.state.underruns.load(Ordering::Relaxed);
+    }
+
+    fn rendered_frames(&self) -> u64 {
+        return self.state.rendered_frames.load(Ordering::Relaxed);
+    }
+}
+
+impl Drop for NullSink {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
@@ -0,0 +1,144 @@
+/**
+ * wav 파일로 쓰는 출력
+ * 기본값은 실시간 속도로 렌더링함(실시간 연주를 녹음할 때). 미디 파일을 빠르게 렌더링하는 용도면 set_realtime(false)
+ * set_max_frames로 길이를 정하면 그만큼 쓰고 알아서 멈춤
+ */
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::thread::JoinHandle;
+use anyhow::Context;
+use super::{ AudioConfig, AudioSink, RenderCallback, SinkState, run_render_loop };
+
+// wav 파일의 샘플 형식
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Float32
+}
+
+pub struct WavSink {
+    path: PathBuf,
+    format: WavFormat,
+    realtime: bool,
+    max_frames: Option<u64>,
+    state: Arc<SinkState>,
+    thread: Option<JoinHandle<anyhow::Result<()>>>
+}
+
+impl WavSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        return Self {
+            path: path.as_ref().to_owned(),
+            format: WavFormat::Int16,
+            realtime: true,
+            max_frames: None,
+            state: Arc::new(SinkState::default()),
+            thread: None
+        };
+    }
+
+    pub fn set_format(&mut self, format: WavFormat) {
+        self.format = format;
+    }
+
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+    }
+
+    // 쓸 샘플 수. None이면 stop할 때까지 씀
+    pub fn set_max_frames(&mut self, frames: Option<u64>) {
+        self.max_frames = frames;
+    }
+}
+
+impl AudioSink for WavSink {
+    fn start(&mut self, config: &AudioConfig, mut callback: RenderCallback) -> anyhow::Result<()> {
+        self.stop()?;
+
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: config.sample_rate,
+            bits_per_sample: match self.format {
+                WavFormat::Int16 => 16,
+                WavFormat::Int24 => 24,
+                WavFormat::Float32 => 32
+            },
+            sample_format: match self.format {
+                WavFormat::Float32 => hound::SampleFormat::Float,
+                _ => hound::SampleFormat::Int
+            }
+        };
+        let file = File::create(&self.path).with_context(|| format!("Failed to create output file: {}", self.path.display()))?;
+        let mut writer = hound::WavWriter::new(BufWriter::new(file), spec)?;
+        self.state.reset();
+
+        let config = config.clone();
+        let state = self.state.clone();
+        let realtime = self.realtime;
+        let format = self.format;
+        let max_frames = self.max_frames;
+        self.thread = Some(std::thread::Builder::new().name("whitesynth-wav-sink".into()).spawn(move || {
+            let mut written = 0;
+            let result = run_render_loop(&config, &state, &mut callback, realtime, |left, right| {
+                let len = match max_frames {
+                    Some(max_frames) => left.len().min((max_frames - written) as usize),
+                    None => left.len()
+                };
+                for i in 0..len {
+                    for sample in [left[i], right[i]] {
+                        let clamped = sample.max(-1.0).min(1.0);
+                        match format {
+                            WavFormat::Int16 => writer.write_sample((clamped * 32767.0).round() as i16)?,
+                            WavFormat::Int24 => writer.write_sample((clamped * 8388607.0).round() as i32)?,
+                            WavFormat::Float32 => writer.write_sample(sample as f32)?
+                        }
+                    }
+                }
+                written += len as u64;
+                if max_frames.is_some_and(|max_frames| written >= max_frames) {
+                    state.running.store(false, Ordering::Release);
+                }
+                return Ok(());
+            });
+            state.running.store(false, Ordering::Release);
+            writer.finalize()?;
+            return result;
+        })?);
+        return Ok(());
+    }
+
+    // 파일을 마무리하고 닫음
+    fn stop(&mut self) -> anyhow::Result<()> {
+        self.state.running.store(false, Ordering::Release);
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        return match thread.join() {
+            Ok(result) => result.with_context(|| format!("Failed to write output file: {}", self.path.display())),
+            Err(_) => Err(anyhow::anyhow!("Render thread panicked"))
+        };
+    }
+
+    fn is_running(&self) -> bool {
+        return self.thread.as_ref().is_some_and(|thread| !thread.is_finished());
+    }
+
+    fn underruns(&self) -> u64 {
+        return self.state.underruns.load(Ordering::Relaxed);
+    }
+
+    fn rendered_frames(&self) -> u64 {
+        return self.state.rendered_frames.load(Ordering::Relaxed);
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
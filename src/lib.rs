@@ -9,5 +9,6 @@
 
 pub mod synth;
 pub mod midi;
+pub mod audio;
 pub mod soundbank;
 pub mod util;
\ No newline at end of file
@@ -1,3 +1,360 @@
-fn main() -> anyhow::Result<()> {
+/**
+ * whitesynth 명령줄 도구
+ * - render: 미디 파일을 wav 파일로 렌더링함(실시간보다 빠르게)
+ * - live: 미디 입력 포트에서 받은 메세지를 실시간으로 연주해서 오디오 장치(또는 wav 파일)로 내보냄
+ */
+
+use std::fs::File;
+use std::io::{ BufRead, BufReader };
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::time::{ Duration, Instant };
+use anyhow::Context;
+use clap::{ Args, Parser, Subcommand, ValueEnum };
+use log::LevelFilter;
+use log4rs::append::console::{ ConsoleAppender, Target };
+use log4rs::config::{ Appender, Config, Root };
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+use whitesynth::midi::smf::MidiFile;
+use whitesynth::midi::player::Player;
+use whitesynth::midi::live::{ input_port_names, LiveMidiInput };
+use whitesynth::audio::{ synth_render_callback, AudioConfig, AudioSink, NullSink, WavSink };
+use whitesynth::audio::wav::WavFormat;
+use whitesynth::soundbank::{ read_soundbank, read_soundbank_streamed, Soundbank };
+use whitesynth::soundbank::stream::{ SampleLoader, StreamOptions };
+
+// render에서 한 번에 렌더링하는 샘플 수
+const RENDER_CHUNK_SIZE: usize = 4096;
+
+#[derive(Parser)]
+#[command(name = "whitesynth", version, about = "whitesynth software synthesizer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Render a MIDI file to a WAV file")]
+    Render(RenderArgs),
+    #[command(about = "Play MIDI input ports in real time")]
+    Live(LiveArgs)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BitDepth {
+    #[value(name = "16")]
+    Int16,
+    #[value(name = "24")]
+    Int24,
+    #[value(name = "32f")]
+    Float32
+}
+
+impl BitDepth {
+    fn wav_format(self) -> WavFormat {
+        return match self {
+            BitDepth::Int16 => WavFormat::Int16,
+            BitDepth::Int24 => WavFormat::Int24,
+            BitDepth::Float32 => WavFormat::Float32
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SinkKind {
+    // 기본 오디오 장치
+    Device,
+    // wav 파일로 녹음
+    Wav,
+    // 소리를 버림(시험용)
+    Null
+}
+
+// 렌더링할 때 쓰는 synth 설정
+#[derive(Args)]
+struct SynthArgs {
+    #[arg(long, default_value_t = 48000, help = "Sample rate (8000 - 192000)")]
+    sample_rate: u32,
+
+    #[arg(long, default_value_t = 384, help = "Maximum number of voices")]
+    polyphony: usize,
+
+    #[arg(long, default_value_t = 1, help = "Number of worker threads for voice rendering")]
+    threads: usize,
+
+    #[arg(long, default_value_t = 1.0, help = "Output gain (linear)")]
+    gain: f64
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    #[arg(help = "Soundbank file (WSBK or SF2)")]
+    soundbank: PathBuf,
+
+    #[arg(help = "MIDI file (.mid, .kar)")]
+    midi: PathBuf,
+
+    #[arg(short, long, help = "Output WAV file [default: MIDI file name with .wav]")]
+    output: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = BitDepth::Int16, help = "Output bit depth")]
+    bit_depth: BitDepth,
+
+    #[arg(long, default_value_t = 2.0, help = "Seconds to keep rendering after the song ends")]
+    tail: f64,
+
+    #[command(flatten)]
+    synth: SynthArgs
+}
+
+// 경고 이상의 로그를 stderr로 출력함
+fn init_logger() -> anyhow::Result<()> {
+    let stderr = ConsoleAppender::builder().target(Target::Stderr).build();
+    let config = Config::builder()
+        .appender(Appender::builder().build("stderr", Box::new(stderr)))
+        .build(Root::builder().appender("stderr").build(LevelFilter::Warn))?;
+    log4rs::init_config(config)?;
+    return Ok(());
+}
+
+#[derive(Args)]
+struct LiveArgs {
+    #[arg(required_unless_present = "list", help = "Soundbank file (WSBK or SF2)")]
+    soundbank: Option<PathBuf>,
+
+    #[arg(long, help = "List MIDI input ports and exit")]
+    list: bool,
+
+    #[arg(
+        short,
+        long = "port",
+        value_name = "PORT[=A|B]",
+        help = "MIDI input port number or name to connect (repeatable). \
+            Ports are mapped to synth ports A, B, A, ... in order unless =A or =B is given [default: all ports]"
+    )]
+    ports: Vec<String>,
+
+    #[arg(long, value_enum, default_value_t = SinkKind::Device, help = "Audio output")]
+    sink: SinkKind,
+
+    #[arg(long, help = "Audio output device name or part of it (with --sink device) [default: system default device]")]
+    device: Option<String>,
+
+    #[arg(short, long, required_if_eq("sink", "wav"), help = "Output WAV file (with --sink wav)")]
+    output: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = BitDepth::Int16, help = "Output bit depth (with --sink wav)")]
+    bit_depth: BitDepth,
+
+    #[arg(long, default_value_t = 256, help = "Audio buffer size in frames (16 - 8192)")]
+    buffer_size: usize,
+
+    #[arg(long, default_value_t = 2, help = "Number of audio buffers to queue (1 - 16)")]
+    buffers: usize,
+
+    #[arg(long, help = "Load sample data from disk on demand instead of loading the whole soundbank into memory")]
+    stream: bool,
+
+    #[arg(long, default_value_t = 16384, help = "Frames of each sample to keep in memory (with --stream)")]
+    preload_frames: usize,
+
+    #[command(flatten)]
+    synth: SynthArgs
+}
+
+fn load_soundbank(path: &Path) -> anyhow::Result<Soundbank> {
+    let file = File::open(path).with_context(|| format!("Failed to open soundbank: {}", path.display()))?;
+    let soundbank = read_soundbank(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to read soundbank: {}", path.display()))?;
+    return check_soundbank(soundbank, path);
+}
+
+// 샘플 데이터를 필요할 때 불러오도록 읽음
+fn load_soundbank_streamed(path: &Path, options: &StreamOptions) -> anyhow::Result<(Soundbank, SampleLoader)> {
+    let loader = SampleLoader::open(path, options)?;
+    let soundbank = read_soundbank_streamed(&loader)
+        .with_context(|| format!("Failed to read soundbank: {}", path.display()))?;
+    return Ok((check_soundbank(soundbank, path)?, loader));
+}
+
+fn check_soundbank(soundbank: Soundbank, path: &Path) -> anyhow::Result<Soundbank> {
+    if soundbank.presets().is_empty() {
+        log::warn!("Soundbank has no presets: {}", path.display());
+    }
+    return Ok(soundbank);
+}
+
+fn create_synth(args: &SynthArgs, soundbank: Soundbank) -> Synth {
+    let mut settings = SynthCreateSettings::new();
+    settings.set_sample_rate(args.sample_rate);
+    settings.set_polyphony(args.polyphony);
+    settings.set_max_worker_threads(args.threads);
+
+    let mut synth = Synth::new(settings);
+    synth.settings_mut().set_output_gain(args.gain);
+    synth.add_soundbank(soundbank);
+    return synth;
+}
+
+fn render(args: &RenderArgs) -> anyhow::Result<()> {
+    let soundbank = load_soundbank(&args.soundbank)?;
+    let file = File::open(&args.midi).with_context(|| format!("Failed to open midi file: {}", args.midi.display()))?;
+    let midi_file = MidiFile::read(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to read midi file: {}", args.midi.display()))?;
+    let output = args.output.clone().unwrap_or_else(|| args.midi.with_extension("wav"));
+
+    let mut player = Player::new(create_synth(&args.synth, soundbank));
+    player.load(&midi_file);
+    // 구간 반복이 있는 곡이라도 한 번만 렌더링함
+    player.set_looping(false);
+    player.play();
+
+    let sample_rate = player.synth().sample_rate();
+    let mut config = AudioConfig::new();
+    config.set_sample_rate(sample_rate);
+    config.set_buffer_size(RENDER_CHUNK_SIZE);
+
+    // 곡이 끝난 다음에도 tail초 동안 남은 소리를 렌더링함
+    let frames = ((player.duration() + args.tail.max(0.0)) * sample_rate as f64).ceil() as u64;
+    let mut sink = WavSink::new(&output);
+    sink.set_format(args.bit_depth.wav_format());
+    sink.set_realtime(false);
+    sink.set_max_frames(Some(frames));
+
+    let started = Instant::now();
+    let clipped = Arc::new(AtomicU64::new(0));
+    let clipped_in_callback = clipped.clone();
+    sink.start(&config, Box::new(move |left, right| {
+        player.render(left, right);
+        let count = left.iter().chain(right.iter()).filter(|sample| sample.abs() > 1.0).count();
+        clipped_in_callback.fetch_add(count as u64, Ordering::Relaxed);
+    }))?;
+    while sink.is_running() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    sink.stop()?;
+
+    let seconds = frames as f64 / sample_rate as f64;
+    let elapsed = started.elapsed().as_secs_f64();
+    println!(
+        "Rendered {:.1}s of audio to {} in {:.1}s ({:.1}x realtime)",
+        seconds, output.display(), elapsed, seconds / elapsed.max(0.001)
+    );
+    let clipped = clipped.load(Ordering::Relaxed);
+    if clipped > 0 {
+        log::warn!("{} samples exceeded full scale (try lowering --gain)", clipped);
+    }
     return Ok(());
-}
\ No newline at end of file
+}
+
+// "이름=A" 형식에서 포트 이름과 synth 포트를 나눔
+fn parse_port_arg(arg: &str, index: usize) -> anyhow::Result<(&str, u8)> {
+    return match arg.rsplit_once('=') {
+        Some((name, port)) => match port.to_ascii_uppercase().as_str() {
+            "A" => Ok((name, 0)),
+            "B" => Ok((name, 1)),
+            _ => anyhow::bail!("Invalid synth port (expected A or B): {}", port)
+        },
+        None => Ok((arg, (index % 2) as u8))
+    };
+}
+
+fn create_sink(args: &LiveArgs) -> anyhow::Result<Box<dyn AudioSink>> {
+    return match args.sink {
+        #[cfg(feature = "cpal")]
+        SinkKind::Device => Ok(Box::new(match &args.device {
+            Some(name) => whitesynth::audio::DeviceSink::with_device_name(name),
+            None => whitesynth::audio::DeviceSink::new()
+        })),
+        #[cfg(not(feature = "cpal"))]
+        SinkKind::Device => anyhow::bail!("Built without audio device support (enable the cpal feature)"),
+        SinkKind::Wav => {
+            let Some(output) = &args.output else {
+                anyhow::bail!("Output WAV file is required");
+            };
+            let mut sink = WavSink::new(output);
+            sink.set_format(args.bit_depth.wav_format());
+            Ok(Box::new(sink))
+        },
+        SinkKind::Null => Ok(Box::new(NullSink::new()))
+    };
+}
+
+fn live(args: &LiveArgs) -> anyhow::Result<()> {
+    let port_names = input_port_names().context("Failed to open midi input")?;
+    if args.list {
+        if port_names.is_empty() {
+            println!("No MIDI input ports found");
+        }
+        for (i, name) in port_names.iter().enumerate() {
+            println!("{}: {}", i, name);
+        }
+        return Ok(());
+    }
+
+    let Some(soundbank_path) = &args.soundbank else {
+        anyhow::bail!("Soundbank file is required");
+    };
+    let (soundbank, loader) = if args.stream {
+        let mut options = StreamOptions::new();
+        options.set_preload_frames(args.preload_frames);
+        let (soundbank, loader) = load_soundbank_streamed(soundbank_path, &options)?;
+        (soundbank, Some(loader))
+    } else {
+        (load_soundbank(soundbank_path)?, None)
+    };
+    let synth = create_synth(&args.synth, soundbank);
+    let sample_rate = synth.sample_rate();
+
+    let mut config = AudioConfig::new();
+    config.set_sample_rate(sample_rate);
+    config.set_buffer_size(args.buffer_size);
+    config.set_buffer_count(args.buffers);
+
+    let mut midi_input = LiveMidiInput::new(synth.midi_message_sender(), sample_rate);
+    midi_input.set_latency_frames(config.buffer_size() as u32);
+    let ports: Vec<String> = if args.ports.is_empty() {
+        (0..port_names.len()).map(|i| i.to_string()).collect()
+    } else {
+        args.ports.clone()
+    };
+    for (i, arg) in ports.iter().enumerate() {
+        let (name, port) = parse_port_arg(arg, i)?;
+        let connected = midi_input.connect(name, port)?;
+        println!("Connected {} to port {}", connected, if port == 0 { "A" } else { "B" });
+    }
+    if midi_input.connected_ports().is_empty() {
+        log::warn!("No MIDI input ports connected");
+    }
+
+    let mut sink = create_sink(args)?;
+    sink.start(&config, synth_render_callback(synth))?;
+    println!(
+        "Playing at {} Hz ({:.1} ms latency), press Enter to quit",
+        sample_rate, config.latency_seconds() * 1000.0
+    );
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+
+    midi_input.disconnect_all();
+    sink.stop()?;
+    if sink.underruns() > 0 {
+        log::warn!("{} audio underruns (try a larger --buffer-size or --buffers)", sink.underruns());
+    }
+    if let Some(loader) = loader.filter(|loader| loader.misses() > 0) {
+        log::warn!("{} samples played before loading finished (try a larger --preload-frames)", loader.misses());
+    }
+    return Ok(());
+}
+
+fn main() -> anyhow::Result<()> {
+    init_logger()?;
+    let cli = Cli::parse();
+    return match &cli.command {
+        Command::Render(args) => render(args),
+        Command::Live(args) => live(args)
+    };
+}
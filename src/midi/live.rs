@@ -0,0 +1,255 @@
+/**
+ * 실시간 미디 입력(midir 사용)
+ *
+ * 미디 입력 포트(건반, 다른 컴퓨터의 시퀀서 등)에 연결해서 들어오는 메세지를 synth의 대기열(MidiMessageSender)로 보냄
+ * - 입력 포트마다 synth의 포트 A/B 중 하나를 정해서 연결함
+ * - midir이 준 타임스탬프(마이크로초)를 샘플 위치로 바꿔서 넣으므로 오디오 버퍼 크기에 따른 타이밍 흔들림이 없음
+ *   대신 항상 latency_frames만큼 늦게 적용됨
+ * - 여러 조각으로 나뉘어 들어온 sysex는 f7이 나올 때까지 모아서 한 번에 보냄
+ */
+
+use midir::{ Ignore, MidiInput, MidiInputConnection };
+use crate::synth::channel::MIDI_PORTS;
+use crate::synth::midi_queue::MidiMessageSender;
+
+// midir에 알려주는 클라이언트 이름
+const CLIENT_NAME: &str = "whitesynth";
+
+// 모아둘 수 있는 sysex의 최대 길이. 넘으면 버림
+const MAX_SYSEX_LEN: usize = 65536;
+
+/**
+ * 입력 포트 하나에서 들어온 바이트를 메세지 단위로 정리해서 synth로 보냄
+ * midir을 쓰지 않는 입력(네트워크 미디 등)에서도 receive를 직접 불러서 쓸 수 있음
+ */
+pub struct LiveMidiReceiver {
+    sender: MidiMessageSender,
+    sample_rate: u32,
+    port: u8,
+    latency_frames: u32,
+    // 타임스탬프와 synth 샘플 위치를 맞춰둔 기준점 (타임스탬프, 샘플 위치)
+    anchor: Option<(u64, u64)>,
+    sysex: Vec<u8>,
+    in_sysex: bool
+}
+
+impl LiveMidiReceiver {
+    /**
+     * port: 메세지를 보낼 synth 포트(0 = 포트 A, 1 = 포트 B)
+     * latency_frames: 메세지를 늦춰서 적용하는 정도. 오디오 출력의 버퍼 크기 정도로 정하면 됨
+     */
+    pub fn new(sender: MidiMessageSender, sample_rate: u32, port: u8, latency_frames: u32) -> Self {
+        return Self {
+            sender,
+            sample_rate: sample_rate.max(1),
+            port: port.min(MIDI_PORTS as u8 - 1),
+            latency_frames,
+            anchor: None,
+            sysex: Vec::new(),
+            in_sysex: false
+        };
+    }
+
+    pub fn port(&self) -> u8 {
+        return self.port;
+    }
+
+    /**
+     * 들어온 바이트를 처리함
+     * timestamp: 마이크로초 단위 타임스탬프(기준점은 상관없지만 계속 늘어나야 함)
+     */
+    pub fn receive(&mut self, timestamp: u64, data: &[u8]) {
+        let mut i = 0;
+        while i < data.len() {
+            let byte = data[i];
+
+            // 리얼타임 메세지(클럭, 액티브 센싱 등)는 sysex 중간에도 올 수 있음. synth에서 쓰지 않으므로 버림
+            if byte >= 0xf8 {
+                i += 1;
+                continue;
+            }
+
+            if self.in_sysex {
+                if byte == 0xf7 {
+                    self.sysex.push(byte);
+                    self.in_sysex = false;
+                    let sysex = std::mem::take(&mut self.sysex);
+                    self.send(timestamp, &sysex);
+                    self.sysex = sysex;
+                    self.sysex.clear();
+                    i += 1;
+                    continue;
+                }
+                if byte < 0x80 {
+                    if self.sysex.len() < MAX_SYSEX_LEN {
+                        self.sysex.push(byte);
+                    } else if self.sysex.len() == MAX_SYSEX_LEN {
+                        log::warn!("Sysex message too long, dropped");
+                        self.sysex.push(byte);
+                    }
+                    i += 1;
+                    continue;
+                }
+                // f7 없이 다른 상태 바이트가 오면 sysex는 끊긴 것으로 봄
+                log::warn!("Unterminated sysex message dropped");
+                self.in_sysex = false;
+                self.sysex.clear();
+            }
+
+            if byte == 0xf0 {
+                self.in_sysex = true;
+                self.sysex.clear();
+                self.sysex.push(byte);
+                i += 1;
+                continue;
+            }
+            if byte < 0x80 {
+                // midir은 running status를 풀어서 주므로 여기로 오는 데이터 바이트는 버림
+                i += 1;
+                continue;
+            }
+
+            let len = match byte >> 4 {
+                0xc | 0xd => 2,
+                0x8..=0xe => 3,
+                _ => match byte {
+                    0xf1 | 0xf3 => 2,
+                    0xf2 => 3,
+                    _ => 1
+                }
+            };
+            let end = (i + len).min(data.len());
+            // 채널 메세지만 보냄(시스템 공통 메세지는 synth에서 쓰지 않음)
+            if byte < 0xf0 {
+                if end - i == len {
+                    self.send(timestamp, &data[i..end]);
+                } else {
+                    log::warn!("Incomplete midi message dropped");
+                }
+            }
+            i = end;
+        }
+    }
+
+    fn send(&mut self, timestamp: u64, msg: &[u8]) {
+        if msg.len() > MAX_SYSEX_LEN {
+            return;
+        }
+        let frame_offset = self.frame_offset(timestamp);
+        self.sender.queue_port_midi_message(frame_offset, self.port, msg);
+    }
+
+    /**
+     * 타임스탬프를 다음 render 기준 샘플 위치로 바꿈
+     * 처음 들어온 메세지를 latency_frames 뒤에 적용하도록 기준점을 잡고, 그 뒤로는 타임스탬프 차이만큼 띄움
+     * 미디 시계와 오디오 시계가 조금씩 어긋나서 범위를 벗어나면 기준점을 다시 잡음
+     */
+    fn frame_offset(&mut self, timestamp: u64) -> u32 {
+        let position = self.sender.frame_position();
+        let max_offset = self.latency_frames as u64 * 2 + self.sample_rate as u64 / 100;
+        if let Some((anchor_timestamp, anchor_frame)) = self.anchor {
+            if timestamp >= anchor_timestamp {
+                let elapsed = (timestamp - anchor_timestamp) as f64 * self.sample_rate as f64 / 1000000.0;
+                let frame = anchor_frame + elapsed.round() as u64;
+                if frame >= position && frame - position <= max_offset {
+                    return (frame - position) as u32;
+                }
+            }
+        }
+
+        self.anchor = Some((timestamp, position + self.latency_frames as u64));
+        return self.latency_frames;
+    }
+}
+
+// 미디 입력 포트 이름 목록
+pub fn input_port_names() -> anyhow::Result<Vec<String>> {
+    let input = MidiInput::new(CLIENT_NAME)?;
+    let mut names = vec![];
+    for port in input.ports().iter() {
+        names.push(input.port_name(port)?);
+    }
+    return Ok(names);
+}
+
+/**
+ * 연결한 미디 입력 포트들
+ * drop하면 연결이 끊어짐
+ */
+pub struct LiveMidiInput {
+    sender: MidiMessageSender,
+    sample_rate: u32,
+    latency_frames: u32,
+    // (포트 이름, synth 포트, 연결)
+    connections: Vec<(String, u8, MidiInputConnection<LiveMidiReceiver>)>
+}
+
+impl LiveMidiInput {
+    pub fn new(sender: MidiMessageSender, sample_rate: u32) -> Self {
+        return Self {
+            sender,
+            sample_rate,
+            latency_frames: 0,
+            connections: vec![]
+        };
+    }
+
+    // 이 다음에 연결하는 포트부터 적용됨
+    pub fn set_latency_frames(&mut self, frames: u32) {
+        self.latency_frames = frames;
+    }
+
+    /**
+     * 입력 포트에 연결함
+     * name: 포트 번호(input_port_names 순서) 또는 포트 이름의 일부(대소문자 구분 안 함)
+     * port: 메세지를 보낼 synth 포트(0 = 포트 A, 1 = 포트 B)
+     * 연결한 포트의 전체 이름을 돌려줌
+     */
+    pub fn connect(&mut self, name: &str, port: u8) -> anyhow::Result<String> {
+        if port as usize >= MIDI_PORTS {
+            anyhow::bail!("Invalid synth port: {}", port);
+        }
+
+        let mut input = MidiInput::new(CLIENT_NAME)?;
+        // sysex도 받음(기본값은 sysex, 클럭, 액티브 센싱을 무시함)
+        input.ignore(Ignore::None);
+
+        let ports = input.ports();
+        let mut found = None;
+        if let Ok(index) = name.parse::<usize>() {
+            found = ports.get(index).cloned();
+        }
+        if found.is_none() {
+            let lower = name.to_lowercase();
+            for candidate in ports.iter() {
+                if input.port_name(candidate)?.to_lowercase().contains(&lower) {
+                    found = Some(candidate.clone());
+                    break;
+                }
+            }
+        }
+        let Some(found) = found else {
+            anyhow::bail!("Midi input port not found: {}", name);
+        };
+        let port_name = input.port_name(&found)?;
+
+        let receiver = LiveMidiReceiver::new(self.sender.clone(), self.sample_rate, port, self.latency_frames);
+        let connection = input.connect(&found, CLIENT_NAME, |timestamp, data, receiver: &mut LiveMidiReceiver| {
+            receiver.receive(timestamp, data);
+        }, receiver).map_err(|err| anyhow::anyhow!("Failed to connect to midi input port {}: {}", port_name, err))?;
+
+        self.connections.push((port_name.clone(), port, connection));
+        return Ok(port_name);
+    }
+
+    // 연결한 포트의 (이름, synth 포트)
+    pub fn connected_ports(&self) -> Vec<(&str, u8)> {
+        return self.connections.iter().map(|(name, port, _)| (name.as_str(), *port)).collect();
+    }
+
+    pub fn disconnect_all(&mut self) {
+        for (_, _, connection) in self.connections.drain(..) {
+            connection.close();
+        }
+    }
+}
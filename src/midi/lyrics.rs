@@ -0,0 +1,340 @@
+/**
+ * 미디 파일에서 가사 뽑아내기
+ *
+ * - kar(soft karaoke): 텍스트 이벤트(01h)에 가사가 있음
+ *   @로 시작하는 텍스트는 정보(@K: kar 표시, @T: 제목/가수, @L: 언어, @I: 기타 정보)
+ *   가사 앞의 /는 줄바꿈, \는 문단 바꿈(화면 지우기)
+ * - 그 외: 가사 이벤트(05h)를 씀
+ *   앞뒤의 \r, \n은 줄바꿈. xf 방식으로 앞에 붙은 /는 줄바꿈, <는 문단 바꿈으로 취급함
+ *
+ * 가사의 인코딩은 파일에 적혀있지 않아서 모든 가사 바이트를 보고 추측함(utf-8, cp949, shift-jis)
+ */
+
+use encoding_rs::{ Encoding, UTF_8, EUC_KR, SHIFT_JIS, WINDOWS_1252 };
+use super::smf::{ Sequence, SequenceEvent, MidiEvent, MetaEvent };
+
+// 가사 한 조각(음절)
+#[derive(Clone, Debug)]
+pub struct LyricSyllable {
+    pub tick: u64,
+    pub seconds: f64,
+    // 줄바꿈 기호를 뺀 텍스트
+    pub text: String,
+    // 이 음절부터 새 줄이 시작됨
+    pub new_line: bool,
+    // 이 음절부터 새 문단(화면)이 시작됨
+    pub new_paragraph: bool
+}
+
+#[derive(Clone, Debug)]
+pub struct Lyrics {
+    // kar의 @T 정보(보통 제목, 가수 순서)
+    pub titles: Vec<String>,
+    // kar의 @L 정보
+    pub language: Option<String>,
+    // kar의 @I 정보
+    pub info: Vec<String>,
+    pub syllables: Vec<LyricSyllable>,
+    // 가사를 읽을 때 쓴 인코딩
+    pub encoding: &'static Encoding
+}
+
+/**
+ * 텍스트 바이트의 인코딩을 추측함
+ * utf-8로 읽을 수 있으면 utf-8, 아니면 cp949와 shift-jis로 읽어보고 한글/일본어 글자가 더 많은 쪽을 고름
+ */
+pub fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    // EUC_KR은 실제로는 cp949(windows-949)임
+    // cp949의 확장 한글 영역은 shift-jis 바이트와 많이 겹치므로 ks x 1001 한글(B0-C8 A1-FE)만 셈
+    let korean = EUC_KR.decode_without_bom_handling_and_without_replacement(bytes).map(|_| {
+        let mut count = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] < 0x80 {
+                i += 1;
+                continue;
+            }
+            if (0xb0..=0xc8).contains(&bytes[i]) && bytes.get(i + 1).is_some_and(|b| *b >= 0xa1 && *b != 0xff) {
+                count += 1;
+            }
+            i += 2;
+        }
+        return count;
+    });
+    let japanese = SHIFT_JIS.decode_without_bom_handling_and_without_replacement(bytes).map(|text| {
+        return text.chars().filter(|c| ('\u{3040}'..='\u{30ff}').contains(c) || ('\u{4e00}'..='\u{9fff}').contains(c)).count();
+    });
+    return match (korean, japanese) {
+        (Some(korean), Some(japanese)) => if japanese > korean { SHIFT_JIS } else { EUC_KR },
+        (Some(_), None) => EUC_KR,
+        (None, Some(_)) => SHIFT_JIS,
+        (None, None) => WINDOWS_1252
+    };
+}
+
+// kar 파일인지(@K로 시작하는 텍스트 이벤트가 있는지)
+fn is_kar(sequence: &Sequence) -> bool {
+    return sequence.events.iter().any(|event| {
+        return matches!(&event.event, MidiEvent::Meta(MetaEvent::Text(text)) if text.starts_with(b"@K"));
+    });
+}
+
+impl Lyrics {
+    pub fn from_sequence(sequence: &Sequence) -> Self {
+        let events = Self::lyric_events(sequence);
+        let bytes: Vec<u8> = events.iter().flat_map(|(_, text)| text.iter().copied()).collect();
+        return Self::decode(&events, detect_encoding(&bytes));
+    }
+
+    // 인코딩을 직접 정해서 읽음
+    pub fn from_sequence_with_encoding(sequence: &Sequence, encoding: &'static Encoding) -> Self {
+        return Self::decode(&Self::lyric_events(sequence), encoding);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.syllables.is_empty();
+    }
+
+    // 줄 단위로 합친 가사
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        for syllable in self.syllables.iter() {
+            match lines.last_mut() {
+                Some(line) if !syllable.new_line => line.push_str(&syllable.text),
+                _ => lines.push(syllable.text.clone())
+            }
+        }
+        return lines;
+    }
+
+    /**
+     * 가사가 들어있는 이벤트의 (이벤트, 텍스트)
+     * kar면 가사 텍스트 이벤트가 가장 많은 트랙(보통 "Words" 트랙)의 텍스트 이벤트와 모든 트랙의 @ 정보를 씀
+     */
+    fn lyric_events(sequence: &Sequence) -> Vec<(&SequenceEvent, &[u8])> {
+        if is_kar(sequence) {
+            let mut counts = std::collections::HashMap::new();
+            for event in sequence.events.iter() {
+                if let MidiEvent::Meta(MetaEvent::Text(text)) = &event.event {
+                    if !text.starts_with(b"@") {
+                        *counts.entry(event.track).or_insert(0) += 1;
+                    }
+                }
+            }
+            let lyric_track = counts.into_iter().max_by_key(|(track, count)| (*count, usize::MAX - track)).map(|(track, _)| track);
+
+            return sequence.events.iter().filter_map(|event| {
+                return match &event.event {
+                    MidiEvent::Meta(MetaEvent::Text(text)) if text.starts_with(b"@") || Some(event.track) == lyric_track => {
+                        Some((event, text.as_slice()))
+                    },
+                    _ => None
+                };
+            }).collect();
+        }
+
+        return sequence.events.iter().filter_map(|event| {
+            return match &event.event {
+                MidiEvent::Meta(MetaEvent::Lyric(text)) => Some((event, text.as_slice())),
+                _ => None
+            };
+        }).collect();
+    }
+
+    fn decode(events: &[(&SequenceEvent, &[u8])], encoding: &'static Encoding) -> Self {
+        let mut lyrics = Self {
+            titles: vec![],
+            language: None,
+            info: vec![],
+            syllables: vec![],
+            encoding
+        };
+
+        // 앞 음절 끝에 줄바꿈이 있었으면 다음 음절에 표시함
+        let mut new_line = true;
+        let mut new_paragraph = true;
+        for (event, bytes) in events.iter() {
+            let (text, _) = encoding.decode_without_bom_handling(bytes);
+            if let Some(header) = text.strip_prefix('@') {
+                let value = header.get(1..).unwrap_or("").trim().to_owned();
+                match header.chars().next() {
+                    Some('T') => lyrics.titles.push(value),
+                    Some('L') => lyrics.language = Some(value),
+                    Some('I') => lyrics.info.push(value),
+                    _ => {}
+                }
+                continue;
+            }
+
+            let mut text: &str = &text;
+            loop {
+                if let Some(rest) = text.strip_prefix(['\\', '<']) {
+                    new_paragraph = true;
+                    new_line = true;
+                    text = rest;
+                } else if let Some(rest) = text.strip_prefix(['/', '\r', '\n']) {
+                    new_line = true;
+                    text = rest;
+                } else {
+                    break;
+                }
+            }
+            let line_end = text.ends_with(['\r', '\n']);
+            let text = text.trim_end_matches(['\r', '\n']);
+
+            if !text.is_empty() {
+                lyrics.syllables.push(LyricSyllable {
+                    tick: event.tick,
+                    seconds: event.seconds,
+                    text: text.to_owned(),
+                    new_line,
+                    new_paragraph
+                });
+                new_line = false;
+                new_paragraph = false;
+            }
+            new_line |= line_end;
+        }
+
+        return lyrics;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::smf::{ TempoMap, Timing };
+
+    // (트랙, 틱, 메타 이벤트) 목록으로 곡을 만듦
+    fn sequence(events: Vec<(usize, u64, MetaEvent)>) -> Sequence {
+        let tempo_map = TempoMap::new(Timing::TicksPerQuarter(480), &[]);
+        let end_tick = events.iter().map(|(_, tick, _)| *tick).max().unwrap_or(0);
+        let events = events.into_iter().map(|(track, tick, event)| SequenceEvent {
+            tick,
+            seconds: tempo_map.tick_to_seconds(tick),
+            track,
+            port: 0,
+            event: MidiEvent::Meta(event)
+        }).collect();
+        return Sequence { events, tempo_map, end_tick };
+    }
+
+    fn text(track: usize, tick: u64, text: &str) -> (usize, u64, MetaEvent) {
+        return (track, tick, MetaEvent::Text(text.as_bytes().to_vec()));
+    }
+
+    fn lyric(tick: u64, text: &[u8]) -> (usize, u64, MetaEvent) {
+        return (0, tick, MetaEvent::Lyric(text.to_vec()));
+    }
+
+    // 음절별 (텍스트, 새 줄, 새 문단)
+    fn syllables(lyrics: &Lyrics) -> Vec<(&str, bool, bool)> {
+        return lyrics.syllables.iter().map(|syllable| (syllable.text.as_str(), syllable.new_line, syllable.new_paragraph)).collect();
+    }
+
+    #[test]
+    fn detects_encoding() {
+        assert_eq!(detect_encoding("ascii only".as_bytes()), UTF_8);
+        assert_eq!(detect_encoding("아리랑 アリラン".as_bytes()), UTF_8);
+
+        // "아리랑 아리랑 아라리요"
+        let korean = [
+            0xbe, 0xc6, 0xb8, 0xae, 0xb6, 0xfb, 0x20, 0xbe, 0xc6, 0xb8, 0xae, 0xb6, 0xfb, 0x20,
+            0xbe, 0xc6, 0xb6, 0xf3, 0xb8, 0xae, 0xbf, 0xe4
+        ];
+        assert_eq!(detect_encoding(&korean), EUC_KR);
+        assert_eq!(EUC_KR.decode_without_bom_handling(&korean).0, "아리랑 아리랑 아라리요");
+
+        // "さくら さくら 弥生の空は"
+        let japanese = [
+            0x82, 0xb3, 0x82, 0xad, 0x82, 0xe7, 0x20, 0x82, 0xb3, 0x82, 0xad, 0x82, 0xe7, 0x20,
+            0x96, 0xed, 0x90, 0xb6, 0x82, 0xcc, 0x8b, 0xf3, 0x82, 0xcd
+        ];
+        assert_eq!(detect_encoding(&japanese), SHIFT_JIS);
+        assert_eq!(SHIFT_JIS.decode_without_bom_handling(&japanese).0, "さくら さくら 弥生の空は");
+
+        // 어느 쪽으로도 읽을 수 없으면 windows-1252
+        assert_eq!(detect_encoding(&[b'a', 0xff, 0xff]), WINDOWS_1252);
+    }
+
+    #[test]
+    fn lyrics_are_decoded_with_detected_encoding() {
+        // "아리랑"을 음절마다 나눈 가사
+        let song = sequence(vec![lyric(0, &[0xbe, 0xc6]), lyric(120, &[0xb8, 0xae]), lyric(240, &[0xb6, 0xfb])]);
+        let lyrics = Lyrics::from_sequence(&song);
+        assert_eq!(lyrics.encoding, EUC_KR);
+        assert_eq!(lyrics.lines(), vec!["아리랑"]);
+        assert_eq!(lyrics.syllables[1].tick, 120);
+        assert_eq!(lyrics.syllables[1].seconds, 0.125);
+
+        // 인코딩을 직접 정하면 추측하지 않음
+        let lyrics = Lyrics::from_sequence_with_encoding(&song, WINDOWS_1252);
+        assert_eq!(lyrics.encoding, WINDOWS_1252);
+        assert_eq!(lyrics.syllables[0].text, "¾Æ");
+    }
+
+    #[test]
+    fn kar_headers_and_line_breaks() {
+        let song = sequence(vec![
+            text(1, 0, "@KMIDI KARAOKE FILE"),
+            text(1, 0, "@V0100"),
+            text(1, 0, "@I first info"),
+            text(2, 0, "@LKOR"),
+            text(2, 0, "@TSong title"),
+            text(2, 0, "@TSinger"),
+            text(2, 0, "@Isecond info"),
+            // 가사 트랙(텍스트 이벤트가 가장 많은 트랙)만 씀
+            text(3, 0, "Track name"),
+            text(2, 100, "\\Twin"),
+            text(2, 200, "kle "),
+            text(2, 300, "/twin"),
+            text(2, 400, "kle"),
+            text(2, 500, "\\Little "),
+            text(2, 600, "star"),
+            // kar에서는 가사 이벤트를 쓰지 않음
+            lyric(700, b"ignored")
+        ]);
+        let lyrics = Lyrics::from_sequence(&song);
+        assert_eq!(lyrics.titles, vec!["Song title", "Singer"]);
+        assert_eq!(lyrics.language.as_deref(), Some("KOR"));
+        assert_eq!(lyrics.info, vec!["first info", "second info"]);
+        assert_eq!(syllables(&lyrics), vec![
+            ("Twin", true, true),
+            ("kle ", false, false),
+            ("twin", true, false),
+            ("kle", false, false),
+            ("Little ", true, true),
+            ("star", false, false)
+        ]);
+        assert_eq!(lyrics.lines(), vec!["Twinkle ", "twinkle", "Little star"]);
+    }
+
+    #[test]
+    fn lyric_event_line_breaks() {
+        let song = sequence(vec![
+            lyric(0, b"Twin"),
+            lyric(100, b"kle\r"),
+            lyric(200, b"twin"),
+            lyric(300, b"/kle"),
+            lyric(400, b"<Little"),
+            // 줄바꿈만 있는 가사는 다음 음절에 표시함
+            lyric(500, b"\n"),
+            lyric(600, b"star")
+        ]);
+        let lyrics = Lyrics::from_sequence(&song);
+        assert!(lyrics.titles.is_empty());
+        assert_eq!(syllables(&lyrics), vec![
+            ("Twin", true, true),
+            ("kle", false, false),
+            ("twin", true, false),
+            ("kle", true, false),
+            ("Little", true, true),
+            ("star", true, false)
+        ]);
+        assert_eq!(lyrics.syllables[5].tick, 600);
+    }
+}
@@ -0,0 +1,8 @@
+/**
+ * 미디 파일 관련 기능
+ */
+
+pub mod smf;
+pub mod player;
+pub mod lyrics;
+pub mod live;
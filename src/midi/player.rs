@@ -0,0 +1,488 @@
+/**
+ * 미디 파일 재생기
+ * Synth를 갖고 있으면서 render할 때마다 그 구간에 해당하는 이벤트를 샘플 단위로 예약함
+ *
+ * 재생 위치는 템포 배율을 적용하기 전의 곡 시간(초)으로 관리함
+ * 위치를 옮기면(seek) 처음부터 그 위치까지의 controller/program change/sysex를 다시 보내서
+ * 그 위치에서 원래 들려야 하는 악기와 설정으로 이어서 재생함
+ *
+ * 가사 함수(set_lyric_callback)는 그 음절이 들어있는 소리를 render로 내보낸 직후에 호출됨
+ */
+
+use std::collections::VecDeque;
+
+use crate::synth::Synth;
+use crate::synth::wsn::{ self, SoundbankMessage };
+use crate::synth::channel::{ MIDI_PORTS, CHANNELS_PER_PORT, MIDI_CHANNELS };
+use crate::util::midi::cc_ids;
+use super::smf::{ MidiFile, Sequence, SequenceEvent, MidiEvent, MetaEvent };
+use super::lyrics::{ Lyrics, LyricSyllable };
+
+// 구간 반복 시작 지점을 표시하는 cc(rpg maker 방식)
+pub const LOOP_START_CC: u8 = 111;
+
+// 템포 배율 범위
+const MIN_TEMPO_SCALE: f64 = 0.1;
+const MAX_TEMPO_SCALE: f64 = 10.0;
+
+// 가사 음절을 받는 함수(음절 번호, 음절)
+type LyricCallback = Box<dyn FnMut(usize, &LyricSyllable) + Send>;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PlayerState {
+    Stopped,
+    Playing,
+    Paused
+}
+
+// 구간 반복 지점(틱 단위. end_tick에 도달하면 start_tick으로 돌아감)
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct LoopPoints {
+    pub start_tick: u64,
+    pub end_tick: u64
+}
+
+impl LoopPoints {
+    /**
+     * 곡 안의 반복 지점 표시를 찾음
+     * - 시작: cc 111(채널 상관 없음) 또는 "loopStart" 마커
+     * - 끝: "loopEnd" 마커. 없으면 곡 끝
+     */
+    pub fn find(sequence: &Sequence) -> Option<Self> {
+        let mut start_tick = None;
+        let mut end_tick = None;
+        for event in sequence.events.iter() {
+            match &event.event {
+                MidiEvent::Channel(message) if message.kind() == 0xb && message.data1() == LOOP_START_CC => {
+                    start_tick = start_tick.or(Some(event.tick));
+                },
+                MidiEvent::Meta(MetaEvent::Marker(text)) => {
+                    let text = String::from_utf8_lossy(text);
+                    if text.trim().eq_ignore_ascii_case("loopstart") {
+                        start_tick = start_tick.or(Some(event.tick));
+                    } else if text.trim().eq_ignore_ascii_case("loopend") {
+                        end_tick = end_tick.or(Some(event.tick));
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let loop_points = Self {
+            start_tick: start_tick?,
+            end_tick: end_tick.unwrap_or(sequence.end_tick)
+        };
+        if loop_points.end_tick <= loop_points.start_tick {
+            return None;
+        }
+        return Some(loop_points);
+    }
+}
+
+pub struct Player {
+    synth: Synth,
+    sequence: Option<Sequence>,
+    state: PlayerState,
+
+    // 현재 재생 위치(곡 시작부터의 시간(초). 템포 배율 적용 전)
+    position: f64,
+
+    // 다음에 보낼 이벤트 번호
+    next_event: usize,
+
+    // 템포 배율(2.0 = 2배 빠르게)
+    tempo_scale: f64,
+
+    looping: bool,
+    loop_points: Option<LoopPoints>,
+
+    // 소리가 나고 있는 건반(채널(포트 * 16 + 채널 번호)별 비트)
+    // 구간 반복으로 돌아갈 때 note off를 보내기 위해 씀
+    active_notes: [u128; MIDI_CHANNELS],
+
+    lyrics: Option<Lyrics>,
+
+    // 다음에 예약할 가사 음절 번호
+    next_lyric: usize,
+
+    // 예약한 가사 음절(synth의 출력 기준 샘플 위치, 음절 번호)
+    scheduled_lyrics: VecDeque<(u64, usize)>,
+
+    lyric_callback: Option<LyricCallback>
+}
+
+impl Player {
+    pub fn new(synth: Synth) -> Self {
+        return Self {
+            synth,
+            sequence: None,
+            state: PlayerState::Stopped,
+            position: 0.0,
+            next_event: 0,
+            tempo_scale: 1.0,
+            looping: false,
+            loop_points: None,
+            active_notes: [0; MIDI_CHANNELS],
+            lyrics: None,
+            next_lyric: 0,
+            scheduled_lyrics: VecDeque::new(),
+            lyric_callback: None
+        };
+    }
+
+    pub fn synth(&self) -> &Synth {
+        return &self.synth;
+    }
+
+    pub fn synth_mut(&mut self) -> &mut Synth {
+        return &mut self.synth;
+    }
+
+    pub fn into_synth(self) -> Synth {
+        return self.synth;
+    }
+
+    pub fn sequence(&self) -> Option<&Sequence> {
+        return self.sequence.as_ref();
+    }
+
+    pub fn load(&mut self, midi_file: &MidiFile) {
+        self.load_sequence(midi_file.to_sequence());
+    }
+
+    // 곡을 바꿈. 멈춘 상태가 되고, 곡 안에 반복 지점 표시가 있으면 구간 반복을 켬
+    pub fn load_sequence(&mut self, sequence: Sequence) {
+        self.stop();
+        self.loop_points = LoopPoints::find(&sequence);
+        self.looping = self.loop_points.is_some();
+        let lyrics = Lyrics::from_sequence(&sequence);
+        self.lyrics = if lyrics.is_empty() && lyrics.titles.is_empty() { None } else { Some(lyrics) };
+        self.sequence = Some(sequence);
+    }
+
+    // 가사가 없는 곡이면 None
+    pub fn lyrics(&self) -> Option<&Lyrics> {
+        return self.lyrics.as_ref();
+    }
+
+    pub fn set_lyric_callback<F: FnMut(usize, &LyricSyllable) + Send + 'static>(&mut self, callback: F) {
+        self.lyric_callback = Some(Box::new(callback));
+    }
+
+    pub fn state(&self) -> PlayerState {
+        return self.state;
+    }
+
+    // 현재 재생 위치(초)
+    pub fn position(&self) -> f64 {
+        return self.position;
+    }
+
+    // 곡 길이(초)
+    pub fn duration(&self) -> f64 {
+        return self.sequence.as_ref().map_or(0.0, |sequence| sequence.duration());
+    }
+
+    /**
+     * 재생 시작
+     * 멈춘 상태(곡이 끝났을 때 포함)면 처음부터, 일시정지 상태면 멈춘 위치부터 재생함
+     */
+    pub fn play(&mut self) {
+        if self.sequence.is_none() {
+            return;
+        }
+        match self.state {
+            PlayerState::Stopped => self.seek(0.0),
+            PlayerState::Paused => self.seek(self.position),
+            PlayerState::Playing => return
+        }
+        self.state = PlayerState::Playing;
+    }
+
+    // 일시정지. 소리는 바로 끊음
+    pub fn pause(&mut self) {
+        if self.state == PlayerState::Playing {
+            self.silence();
+            self.state = PlayerState::Paused;
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.silence();
+        self.state = PlayerState::Stopped;
+        self.position = 0.0;
+        self.next_event = 0;
+        self.next_lyric = 0;
+    }
+
+    /**
+     * 재생 위치를 옮김(초)
+     * synth를 초기화한 다음 그 위치 전까지의 note 이외의 메세지를 모두 다시 보냄
+     * wsn 사운드뱅크 메세지는 다시 보내면 파일을 또 읽게 되므로 따로 처리함
+     * 마지막으로 모두 뺀 다음에 불러온 것만 보내고(synth는 이미 불러온 번호면 무시함),
+     * 지금 불러온 사운드뱅크가 그 위치와 다를 때만 곡에 있는 모두 빼기 메세지를 다시 보냄
+     */
+    pub fn seek(&mut self, seconds: f64) {
+        let sequence = match &self.sequence {
+            Some(sequence) => sequence,
+            None => return
+        };
+        let seconds = seconds.max(0.0).min(sequence.duration());
+
+        self.synth.clear_scheduled_messages();
+        self.synth.gs_reset();
+        let mut soundbank_clear = None;
+        let mut soundbank_loads = vec![];
+        let mut index = 0;
+        while let Some(event) = sequence.events.get(index) {
+            if event.seconds >= seconds {
+                break;
+            }
+            let port = Self::port_of(event);
+            match &event.event {
+                // note on/off, poly aftertouch는 빼고 보냄
+                MidiEvent::Channel(message) if !matches!(message.kind(), 0x8..=0xa) => {
+                    self.synth.handle_port_midi_message(port, message.as_bytes());
+                },
+                MidiEvent::SysEx(data) => match wsn::soundbank_message(data) {
+                    Some(SoundbankMessage::Load(soundbank)) => soundbank_loads.push((port, data, soundbank)),
+                    Some(SoundbankMessage::Clear) => {
+                        soundbank_clear = Some((port, data));
+                        soundbank_loads.clear();
+                    },
+                    None => self.synth.handle_port_midi_message(port, data)
+                },
+                _ => {}
+            }
+            index += 1;
+        }
+        let target: Vec<u16> = soundbank_loads.iter().map(|(_, _, soundbank)| *soundbank).collect();
+        if let Some((port, data)) = soundbank_clear.filter(|_| !target.starts_with(self.synth.requested_soundbanks())) {
+            self.synth.handle_port_midi_message(port, data);
+        }
+        for (port, data, _) in soundbank_loads {
+            self.synth.handle_port_midi_message(port, data);
+        }
+
+        self.active_notes = [0; MIDI_CHANNELS];
+        self.position = seconds;
+        self.next_event = index;
+        self.scheduled_lyrics.clear();
+        self.next_lyric = self.lyrics.as_ref().map_or(0, |lyrics| {
+            return lyrics.syllables.partition_point(|syllable| syllable.seconds < seconds);
+        });
+    }
+
+    pub fn tempo_scale(&self) -> f64 {
+        return self.tempo_scale;
+    }
+
+    pub fn set_tempo_scale(&mut self, scale: f64) {
+        self.tempo_scale = scale.max(MIN_TEMPO_SCALE).min(MAX_TEMPO_SCALE);
+    }
+
+    pub fn transpose(&self) -> i32 {
+        return self.synth.transpose();
+    }
+
+    // 조옮김(Synth::set_transpose). 곡에서 설정한 master coarse tuning에 더해짐
+    // 이미 소리가 나고 있는 노트에는 적용되지 않음
+    pub fn set_transpose(&mut self, key: i32) {
+        self.synth.set_transpose(key);
+    }
+
+    pub fn is_looping(&self) -> bool {
+        return self.looping;
+    }
+
+    // 구간 반복 켜기/끄기. 반복 지점이 없으면 곡 전체를 반복함
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        return self.loop_points;
+    }
+
+    pub fn set_loop_points(&mut self, loop_points: Option<LoopPoints>) {
+        self.loop_points = loop_points.filter(|loop_points| loop_points.end_tick > loop_points.start_tick);
+    }
+
+    // left/right 버퍼를 채움(Synth::render와 같음)
+    pub fn render(&mut self, left: &mut [f64], right: &mut [f64]) {
+        if self.state == PlayerState::Playing {
+            self.schedule_events(left.len().min(right.len()));
+        }
+        self.synth.render(left, right);
+        self.emit_lyrics();
+    }
+
+    // left, right, left, right, ... 순서로 된 배열 하나를 채움
+    pub fn render_as_one_array(&mut self, output: &mut [f64]) {
+        if self.state == PlayerState::Playing {
+            self.schedule_events(output.len() / 2);
+        }
+        self.synth.render_as_one_array(output);
+        self.emit_lyrics();
+    }
+
+    // 미디 포트는 2개뿐이므로 그 이상의 포트는 A/B에 번갈아 보냄
+    fn port_of(event: &SequenceEvent) -> u8 {
+        return (event.port as usize % MIDI_PORTS) as u8;
+    }
+
+    // 현재 반복 구간의 (시작 시간, 끝 시간, 시작 틱). 반복하지 않으면 None
+    fn active_loop(&self, sequence: &Sequence) -> Option<(f64, f64, u64)> {
+        if !self.looping {
+            return None;
+        }
+        let loop_points = self.loop_points.unwrap_or(LoopPoints {
+            start_tick: 0,
+            end_tick: sequence.end_tick
+        });
+        let start = sequence.tempo_map.tick_to_seconds(loop_points.start_tick);
+        let end = sequence.tempo_map.tick_to_seconds(loop_points.end_tick);
+        // 한 바퀴가 1샘플도 안 되면 무한 반복이 되므로 반복하지 않음
+        let sample_rate = self.synth.sample_rate() as f64;
+        if (end - start) / self.tempo_scale * sample_rate < 1.0 {
+            return None;
+        }
+        return Some((start, end, loop_points.start_tick));
+    }
+
+    // 이번 render에서 내보낼 len 샘플 동안의 이벤트를 synth에 예약함
+    fn schedule_events(&mut self, len: usize) {
+        let sequence = match self.sequence.take() {
+            Some(sequence) => sequence,
+            None => return
+        };
+        let sample_rate = self.synth.sample_rate() as f64;
+        let active_loop = self.active_loop(&sequence);
+
+        let mut frame = 0;
+        while frame < len {
+            let end = self.position + (len - frame) as f64 / sample_rate * self.tempo_scale;
+            let limit = match active_loop {
+                Some((_, loop_end, _)) => end.min(loop_end),
+                None => end
+            };
+
+            while let Some(event) = sequence.events.get(self.next_event) {
+                if event.seconds >= limit {
+                    break;
+                }
+                let offset = frame + ((event.seconds - self.position).max(0.0) / self.tempo_scale * sample_rate).round() as usize;
+                self.send_event(offset, event);
+                self.next_event += 1;
+            }
+            self.schedule_lyrics(frame, limit);
+
+            match active_loop {
+                Some((loop_start, loop_end, loop_start_tick)) if end >= loop_end => {
+                    frame += ((loop_end - self.position).max(0.0) / self.tempo_scale * sample_rate).round() as usize;
+                    self.release_active_notes(frame);
+                    self.position = loop_start;
+                    self.next_event = sequence.events.partition_point(|event| event.tick < loop_start_tick);
+                    self.next_lyric = self.lyrics.as_ref().map_or(0, |lyrics| {
+                        return lyrics.syllables.partition_point(|syllable| syllable.tick < loop_start_tick);
+                    });
+                },
+                _ => {
+                    self.position = end;
+                    frame = len;
+                }
+            }
+        }
+
+        // 곡이 끝나면 멈춘 상태가 됨(남은 소리는 그대로 둠)
+        if active_loop.is_none() && self.next_event >= sequence.events.len() && self.position >= sequence.duration() {
+            self.position = sequence.duration();
+            self.state = PlayerState::Stopped;
+        }
+        self.sequence = Some(sequence);
+    }
+
+    fn send_event(&mut self, offset: usize, event: &SequenceEvent) {
+        let port = Self::port_of(event);
+        match &event.event {
+            MidiEvent::Channel(message) => {
+                let index = port as usize * CHANNELS_PER_PORT + message.channel() as usize;
+                let bit = 1_u128 << message.data1();
+                match message.kind() {
+                    0x9 if message.data2() > 0 => self.active_notes[index] |= bit,
+                    0x8 | 0x9 => self.active_notes[index] &= !bit,
+                    _ => {}
+                }
+                self.synth.queue_port_midi_message(offset as u32, port, message.as_bytes());
+            },
+            MidiEvent::SysEx(data) | MidiEvent::Escape(data) => self.synth.queue_port_midi_message(offset as u32, port, data),
+            MidiEvent::Meta(_) => {}
+        }
+    }
+
+    /**
+     * 이번 render에서 frame 위치부터 곡 시간 limit 전까지의 가사 음절을 예약함
+     * synth는 예약한 메세지를 render_buffer_size만큼 늦게 적용하므로 가사도 똑같이 늦춤
+     */
+    fn schedule_lyrics(&mut self, frame: usize, limit: f64) {
+        let lyrics = match &self.lyrics {
+            Some(lyrics) => lyrics,
+            None => return
+        };
+        let sample_rate = self.synth.sample_rate() as f64;
+        let start = self.synth.frame_position() + self.synth.render_buffer_size() as u64 + frame as u64;
+        while let Some(syllable) = lyrics.syllables.get(self.next_lyric) {
+            if syllable.seconds >= limit {
+                break;
+            }
+            let offset = ((syllable.seconds - self.position).max(0.0) / self.tempo_scale * sample_rate).round() as u64;
+            self.scheduled_lyrics.push_back((start + offset, self.next_lyric));
+            self.next_lyric += 1;
+        }
+    }
+
+    // 내보낸 소리에 해당하는 가사 음절을 가사 함수로 보냄
+    fn emit_lyrics(&mut self) {
+        let lyrics = match &self.lyrics {
+            Some(lyrics) => lyrics,
+            None => return
+        };
+        let frame_position = self.synth.frame_position();
+        while let Some((frame, index)) = self.scheduled_lyrics.front() {
+            if *frame >= frame_position {
+                break;
+            }
+            if let Some(callback) = self.lyric_callback.as_mut() {
+                callback(*index, &lyrics.syllables[*index]);
+            }
+            self.scheduled_lyrics.pop_front();
+        }
+    }
+
+    // 소리가 나고 있는 모든 노트에 note off를 보냄
+    fn release_active_notes(&mut self, offset: usize) {
+        for (index, notes) in self.active_notes.iter_mut().enumerate() {
+            let port = (index / CHANNELS_PER_PORT) as u8;
+            let channel = (index % CHANNELS_PER_PORT) as u8;
+            for key in 0..128_u8 {
+                if *notes & (1 << key) != 0 {
+                    self.synth.queue_port_midi_message(offset as u32, port, &[0x80 | channel, key, 0]);
+                }
+            }
+            *notes = 0;
+        }
+    }
+
+    // 예약한 메세지를 모두 버리고 모든 채널의 소리를 바로 끊음
+    fn silence(&mut self) {
+        self.synth.clear_scheduled_messages();
+        self.scheduled_lyrics.clear();
+        for port in 0..MIDI_PORTS {
+            for channel in 0..CHANNELS_PER_PORT {
+                let msg = [0xb0 | channel as u8, cc_ids::ALL_SOUND_OFF, 0];
+                self.synth.handle_port_midi_message(port as u8, &msg);
+            }
+        }
+        self.active_notes = [0; MIDI_CHANNELS];
+    }
+}
@@ -0,0 +1,811 @@
+/**
+ * 표준 미디 파일(smf. .mid, .kar) 읽기
+ * format 0/1/2를 모두 지원함
+ * 참고문헌: https://www.music.mcgill.ca/~ich/classes/mumt306/StandardMIDIfileformat.html
+ *
+ * - format 0/1: 모든 트랙을 동시에 재생함
+ * - format 2: 트랙마다 독립된 곡(패턴)이므로 트랙을 순서대로 이어서 재생함
+ */
+
+use std::io::Read;
+use anyhow::bail;
+
+// 템포 메세지가 없을 때의 템포(4분음표 1개당 마이크로초. = 120bpm)
+pub const DEFAULT_TEMPO: u32 = 500000;
+
+// 에러 처리용 구조체
+#[derive(Debug, Clone)]
+pub struct MidiFileError {
+    message: String
+}
+
+impl MidiFileError {
+    fn new(message: &str) -> Self {
+        return Self {
+            message: message.to_owned()
+        };
+    }
+}
+
+impl std::error::Error for MidiFileError {}
+
+impl std::fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "{}", self.message);
+    }
+}
+
+/**
+ * 시간 단위
+ * - TicksPerQuarter: 4분음표 1개당 틱 수(템포의 영향을 받음)
+ * - Smpte: 초당 프레임 수(24, 25, 29(=29.97), 30)와 프레임당 틱 수(템포와 상관 없음)
+ */
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Timing {
+    TicksPerQuarter(u16),
+    Smpte { fps: u8, ticks_per_frame: u8 }
+}
+
+// 채널 메세지(note on, cc 등). running status는 이미 풀어서 status 바이트가 항상 있음
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ChannelMessage {
+    bytes: [u8; 3]
+}
+
+impl ChannelMessage {
+    pub fn new(status: u8, data1: u8, data2: u8) -> Self {
+        return Self {
+            bytes: [status, data1 & 0x7f, data2 & 0x7f]
+        };
+    }
+
+    // status 바이트에 따른 메세지 길이
+    fn len_of(status: u8) -> usize {
+        return match status >> 4 {
+            0xc | 0xd => 2,
+            _ => 3
+        };
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        return &self.bytes[0..Self::len_of(self.bytes[0])];
+    }
+
+    pub fn status(&self) -> u8 {
+        return self.bytes[0];
+    }
+
+    // 상위 4비트(8h = note off, 9h = note on, ...)
+    pub fn kind(&self) -> u8 {
+        return self.bytes[0] >> 4;
+    }
+
+    pub fn channel(&self) -> u8 {
+        return self.bytes[0] & 0x0f;
+    }
+
+    pub fn data1(&self) -> u8 {
+        return self.bytes[1];
+    }
+
+    pub fn data2(&self) -> u8 {
+        return self.bytes[2];
+    }
+}
+
+/**
+ * 메타 이벤트
+ * 텍스트는 인코딩이 파일마다 제각각이라 원래 바이트 그대로 둠
+ */
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum MetaEvent {
+    SequenceNumber(u16),
+    Text(Vec<u8>),
+    Copyright(Vec<u8>),
+    TrackName(Vec<u8>),
+    InstrumentName(Vec<u8>),
+    Lyric(Vec<u8>),
+    Marker(Vec<u8>),
+    CuePoint(Vec<u8>),
+    ChannelPrefix(u8),
+    // 이 트랙의 이벤트를 보낼 미디 포트(0 = 포트 A)
+    Port(u8),
+    EndOfTrack,
+    // 4분음표 1개당 마이크로초
+    Tempo(u32),
+    SmpteOffset { hours: u8, minutes: u8, seconds: u8, frames: u8, subframes: u8 },
+    // denominator는 2의 거듭제곱 지수(2 = 4분음표)
+    TimeSignature { numerator: u8, denominator: u8, clocks_per_click: u8, notated_32nds_per_quarter: u8 },
+    // sharps: 음수면 flat 개수
+    KeySignature { sharps: i8, minor: bool },
+    SequencerSpecific(Vec<u8>),
+    Unknown(u8, Vec<u8>)
+}
+
+impl MetaEvent {
+    fn parse(meta_type: u8, data: Vec<u8>) -> Self {
+        let expected_len = match meta_type {
+            0x00 => Some(2),
+            0x20 | 0x21 => Some(1),
+            0x2f => Some(0),
+            0x51 => Some(3),
+            0x54 => Some(5),
+            0x58 => Some(4),
+            0x59 => Some(2),
+            _ => None
+        };
+        if expected_len.is_some_and(|len| data.len() < len) {
+            log::warn!("Invalid length of meta event {:02X}h: {}", meta_type, data.len());
+            return Self::Unknown(meta_type, data);
+        }
+
+        return match meta_type {
+            0x00 => Self::SequenceNumber(((data[0] as u16) << 8) | data[1] as u16),
+            0x01 => Self::Text(data),
+            0x02 => Self::Copyright(data),
+            0x03 => Self::TrackName(data),
+            0x04 => Self::InstrumentName(data),
+            0x05 => Self::Lyric(data),
+            0x06 => Self::Marker(data),
+            0x07 => Self::CuePoint(data),
+            0x20 => Self::ChannelPrefix(data[0]),
+            0x21 => Self::Port(data[0]),
+            0x2f => Self::EndOfTrack,
+            0x51 => Self::Tempo(((data[0] as u32) << 16) | ((data[1] as u32) << 8) | data[2] as u32),
+            0x54 => Self::SmpteOffset {
+                hours: data[0],
+                minutes: data[1],
+                seconds: data[2],
+                frames: data[3],
+                subframes: data[4]
+            },
+            0x58 => Self::TimeSignature {
+                numerator: data[0],
+                denominator: data[1],
+                clocks_per_click: data[2],
+                notated_32nds_per_quarter: data[3]
+            },
+            0x59 => Self::KeySignature {
+                sharps: data[0] as i8,
+                minor: data[1] != 0
+            },
+            0x7f => Self::SequencerSpecific(data),
+            _ => Self::Unknown(meta_type, data)
+        };
+    }
+}
+
+/**
+ * 트랙 안의 이벤트 1개
+ * - SysEx: F0부터 F7까지 완성된 메세지(여러 조각으로 나뉜 것도 하나로 합쳐 둠)
+ * - Escape: F7 이벤트로 들어온 임의의 바이트(그대로 장치로 보내면 됨)
+ */
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum MidiEvent {
+    Channel(ChannelMessage),
+    SysEx(Vec<u8>),
+    Escape(Vec<u8>),
+    Meta(MetaEvent)
+}
+
+#[derive(Clone, Debug)]
+pub struct TrackEvent {
+    // 트랙 시작부터의 틱 수(delta time을 누적한 값)
+    pub tick: u64,
+    pub event: MidiEvent
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Track {
+    pub events: Vec<TrackEvent>
+}
+
+impl Track {
+    // 마지막 이벤트(보통 end of track)의 틱 수
+    pub fn end_tick(&self) -> u64 {
+        return self.events.last().map_or(0, |event| event.tick);
+    }
+}
+
+// 템포가 바뀌는 지점
+#[derive(Clone, Copy, Debug)]
+pub struct TempoChange {
+    pub tick: u64,
+    pub tempo: u32,
+    // 곡 시작부터 이 지점까지의 시간(초)
+    pub seconds: f64
+}
+
+/**
+ * 틱 <=> 시간 변환표
+ * TicksPerQuarter면 항상 틱 0에 해당하는 항목이 처음에 있음
+ */
+#[derive(Clone, Debug)]
+pub struct TempoMap {
+    timing: Timing,
+    changes: Vec<TempoChange>
+}
+
+impl TempoMap {
+    // (틱, 템포) 목록으로 변환표를 만듦. 틱 순서대로 정렬되어 있어야 함
+    pub fn new(timing: Timing, tempos: &[(u64, u32)]) -> Self {
+        let mut changes = vec![TempoChange {
+            tick: 0,
+            tempo: DEFAULT_TEMPO,
+            seconds: 0.0
+        }];
+        if let Timing::TicksPerQuarter(ticks_per_quarter) = timing {
+            for (tick, tempo) in tempos.iter() {
+                let last = *changes.last().unwrap();
+                let seconds = last.seconds
+                    + (*tick - last.tick) as f64 * last.tempo as f64 / 1000000.0 / ticks_per_quarter as f64;
+                // 같은 틱에 템포가 여러 번 있으면 마지막 것만 씀
+                if last.tick == *tick {
+                    changes.pop();
+                }
+                changes.push(TempoChange {
+                    tick: *tick,
+                    tempo: *tempo,
+                    seconds
+                });
+            }
+        }
+
+        return Self {
+            timing,
+            changes
+        };
+    }
+
+    pub fn timing(&self) -> Timing {
+        return self.timing;
+    }
+
+    pub fn changes(&self) -> &[TempoChange] {
+        return &self.changes;
+    }
+
+    // 해당 틱에 적용되는 템포(4분음표 1개당 마이크로초)
+    pub fn tempo_at(&self, tick: u64) -> u32 {
+        let index = self.changes.partition_point(|change| change.tick <= tick);
+        return self.changes[index.max(1) - 1].tempo;
+    }
+
+    pub fn tick_to_seconds(&self, tick: u64) -> f64 {
+        return match self.timing {
+            Timing::TicksPerQuarter(ticks_per_quarter) => {
+                let index = self.changes.partition_point(|change| change.tick <= tick);
+                let change = &self.changes[index.max(1) - 1];
+                change.seconds
+                    + (tick - change.tick) as f64 * change.tempo as f64 / 1000000.0 / ticks_per_quarter as f64
+            },
+            Timing::Smpte { .. } => tick as f64 / self.smpte_ticks_per_second()
+        };
+    }
+
+    // 해당 시간에 해당하는 틱(소수점 포함)
+    pub fn seconds_to_tick(&self, seconds: f64) -> f64 {
+        let seconds = seconds.max(0.0);
+        return match self.timing {
+            Timing::TicksPerQuarter(ticks_per_quarter) => {
+                let index = self.changes.partition_point(|change| change.seconds <= seconds);
+                let change = &self.changes[index.max(1) - 1];
+                change.tick as f64
+                    + (seconds - change.seconds) * 1000000.0 / change.tempo as f64 * ticks_per_quarter as f64
+            },
+            Timing::Smpte { .. } => seconds * self.smpte_ticks_per_second()
+        };
+    }
+
+    // 해당 틱에 해당하는 샘플 위치
+    pub fn tick_to_frame(&self, tick: u64, sample_rate: u32) -> u64 {
+        return (self.tick_to_seconds(tick) * sample_rate as f64).round() as u64;
+    }
+
+    fn smpte_ticks_per_second(&self) -> f64 {
+        return match self.timing {
+            Timing::Smpte { fps, ticks_per_frame } => {
+                // 29는 drop frame(29.97fps)
+                let fps = if fps == 29 { 30000.0 / 1001.0 } else { fps as f64 };
+                fps * ticks_per_frame as f64
+            },
+            _ => 1.0
+        };
+    }
+}
+
+// 모든 트랙을 합친 이벤트 1개
+#[derive(Clone, Debug)]
+pub struct SequenceEvent {
+    // 곡 시작부터의 틱 수
+    pub tick: u64,
+    // 곡 시작부터의 시간(초)
+    pub seconds: f64,
+    // 이 이벤트가 있던 트랙 번호
+    pub track: usize,
+    // 미디 포트(port 메타 이벤트로 정해짐. 기본값 = 0)
+    pub port: u8,
+    pub event: MidiEvent
+}
+
+/**
+ * 모든 트랙을 시간 순서대로 합친 것
+ * 시간이 같으면 트랙 번호 순서, 트랙도 같으면 파일에 있던 순서를 따름
+ * end of track 이벤트는 빠져 있음(곡 길이는 end_tick으로 알 수 있음)
+ */
+#[derive(Clone, Debug)]
+pub struct Sequence {
+    pub events: Vec<SequenceEvent>,
+    pub tempo_map: TempoMap,
+    pub end_tick: u64
+}
+
+impl Sequence {
+    // 곡 길이(초)
+    pub fn duration(&self) -> f64 {
+        return self.tempo_map.tick_to_seconds(self.end_tick);
+    }
+}
+
+pub struct MidiFile {
+    // 0, 1, 2
+    pub format: u16,
+    pub timing: Timing,
+    pub tracks: Vec<Track>
+}
+
+// 바이트 배열에서 차례대로 읽기
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        return Self {
+            data,
+            pos: 0
+        };
+    }
+
+    fn is_end(&self) -> bool {
+        return self.pos >= self.data.len();
+    }
+
+    fn read_bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            bail!(MidiFileError::new("Unexpected end of data"));
+        }
+        let bytes = &self.data[self.pos..(self.pos + len)];
+        self.pos += len;
+        return Ok(bytes);
+    }
+
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        return Ok(self.read_bytes(1)?[0]);
+    }
+
+    fn peek_u8(&self) -> anyhow::Result<u8> {
+        return match self.data.get(self.pos) {
+            Some(byte) => Ok(*byte),
+            None => bail!(MidiFileError::new("Unexpected end of data"))
+        };
+    }
+
+    fn read_u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.read_bytes(2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+    }
+
+    fn read_u32(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.read_bytes(4)?;
+        return Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    }
+
+    // 가변 길이 정수(7비트씩, 최대 4바이트)
+    fn read_var_len(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!(MidiFileError::new("Variable-length quantity too long"));
+    }
+}
+
+impl MidiFile {
+    pub fn read<T: Read>(stream: &mut T) -> anyhow::Result<Self> {
+        let mut data = vec![];
+        stream.read_to_end(&mut data)?;
+        return Self::from_bytes(&data);
+    }
+
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = ByteReader::new(data);
+
+        // rmid(riff로 감싼 미디 파일)면 안에 있는 data 청크를 씀
+        if data.len() >= 20 && &data[0..4] == b"RIFF" && &data[8..12] == b"RMID" {
+            let mut riff = ByteReader::new(&data[12..]);
+            while !riff.is_end() {
+                let id = riff.read_bytes(4)?;
+                let len = riff.read_u32()?.swap_bytes() as usize;
+                let body = riff.read_bytes(len.min(riff.data.len() - riff.pos))?;
+                if id == b"data" {
+                    return Self::from_bytes(body);
+                }
+                if len & 1 == 1 && !riff.is_end() {
+                    riff.read_u8()?;
+                }
+            }
+            bail!(MidiFileError::new("No data chunk in RMID file"));
+        }
+
+        if reader.read_bytes(4)? != b"MThd" {
+            bail!(MidiFileError::new("Not a standard midi file"));
+        }
+        let header_len = reader.read_u32()? as usize;
+        if header_len < 6 {
+            bail!(MidiFileError::new("Invalid length of chunk 'MThd'"));
+        }
+        let header = reader.read_bytes(header_len)?;
+        let format = u16::from_be_bytes([header[0], header[1]]);
+        let track_count = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let division = u16::from_be_bytes([header[4], header[5]]);
+        if format > 2 {
+            bail!(MidiFileError::new(&format!("Unsupported midi file format: {}", format)));
+        }
+
+        let timing = if division & 0x8000 != 0 {
+            // 상위 바이트는 fps의 2의 보수(-24, -25, -29, -30). 80h는 부호를 바꿀 수 없으므로 wrapping_neg를 씀
+            let fps = ((division >> 8) as u8 as i8).wrapping_neg() as u8;
+            let ticks_per_frame = (division & 0xff) as u8;
+            if !matches!(fps, 24 | 25 | 29 | 30) || ticks_per_frame == 0 {
+                bail!(MidiFileError::new("Invalid SMPTE time division"));
+            }
+            Timing::Smpte { fps, ticks_per_frame }
+        } else {
+            if division == 0 {
+                bail!(MidiFileError::new("Invalid time division: 0"));
+            }
+            Timing::TicksPerQuarter(division)
+        };
+
+        // 모르는 청크는 건너뜀
+        // 헤더의 트랙 수가 실제와 다르거나 마지막 청크가 잘린 파일이 꽤 있어서 최대한 읽어봄
+        let mut tracks = vec![];
+        while !reader.is_end() && tracks.len() < track_count {
+            if reader.data.len() - reader.pos < 8 {
+                log::warn!("Trailing garbage at the end of midi file");
+                break;
+            }
+            let id = reader.read_bytes(4)?;
+            let mut len = reader.read_u32()? as usize;
+            if len > reader.data.len() - reader.pos {
+                log::warn!("Truncated chunk in midi file");
+                len = reader.data.len() - reader.pos;
+            }
+            let body = reader.read_bytes(len)?;
+            if id == b"MTrk" {
+                tracks.push(Self::parse_track(body)?);
+            }
+        }
+        if tracks.len() < track_count {
+            log::warn!("Midi file has {} tracks (expected {})", tracks.len(), track_count);
+        }
+
+        return Ok(Self {
+            format,
+            timing,
+            tracks
+        });
+    }
+
+    fn parse_track(data: &[u8]) -> anyhow::Result<Track> {
+        let mut reader = ByteReader::new(data);
+        let mut events = vec![];
+        let mut tick = 0_u64;
+        let mut running_status: Option<u8> = None;
+        // 여러 조각으로 나뉜 sysex를 모으는 중이면 Some
+        let mut pending_sysex: Option<Vec<u8>> = None;
+
+        while !reader.is_end() {
+            tick += reader.read_var_len()? as u64;
+            let status = match reader.peek_u8()? {
+                status if status >= 0x80 => {
+                    reader.read_u8()?;
+                    status
+                },
+                _ => match running_status {
+                    Some(status) => status,
+                    None => bail!(MidiFileError::new("Data byte without running status"))
+                }
+            };
+
+            let event = match status {
+                0x80..=0xef => {
+                    running_status = Some(status);
+                    let data1 = reader.read_u8()?;
+                    let data2 = if ChannelMessage::len_of(status) == 3 { reader.read_u8()? } else { 0 };
+                    MidiEvent::Channel(ChannelMessage::new(status, data1, data2))
+                },
+                // 원래는 sysex와 메타 이벤트가 running status를 취소하지만
+                // 올바른 파일에서는 어차피 영향이 없으므로 그대로 유지함(잘못 만든 파일을 위해)
+                0xf0 => {
+                    let len = reader.read_var_len()? as usize;
+                    let mut message = vec![0xf0];
+                    message.extend_from_slice(reader.read_bytes(len)?);
+                    if pending_sysex.is_some() {
+                        log::warn!("Unterminated sysex message in midi file");
+                    }
+                    if message.last() == Some(&0xf7) {
+                        pending_sysex = None;
+                        MidiEvent::SysEx(message)
+                    } else {
+                        pending_sysex = Some(message);
+                        continue;
+                    }
+                },
+                0xf7 => {
+                    let len = reader.read_var_len()? as usize;
+                    let bytes = reader.read_bytes(len)?;
+                    match pending_sysex.take() {
+                        // 앞의 sysex에 이어지는 조각
+                        Some(mut message) => {
+                            message.extend_from_slice(bytes);
+                            if message.last() == Some(&0xf7) {
+                                MidiEvent::SysEx(message)
+                            } else {
+                                pending_sysex = Some(message);
+                                continue;
+                            }
+                        },
+                        None => MidiEvent::Escape(bytes.to_vec())
+                    }
+                },
+                0xff => {
+                    let meta_type = reader.read_u8()?;
+                    let len = reader.read_var_len()? as usize;
+                    let data = reader.read_bytes(len)?.to_vec();
+                    MidiEvent::Meta(MetaEvent::parse(meta_type, data))
+                },
+                _ => bail!(MidiFileError::new(&format!("Invalid status byte in midi file: {:02X}h", status)))
+            };
+
+            let is_end = event == MidiEvent::Meta(MetaEvent::EndOfTrack);
+            events.push(TrackEvent { tick, event });
+            if is_end {
+                break;
+            }
+        }
+
+        if pending_sysex.is_some() {
+            log::warn!("Unterminated sysex message in midi file");
+        }
+        if !events.last().is_some_and(|event| event.event == MidiEvent::Meta(MetaEvent::EndOfTrack)) {
+            log::warn!("Missing end of track event");
+        }
+        return Ok(Track { events });
+    }
+
+    // 각 트랙이 곡 시작부터 몇 틱 뒤에 시작하는지(format 2는 트랙을 이어서 재생함)
+    fn track_offsets(&self) -> Vec<u64> {
+        let mut offset = 0;
+        return self.tracks.iter().map(|track| {
+            if self.format != 2 {
+                return 0;
+            }
+            let start = offset;
+            offset += track.end_tick();
+            return start;
+        }).collect();
+    }
+
+    // 모든 트랙의 템포 이벤트로 템포 변환표를 만듦
+    pub fn tempo_map(&self) -> TempoMap {
+        let offsets = self.track_offsets();
+        let mut tempos = vec![];
+        for (track, offset) in self.tracks.iter().zip(offsets.iter()) {
+            for event in track.events.iter() {
+                if let MidiEvent::Meta(MetaEvent::Tempo(tempo)) = event.event {
+                    tempos.push((event.tick + offset, tempo));
+                }
+            }
+        }
+        // 정렬이 안정적이므로 같은 틱이면 트랙 순서가 유지됨
+        tempos.sort_by_key(|(tick, _)| *tick);
+        return TempoMap::new(self.timing, &tempos);
+    }
+
+    // 모든 트랙을 시간 순서대로 합침
+    pub fn to_sequence(&self) -> Sequence {
+        let tempo_map = self.tempo_map();
+        let offsets = self.track_offsets();
+
+        let mut events = vec![];
+        let mut end_tick = 0;
+        for (track_no, (track, offset)) in self.tracks.iter().zip(offsets.iter()).enumerate() {
+            let mut port = 0;
+            for event in track.events.iter() {
+                let tick = event.tick + offset;
+                end_tick = end_tick.max(tick);
+                match event.event {
+                    MidiEvent::Meta(MetaEvent::EndOfTrack) => continue,
+                    MidiEvent::Meta(MetaEvent::Port(new_port)) => port = new_port,
+                    _ => {}
+                }
+                events.push(SequenceEvent {
+                    tick,
+                    seconds: 0.0,
+                    track: track_no,
+                    port,
+                    event: event.event.clone()
+                });
+            }
+        }
+        events.sort_by_key(|event| (event.tick, event.track));
+        for event in events.iter_mut() {
+            event.seconds = tempo_map.tick_to_seconds(event.tick);
+        }
+
+        return Sequence {
+            events,
+            tempo_map,
+            end_tick
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 트랙 데이터(MTrk 청크 내용)로 미디 파일을 만듦
+    fn smf(format: u16, division: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"MThd".to_vec();
+        data.extend(6_u32.to_be_bytes());
+        data.extend(format.to_be_bytes());
+        data.extend((tracks.len() as u16).to_be_bytes());
+        data.extend(division.to_be_bytes());
+        for track in tracks {
+            data.extend(b"MTrk");
+            data.extend((track.len() as u32).to_be_bytes());
+            data.extend_from_slice(track);
+        }
+        return data;
+    }
+
+    const END_OF_TRACK: [u8; 4] = [0x00, 0xff, 0x2f, 0x00];
+
+    fn events(track: &Track) -> Vec<(u64, MidiEvent)> {
+        return track.events.iter().map(|event| (event.tick, event.event.clone())).collect();
+    }
+
+    fn channel(status: u8, data1: u8, data2: u8) -> MidiEvent {
+        return MidiEvent::Channel(ChannelMessage::new(status, data1, data2));
+    }
+
+    #[test]
+    fn smpte_division() {
+        // E7h = -25fps, 프레임당 40틱 => 초당 1000틱
+        let file = MidiFile::from_bytes(&smf(0, 0xe728, &[&END_OF_TRACK])).unwrap();
+        assert_eq!(file.timing, Timing::Smpte { fps: 25, ticks_per_frame: 40 });
+        let tempo_map = file.tempo_map();
+        assert_eq!(tempo_map.tick_to_seconds(1500), 1.5);
+        assert_eq!(tempo_map.seconds_to_tick(0.25), 250.0);
+        assert_eq!(tempo_map.tick_to_frame(1500, 48000), 72000);
+
+        // 29 = 29.97fps(drop frame)
+        let file = MidiFile::from_bytes(&smf(0, 0xe364, &[&END_OF_TRACK])).unwrap();
+        let ticks_per_second = 30000.0 / 1001.0 * 100.0;
+        assert!((file.tempo_map().tick_to_seconds(2997) - 2997.0 / ticks_per_second).abs() < 1e-12);
+
+        // 24, 25, 29, 30 이외의 fps(80h 포함)와 프레임당 0틱은 읽지 않음
+        for division in [0x8004, 0xe000 | 0x0a, 0xff04, 0xe800, 0x0000] {
+            assert!(MidiFile::from_bytes(&smf(0, division, &[&END_OF_TRACK])).is_err(), "{:04X}h", division);
+        }
+    }
+
+    #[test]
+    fn running_status() {
+        let track = [
+            0x00, 0x90, 0x3c, 0x64,
+            0x10, 0x3e, 0x50, // note on(running status)
+            0x00, 0xc1, 0x05,
+            0x08, 0x07, // program change(running status, 데이터 1바이트)
+            0x00, 0xff, 0x2f, 0x00
+        ];
+        let file = MidiFile::from_bytes(&smf(0, 480, &[&track])).unwrap();
+        assert_eq!(events(&file.tracks[0]), vec![
+            (0, channel(0x90, 0x3c, 0x64)),
+            (16, channel(0x90, 0x3e, 0x50)),
+            (16, channel(0xc1, 0x05, 0)),
+            (24, channel(0xc1, 0x07, 0)),
+            (24, MidiEvent::Meta(MetaEvent::EndOfTrack))
+        ]);
+        if let MidiEvent::Channel(message) = file.tracks[0].events[3].event {
+            assert_eq!(message.as_bytes(), &[0xc1, 0x07]);
+        }
+
+        // 처음부터 데이터 바이트가 오면 읽지 않음
+        assert!(MidiFile::from_bytes(&smf(0, 480, &[&[0x00, 0x3c, 0x64, 0x00, 0xff, 0x2f, 0x00]])).is_err());
+    }
+
+    #[test]
+    fn meta_sysex_and_escape_events() {
+        let track = [
+            0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20,
+            0x00, 0xff, 0x01, 0x03, b'a', b'b', b'c',
+            0x00, 0xff, 0x21, 0x01, 0x01,
+            0x00, 0xff, 0x59, 0x02, 0xfd, 0x01,
+            0x00, 0xff, 0x51, 0x01, 0x00, // 길이가 모자란 템포
+            // 두 조각으로 나뉜 sysex
+            0x00, 0xf0, 0x03, 0x41, 0x10, 0x42,
+            0x10, 0xf7, 0x02, 0x12, 0xf7,
+            // 앞에 이어지는 sysex가 없으면 escape
+            0x00, 0xf7, 0x02, 0xf3, 0x01,
+            0x00, 0xff, 0x2f, 0x00,
+            // end of track 뒤에 있는 건 무시함
+            0x00, 0x90, 0x3c, 0x64
+        ];
+        let file = MidiFile::from_bytes(&smf(0, 480, &[&track])).unwrap();
+        assert_eq!(events(&file.tracks[0]), vec![
+            (0, MidiEvent::Meta(MetaEvent::Tempo(500000))),
+            (0, MidiEvent::Meta(MetaEvent::Text(b"abc".to_vec()))),
+            (0, MidiEvent::Meta(MetaEvent::Port(1))),
+            (0, MidiEvent::Meta(MetaEvent::KeySignature { sharps: -3, minor: true })),
+            (0, MidiEvent::Meta(MetaEvent::Unknown(0x51, vec![0x00]))),
+            (16, MidiEvent::SysEx(vec![0xf0, 0x41, 0x10, 0x42, 0x12, 0xf7])),
+            (16, MidiEvent::Escape(vec![0xf3, 0x01])),
+            (16, MidiEvent::Meta(MetaEvent::EndOfTrack))
+        ]);
+    }
+
+    #[test]
+    fn tracks_are_merged_in_order() {
+        let conductor = [0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, 0x83, 0x60, 0xff, 0x2f, 0x00];
+        let track1 = [
+            0x00, 0xff, 0x21, 0x01, 0x01,
+            0x00, 0x90, 0x3c, 0x64,
+            0x83, 0x60, 0x80, 0x3c, 0x00,
+            0x00, 0xff, 0x2f, 0x00
+        ];
+        let track2 = [0x00, 0x91, 0x40, 0x64, 0x81, 0x70, 0x81, 0x40, 0x00, 0x00, 0xff, 0x2f, 0x00];
+
+        // format 1: 같은 틱이면 트랙 순서, 같은 트랙이면 파일에 있던 순서
+        let sequence = MidiFile::from_bytes(&smf(1, 480, &[&conductor, &track1, &track2])).unwrap().to_sequence();
+        let merged: Vec<(u64, usize, u8)> = sequence.events.iter().map(|event| (event.tick, event.track, event.port)).collect();
+        assert_eq!(merged, vec![(0, 0, 0), (0, 1, 1), (0, 1, 1), (0, 2, 0), (240, 2, 0), (480, 1, 1)]);
+        assert_eq!(sequence.events[2].event, channel(0x90, 0x3c, 0x64));
+        assert_eq!(sequence.end_tick, 480);
+        assert_eq!(sequence.duration(), 0.5);
+
+        // format 2: 트랙을 이어서 재생함
+        let sequence = MidiFile::from_bytes(&smf(2, 480, &[&track2, &track1])).unwrap().to_sequence();
+        let ticks: Vec<u64> = sequence.events.iter().map(|event| event.tick).collect();
+        assert_eq!(ticks, vec![0, 240, 240, 240, 720]);
+        assert_eq!(sequence.end_tick, 720);
+    }
+
+    #[test]
+    fn tempo_map_converts_ticks() {
+        // 틱 960에서 120bpm => 240bpm, 같은 틱에 템포가 두 번 있으면 마지막 것만 씀
+        let tempo_map = TempoMap::new(Timing::TicksPerQuarter(480), &[(0, 500000), (960, 300000), (960, 250000)]);
+        assert_eq!(tempo_map.changes().len(), 2);
+        assert_eq!(tempo_map.tempo_at(959), 500000);
+        assert_eq!(tempo_map.tempo_at(960), 250000);
+        assert_eq!(tempo_map.tick_to_seconds(480), 0.5);
+        assert_eq!(tempo_map.tick_to_seconds(960), 1.0);
+        assert_eq!(tempo_map.tick_to_seconds(1440), 1.25);
+        assert_eq!(tempo_map.seconds_to_tick(1.25), 1440.0);
+        assert_eq!(tempo_map.seconds_to_tick(0.75), 720.0);
+        assert_eq!(tempo_map.tick_to_frame(1440, 48000), 60000);
+        assert_eq!(tempo_map.tick_to_frame(1, 44100), 46);
+
+        // 템포 이벤트가 없으면 120bpm
+        let default = TempoMap::new(Timing::TicksPerQuarter(96), &[]);
+        assert_eq!(default.tick_to_seconds(96 * 4), 2.0);
+    }
+}
@@ -1,2 +1,82 @@
 pub mod wsbk;
-pub mod sf2;
\ No newline at end of file
+pub mod sf2;
+pub mod stream;
+
+use std::io::{ Read, Seek, SeekFrom };
+
+/**
+ * synth에 넣는 사운드뱅크
+ * sf2는 wsbk로 바꾸지 않고 샘플 데이터를 그대로 써서 재생함
+ */
+pub enum Soundbank {
+    WSBK(wsbk::WSBK),
+    SF2(sf2::bank::SF2Bank)
+}
+
+impl Soundbank {
+    pub fn instruments(&self) -> &[wsbk::Instrument] {
+        return match self {
+            Self::WSBK(bank) => &bank.instruments,
+            Self::SF2(bank) => &bank.instruments
+        };
+    }
+
+    pub fn presets(&self) -> &[wsbk::Preset] {
+        return match self {
+            Self::WSBK(bank) => &bank.presets,
+            Self::SF2(bank) => &bank.presets
+        };
+    }
+
+    // bank select/program 번호에 맞는 preset을 찾음
+    pub fn find_preset(
+        &self,
+        bank_msb: u8, bank_lsb: u8,
+        program_no: u16, type_flag: wsbk::PresetType
+    ) -> Option<&wsbk::Preset> {
+        return self.presets().iter().find(|preset| {
+            preset.type_flag == type_flag
+                && preset.program_no == program_no
+                && preset.bank_msb == bank_msb
+                && preset.bank_lsb == bank_lsb
+        });
+    }
+}
+
+impl From<wsbk::WSBK> for Soundbank {
+    fn from(bank: wsbk::WSBK) -> Self {
+        return Self::WSBK(bank);
+    }
+}
+
+impl From<sf2::bank::SF2Bank> for Soundbank {
+    fn from(bank: sf2::bank::SF2Bank) -> Self {
+        return Self::SF2(bank);
+    }
+}
+
+impl From<&sf2::SF2> for Soundbank {
+    fn from(sf2: &sf2::SF2) -> Self {
+        return Self::SF2(sf2::bank::SF2Bank::new(sf2));
+    }
+}
+
+// 파일 형식(riff 형식 id)을 보고 wsbk 또는 sf2 파일을 읽음
+pub fn read_soundbank<T: Read + Seek>(stream: &mut T) -> anyhow::Result<Soundbank> {
+    let mut header = [0; 12];
+    stream.read_exact(&mut header)?;
+    stream.seek(SeekFrom::Start(0))?;
+
+    if &header[8..12] == b"sfbk" {
+        return Ok(Soundbank::from(&sf2::SF2::new(stream)?));
+    }
+    return Ok(Soundbank::WSBK(wsbk::WSBK::read(stream)?));
+}
+
+// 샘플 데이터를 필요할 때 불러오도록 wsbk 또는 sf2 파일을 읽음(stream 모듈 참고)
+pub fn read_soundbank_streamed(loader: &stream::SampleLoader) -> anyhow::Result<Soundbank> {
+    if loader.bytes().get(8..12) == Some(b"sfbk") {
+        return Ok(Soundbank::from(&sf2::SF2::read_streamed(loader)?));
+    }
+    return Ok(Soundbank::WSBK(wsbk::WSBK::read_streamed(loader)?));
+}
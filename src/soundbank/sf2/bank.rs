@@ -0,0 +1,76 @@
+/**
+ * sf2를 wsbk로 바꾸지 않고 바로 재생하기 위한 사운드뱅크
+ * preset/instrument/region은 to_wsbk와 같은 방식으로 만들고(sf2 2.04 규칙: global zone은 기본값,
+ * preset generator는 instrument generator에 더함, 기본 modulator 적용)
+ * 샘플은 데이터를 복사하지 않고 SF2의 샘플 데이터에서 위치만 가리킴
+ * SF2::read_streamed로 읽은 경우에는 샘플마다 필요할 때 불러오는 SampleStream을 씀
+ */
+
+use std::sync::Arc;
+use crate::soundbank::wsbk::{ Instrument, Preset, LoopType };
+use crate::soundbank::stream::SampleStream;
+use super::{ SF2, SF2SampleData, SF2Sm24Data };
+
+// SF2 샘플 데이터의 일부를 가리키는 샘플
+pub struct SF2BankSample {
+    pub name: String,
+
+    // 샘플 데이터에서 샘플이 시작하는 위치(stereo면 왼쪽 채널)
+    pub start: usize,
+
+    // stereo면 오른쪽 채널이 시작하는 위치
+    pub right_start: Option<usize>,
+
+    // 길이(샘플 단위)
+    pub frame_count: usize,
+
+    // 16 또는 24(sm24 청크가 있는 경우)
+    pub bit_depth: u16,
+
+    // 루프 위치는 start 기준
+    pub loop_type: LoopType,
+    pub loop_start: u32,
+    pub loop_end: u32,
+
+    pub sample_rate: u32,
+    pub base_key: u8,
+    pub cent_correction: i8,
+
+    // 필요할 때 불러오는 샘플이면 sample_data 대신 이걸 씀
+    pub stream: Option<Arc<SampleStream>>
+}
+
+pub struct SF2Bank {
+    // SF2와 공유하는 샘플 데이터 전체
+    pub(crate) sample_data: SF2SampleData,
+
+    // 24비트 샘플이면 하위 8비트
+    pub(crate) sm24_data: Option<SF2Sm24Data>,
+
+    pub samples: Vec<SF2BankSample>,
+
+    // region의 target_index는 wsbk와 같이 instrument => samples, preset => instruments 번호
+    pub instruments: Vec<Instrument>,
+    pub presets: Vec<Preset>
+}
+
+impl SF2Bank {
+    pub fn new(sf2: &SF2) -> Self {
+        let (samples, instruments, presets) = sf2.convert(SF2::make_bank_sample);
+        return Self {
+            sample_data: sf2.sample_data.clone(),
+            sm24_data: sf2.sm24_data.clone(),
+            samples,
+            instruments,
+            presets
+        };
+    }
+
+    pub fn sample_data(&self) -> &SF2SampleData {
+        return &self.sample_data;
+    }
+
+    pub fn sm24_data(&self) -> Option<&SF2Sm24Data> {
+        return self.sm24_data.as_ref();
+    }
+}
@@ -0,0 +1,45 @@
+use riff::ChunkId;
+
+/**
+ * sfbk = 사운드폰트 파일
+ *
+ * INFO = 파일 정보 (ifil = 버전, isng = 대상 음원 칩, INAM = 이름, irom/iver = ROM 이름/버전,
+ *        ICRD = 만든 날짜, IENG = 만든 사람, IPRD = 대상 제품, ICOP = 저작권, ICMT = 설명, ISFT = 만든 프로그램)
+ *
+ * sdta = 샘플 데이터 (smpl = 16비트 샘플, sm24 = 24비트 샘플의 하위 8비트)
+ *
+ * pdta = preset/instrument/샘플 정보
+ * phdr, pbag, pmod, pgen = preset 헤더, bag, modulator, generator
+ * inst, ibag, imod, igen = instrument 헤더, bag, modulator, generator
+ * shdr = 샘플 헤더
+ */
+
+pub const SFBK: ChunkId = ChunkId { value: [b's', b'f', b'b', b'k'] };
+
+pub const INFO: ChunkId = ChunkId { value: [b'I', b'N', b'F', b'O'] };
+pub const IFIL: ChunkId = ChunkId { value: [b'i', b'f', b'i', b'l'] };
+pub const ISNG: ChunkId = ChunkId { value: [b'i', b's', b'n', b'g'] };
+pub const INAM: ChunkId = ChunkId { value: [b'I', b'N', b'A', b'M'] };
+pub const IROM: ChunkId = ChunkId { value: [b'i', b'r', b'o', b'm'] };
+pub const IVER: ChunkId = ChunkId { value: [b'i', b'v', b'e', b'r'] };
+pub const ICRD: ChunkId = ChunkId { value: [b'I', b'C', b'R', b'D'] };
+pub const IENG: ChunkId = ChunkId { value: [b'I', b'E', b'N', b'G'] };
+pub const IPRD: ChunkId = ChunkId { value: [b'I', b'P', b'R', b'D'] };
+pub const ICOP: ChunkId = ChunkId { value: [b'I', b'C', b'O', b'P'] };
+pub const ICMT: ChunkId = ChunkId { value: [b'I', b'C', b'M', b'T'] };
+pub const ISFT: ChunkId = ChunkId { value: [b'I', b'S', b'F', b'T'] };
+
+pub const SDTA: ChunkId = ChunkId { value: [b's', b'd', b't', b'a'] };
+pub const SMPL: ChunkId = ChunkId { value: [b's', b'm', b'p', b'l'] };
+pub const SM24: ChunkId = ChunkId { value: [b's', b'm', b'2', b'4'] };
+
+pub const PDTA: ChunkId = ChunkId { value: [b'p', b'd', b't', b'a'] };
+pub const PHDR: ChunkId = ChunkId { value: [b'p', b'h', b'd', b'r'] };
+pub const PBAG: ChunkId = ChunkId { value: [b'p', b'b', b'a', b'g'] };
+pub const PMOD: ChunkId = ChunkId { value: [b'p', b'm', b'o', b'd'] };
+pub const PGEN: ChunkId = ChunkId { value: [b'p', b'g', b'e', b'n'] };
+pub const INST: ChunkId = ChunkId { value: [b'i', b'n', b's', b't'] };
+pub const IBAG: ChunkId = ChunkId { value: [b'i', b'b', b'a', b'g'] };
+pub const IMOD: ChunkId = ChunkId { value: [b'i', b'm', b'o', b'd'] };
+pub const IGEN: ChunkId = ChunkId { value: [b'i', b'g', b'e', b'n'] };
+pub const SHDR: ChunkId = ChunkId { value: [b's', b'h', b'd', b'r'] };
@@ -5,7 +5,6 @@
  * 그리고 저기서 이어지는 모든 글들
  */
 use crate::util;
-use std::convert::TryInto;
 use std::sync::Arc;
 use std::io::{ Read, Seek, SeekFrom };
 use anyhow::*;
@@ -13,35 +12,190 @@ use anyhow::*;
 pub mod gen_ids;
 pub mod structure;
 pub mod to_wsbk;
+pub mod bank;
+pub mod vorbis;
+pub mod fourcc;
+pub mod write;
 use structure::*;
+use super::stream::SampleLoader;
 
-// 에러 처리용 구조체
+// 에러 처리용 enum
 #[derive(Debug, Clone)]
-pub struct SF2Error {
-    message: String
+pub enum SF2Error {
+    // 꼭 있어야 하는 청크가 없음
+    MissingChunk(&'static str),
+
+    // 청크 크기가 레코드 크기에 맞지 않음
+    BadRecordSize { chunk: &'static str, len: usize },
+
+    // 끝을 나타내는 레코드(EOI, EOP, EOS 등)가 없음
+    MissingTerminalRecord(&'static str),
+
+    // 인덱스가 가리키는 레코드가 없음
+    OutOfRange { chunk: &'static str, index: usize, len: usize },
+
+    // 청크가 파일 끝을 넘어감
+    Truncated { chunk: String, len: u64 }
 }
 
-impl SF2Error {
-    fn new(message: &str) -> Self {
-        return Self {
-            message: message.to_owned()
+impl std::error::Error for SF2Error {}
+
+impl std::fmt::Display for SF2Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            Self::MissingChunk(chunk) => write!(f, "No {} chunk", chunk),
+            Self::BadRecordSize { chunk, len } => write!(f, "Invalid length of chunk '{}': {}", chunk, len),
+            Self::MissingTerminalRecord(chunk) => write!(f, "No terminal record in chunk '{}'", chunk),
+            Self::OutOfRange { chunk, index, len } => write!(f, "Index out of range in chunk '{}': {} (len {})", chunk, index, len),
+            Self::Truncated { chunk, len } => write!(f, "Chunk '{}' exceeds the end of file (len {})", chunk, len)
         };
     }
+}
+
+// 청크 id(riff 크레이트의 as_str은 UTF-8이 아니면 panic이 나므로 따로 변환함)
+fn chunk_id(id: &riff::ChunkId) -> String {
+    return String::from_utf8_lossy(&id.value).into_owned();
+}
+
+// 청크 내용을 읽음. 파일 끝을 넘어가는 청크는 메모리를 잡기 전에 에러로 처리함
+fn read_chunk<T: Read + Seek>(chunk: &riff::Chunk, stream: &mut T, stream_len: u64) -> anyhow::Result<Vec<u8>> {
+    check_chunk(chunk, stream_len)?;
+    return Ok(chunk.read_contents(stream)?);
+}
 
-    fn new_box(message: &str) -> Box<Self> {
-        return Box::new(Self::new(message));
+fn check_chunk(chunk: &riff::Chunk, stream_len: u64) -> anyhow::Result<()> {
+    if chunk.offset() + 8 + chunk.len() as u64 > stream_len {
+        bail!(SF2Error::Truncated { chunk: chunk_id(&chunk.id()), len: chunk.len() as u64 });
     }
+    return Ok(());
 }
 
-impl std::error::Error for SF2Error {}
+// 고정 길이 레코드로 이루어진 청크인지 확인. 끝을 나타내는 레코드가 있어야 하므로 비어 있으면 안 됨
+fn check_records(contents: &[u8], chunk: &'static str, record_size: usize) -> anyhow::Result<usize> {
+    if contents.len() % record_size != 0 {
+        bail!(SF2Error::BadRecordSize { chunk, len: contents.len() });
+    }
+    if contents.is_empty() {
+        bail!(SF2Error::MissingTerminalRecord(chunk));
+    }
+    return Ok(contents.len() / record_size);
+}
 
-impl std::fmt::Display for SF2Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        return write!(f, "{}", self.message);
+// 20바이트 이름(\0으로 끝남. 20바이트를 다 쓰면 \0이 없음)
+// UTF-8이 아닌 이름(EUC-KR, Shift_JIS 등)은 깨지는 부분만 대체 문자로 바꿈
+fn read_name(bytes: &[u8]) -> String {
+    let bytes = &bytes[..bytes.len().min(20)];
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    return String::from_utf8_lossy(&bytes[..len]).into_owned();
+}
+
+// 읽은 이름 그대로(다시 저장할 때 이름을 바꾸지 않았으면 이걸 씀)
+fn read_raw_name(bytes: &[u8]) -> [u8; 20] {
+    let mut raw_name = [0; 20];
+    let len = bytes.len().min(20);
+    raw_name[..len].copy_from_slice(&bytes[..len]);
+    return raw_name;
+}
+
+// INFO 청크의 문자열. 끝의 \0(종료 문자, 짝수 길이 padding)은 뺌. 쓸 때 다시 붙임
+fn read_info_string(contents: &[u8]) -> String {
+    return String::from_utf8_lossy(contents).trim_end_matches('\0').to_owned();
+}
+
+fn read_version(contents: &[u8], chunk: &'static str) -> anyhow::Result<[u16; 2]> {
+    if contents.len() < 4 {
+        bail!(SF2Error::BadRecordSize { chunk, len: contents.len() });
+    }
+    return Ok([
+        u16::from_le_bytes([contents[0], contents[1]]),
+        u16::from_le_bytes([contents[2], contents[3]])
+    ]);
+}
+
+/**
+ * bag 레코드를 읽어 bag마다 generator와 modulator 범위를 정함
+ * 범위는 다음 bag의 시작 위치 직전까지임(마지막 bag은 끝까지)
+ * 시작 위치가 거꾸로 되어 있으면 그 bag에는 generator 또는 modulator가 없는 것으로 봄
+ */
+fn read_bags(bag: &[u8], chunk: &'static str, generators_len: usize, modulators_len: usize) -> anyhow::Result<Vec<SF2Bag>> {
+    let starts: Vec<(usize, usize)> = bag.chunks_exact(4).map(|record| {
+        return (u16::from_le_bytes([record[0], record[1]]) as usize, u16::from_le_bytes([record[2], record[3]]) as usize);
+    }).collect();
+    let mut bags = vec![];
+    for i in 0..starts.len() {
+        let (generator_start, modulator_start) = starts[i];
+        // bag의 위치는 u16이라 마지막 bag도 0xffff번 레코드까지만 가리킬 수 있음
+        let (generator_next, modulator_next) = starts.get(i + 1).copied()
+            .unwrap_or((generators_len.min(0x10000), modulators_len.min(0x10000)));
+        let is_generator = generator_next > generator_start;
+        let is_modulator = modulator_next > modulator_start;
+        if is_generator && generator_next > generators_len {
+            bail!(SF2Error::OutOfRange { chunk, index: generator_next - 1, len: generators_len });
+        }
+        if is_modulator && modulator_next > modulators_len {
+            bail!(SF2Error::OutOfRange { chunk, index: modulator_next - 1, len: modulators_len });
+        }
+        bags.push(SF2Bag {
+            is_generator,
+            generator_start: generator_start as u16,
+            generator_end: if is_generator { (generator_next - 1) as u16 } else { 0 },
+            is_modulator,
+            modulator_start: modulator_start as u16,
+            modulator_end: if is_modulator { (modulator_next - 1) as u16 } else { 0 }
+        });
+    }
+    return Ok(bags);
+}
+
+// 레코드 i가 가리키는 bag 범위(다음 레코드의 시작 위치 직전까지). 시작 위치가 거꾸로 되어 있으면 None
+fn bag_range(bag_indexes: &[usize], i: usize, chunk: &'static str, bags_len: usize) -> anyhow::Result<Option<std::ops::Range<usize>>> {
+    let start = bag_indexes[i];
+    let next = bag_indexes.get(i + 1).copied().unwrap_or(bags_len);
+    if next <= start {
+        return Ok(None);
+    }
+    if next > bags_len {
+        bail!(SF2Error::OutOfRange { chunk, index: next - 1, len: bags_len });
+    }
+    return Ok(Some(start..next));
+}
+
+// bag에 들어 있는 generator와 modulator로 zone을 만듦
+fn make_zone(bag: &SF2Bag, generators: &[SF2Generator], modulators: &[SF2Modulator]) -> SF2Zone {
+    let mut zone = SF2Zone::new();
+    if bag.is_generator {
+        zone.generators.extend_from_slice(&generators[(bag.generator_start as usize)..=(bag.generator_end as usize)]);
+        zone.update_generators_lookup(); // generator를 빠르게 찾을 수 있도록 함
     }
+    if bag.is_modulator {
+        zone.modulators.extend_from_slice(&modulators[(bag.modulator_start as usize)..=(bag.modulator_end as usize)]);
+    }
+    return zone;
+}
+
+fn read_generators(gen: &[u8]) -> Vec<SF2Generator> {
+    return gen.chunks_exact(4).map(|record| {
+        return SF2Generator {
+            operator: u16::from_le_bytes([record[0], record[1]]),
+            amount: SF2GeneratorAmount::new([record[2], record[3]])
+        };
+    }).collect();
 }
 
-type SF2SampleData = Arc<Vec<i16>>;
+fn read_modulators(modulator: &[u8]) -> Vec<SF2Modulator> {
+    return modulator.chunks_exact(10).map(|record| {
+        return SF2Modulator {
+            src_operator: u16::from_le_bytes([record[0], record[1]]),
+            dest_operator: u16::from_le_bytes([record[2], record[3]]),
+            mod_amount: i16::from_le_bytes([record[4], record[5]]),
+            amount_src_operator: u16::from_le_bytes([record[6], record[7]]),
+            mod_trans_operator: u16::from_le_bytes([record[8], record[9]])
+        };
+    }).collect();
+}
+
+pub type SF2SampleData = Arc<Vec<i16>>;
+pub type SF2Sm24Data = Arc<Vec<u8>>;
 
 pub struct SF2 {
     // 사운드폰트 파일 정보
@@ -50,9 +204,18 @@ pub struct SF2 {
     // 사운드폰트 파일에 있는 모든 샘플의 정보
     pub sample_headers: Vec<SF2SampleHeader>,
 
-    // 샘플 데이터 전체를 담고 있음
+    // 샘플 데이터 전체를 담고 있음(필요할 때 불러오는 경우에는 비어 있음)
     sample_data: SF2SampleData,
 
+    // 24비트 샘플(sm24 청크)이면 샘플마다 하위 8비트. 16비트 샘플이면 None
+    sm24_data: Option<SF2Sm24Data>,
+
+    // 필요할 때 불러오는 경우 샘플 데이터를 읽을 로더와 파일 안에서 smpl/sm24 청크 데이터의 위치, 길이(샘플 단위)
+    loader: Option<SampleLoader>,
+    smpl_offset: usize,
+    sm24_offset: Option<usize>,
+    smpl_len: usize,
+
     // 악기(instrument) 데이터
     pub instruments: Vec<SF2Instrument>,
 
@@ -66,413 +229,204 @@ impl SF2 {
     pub fn new<T: Read + Seek>(
         stream: &mut T
     ) -> anyhow::Result<Self> {
+        return Self::read_with_loader(stream, None);
+    }
+
+    /**
+     * 샘플 데이터를 필요할 때 불러오도록 읽음(큰 사운드뱅크용)
+     * 샘플마다 앞부분만 메모리에 올리고 나머지는 재생할 때 백그라운드 스레드가 불러옴
+     */
+    pub fn read_streamed(loader: &SampleLoader) -> anyhow::Result<Self> {
+        return Self::read_with_loader(&mut std::io::Cursor::new(loader.bytes()), Some(loader));
+    }
+
+    // 24비트 샘플(sm24 청크)이 있는지 여부
+    pub fn is_24bit(&self) -> bool {
+        return self.sm24_data.is_some() || self.sm24_offset.is_some();
+    }
+
+    fn read_with_loader<T: Read + Seek>(
+        stream: &mut T,
+        loader: Option<&SampleLoader>
+    ) -> anyhow::Result<Self> {
+        let stream_len = stream.seek(SeekFrom::End(0))?;
         let riff_data = riff::Chunk::read(stream, 0)?;
+        check_chunk(&riff_data, stream_len)?;
         let mut smpl_chunk: Option<riff::Chunk> = None;
-        //let mut sm24_chunk: Option<riff::Chunk> = None; // 호환용이므로 일단 sm24 청크는 안 쓰는 걸로
-        let mut shdr: Vec<u8> = vec![];
-        let mut phdr: Vec<u8> = vec![];
-        let mut pbag: Vec<u8> = vec![];
+        let mut sm24_chunk: Option<riff::Chunk> = None;
+        let mut shdr: Option<Vec<u8>> = None;
+        let mut phdr: Option<Vec<u8>> = None;
+        let mut pbag: Option<Vec<u8>> = None;
         let mut pmod: Vec<u8> = vec![];
-        let mut pgen: Vec<u8> = vec![];
-        let mut inst: Vec<u8> = vec![];
-        let mut ibag: Vec<u8> = vec![];
+        let mut pgen: Option<Vec<u8>> = None;
+        let mut inst: Option<Vec<u8>> = None;
+        let mut ibag: Option<Vec<u8>> = None;
         let mut imod: Vec<u8> = vec![];
-        let mut igen: Vec<u8> = vec![];
+        let mut igen: Option<Vec<u8>> = None;
         let mut info = SF2Info::new();
         let mut sample_headers: Vec<SF2SampleHeader> = vec![];
-        let mut inst_zone = SF2Zone::new();
-        let mut inst_bags: Vec<SF2Bag> = vec![];
         let mut instruments: Vec<SF2Instrument> = vec![];
-        let mut preset_zone = SF2Zone::new();
-        let mut preset_bags: Vec<SF2Bag> = vec![];
         let mut presets: Vec<SF2Preset> = vec![];
 
         let lists_iter = util::unwrap_result_iter(riff_data.iter(stream))?;
         for child in lists_iter {
-            let child_type = child.read_type(stream)?.as_str().to_owned();
+            check_chunk(&child, stream_len)?;
+            let child_type = chunk_id(&child.read_type(stream)?);
             let chunks_iter = util::unwrap_result_iter(child.iter(stream))?;
             for chunk in chunks_iter {
                 if child_type == "INFO" {
-                    match chunk.id().as_str() {
-                        "ifil" => {
-                            let contents = chunk.read_contents(stream)?;
-                            let major = u16::from_le_bytes(contents[0..=1].try_into()?);
-                            let minor = u16::from_le_bytes(contents[2..=3].try_into()?);
-                            info.sf_version = [major, minor];
-                        }
-                        "isng" => info
-                            .target_sound_engine
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "INAM" => info
-                            .bank_name
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "irom" => info
-                            .rom_name
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "iver" => {
-                            let contents = chunk.read_contents(stream)?;
-                            let major = u16::from_le_bytes(contents[0..=1].try_into()?);
-                            let minor = u16::from_le_bytes(contents[2..=3].try_into()?);
-                            info.rom_version = [major, minor];
-                        }
-                        "ICRD" => info
-                            .created_date
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "IENG" => info
-                            .engineers
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "IPRD" => info
-                            .target_hardware
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "ICOP" => info
-                            .copyright
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "ICMT" => info
-                            .comments
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
-                        "ISFT" => info
-                            .created_software
-                            .push_str(std::str::from_utf8(&chunk.read_contents(stream)?)?),
+                    let contents = read_chunk(&chunk, stream, stream_len)?;
+                    match chunk_id(&chunk.id()).as_str() {
+                        "ifil" => info.sf_version = read_version(&contents, "ifil")?,
+                        "isng" => info.target_sound_engine.push_str(&read_info_string(&contents)),
+                        "INAM" => info.bank_name.push_str(&read_info_string(&contents)),
+                        "irom" => info.rom_name.push_str(&read_info_string(&contents)),
+                        "iver" => info.rom_version = read_version(&contents, "iver")?,
+                        "ICRD" => info.created_date.push_str(&read_info_string(&contents)),
+                        "IENG" => info.engineers.push_str(&read_info_string(&contents)),
+                        "IPRD" => info.target_hardware.push_str(&read_info_string(&contents)),
+                        "ICOP" => info.copyright.push_str(&read_info_string(&contents)),
+                        "ICMT" => info.comments.push_str(&read_info_string(&contents)),
+                        "ISFT" => info.created_software.push_str(&read_info_string(&contents)),
                         &_ => {}
                     }
                 } else if child_type == "sdta" {
-                    match chunk.id().as_str() {
+                    match chunk_id(&chunk.id()).as_str() {
                         "smpl" => smpl_chunk = Some(chunk),
-                        //"sm24" => sm24_chunk = Some(chunk),
+                        "sm24" => sm24_chunk = Some(chunk),
                         &_ => {}
                     }
                 } else if child_type == "pdta" {
-                    let contents = chunk.read_contents(stream)?;
-                    match chunk.id().as_str() {
-                        "shdr" => {
-                            shdr = contents;
-                            if shdr.len() % 46 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'shdr'"));
-                            }
-                        }
-                        "inst" => {
-                            inst = contents;
-                            if inst.len() % 22 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'inst'"));
-                            }
-                        }
-                        "ibag" => {
-                            ibag = contents;
-                            if ibag.len() % 4 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'ibag'"));
-                            }
-                        }
-                        "imod" => {
-                            imod = contents;
-                            if imod.len() % 10 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'imod'"));
-                            }
-                        }
-                        "igen" => {
-                            igen = contents;
-                            if igen.len() % 4 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'igen'"));
-                            }
-                        }
-                        "phdr" => {
-                            phdr = contents;
-                            if phdr.len() % 38 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'phdr'"));
-                            }
-                        }
-                        "pbag" => {
-                            pbag = contents;
-                            if pbag.len() % 4 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'pbag'"));
-                            }
-                        }
-                        "pmod" => {
-                            pmod = contents;
-                            if pmod.len() % 10 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'pmod'"));
-                            }
-                        }
-                        "pgen" => {
-                            pgen = contents;
-                            if pgen.len() % 4 != 0 {
-                                bail!(SF2Error::new("invalid length of chunk 'pgen'"));
-                            }
-                        }
+                    let contents = read_chunk(&chunk, stream, stream_len)?;
+                    match chunk_id(&chunk.id()).as_str() {
+                        "shdr" => shdr = Some(contents),
+                        "inst" => inst = Some(contents),
+                        "ibag" => ibag = Some(contents),
+                        "imod" => imod = contents,
+                        "igen" => igen = Some(contents),
+                        "phdr" => phdr = Some(contents),
+                        "pbag" => pbag = Some(contents),
+                        "pmod" => pmod = contents,
+                        "pgen" => pgen = Some(contents),
                         &_ => {}
                     }
                 }
             }
         }
 
+        // modulator 청크는 없어도 되지만 나머지는 꼭 있어야 함
+        let shdr = shdr.ok_or(SF2Error::MissingChunk("shdr"))?;
+        let inst = inst.ok_or(SF2Error::MissingChunk("inst"))?;
+        let ibag = ibag.ok_or(SF2Error::MissingChunk("ibag"))?;
+        let igen = igen.ok_or(SF2Error::MissingChunk("igen"))?;
+        let phdr = phdr.ok_or(SF2Error::MissingChunk("phdr"))?;
+        let pbag = pbag.ok_or(SF2Error::MissingChunk("pbag"))?;
+        let pgen = pgen.ok_or(SF2Error::MissingChunk("pgen"))?;
+        let sample_headers_len = check_records(&shdr, "shdr", 46)?;
+        let instruments_len = check_records(&inst, "inst", 22)?;
+        check_records(&ibag, "ibag", 4)?;
+        check_records(&igen, "igen", 4)?;
+        let presets_len = check_records(&phdr, "phdr", 38)?;
+        check_records(&pbag, "pbag", 4)?;
+        check_records(&pgen, "pgen", 4)?;
+        if imod.len() % 10 != 0 {
+            bail!(SF2Error::BadRecordSize { chunk: "imod", len: imod.len() });
+        }
+        if pmod.len() % 10 != 0 {
+            bail!(SF2Error::BadRecordSize { chunk: "pmod", len: pmod.len() });
+        }
+
         // 샘플 헤더
-        let sample_headers_len = shdr.len() / 46;
         for ii in 0..sample_headers_len {
-            let i = ii * 46;
-            let mut end_of_name = 0;
-            for j in 0..=19 {
-                if shdr[i + j] == 0 { // \0
-                    end_of_name = j - 1;
-                    break;
-                }
-            }
+            let record = &shdr[(ii * 46)..((ii + 1) * 46)];
+            let read_u32 = |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
             let sample_header = SF2SampleHeader {
                 index: ii,
-                name: String::from_utf8(shdr[i..=(i + end_of_name)].try_into()?)?,
-                smpl_start: u32::from_le_bytes(shdr[(i + 20)..=(i + 23)].try_into()?),
-                smpl_end: u32::from_le_bytes(shdr[(i + 24)..=(i + 27)].try_into()?),
-                loop_start: u32::from_le_bytes(shdr[(i + 28)..=(i + 31)].try_into()?),
-                loop_end: u32::from_le_bytes(shdr[(i + 32)..=(i + 35)].try_into()?),
-                sample_rate: u32::from_le_bytes(shdr[(i + 36)..=(i + 39)].try_into()?),
-                base_key: shdr[i + 40],
-                correction: i8::from_le_bytes([shdr[i + 41]]),
-                linked_sample_index: u16::from_le_bytes(shdr[(i + 42)..=(i + 43)].try_into()?),
-                sample_type: u16::from_le_bytes(shdr[(i + 44)..=(i + 45)].try_into()?)
+                name: read_name(&record[0..20]),
+                raw_name: read_raw_name(&record[0..20]),
+                smpl_start: read_u32(20),
+                smpl_end: read_u32(24),
+                loop_start: read_u32(28),
+                loop_end: read_u32(32),
+                sample_rate: read_u32(36),
+                base_key: record[40],
+                correction: record[41] as i8,
+                linked_sample_index: u16::from_le_bytes([record[42], record[43]]),
+                sample_type: u16::from_le_bytes([record[44], record[45]])
             };
             sample_headers.push(sample_header);
         }
 
-        // instrument generator 처리
+        // instrument generator, modulator 처리
         // operator가 무엇이냐에 따라 처리할 파라미터가 달라짐
         // 중요: 여기서 operator가 53번이면 amount는 샘플 헤더 index를 가리킴
-        let inst_generators_len = igen.len() / 4;
-        for ii in 0..inst_generators_len {
-            let i = ii * 4;
-            inst_zone.generators.push(SF2Generator {
-                operator: u16::from_le_bytes(igen[i..=(i + 1)].try_into()?),
-                amount: SF2GeneratorAmount::new(igen[(i + 2)..=(i + 3)].try_into()?)
-            });
-        }
-
-        // instrument modulator 처리
-        let inst_modulators_len = imod.len() / 10;
-        for ii in 0..inst_modulators_len {
-            let i = ii * 10;
-            inst_zone.modulators.push(SF2Modulator {
-                src_operator: u16::from_le_bytes(imod[i..=(i + 1)].try_into()?),
-                dest_operator: u16::from_le_bytes(imod[(i + 2)..=(i + 3)].try_into()?),
-                mod_amount: i16::from_le_bytes(imod[(i + 4)..=(i + 5)].try_into()?),
-                amount_src_operator: u16::from_le_bytes(imod[(i + 6)..=(i + 7)].try_into()?),
-                mod_trans_operator: u16::from_le_bytes(imod[(i + 8)..=(i + 9)].try_into()?)
-            });
-        }
+        let inst_generators = read_generators(&igen);
+        let inst_modulators = read_modulators(&imod);
 
         // instrument bag 처리
         // generator와 modulator들은 bag이라는 단위로 묶여 있음
-        let inst_bags_len = ibag.len() / 4;
-        for ii in 0..inst_bags_len {
-            let i = ii * 4;
-            inst_bags.push(SF2Bag {
-                is_generator: true,
-                generator_start: u16::from_le_bytes(ibag[i..=(i + 1)].try_into()?),
-                generator_end: 0,
-                is_modulator: true,
-                modulator_start: u16::from_le_bytes(ibag[(i + 2)..=(i + 3)].try_into()?),
-                modulator_end: 0
-            });
-        }
-
-        // 각각의 bag에 generator와 modulator가 있는지 검사
-        for i in 0..inst_bags_len {
-            inst_bags[i].generator_end = if i == inst_bags_len - 1 {
-                (inst_generators_len - 1) as u16
-            } else if inst_bags[i + 1].generator_start > inst_bags[i].generator_start {
-                inst_bags[i + 1].generator_start - 1
-            } else {
-                inst_bags[i].is_generator = false;
-                0
-            };
-
-            inst_bags[i].modulator_end = if i == inst_bags_len - 1 {
-                (inst_modulators_len - 1) as u16
-            } else if inst_bags[i + 1].modulator_start > inst_bags[i].modulator_start {
-                inst_bags[i + 1].modulator_start - 1
-            } else {
-                inst_bags[i].is_modulator = false;
-                0
-            };
-        }
+        let inst_bags = read_bags(&ibag, "ibag", inst_generators.len(), inst_modulators.len())?;
 
         // instrument 처리
-        let instruments_len = inst.len() / 22;
         for ii in 0..instruments_len {
-            let i = ii * 22;
-            let mut end_of_name = 0;
-            for j in 0..=19 {
-                if inst[i + j] == 0 {
-                    end_of_name = j - 1;
-                    break;
-                }
-            }
+            let record = &inst[(ii * 22)..((ii + 1) * 22)];
             let instrument = SF2Instrument {
-                name: String::from_utf8(inst[i..=(i + end_of_name)].try_into()?)?,
-                ibag_index: u16::from_le_bytes(inst[(i + 20)..=(i + 21)].try_into()?),
+                name: read_name(&record[0..20]),
+                raw_name: read_raw_name(&record[0..20]),
+                ibag_index: u16::from_le_bytes([record[20], record[21]]),
                 zones: vec![] // 이 다음 반복문에서 채울 거임
             };
             instruments.push(instrument);
         }
 
         // instrument별로 bag을 풀어서 zone 분류
+        // 샘플 index는 여기서 확인하지 않고 변환할 때 확인함(잘못된 zone만 건너뜀)
+        let ibag_indexes: Vec<usize> = instruments.iter().map(|instrument| instrument.ibag_index as usize).collect();
         for i in 0..instruments_len {
-            let mut is_ibag = true;
-            let ibag_start = instruments[i].ibag_index as usize;
-            let ibag_end = if i == instruments_len - 1 {
-                inst_bags_len - 1
-            } else if instruments[i + 1].ibag_index > ibag_start as u16 {
-                (instruments[i + 1].ibag_index - 1) as usize
-            } else {
-                is_ibag = false;
-                0
-            };
-
-            if !is_ibag {
+            let Some(bag_range) = bag_range(&ibag_indexes, i, "inst", inst_bags.len())? else {
                 continue;
-            }
-
-            for j in ibag_start..=ibag_end {
-                let mut zone = SF2Zone::new();
-                if inst_bags[j].is_generator {
-                    for k in (inst_bags[j].generator_start as usize)
-                        ..=(inst_bags[j].generator_end as usize)
-                    {
-                        zone.generators.push(inst_zone.generators[k]);
-                        if inst_zone.generators[k].operator == gen_ids::SAMPLE_ID {
-                            zone.target_sample_index = Some(inst_zone.generators[k].amount.get_u16() as usize);
-                        }
-                    }
-                    zone.update_generators_lookup(); // generator를 빠르게 찾을 수 있도록 함
-                }
-
-                if inst_bags[j].is_modulator {
-                    for k in (inst_bags[j].modulator_start as usize)
-                        ..=(inst_bags[j].modulator_end as usize)
-                    {
-                        zone.modulators.push(inst_zone.modulators[k]);
-                    }
-                }
+            };
+            for bag in inst_bags[bag_range].iter() {
+                let mut zone = make_zone(bag, &inst_generators, &inst_modulators);
+                zone.target_sample_index = zone.get_gen(gen_ids::SAMPLE_ID).map(|amount| amount.get_u16() as usize);
                 instruments[i].zones.push(Arc::new(zone));
             }
         }
 
-        // preset generator 처리
-        // 중요: 여기서 operator가 41번이면 amount는 instrument 헤더 index를 가리킴
-        let preset_generators_len = pgen.len() / 4;
-        for ii in 0..preset_generators_len {
-            let i = ii * 4;
-            preset_zone.generators.push(SF2Generator {
-                operator: u16::from_le_bytes(pgen[i..=(i + 1)].try_into()?),
-                amount: SF2GeneratorAmount::new(pgen[(i + 2)..=(i + 3)].try_into()?)
-            });
-        }
-
-        // preset modulator 처리
-        let preset_modulators_len = pmod.len() / 10;
-        for ii in 0..preset_modulators_len {
-            let i = ii * 10;
-            preset_zone.modulators.push(SF2Modulator {
-                src_operator: u16::from_le_bytes(pmod[i..=(i + 1)].try_into()?),
-                dest_operator: u16::from_le_bytes(pmod[(i + 2)..=(i + 3)].try_into()?),
-                mod_amount: i16::from_le_bytes(pmod[(i + 4)..=(i + 5)].try_into()?),
-                amount_src_operator: u16::from_le_bytes(pmod[(i + 6)..=(i + 7)].try_into()?),
-                mod_trans_operator: u16::from_le_bytes(pmod[(i + 8)..=(i + 9)].try_into()?)
-            });
-        }
-
-        // preset bag 처리
+        // preset generator, modulator, bag 처리
         // instrument 쪽에서 하는 것과 대부분 같음
-        let preset_bags_len = pbag.len() / 4;
-        for ii in 0..preset_bags_len {
-            let i = ii * 4;
-            preset_bags.push(SF2Bag {
-                is_generator: true,
-                generator_start: u16::from_le_bytes(pbag[i..=(i + 1)].try_into()?),
-                generator_end: 0,
-                is_modulator: true,
-                modulator_start: u16::from_le_bytes(pbag[(i + 2)..=(i + 3)].try_into()?),
-                modulator_end: 0
-            });
-        }
-
-        for i in 0..preset_bags_len {
-            preset_bags[i].generator_end = if i == preset_bags_len - 1 {
-                (preset_generators_len - 1) as u16
-            } else if preset_bags[i + 1].generator_start > preset_bags[i].generator_start {
-                preset_bags[i + 1].generator_start - 1
-            } else {
-                preset_bags[i].is_generator = false;
-                0
-            };
-
-            preset_bags[i].modulator_end = if i == preset_bags_len - 1 {
-                (preset_modulators_len - 1) as u16
-            } else if preset_bags[i + 1].modulator_start > preset_bags[i].modulator_start {
-                preset_bags[i + 1].modulator_start - 1
-            } else {
-                preset_bags[i].is_modulator = false;
-                0
-            };
-        }
+        // 중요: 여기서 operator가 41번이면 amount는 instrument 헤더 index를 가리킴
+        let preset_generators = read_generators(&pgen);
+        let preset_modulators = read_modulators(&pmod);
+        let preset_bags = read_bags(&pbag, "pbag", preset_generators.len(), preset_modulators.len())?;
 
         // preset 처리
-        let presets_len = phdr.len() / 38;
         for ii in 0..presets_len {
-            let i = ii * 38;
-            let mut end_of_name = 0;
-            for j in 0..=19 {
-                if phdr[i + j] == 0 {
-                    end_of_name = j - 1;
-                    break;
-                }
-            }
+            let record = &phdr[(ii * 38)..((ii + 1) * 38)];
+            let read_u16 = |i: usize| u16::from_le_bytes([record[i], record[i + 1]]);
+            let read_u32 = |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
             let preset = SF2Preset {
-                name: String::from_utf8(phdr[i..=(i + end_of_name)].try_into()?)?,
-                program_no: u16::from_le_bytes(phdr[(i + 20)..=(i + 21)].try_into()?),
-                bank: u16::from_le_bytes(phdr[(i + 22)..=(i + 23)].try_into()?),
-                pbag_index: u16::from_le_bytes(phdr[(i + 24)..=(i + 25)].try_into()?),
+                name: read_name(&record[0..20]),
+                raw_name: read_raw_name(&record[0..20]),
+                program_no: read_u16(20),
+                bank: read_u16(22),
+                pbag_index: read_u16(24),
                 zones: vec![],
-                library: u32::from_le_bytes(phdr[(i + 26)..=(i + 29)].try_into()?),
-                genre: u32::from_le_bytes(phdr[(i + 30)..=(i + 33)].try_into()?),
-                morph: u32::from_le_bytes(phdr[(i + 34)..=(i + 37)].try_into()?)
+                library: read_u32(26),
+                genre: read_u32(30),
+                morph: read_u32(34)
             };
             presets.push(preset);
         }
 
+        let pbag_indexes: Vec<usize> = presets.iter().map(|preset| preset.pbag_index as usize).collect();
         for i in 0..presets_len {
-            let mut is_pbag = true;
-            let pbag_start = presets[i].pbag_index as usize;
-            let pbag_end = if i == presets_len - 1 {
-                preset_bags_len - 1
-            } else if presets[i + 1].pbag_index > pbag_start as u16 {
-                (presets[i + 1].pbag_index - 1) as usize
-            } else {
-                is_pbag = false;
-                0
-            };
-
-            if !is_pbag {
+            let Some(bag_range) = bag_range(&pbag_indexes, i, "phdr", preset_bags.len())? else {
                 continue;
             };
-
-            for j in pbag_start..=pbag_end {
-                let mut zone = SF2Zone::new();
-                if preset_bags[j].is_generator {
-                    for k in (preset_bags[j].generator_start as usize)
-                        ..=(preset_bags[j].generator_end as usize)
-                    {
-                        zone.generators.push(preset_zone.generators[k]);
-                        if preset_zone.generators[k].operator == gen_ids::INSTRUMENT {
-                            zone.target_instrument_index = Some(preset_zone.generators[k].amount.get_u16() as usize);
-                        }
-                    }
-                    zone.update_generators_lookup(); // generator를 빠르게 찾을 수 있도록 함
-                }
-
-                if preset_bags[j].is_modulator {
-                    for k in (preset_bags[j].modulator_start as usize)
-                        ..=(preset_bags[j].modulator_end as usize)
-                    {
-                        zone.modulators.push(preset_zone.modulators[k]);
-                    }
-                }
+            for bag in preset_bags[bag_range].iter() {
+                let mut zone = make_zone(bag, &preset_generators, &preset_modulators);
+                zone.target_instrument_index = zone.get_gen(gen_ids::INSTRUMENT).map(|amount| amount.get_u16() as usize);
                 presets[i].zones.push(Arc::new(zone));
             }
         }
@@ -481,56 +435,81 @@ impl SF2 {
         // 참조 소스 코드: https://github.com/sinshu/rustysynth/blob/main/rustysynth/src/binary_reader.rs
         let smpl_chunk_info = match smpl_chunk {
             Some(chunk) => chunk,
-            None => bail!(SF2Error::new("No smpl chunk"))
+            None => bail!(SF2Error::MissingChunk("smpl"))
         };
+        check_chunk(&smpl_chunk_info, stream_len)?;
         let sample_len = smpl_chunk_info.len() as usize / 2;
-        let mut sample_data: Vec<i16> = vec![0; sample_len];
-        let data = unsafe {
-            // 속도를 위해 안전성을 희생하는 부분
-            // 이 부분 때문에 문제가 생기면 다시 아래 코드로 돌아갈 예정
-            let ptr = sample_data.as_mut_ptr() as *mut u8;
-            std::slice::from_raw_parts_mut(ptr, smpl_chunk_info.len() as usize)
-        };
-        stream.seek(SeekFrom::Start(smpl_chunk_info.offset() + 8))?;
-        stream.read_exact(data)?;
-
-        // 기존 샘플 처리 코드(모조리 i32형으로 변환)
-        // 여기서 시간이 좀 많이 걸려서 위에 있는 처리 코드로 바꿨음
-        /*let smpl_contents = match smpl_chunk {
-            Some(chunk) => chunk.read_contents(stream)?,
-            None => bail!(SF2Error::new("No smpl chunk"))
-        };
-        let sm24_contents = match sm24_chunk {
-            Some(chunk) => Some(chunk.read_contents(stream)?),
+
+        // sf3: 압축된 샘플이 있으면 불러올 때 압축을 모두 풀어야 함(vorbis 모듈 참고)
+        let compressed = sample_headers.iter().any(vorbis::is_compressed);
+
+        // sm24 청크는 2.04 이상이고 크기가 smpl 청크의 샘플 수(짝수로 올림한 값도 허용)와 같을 때만 씀(아니면 무시함)
+        let sm24_len_valid = |len: usize| len == sample_len || len == sample_len + sample_len % 2;
+        let sm24_chunk = match sm24_chunk {
+            Some(chunk) if !compressed && info.sf_version >= [2, 4] && sm24_len_valid(chunk.len() as usize)
+                && check_chunk(&chunk, stream_len).is_ok() => Some(chunk),
+            Some(_) => {
+                log::warn!("Ignoring invalid sm24 chunk");
+                None
+            },
             None => None
         };
-        let sample_len = smpl_contents.len() / 2;
-        let mut sample_data: Vec<i32> = vec![];
-        match sm24_contents {
-            Some(sm24_contents) => {
-                for i in 0..sample_len {
-                    sample_data.push(i32::from_le_bytes([
-                        0, sm24_contents[i],
-                        smpl_contents[i * 2],
-                        smpl_contents[i * 2 + 1]
-                    ]));
-                }
+
+        if compressed && loader.is_some() {
+            log::info!("Soundbank has compressed samples, decoding all samples at load time instead of streaming");
+        }
+        if let Some(loader) = loader.filter(|_| !compressed) {
+            return Ok(Self {
+                info,
+                sample_headers,
+                sample_data: Arc::new(vec![]),
+                sm24_data: None,
+                loader: Some(loader.clone()),
+                smpl_offset: smpl_chunk_info.offset() as usize + 8,
+                sm24_offset: sm24_chunk.map(|chunk| chunk.offset() as usize + 8),
+                smpl_len: sample_len,
+                instruments,
+                presets
+            });
+        }
+        let mut sample_data: Vec<i16>;
+        if compressed {
+            // 압축된 샘플과 압축되지 않은 샘플이 섞여 있을 수 있음. 둘 다 샘플 데이터에 새로 옮김
+            let smpl = smpl_chunk_info.read_contents(stream)?;
+            sample_data = vorbis::decode_samples(&mut sample_headers, &smpl);
+        } else {
+            sample_data = vec![0; sample_len];
+            // 속도를 위해 안전성을 희생하는 부분
+            // 이 부분 때문에 문제가 생기면 i16::from_le_bytes로 하나씩 바꾸는 방식으로 돌아갈 예정
+            // 크기가 홀수인 청크도 있으므로 마지막 1바이트는 버림
+            let data = unsafe {
+                let ptr = sample_data.as_mut_ptr() as *mut u8;
+                std::slice::from_raw_parts_mut(ptr, sample_len * 2)
+            };
+            stream.seek(SeekFrom::Start(smpl_chunk_info.offset() + 8))?;
+            stream.read_exact(data)?;
+        }
+
+        // 24비트면 하위 8비트는 따로 둠(16비트 샘플은 위와 같이 바로 읽음)
+        let sm24_data = match sm24_chunk {
+            Some(chunk) => {
+                let mut contents = chunk.read_contents(stream)?;
+                contents.truncate(sample_len);
+                Some(Arc::new(contents))
             },
-            None => {
-                for i in 0..sample_len {
-                    sample_data.push(i32::from_le_bytes([
-                        0, 0,
-                        smpl_contents[i * 2],
-                        smpl_contents[i * 2 + 1]
-                    ]));
-                }
-            }
-        }*/
+            None => None
+        };
 
+        let sample_data_len = sample_data.len();
         return Ok(Self {
             info: info,
             sample_headers: sample_headers,
             sample_data: Arc::new(sample_data),
+            sm24_data,
+            loader: None,
+            smpl_offset: 0,
+            sm24_offset: None,
+            smpl_len: sample_data_len,
             instruments: instruments,
             presets: presets
         });
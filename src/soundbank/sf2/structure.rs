@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use std::collections::HashMap;
 
+#[derive(Debug, PartialEq)]
 pub struct SF2Info {
     pub sf_version: [u16; 2], // ifil
     pub target_sound_engine: String, // isng
@@ -35,9 +36,12 @@ impl SF2Info {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct SF2SampleHeader {
     pub index: usize,
     pub name: String,
+    // 파일에 있던 이름 20바이트. name을 바꾸지 않았으면 저장할 때 이걸 그대로 씀
+    pub raw_name: [u8; 20],
     pub smpl_start: u32,
     pub smpl_end: u32,
     pub loop_start: u32,
@@ -58,7 +62,7 @@ pub struct SF2Bag {
     pub modulator_end: u16
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct SF2Modulator {
     pub src_operator: u16,
     pub dest_operator: u16,
@@ -69,7 +73,7 @@ pub struct SF2Modulator {
 
 // i16형의 값이 쓰일 수도 있고 u16형의 값이 쓰일 수도 있음
 // 이러면 값 하나가 총 6바이트를 차지하게 되는데, 현대의 컴퓨터라면 문제 없을 거라 생각함
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct SF2GeneratorAmount {
     i16_amount: i16,
     u16_amount: u16,
@@ -119,12 +123,13 @@ impl SF2GeneratorAmount {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct SF2Generator {
     pub operator: u16,
     pub amount: SF2GeneratorAmount
 }
 
+#[derive(Debug, PartialEq)]
 pub struct SF2Zone {
     pub modulators: Vec<SF2Modulator>,
     pub generators: Vec<SF2Generator>,
@@ -157,14 +162,18 @@ impl SF2Zone {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct SF2Instrument {
     pub name: String,
+    pub raw_name: [u8; 20],
     pub ibag_index: u16,
     pub zones: Vec<Arc<SF2Zone>> // 첫 번째 zone은 instrument 전체에 적용되는 global zone이라 sample index가 없으니 주의!
 }
 
+#[derive(Debug, PartialEq)]
 pub struct SF2Preset {
     pub name: String,
+    pub raw_name: [u8; 20],
     pub program_no: u16,
     pub bank: u16,
     pub pbag_index: u16,
@@ -1,18 +1,716 @@
+/**
+ * sf2 => wsbk 변환
+ *
+ * - 샘플: zone에서 쓰는 샘플과 샘플 관련 generator(시작/끝/루프 위치 offset, 루프 방식, root key) 조합마다 wsbk 샘플을 하나씩 만듦
+ *   서로 연결된 left/right 샘플을 쓰는 zone 2개는 stereo 샘플 하나로 합침
+ * - instrument/preset zone => Region. global zone은 다른 zone의 기본값으로 합쳐서 없앰
+ * - generator => source 없는 Articulator
+ *   instrument 쪽은 절대값, preset 쪽은 instrument 쪽에 더하는 값으로 바꿈(synth에서 둘을 더해서 씀)
+ * - keynum, velocity, exclusiveClass generator(instrument 쪽만) => Region.generators
+ * - modulator(instrument는 기본 modulator 포함) => Articulator
+ *   synth에서 직접 처리하는 컨트롤러(pitch wheel, cc 1/7/10/11/91/93)의 기본 modulator는 두 번 적용되지 않도록 뺌
+ *
+ * 참고문헌: https://static.choyunjin.kr/files/pdf/sfspec/sfspec24.pdf (8.1.2 generator, 8.4 modulator)
+ */
+
+use std::collections::{ BTreeMap, HashMap };
 use crate::soundbank::wsbk;
-//use crate::soundbank::sf2;
+use crate::soundbank::wsbk::consts::{ artc_src, artc_dest, artc_transform, region_gen };
+use crate::synth::articulator::{ AriculationValues, NEAR_ZERO_VALUE };
+use super::gen_ids;
+use super::structure::*;
+use super::bank::SF2BankSample;
+use crate::soundbank::stream::SampleLayout;
+
+// timecent/cent 1 = wsbk 시간/Hz 단위 값 10000 / 1200
+const CENT_TO_VALUE: f64 = 10000.0 / 1200.0;
+
+// timecent 0(1초)을 wsbk 시간 단위로 바꾼 값(10000 * log2(1000))
+const ONE_SECOND_VALUE: f64 = 99657.84284662087;
+
+// 절대 cent 0(8.176Hz)을 wsbk Hz 단위로 바꾼 값(10000 * log2(8.176))
+const ZERO_CENT_HZ_VALUE: f64 = 30314.0;
+
+// 샘플 종류(sfSampleType)
+const SAMPLE_TYPE_RIGHT: u16 = 0x0002;
+const SAMPLE_TYPE_LEFT: u16 = 0x0004;
+const SAMPLE_TYPE_ROM: u16 = 0x8000;
+
+/**
+ * sf2 2.04 기본 modulator
+ * 앞의 3개만 articulator로 바꾸고 나머지는 synth에서 직접 처리함(NATIVE_MODULATORS)
+ */
+const DEFAULT_MODULATORS: [SF2Modulator; 10] = [
+    // velocity => 음량(concave, 960cB)
+    default_modulator(0x0502, gen_ids::INITIAL_ATTEUNATION, 960, 0x0000),
+    // velocity => filter cutoff(-2400cent)
+    default_modulator(0x0102, gen_ids::INITIAL_FILTER_FC, -2400, 0x0000),
+    // channel pressure => 비브라토(50cent)
+    default_modulator(0x000d, gen_ids::VIB_LFO_TO_PITCH, 50, 0x0000),
+    // cc1 => 비브라토(50cent)
+    default_modulator(0x0081, gen_ids::VIB_LFO_TO_PITCH, 50, 0x0000),
+    // cc7 => 음량(concave, 960cB)
+    default_modulator(0x0587, gen_ids::INITIAL_ATTEUNATION, 960, 0x0000),
+    // cc10 => pan(1000)
+    default_modulator(0x028a, gen_ids::PAN, 1000, 0x0000),
+    // cc11 => 음량(concave, 960cB)
+    default_modulator(0x058b, gen_ids::INITIAL_ATTEUNATION, 960, 0x0000),
+    // cc91 => reverb send(200)
+    default_modulator(0x00db, gen_ids::REVERB_EFFECTS_SEND, 200, 0x0000),
+    // cc93 => chorus send(200)
+    default_modulator(0x00dd, gen_ids::CHORUS_EFFECTS_SEND, 200, 0x0000),
+    // pitch wheel * pitch wheel sensitivity => 피치(12700cent)
+    default_modulator(0x020e, gen_ids::FINE_TUNE, 12700, 0x0010)
+];
+
+// synth에서 직접 처리하는 컨트롤러의 modulator (source, destination, amount source)
+const NATIVE_MODULATORS: [(u16, u16, u16); 7] = [
+    (0x0081, gen_ids::VIB_LFO_TO_PITCH, 0x0000),
+    (0x0587, gen_ids::INITIAL_ATTEUNATION, 0x0000),
+    (0x028a, gen_ids::PAN, 0x0000),
+    (0x058b, gen_ids::INITIAL_ATTEUNATION, 0x0000),
+    (0x00db, gen_ids::REVERB_EFFECTS_SEND, 0x0000),
+    (0x00dd, gen_ids::CHORUS_EFFECTS_SEND, 0x0000),
+    (0x020e, gen_ids::FINE_TUNE, 0x0010)
+];
+
+const fn default_modulator(src_operator: u16, dest_operator: u16, mod_amount: i16, amount_src_operator: u16) -> SF2Modulator {
+    return SF2Modulator {
+        src_operator,
+        dest_operator,
+        mod_amount,
+        amount_src_operator,
+        mod_trans_operator: 0
+    };
+}
+
+// 같은 modulator인지(amount만 다르면 같은 modulator로 봄)
+fn is_same_modulator(a: &SF2Modulator, b: &SF2Modulator) -> bool {
+    return a.src_operator == b.src_operator
+        && a.dest_operator == b.dest_operator
+        && a.amount_src_operator == b.amount_src_operator
+        && a.mod_trans_operator == b.mod_trans_operator;
+}
+
+fn is_native_modulator(modulator: &SF2Modulator) -> bool {
+    return NATIVE_MODULATORS.iter().any(|(src, dest, amount_src)| {
+        return modulator.src_operator == *src
+            && modulator.dest_operator == *dest
+            && modulator.amount_src_operator == *amount_src;
+    });
+}
+
+// 같은 modulator가 있으면 바꾸고 없으면 추가함
+fn merge_modulators(modulators: &mut Vec<SF2Modulator>, overrides: &[SF2Modulator]) {
+    for modulator in overrides.iter() {
+        match modulators.iter_mut().find(|m| is_same_modulator(m, modulator)) {
+            Some(m) => *m = *modulator,
+            None => modulators.push(*modulator)
+        }
+    }
+}
+
+/**
+ * generator 값이 바로 더해지는 destination과 generator 단위 1당 wsbk 값
+ * 시간 = timecent, 주파수 = cent, 음량 = cB, pan/send/sustain = 0.1%
+ */
+fn generator_target(gen: u16) -> Option<(u32, f64)> {
+    return Some(match gen {
+        gen_ids::INITIAL_ATTEUNATION => (artc_dest::GAIN, -10.0),
+        gen_ids::PAN => (artc_dest::PAN, 20.0),
+        gen_ids::REVERB_EFFECTS_SEND => (artc_dest::REVERB_SEND_COEFF, 10.0),
+        gen_ids::CHORUS_EFFECTS_SEND => (artc_dest::CHORUS_SEND_COEFF, 10.0),
+        gen_ids::INITIAL_FILTER_FC => (artc_dest::LPF_CUTOFF, CENT_TO_VALUE),
+        gen_ids::INITIAL_FILTER_Q => (artc_dest::LPF_Q, 10.0),
+        gen_ids::COARSE_TUNE => (artc_dest::PITCH, 1000.0),
+        gen_ids::FINE_TUNE => (artc_dest::PITCH, 10.0),
+        gen_ids::VIB_LFO_TO_PITCH => (artc_dest::VIBRATO_LFO_TO_PITCH, 10.0),
+        gen_ids::DELAY_MOD_LFO => (artc_dest::MODULATION_LFO_START_DELAY, CENT_TO_VALUE),
+        gen_ids::FREQ_MOD_LFO => (artc_dest::MODULATION_LFO_FREQUENCY, CENT_TO_VALUE),
+        gen_ids::DELAY_VIB_LFO => (artc_dest::VIBRATO_LFO_START_DELAY, CENT_TO_VALUE),
+        gen_ids::FREQ_VIB_LFO => (artc_dest::VIBRATO_LFO_FREQUENCY, CENT_TO_VALUE),
+        gen_ids::DELAY_VOL_ENV => (artc_dest::VOLUME_ENV_DELAY, CENT_TO_VALUE),
+        gen_ids::ATTACK_VOL_ENV => (artc_dest::VOLUME_ENV_ATTACK, CENT_TO_VALUE),
+        gen_ids::HOLD_VOL_ENV => (artc_dest::VOLUME_ENV_HOLD, CENT_TO_VALUE),
+        gen_ids::DECAY_VOL_ENV => (artc_dest::VOLUME_ENV_DECAY, CENT_TO_VALUE),
+        gen_ids::SUSTAIN_VOL_ENV => (artc_dest::VOLUME_ENV_SUSTAIN, -10.0),
+        gen_ids::RELEASE_VOL_ENV => (artc_dest::VOLUME_ENV_RELEASE, CENT_TO_VALUE),
+        gen_ids::DELAY_MOD_ENV => (artc_dest::MODULATION_ENV_DELAY, CENT_TO_VALUE),
+        gen_ids::ATTACK_MOD_ENV => (artc_dest::MODULATION_ENV_ATTACK, CENT_TO_VALUE),
+        gen_ids::HOLD_MOD_ENV => (artc_dest::MODULATION_ENV_HOLD, CENT_TO_VALUE),
+        gen_ids::DECAY_MOD_ENV => (artc_dest::MODULATION_ENV_DECAY, CENT_TO_VALUE),
+        gen_ids::SUSTAIN_MOD_ENV => (artc_dest::MODULATION_ENV_SUSTAIN, -10.0),
+        gen_ids::RELEASE_MOD_ENV => (artc_dest::MODULATION_ENV_RELEASE, CENT_TO_VALUE),
+        _ => return None
+    });
+}
+
+/**
+ * lfo/envelope를 거쳐서 적용되는 generator의 (source, source 변환 방식, destination, generator 단위 1당 wsbk 값)
+ * lfo는 -1.0 - 1.0 범위로 씀
+ */
+fn generator_route(gen: u16) -> Option<(u32, u8, u32, f64)> {
+    let lfo = artc_transform::LINEAR | artc_transform::BIPOLAR;
+    return Some(match gen {
+        gen_ids::MOD_LFO_TO_PITCH => (artc_src::MODULATION_LFO, lfo, artc_dest::PITCH, 10.0),
+        gen_ids::MOD_LFO_TO_FILTER_FC => (artc_src::MODULATION_LFO, lfo, artc_dest::LPF_CUTOFF, CENT_TO_VALUE),
+        gen_ids::MOD_LFO_TO_VOLUME => (artc_src::MODULATION_LFO, lfo, artc_dest::GAIN, 10.0),
+        gen_ids::MOD_ENV_TO_PITCH => (artc_src::MODULATION_ENV, artc_transform::LINEAR, artc_dest::PITCH, 10.0),
+        gen_ids::MOD_ENV_TO_FILTER_FC => (artc_src::MODULATION_ENV, artc_transform::LINEAR, artc_dest::LPF_CUTOFF, CENT_TO_VALUE),
+        _ => return None
+    });
+}
+
+// key 번호에 따라 바뀌는 envelope 시간 generator의 destination
+fn keynum_target(gen: u16) -> Option<u32> {
+    return match gen {
+        gen_ids::KEYNUM_TO_VOL_ENV_HOLD => Some(artc_dest::VOLUME_ENV_HOLD),
+        gen_ids::KEYNUM_TO_VOL_ENV_DELAY => Some(artc_dest::VOLUME_ENV_DECAY),
+        gen_ids::KEYNUM_TO_MOD_ENV_HOLD => Some(artc_dest::MODULATION_ENV_HOLD),
+        gen_ids::KEYNUM_TO_MOD_ENV_DECAY => Some(artc_dest::MODULATION_ENV_DECAY),
+        _ => None
+    };
+}
+
+/**
+ * generator 값 0이 나타내는 wsbk 값에서 synth 기본값을 뺀 값
+ * instrument 쪽 generator(절대값)를 바꿀 때 더함
+ */
+fn absolute_base(gen: u16) -> f64 {
+    let defaults = AriculationValues::new();
+    return match gen {
+        gen_ids::FREQ_MOD_LFO => ZERO_CENT_HZ_VALUE - defaults.modulation_lfo_freq as f64,
+        gen_ids::FREQ_VIB_LFO => ZERO_CENT_HZ_VALUE - defaults.vibrato_lfo_freq as f64,
+        gen_ids::INITIAL_FILTER_FC => ZERO_CENT_HZ_VALUE - defaults.lpf_cutoff as f64,
+        gen_ids::DELAY_MOD_LFO | gen_ids::DELAY_VIB_LFO
+            | gen_ids::DELAY_VOL_ENV | gen_ids::ATTACK_VOL_ENV | gen_ids::HOLD_VOL_ENV
+            | gen_ids::DECAY_VOL_ENV | gen_ids::RELEASE_VOL_ENV
+            | gen_ids::DELAY_MOD_ENV | gen_ids::ATTACK_MOD_ENV | gen_ids::HOLD_MOD_ENV
+            | gen_ids::DECAY_MOD_ENV | gen_ids::RELEASE_MOD_ENV => ONE_SECOND_VALUE - NEAR_ZERO_VALUE as f64,
+        _ => 0.0
+    };
+}
+
+/**
+ * modulator source를 articulator source와 변환 방식으로 바꿈
+ * 지원하지 않는 source(다른 modulator와 연결 등)면 None
+ */
+fn modulator_source(operator: u16) -> Option<(u32, u8)> {
+    let index = (operator & 0x7f) as u32;
+    let src = if operator & 0x80 != 0 {
+        artc_src::midi_cc(index)
+    } else {
+        match index {
+            0 => artc_src::NONE,
+            2 => artc_src::NOTE_ON_VELOCITY,
+            3 => artc_src::NOTE_NUMBER,
+            10 => artc_src::NOTE_AFTERTOUCH,
+            13 => artc_src::CHANNEL_AFTERTOUCH,
+            14 => artc_src::PITCH_WHEEL,
+            16 => artc_src::midi_rpn(0, 0),
+            _ => return None
+        }
+    };
+
+    let mut transform = match operator >> 10 {
+        0 => artc_transform::LINEAR,
+        1 => artc_transform::CONCAVE,
+        2 => artc_transform::CONVEX,
+        3 => artc_transform::SWITCH,
+        _ => return None
+    };
+    if operator & 0x100 != 0 {
+        transform |= artc_transform::INVERTED;
+    }
+    if operator & 0x200 != 0 {
+        transform |= artc_transform::BIPOLAR;
+    }
+    return Some((src, transform));
+}
+
+fn make_articulator(src: u32, src_transform: u8, control: u32, control_transform: u8, destination: u32, scale: f64) -> wsbk::Articulator {
+    return wsbk::Articulator {
+        src,
+        src_transform,
+        control,
+        control_transform,
+        destination,
+        main_transform: artc_transform::LINEAR,
+        scale
+    };
+}
+
+fn modulator_to_articulator(modulator: &SF2Modulator) -> Option<wsbk::Articulator> {
+    // source가 없는 modulator는 항상 0임
+    if modulator.src_operator & 0xff7f == 0 || modulator.dest_operator & 0x8000 != 0 {
+        return None;
+    }
+    let (src, src_transform) = modulator_source(modulator.src_operator)?;
+    let (control, control_transform) = modulator_source(modulator.amount_src_operator)?;
+    let amount = modulator.mod_amount as f64;
+
+    if let Some((dest, scale)) = generator_target(modulator.dest_operator) {
+        return Some(make_articulator(src, src_transform, control, control_transform, dest, amount * scale));
+    }
+    // lfo/envelope를 거치는 generator는 (lfo/envelope) * source 형태로만 바꿀 수 있음
+    if let Some((route_src, route_transform, dest, scale)) = generator_route(modulator.dest_operator) {
+        if control == artc_src::NONE {
+            return Some(make_articulator(route_src, route_transform, src, src_transform, dest, amount * scale));
+        }
+    }
+    log::debug!("Unsupported sf2 modulator: {:04x} => {}", modulator.src_operator, modulator.dest_operator);
+    return None;
+}
+
+// global zone과 zone의 generator를 합침(zone 쪽이 우선)
+fn merge_generators(global: Option<&SF2Zone>, zone: &SF2Zone) -> HashMap<u16, SF2GeneratorAmount> {
+    let mut generators = HashMap::new();
+    for gen in global.iter().flat_map(|global| global.generators.iter()).chain(zone.generators.iter()) {
+        generators.insert(gen.operator, gen.amount);
+    }
+    return generators;
+}
+
+fn get_range(generators: &HashMap<u16, SF2GeneratorAmount>, gen: u16) -> (u8, u8) {
+    return match generators.get(&gen) {
+        Some(amount) => {
+            let [low, high] = amount.get_u8_array();
+            (low.min(127), high.min(127))
+        },
+        None => (0, 127)
+    };
+}
+
+fn get_i16(generators: &HashMap<u16, SF2GeneratorAmount>, gen: u16) -> Option<i16> {
+    return generators.get(&gen).map(|amount| amount.get_i16());
+}
+
+/**
+ * generator들을 articulator로 바꿈
+ * absolute: true면 instrument 쪽(절대값), false면 preset 쪽(더하는 값)
+ */
+fn generators_to_articulators(
+    generators: &HashMap<u16, SF2GeneratorAmount>,
+    absolute: bool
+) -> Vec<wsbk::Articulator> {
+    // (source, source 변환 방식, destination) => scale
+    // 같은 destination에 더해지는 값은 하나로 합침
+    let mut sums: BTreeMap<(u32, u8, u32), f64> = BTreeMap::new();
+    let mut add = |src: u32, transform: u8, dest: u32, val: f64| {
+        *sums.entry((src, transform, dest)).or_insert(0.0) += val;
+    };
+
+    let mut gen_ids: Vec<u16> = generators.keys().copied().collect();
+    gen_ids.sort();
+    if absolute {
+        // sf2의 lfo 주파수 기본값(8.176Hz)은 synth 기본값과 다름
+        for gen in [gen_ids::FREQ_MOD_LFO, gen_ids::FREQ_VIB_LFO] {
+            if !generators.contains_key(&gen) {
+                gen_ids.push(gen);
+            }
+        }
+    }
+
+    for gen in gen_ids {
+        let mut amount = get_i16(generators, gen).unwrap_or(0) as f64;
+        if let Some((dest, scale)) = generator_target(gen) {
+            if absolute {
+                // sustain은 0 - 100%를 벗어나지 않도록 함
+                if gen == gen_ids::SUSTAIN_VOL_ENV || gen == gen_ids::SUSTAIN_MOD_ENV {
+                    amount = amount.max(0.0).min(1000.0);
+                } else if scale == CENT_TO_VALUE && gen != gen_ids::INITIAL_FILTER_FC {
+                    amount = amount.max(-12000.0).min(8000.0);
+                }
+            }
+            let base = if absolute { absolute_base(gen) } else { 0.0 };
+            add(artc_src::NONE, artc_transform::LINEAR, dest, amount * scale + base);
+        } else if let Some((src, transform, dest, scale)) = generator_route(gen) {
+            add(src, transform, dest, amount * scale);
+        } else if let Some(dest) = keynum_target(gen) {
+            // key 60 기준으로 key 1당 amount timecent씩 줄어듦
+            add(artc_src::NOTE_NUMBER, artc_transform::LINEAR, dest, -amount * 127.0 * CENT_TO_VALUE);
+            add(artc_src::NONE, artc_transform::LINEAR, dest, amount * 60.0 * CENT_TO_VALUE);
+        } else if gen == gen_ids::SCALE_TUNING {
+            // key 1당 피치 변화량(cent). 기본값 = 100
+            // 샘플의 기본 key 기준이라서 preset 쪽도 instrument가 고른 샘플의 기본 key를 씀
+            let per_key = if absolute { amount - 100.0 } else { amount };
+            add(artc_src::NOTE_NUMBER, artc_transform::LINEAR, artc_dest::PITCH, per_key * 10.0 * 127.0);
+            add(artc_src::ROOT_KEY, artc_transform::LINEAR, artc_dest::PITCH, -per_key * 10.0 * 127.0);
+        }
+    }
+
+    return sums.into_iter().filter(|(_, scale)| *scale != 0.0).map(|((src, transform, dest), scale)| {
+        return make_articulator(src, transform, artc_src::NONE, artc_transform::LINEAR, dest, scale);
+    }).collect();
+}
+
+// articulator로 나타낼 수 없는 generator를 region generator로 바꿈
+fn region_generators(generators: &HashMap<u16, SF2GeneratorAmount>) -> HashMap<u16, i32> {
+    let mut result = HashMap::new();
+    if let Some(class) = get_i16(generators, gen_ids::EXCLUSIVE_CLASS).filter(|class| *class != 0) {
+        result.insert(region_gen::EXCLUSIVE_CLASS, class as i32);
+    }
+    if let Some(key) = get_i16(generators, gen_ids::KEYNUM).filter(|key| (0..=127).contains(key)) {
+        result.insert(region_gen::KEY_NUMBER, key as i32);
+    }
+    if let Some(velocity) = get_i16(generators, gen_ids::VELOCITY).filter(|velocity| (1..=127).contains(velocity)) {
+        result.insert(region_gen::VELOCITY, velocity as i32);
+    }
+    return result;
+}
+
+fn modulators_to_articulators(modulators: &[SF2Modulator]) -> Vec<wsbk::Articulator> {
+    return modulators.iter()
+        .filter(|modulator| !is_native_modulator(modulator))
+        .filter_map(modulator_to_articulator)
+        .collect();
+}
+
+// 샘플 하나를 만드는 데 필요한 정보
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) struct SampleKey {
+    sample_index: usize,
+    // stereo면 오른쪽 샘플
+    right_sample_index: Option<usize>,
+    start_offset: i64,
+    end_offset: i64,
+    loop_start_offset: i64,
+    loop_end_offset: i64,
+    sample_mode: u16,
+    root_key: u8
+}
+
+impl SampleKey {
+    fn new(header: &SF2SampleHeader, right_sample_index: Option<usize>, generators: &HashMap<u16, SF2GeneratorAmount>) -> Self {
+        let offset = |fine: u16, coarse: u16| {
+            return get_i16(generators, fine).unwrap_or(0) as i64 + get_i16(generators, coarse).unwrap_or(0) as i64 * 32768;
+        };
+        let root_key = match get_i16(generators, gen_ids::OVERRIDING_ROOT_KEY) {
+            Some(key) if (0..=127).contains(&key) => key as u8,
+            _ if header.base_key <= 127 => header.base_key,
+            _ => 60
+        };
+
+        return Self {
+            sample_index: header.index,
+            right_sample_index,
+            start_offset: offset(gen_ids::START_ADDRS_OFFSET, gen_ids::START_ADDRS_COARSE_OFFSET),
+            end_offset: offset(gen_ids::END_ADDRS_OFFSET, gen_ids::END_ADDRS_COARSE_OFFSET),
+            loop_start_offset: offset(gen_ids::START_LOOP_ADDRS_OFFSET, gen_ids::START_LOOP_ADDRS_COARSE_OFFSET),
+            loop_end_offset: offset(gen_ids::END_LOOP_ADDRS_OFFSET, gen_ids::END_LOOP_ADDRS_COARSE_OFFSET),
+            sample_mode: generators.get(&gen_ids::SAMPLE_MODES).map(|amount| amount.get_u16() & 3).unwrap_or(0),
+            root_key
+        };
+    }
+}
 
 impl super::SF2 {
-    pub fn to_wsbk(&self) -> wsbk::WSBK {
-        let wsbk_bank = wsbk::WSBK::new();
+    // 샘플 데이터에서 (시작, 끝) 범위를 구함. 범위가 이상하면 None
+    fn sample_range(&self, header: &SF2SampleHeader, key: &SampleKey) -> Option<(usize, usize)> {
+        let data_len = self.smpl_len as i64;
+        let start = (header.smpl_start as i64 + key.start_offset).max(0).min(data_len);
+        let end = (header.smpl_end as i64 + key.end_offset).max(0).min(data_len);
+        if start >= end {
+            return None;
+        }
+        return Some((start as usize, end as usize));
+    }
+
+    // 샘플 데이터를 복사하지 않고 위치만 가리키는 샘플을 만듦
+    pub(super) fn make_bank_sample(&self, key: &SampleKey) -> Option<SF2BankSample> {
+        let header = self.sample_headers.get(key.sample_index)?;
+        if header.sample_type & SAMPLE_TYPE_ROM != 0 {
+            log::warn!("ROM sample is not supported: {}", header.name);
+            return None;
+        }
+        let Some((start, end)) = self.sample_range(header, key) else {
+            log::warn!("Invalid sample range: {}", header.name);
+            return None;
+        };
+
+        // stereo면 두 샘플 중 짧은 쪽에 맞춤
+        let right = key.right_sample_index
+            .and_then(|index| self.sample_headers.get(index))
+            .and_then(|right| self.sample_range(right, key));
+        let frame_count = match right {
+            Some((right_start, right_end)) => (end - start).min(right_end - right_start),
+            None => end - start
+        };
 
-        for sf2_smpl_header in self.sample_headers.iter() {
-            let mut wsbk_smpl = wsbk::Sample::new(&sf2_smpl_header.name);
-            wsbk_smpl.bit_depth = 16;
-            wsbk_smpl.sample_rate = sf2_smpl_header.sample_rate;
-            wsbk_smpl.loop_start = sf2_smpl_header.loop_start - sf2_smpl_header.smpl_start;
-            wsbk_smpl.loop_end = sf2_smpl_header.loop_end - sf2_smpl_header.smpl_start;
+        // 루프 위치는 샘플 시작 기준으로 바꿈. 샘플 밖을 가리키면 루프 없이 재생함
+        let loop_start = header.loop_start as i64 + key.loop_start_offset - start as i64;
+        let loop_end = header.loop_end as i64 + key.loop_end_offset - start as i64;
+        let mut loop_type = match key.sample_mode {
+            1 => wsbk::LoopType::Infinite,
+            3 => wsbk::LoopType::UntilReleased,
+            _ => wsbk::LoopType::NoLoop
+        };
+        if loop_type != wsbk::LoopType::NoLoop && !(0 <= loop_start && loop_start < loop_end && loop_end <= frame_count as i64) {
+            log::warn!("Invalid loop points: {}", header.name);
+            loop_type = wsbk::LoopType::NoLoop;
         }
+        let (loop_start, loop_end) = if loop_type == wsbk::LoopType::NoLoop {
+            (0, 0)
+        } else {
+            (loop_start as u32, loop_end as u32)
+        };
+
+        let right_start = right.map(|(right_start, _)| right_start);
+        let bit_depth = if self.is_24bit() { 24 } else { 16 };
+        let stream = self.loader.as_ref().map(|loader| {
+            let layout = SampleLayout::Pcm16 {
+                left: self.smpl_offset + start * 2,
+                right: right_start.map(|right_start| self.smpl_offset + right_start * 2),
+                left_low: self.sm24_offset.map(|sm24_offset| sm24_offset + start),
+                right_low: self.sm24_offset.and_then(|sm24_offset| right_start.map(|right_start| sm24_offset + right_start)),
+                frame_count
+            };
+            return loader.create_stream(layout, bit_depth, if right_start.is_some() { 2 } else { 1 });
+        });
+
+        return Some(SF2BankSample {
+            name: header.name.clone(),
+            start,
+            right_start,
+            frame_count,
+            bit_depth,
+            loop_type,
+            loop_start,
+            loop_end,
+            sample_rate: if header.sample_rate == 0 { 44100 } else { header.sample_rate },
+            base_key: key.root_key,
+            cent_correction: header.correction,
+            stream
+        });
+    }
+
+    // 샘플 데이터를 복사해서 wsbk 샘플을 만듦(stereo는 L R 순서로 섞음)
+    fn make_wsbk_sample(&self, key: &SampleKey) -> Option<wsbk::Sample> {
+        let bank_sample = self.make_bank_sample(key)?;
+        let mut sample = wsbk::Sample::new(&bank_sample.name);
+        sample.bit_depth = bank_sample.bit_depth;
+        sample.sample_rate = bank_sample.sample_rate;
+        sample.base_key = bank_sample.base_key;
+        sample.cent_correction = bank_sample.cent_correction;
+        sample.loop_type = bank_sample.loop_type;
+        sample.loop_start = bank_sample.loop_start;
+        sample.loop_end = bank_sample.loop_end;
 
+        sample.sample_type = if bank_sample.right_start.is_some() {
+            wsbk::SampleType::Stereo
+        } else {
+            wsbk::SampleType::Mono
+        };
+        if bank_sample.stream.is_some() {
+            sample.stream = bank_sample.stream;
+            return Some(sample);
+        }
+
+        let start = bank_sample.start;
+        let len = bank_sample.frame_count;
+        let mut data = vec![];
+        match (&self.sm24_data, bank_sample.right_start) {
+            (None, Some(right_start)) => {
+                data.reserve(len * 4);
+                for i in 0..len {
+                    data.extend_from_slice(&self.sample_data[start + i].to_le_bytes());
+                    data.extend_from_slice(&self.sample_data[right_start + i].to_le_bytes());
+                }
+            },
+            (None, None) => {
+                data.reserve(len * 2);
+                for val in self.sample_data[start..(start + len)].iter() {
+                    data.extend_from_slice(&val.to_le_bytes());
+                }
+            },
+            // 24비트: 하위 8비트 + smpl의 16비트
+            (Some(sm24_data), right_start) => {
+                data.reserve(len * 3 * if right_start.is_some() { 2 } else { 1 });
+                for i in 0..len {
+                    for index in [Some(start + i), right_start.map(|right_start| right_start + i)].into_iter().flatten() {
+                        data.push(sm24_data[index]);
+                        data.extend_from_slice(&self.sample_data[index].to_le_bytes());
+                    }
+                }
+            }
+        }
+        sample.data = std::sync::Arc::new(data);
+
+        return Some(sample);
+    }
+
+    fn convert_instrument<S>(
+        &self,
+        instrument: &SF2Instrument,
+        samples: &mut Vec<S>,
+        sample_indexes: &mut HashMap<SampleKey, Option<u32>>,
+        make_sample: &impl Fn(&Self, &SampleKey) -> Option<S>
+    ) -> wsbk::Instrument {
+        let global = instrument.zones.first().filter(|zone| zone.target_sample_index.is_none()).map(|zone| zone.as_ref());
+        let zones: Vec<&SF2Zone> = instrument.zones.iter()
+            .map(|zone| zone.as_ref())
+            .filter(|zone| zone.target_sample_index.is_some())
+            .collect();
+        let generators: Vec<HashMap<u16, SF2GeneratorAmount>> = zones.iter().map(|zone| merge_generators(global, zone)).collect();
+
+        let mut regions = vec![];
+        let mut merged = vec![false; zones.len()];
+        for i in 0..zones.len() {
+            if merged[i] {
+                continue;
+            }
+            let Some(header) = zones[i].target_sample_index.and_then(|index| self.sample_headers.get(index)) else {
+                log::warn!("Invalid sample index in instrument: {}", instrument.name);
+                continue;
+            };
+            let key_range = get_range(&generators[i], gen_ids::KEY_RANGE);
+            let velocity_range = get_range(&generators[i], gen_ids::VEL_RANGE);
+
+            // 연결된 샘플을 쓰면서 범위가 같은 zone을 찾아서 stereo로 합침
+            let mut pair = None;
+            if header.sample_type & (SAMPLE_TYPE_LEFT | SAMPLE_TYPE_RIGHT) != 0 {
+                pair = (0..zones.len()).find(|j| {
+                    return *j != i && !merged[*j]
+                        && zones[*j].target_sample_index == Some(header.linked_sample_index as usize)
+                        && get_range(&generators[*j], gen_ids::KEY_RANGE) == key_range
+                        && get_range(&generators[*j], gen_ids::VEL_RANGE) == velocity_range;
+                });
+            }
+            // 왼쪽 zone의 설정을 씀
+            let (left, right) = match pair {
+                Some(j) if header.sample_type & SAMPLE_TYPE_RIGHT != 0 => (j, Some(i)),
+                Some(j) => (i, Some(j)),
+                None => (i, None)
+            };
+            let Some(left_header) = zones[left].target_sample_index.and_then(|index| self.sample_headers.get(index)) else {
+                continue;
+            };
+            merged[left] = true;
+            let mut zone_generators = generators[left].clone();
+            if let Some(right) = right {
+                merged[right] = true;
+                // 양쪽 pan의 평균(보통 왼쪽/오른쪽 끝이라 가운데가 됨)
+                let pan = (get_i16(&generators[left], gen_ids::PAN).unwrap_or(0) as i32
+                    + get_i16(&generators[right], gen_ids::PAN).unwrap_or(0) as i32) / 2;
+                let mut amount = SF2GeneratorAmount::new([0, 0]);
+                amount.set_i16(pan as i16);
+                zone_generators.insert(gen_ids::PAN, amount);
+            }
+
+            let sample_key = SampleKey::new(
+                left_header,
+                right.and_then(|right| zones[right].target_sample_index),
+                &zone_generators
+            );
+            let sample_index = *sample_indexes.entry(sample_key).or_insert_with(|| {
+                let sample = make_sample(self, &sample_key)?;
+                samples.push(sample);
+                return Some((samples.len() - 1) as u32);
+            });
+            let Some(sample_index) = sample_index else {
+                continue;
+            };
+
+            let mut modulators = DEFAULT_MODULATORS.to_vec();
+            if let Some(global) = global {
+                merge_modulators(&mut modulators, &global.modulators);
+            }
+            merge_modulators(&mut modulators, &zones[left].modulators);
+
+            let mut articulators = generators_to_articulators(&zone_generators, true);
+            articulators.extend(modulators_to_articulators(&modulators));
+            regions.push(wsbk::Region {
+                key_range,
+                velocity_range,
+                target_index: sample_index,
+                generators: region_generators(&zone_generators),
+                articulators
+            });
+        }
+
+        return wsbk::Instrument {
+            name: instrument.name.clone(),
+            regions
+        };
+    }
+
+    fn convert_preset(&self, preset: &SF2Preset, instrument_count: usize) -> wsbk::Preset {
+        let global = preset.zones.first().filter(|zone| zone.target_instrument_index.is_none()).map(|zone| zone.as_ref());
+
+        let mut regions = vec![];
+        for zone in preset.zones.iter() {
+            let Some(instrument_index) = zone.target_instrument_index else {
+                continue;
+            };
+            if instrument_index >= instrument_count {
+                log::warn!("Invalid instrument index in preset: {}", preset.name);
+                continue;
+            }
+            let generators = merge_generators(global, zone);
+            let mut modulators = vec![];
+            if let Some(global) = global {
+                merge_modulators(&mut modulators, &global.modulators);
+            }
+            merge_modulators(&mut modulators, &zone.modulators);
+
+            // keynum, velocity, exclusiveClass는 instrument에서만 쓰는 generator라서 무시함
+            let mut articulators = generators_to_articulators(&generators, false);
+            articulators.extend(modulators_to_articulators(&modulators));
+            regions.push(wsbk::Region {
+                key_range: get_range(&generators, gen_ids::KEY_RANGE),
+                velocity_range: get_range(&generators, gen_ids::VEL_RANGE),
+                target_index: instrument_index as u32,
+                generators: HashMap::new(),
+                articulators
+            });
+        }
+
+        // bank 128은 드럼 세트
+        let (type_flag, bank_msb, bank_lsb) = if preset.bank == 128 {
+            (wsbk::PresetType::Drum, 0, 0)
+        } else {
+            (wsbk::PresetType::Melodic, (preset.bank & 0x7f) as u8, ((preset.bank >> 7) & 0x7f) as u8)
+        };
+        return wsbk::Preset {
+            name: preset.name.clone(),
+            program_no: preset.program_no & 0x7f,
+            bank_msb,
+            bank_lsb,
+            type_flag,
+            regions
+        };
+    }
+
+    /**
+     * instrument/preset을 모두 바꿈
+     * make_sample: 샘플을 만드는 방법(to_wsbk는 데이터를 복사하고 SF2Bank는 위치만 가리킴)
+     */
+    pub(super) fn convert<S>(
+        &self,
+        make_sample: impl Fn(&Self, &SampleKey) -> Option<S>
+    ) -> (Vec<S>, Vec<wsbk::Instrument>, Vec<wsbk::Preset>) {
+        // 마지막 instrument/preset은 끝을 나타내는 레코드(EOI, EOP)임
+        let instrument_count = self.instruments.len().saturating_sub(1);
+        let preset_count = self.presets.len().saturating_sub(1);
+
+        let mut samples = vec![];
+        let mut sample_indexes = HashMap::new();
+        let instruments = self.instruments[0..instrument_count].iter().map(|instrument| {
+            return self.convert_instrument(instrument, &mut samples, &mut sample_indexes, &make_sample);
+        }).collect();
+        let presets = self.presets[0..preset_count].iter().map(|preset| {
+            return self.convert_preset(preset, instrument_count);
+        }).collect();
+
+        return (samples, instruments, presets);
+    }
+
+    pub fn to_wsbk(&self) -> wsbk::WSBK {
+        let (samples, instruments, presets) = self.convert(Self::make_wsbk_sample);
+        let mut wsbk_bank = wsbk::WSBK::new();
+        wsbk_bank.samples = samples;
+        wsbk_bank.instruments = instruments;
+        wsbk_bank.presets = presets;
         return wsbk_bank;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,88 @@
+/**
+ * sf3(Ogg Vorbis로 압축한 sf2) 샘플 처리
+ * 샘플 헤더의 sample_type에 0x10이 있으면 압축된 샘플임
+ * 이 경우 smpl_start, smpl_end는 smpl 청크 안에서 ogg 데이터가 있는 위치(바이트 단위)이고
+ * loop_start, loop_end는 압축을 푼 샘플의 시작점 기준 위치(샘플 단위)임
+ * 불러올 때 압축을 모두 풀어서 일반 sf2 샘플과 같은 형식으로 바꿈
+ * 압축을 푼 샘플 데이터에는 ogg 데이터를 넣지 않으므로 다시 저장해도 파일이 압축된 크기만큼 커지지 않음
+ */
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use lewton::inside_ogg::OggStreamReader;
+use super::structure::SF2SampleHeader;
+
+pub const SAMPLE_TYPE_VORBIS: u16 = 0x0010;
+
+// sf2 규칙대로 샘플 뒤에 붙이는 무음 길이(보간용)
+const SAMPLE_PADDING: usize = 46;
+
+pub fn is_compressed(header: &SF2SampleHeader) -> bool {
+    return header.sample_type & SAMPLE_TYPE_VORBIS != 0;
+}
+
+// ogg 데이터 하나를 16비트 mono로 풀어냄(stereo면 왼쪽 채널만 씀)
+pub fn decode(data: &[u8]) -> anyhow::Result<Vec<i16>> {
+    let mut reader = OggStreamReader::new(Cursor::new(data))?;
+    let channels = reader.ident_hdr.audio_channels.max(1) as usize;
+    if channels > 1 {
+        log::warn!("Compressed sample has {} channels, using the first one only", channels);
+    }
+    let mut result = vec![];
+    while let Some(packet) = reader.read_dec_packet_itl()? {
+        result.extend(packet.iter().step_by(channels));
+    }
+    return Ok(result);
+}
+
+/**
+ * 압축된 샘플은 압축을 풀고 압축되지 않은 샘플은 smpl 청크에서 복사해서 새 샘플 데이터를 만듦
+ * 헤더는 새 샘플 데이터 기준의 일반 sf2 샘플처럼 고침(마지막 레코드(EOS)는 그대로 둠)
+ * smpl: smpl 청크 내용 전체
+ * 압축을 풀지 못한 샘플은 길이가 0인 샘플이 됨
+ */
+pub fn decode_samples(sample_headers: &mut [SF2SampleHeader], smpl: &[u8]) -> Vec<i16> {
+    let mut sample_data = vec![];
+    // 같은 범위를 가리키는 헤더는 샘플 데이터를 같이 씀
+    // (압축 여부, 원래 시작, 원래 끝) => (새 시작, 새 끝)
+    let mut placed: HashMap<(bool, u32, u32), (u32, u32)> = HashMap::new();
+    let count = sample_headers.len().saturating_sub(1);
+    for header in sample_headers[0..count].iter_mut() {
+        let compressed = is_compressed(header);
+        let range_key = (compressed, header.smpl_start, header.smpl_end);
+        let (new_start, new_end) = *placed.entry(range_key).or_insert_with(|| {
+            let samples = if compressed {
+                let start = (header.smpl_start as usize).min(smpl.len());
+                let end = (header.smpl_end as usize).min(smpl.len()).max(start);
+                match decode(&smpl[start..end]) {
+                    Ok(decoded) => decoded,
+                    Err(err) => {
+                        log::warn!("Failed to decode compressed sample {}: {}", header.name, err);
+                        vec![]
+                    }
+                }
+            } else {
+                let start = (header.smpl_start as usize * 2).min(smpl.len());
+                let end = (header.smpl_end as usize * 2).min(smpl.len()).max(start);
+                smpl[start..end].chunks_exact(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect()
+            };
+            let position = sample_data.len() as u32;
+            sample_data.extend(samples);
+            let end = sample_data.len() as u32;
+            if end > position {
+                sample_data.extend([0; SAMPLE_PADDING]);
+            }
+            return (position, end);
+        });
+
+        // 압축된 샘플의 루프 위치는 샘플 시작 기준, 아니면 smpl 청크 기준임
+        let loop_base = if compressed { 0 } else { header.smpl_start as i64 };
+        let move_loop = |val: u32| (val as i64 - loop_base + new_start as i64).max(0).min(u32::MAX as i64) as u32;
+        header.loop_start = move_loop(header.loop_start);
+        header.loop_end = move_loop(header.loop_end);
+        header.smpl_start = new_start;
+        header.smpl_end = new_end;
+        header.sample_type &= !SAMPLE_TYPE_VORBIS;
+    }
+    return sample_data;
+}
@@ -0,0 +1,312 @@
+/**
+ * SF2를 사운드폰트 파일로 저장
+ * instrument, preset, 샘플 헤더 목록의 마지막 레코드는 끝을 나타내는 레코드(EOI, EOP, EOS)로 보고 이름 등을 그대로 씀
+ * (읽을 때와 같은 방식. 목록이 비어 있으면 새로 만듦)
+ * bag 위치(ibag_index, pbag_index)는 zone 순서대로 다시 계산하고, zone은 generators와 modulators만 씀
+ * 끝을 나타내는 bag, generator, modulator 레코드는 항상 새로 만듦(모두 0)
+ * 압축된 샘플(sf3)은 불러올 때 압축을 풀었으므로 압축하지 않은 sf2로 저장함
+ */
+
+use std::io::{ Write, Seek };
+use anyhow::bail;
+use riff::{ ChunkContents, ChunkId };
+use super::{ read_name, SF2, SF2Error };
+use super::structure::*;
+use super::fourcc;
+
+/**
+ * 이름은 20바이트(남는 부분은 \0으로 채움)
+ * 읽은 뒤로 이름을 바꾸지 않았으면 읽은 바이트(raw_name)를 그대로 씀(UTF-8이 아닌 이름이 깨지지 않도록)
+ * 바꿨으면 UTF-8로 쓰고, 20바이트를 넘으면 글자 중간에서 자르지 않도록 그 앞에서 자름
+ */
+fn make_name(name: &str, raw_name: &[u8; 20]) -> [u8; 20] {
+    if read_name(raw_name) == name {
+        return *raw_name;
+    }
+    let mut result = [0; 20];
+    let mut len = name.len().min(20);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    result[..len].copy_from_slice(&name.as_bytes()[..len]);
+    return result;
+}
+
+// INFO 문자열은 \0으로 끝나고 길이가 짝수여야 함
+fn make_info_string(id: ChunkId, text: &str) -> ChunkContents {
+    let mut data = text.as_bytes().to_vec();
+    if data.last() != Some(&0) {
+        data.push(0);
+    }
+    if data.len() % 2 != 0 {
+        data.push(0);
+    }
+    return ChunkContents::Data(id, data);
+}
+
+fn make_version(id: ChunkId, version: [u16; 2]) -> ChunkContents {
+    let mut data = version[0].to_le_bytes().to_vec();
+    data.extend_from_slice(&version[1].to_le_bytes());
+    return ChunkContents::Data(id, data);
+}
+
+// bag에서 가리키는 위치는 u16이라 레코드 수가 그보다 많으면 저장할 수 없음
+fn record_index(chunk: &'static str, index: usize) -> anyhow::Result<u16> {
+    if index > u16::MAX as usize {
+        bail!(SF2Error::OutOfRange { chunk, index, len: u16::MAX as usize + 1 });
+    }
+    return Ok(index as u16);
+}
+
+// preset 또는 instrument의 zone을 bag, generator, modulator 레코드로 바꿈
+struct ZoneRecords {
+    bag_chunk: &'static str,
+    generator_chunk: &'static str,
+    modulator_chunk: &'static str,
+    bags: Vec<u8>,
+    generators: Vec<u8>,
+    modulators: Vec<u8>
+}
+
+impl ZoneRecords {
+    fn new(bag_chunk: &'static str, generator_chunk: &'static str, modulator_chunk: &'static str) -> Self {
+        return Self {
+            bag_chunk,
+            generator_chunk,
+            modulator_chunk,
+            bags: vec![],
+            generators: vec![],
+            modulators: vec![]
+        };
+    }
+
+    // 다음에 추가할 bag의 위치(preset/instrument 헤더에 씀)
+    fn bag_index(&self) -> anyhow::Result<u16> {
+        return record_index(self.bag_chunk, self.bags.len() / 4);
+    }
+
+    fn push_bag(&mut self) -> anyhow::Result<()> {
+        let generator_index = record_index(self.generator_chunk, self.generators.len() / 4)?;
+        let modulator_index = record_index(self.modulator_chunk, self.modulators.len() / 10)?;
+        self.bags.extend_from_slice(&generator_index.to_le_bytes());
+        self.bags.extend_from_slice(&modulator_index.to_le_bytes());
+        return Ok(());
+    }
+
+    fn push_zone(&mut self, zone: &SF2Zone) -> anyhow::Result<()> {
+        self.push_bag()?;
+        for generator in zone.generators.iter() {
+            self.generators.extend_from_slice(&generator.operator.to_le_bytes());
+            self.generators.extend_from_slice(&generator.amount.get_u8_array());
+        }
+        for modulator in zone.modulators.iter() {
+            self.modulators.extend_from_slice(&modulator.src_operator.to_le_bytes());
+            self.modulators.extend_from_slice(&modulator.dest_operator.to_le_bytes());
+            self.modulators.extend_from_slice(&modulator.mod_amount.to_le_bytes());
+            self.modulators.extend_from_slice(&modulator.amount_src_operator.to_le_bytes());
+            self.modulators.extend_from_slice(&modulator.mod_trans_operator.to_le_bytes());
+        }
+        return Ok(());
+    }
+
+    // 끝을 나타내는 bag, generator, modulator
+    fn push_terminal(&mut self) -> anyhow::Result<()> {
+        self.push_bag()?;
+        self.generators.extend_from_slice(&[0; 4]);
+        self.modulators.extend_from_slice(&[0; 10]);
+        return Ok(());
+    }
+}
+
+impl SF2 {
+    fn make_info(&self) -> ChunkContents {
+        let info = &self.info;
+        // 압축된 샘플은 쓰지 않으므로 sf3(3.x)도 2.04로 저장함. 버전 정보가 없었으면 2.01
+        let version = if info.sf_version[0] >= 3 || (self.is_24bit() && info.sf_version < [2, 4]) {
+            [2, 4]
+        } else if info.sf_version[0] < 2 {
+            [2, 1]
+        } else {
+            info.sf_version
+        };
+
+        // ifil, isng, INAM은 꼭 있어야 하고 나머지는 있을 때만 씀
+        let mut chunks = vec![
+            make_version(fourcc::IFIL, version),
+            make_info_string(fourcc::ISNG, if info.target_sound_engine.is_empty() { "EMU8000" } else { &info.target_sound_engine }),
+            make_info_string(fourcc::INAM, &info.bank_name)
+        ];
+        if !info.rom_name.is_empty() {
+            chunks.push(make_info_string(fourcc::IROM, &info.rom_name));
+        }
+        if !info.rom_name.is_empty() || info.rom_version != [0, 0] {
+            chunks.push(make_version(fourcc::IVER, info.rom_version));
+        }
+        for (id, text) in [
+            (fourcc::ICRD, &info.created_date),
+            (fourcc::IENG, &info.engineers),
+            (fourcc::IPRD, &info.target_hardware),
+            (fourcc::ICOP, &info.copyright),
+            (fourcc::ICMT, &info.comments),
+            (fourcc::ISFT, &info.created_software)
+        ] {
+            if !text.is_empty() {
+                chunks.push(make_info_string(id, text));
+            }
+        }
+        return ChunkContents::Children(riff::LIST_ID, fourcc::INFO, chunks);
+    }
+
+    // 16비트 샘플 데이터(필요할 때 불러오는 경우에는 파일에서 그대로 가져옴)
+    fn make_smpl(&self) -> ChunkContents {
+        if let Some(loader) = &self.loader {
+            let bytes = loader.bytes();
+            let start = self.smpl_offset.min(bytes.len());
+            let end = (self.smpl_offset + self.smpl_len * 2).min(bytes.len());
+            return ChunkContents::Data(fourcc::SMPL, bytes[start..end].to_vec());
+        }
+        let mut data = Vec::with_capacity(self.sample_data.len() * 2);
+        for val in self.sample_data.iter() {
+            data.extend_from_slice(&val.to_le_bytes());
+        }
+        return ChunkContents::Data(fourcc::SMPL, data);
+    }
+
+    // 24비트 샘플의 하위 8비트(길이는 짝수로 맞춤)
+    fn make_sm24(&self) -> Option<ChunkContents> {
+        let mut data = match (&self.sm24_data, &self.loader, self.sm24_offset) {
+            (Some(sm24_data), _, _) => Vec::clone(sm24_data),
+            (None, Some(loader), Some(sm24_offset)) => {
+                let bytes = loader.bytes();
+                let start = sm24_offset.min(bytes.len());
+                let end = (sm24_offset + self.smpl_len).min(bytes.len());
+                bytes[start..end].to_vec()
+            },
+            _ => return None
+        };
+        if data.len() % 2 != 0 {
+            data.push(0);
+        }
+        return Some(ChunkContents::Data(fourcc::SM24, data));
+    }
+
+    fn make_sdta(&self) -> ChunkContents {
+        let mut chunks = vec![self.make_smpl()];
+        chunks.extend(self.make_sm24());
+        return ChunkContents::Children(riff::LIST_ID, fourcc::SDTA, chunks);
+    }
+
+    fn make_presets(&self) -> anyhow::Result<Vec<ChunkContents>> {
+        let mut records = ZoneRecords::new("pbag", "pgen", "pmod");
+        let mut phdr = vec![];
+        let terminal = SF2Preset {
+            name: "EOP".to_owned(),
+            raw_name: [0; 20],
+            program_no: 0,
+            bank: 0,
+            pbag_index: 0,
+            zones: vec![],
+            library: 0,
+            genre: 0,
+            morph: 0
+        };
+        let presets: Vec<&SF2Preset> = if self.presets.is_empty() { vec![&terminal] } else { self.presets.iter().collect() };
+        for (i, preset) in presets.iter().enumerate() {
+            phdr.extend_from_slice(&make_name(&preset.name, &preset.raw_name));
+            phdr.extend_from_slice(&preset.program_no.to_le_bytes());
+            phdr.extend_from_slice(&preset.bank.to_le_bytes());
+            phdr.extend_from_slice(&records.bag_index()?.to_le_bytes());
+            phdr.extend_from_slice(&preset.library.to_le_bytes());
+            phdr.extend_from_slice(&preset.genre.to_le_bytes());
+            phdr.extend_from_slice(&preset.morph.to_le_bytes());
+            // 마지막 preset은 끝을 나타내는 레코드(EOP)라 zone을 쓰지 않음
+            if i + 1 < presets.len() {
+                for zone in preset.zones.iter() {
+                    records.push_zone(zone)?;
+                }
+            }
+        }
+        records.push_terminal()?;
+
+        return Ok(vec![
+            ChunkContents::Data(fourcc::PHDR, phdr),
+            ChunkContents::Data(fourcc::PBAG, records.bags),
+            ChunkContents::Data(fourcc::PMOD, records.modulators),
+            ChunkContents::Data(fourcc::PGEN, records.generators)
+        ]);
+    }
+
+    fn make_instruments(&self) -> anyhow::Result<Vec<ChunkContents>> {
+        let mut records = ZoneRecords::new("ibag", "igen", "imod");
+        let mut inst = vec![];
+        let terminal = SF2Instrument {
+            name: "EOI".to_owned(),
+            raw_name: [0; 20],
+            ibag_index: 0,
+            zones: vec![]
+        };
+        let instruments: Vec<&SF2Instrument> = if self.instruments.is_empty() { vec![&terminal] } else { self.instruments.iter().collect() };
+        for (i, instrument) in instruments.iter().enumerate() {
+            inst.extend_from_slice(&make_name(&instrument.name, &instrument.raw_name));
+            inst.extend_from_slice(&records.bag_index()?.to_le_bytes());
+            // 마지막 instrument는 끝을 나타내는 레코드(EOI)라 zone을 쓰지 않음
+            if i + 1 < instruments.len() {
+                for zone in instrument.zones.iter() {
+                    records.push_zone(zone)?;
+                }
+            }
+        }
+        records.push_terminal()?;
+
+        return Ok(vec![
+            ChunkContents::Data(fourcc::INST, inst),
+            ChunkContents::Data(fourcc::IBAG, records.bags),
+            ChunkContents::Data(fourcc::IMOD, records.modulators),
+            ChunkContents::Data(fourcc::IGEN, records.generators)
+        ]);
+    }
+
+    fn make_shdr(&self) -> ChunkContents {
+        let mut shdr = vec![];
+        for header in self.sample_headers.iter() {
+            shdr.extend_from_slice(&make_name(&header.name, &header.raw_name));
+            shdr.extend_from_slice(&header.smpl_start.to_le_bytes());
+            shdr.extend_from_slice(&header.smpl_end.to_le_bytes());
+            shdr.extend_from_slice(&header.loop_start.to_le_bytes());
+            shdr.extend_from_slice(&header.loop_end.to_le_bytes());
+            shdr.extend_from_slice(&header.sample_rate.to_le_bytes());
+            shdr.push(header.base_key);
+            shdr.push(header.correction as u8);
+            shdr.extend_from_slice(&header.linked_sample_index.to_le_bytes());
+            shdr.extend_from_slice(&header.sample_type.to_le_bytes());
+        }
+
+        // 끝을 나타내는 레코드(EOS)가 없으면 만듦
+        if self.sample_headers.is_empty() {
+            shdr.extend_from_slice(&make_name("EOS", &[0; 20]));
+            shdr.extend_from_slice(&[0; 26]);
+        }
+        return ChunkContents::Data(fourcc::SHDR, shdr);
+    }
+
+    fn make_pdta(&self) -> anyhow::Result<ChunkContents> {
+        let mut chunks = self.make_presets()?;
+        chunks.extend(self.make_instruments()?);
+        chunks.push(self.make_shdr());
+        return Ok(ChunkContents::Children(riff::LIST_ID, fourcc::PDTA, chunks));
+    }
+
+    fn make_sfbk(&self) -> anyhow::Result<ChunkContents> {
+        let chunks = vec![
+            self.make_info(),
+            self.make_sdta(),
+            self.make_pdta()?
+        ];
+        return Ok(ChunkContents::Children(riff::RIFF_ID, fourcc::SFBK, chunks));
+    }
+
+    pub fn write<T: Write + Seek>(&self, stream: &mut T) -> anyhow::Result<()> {
+        self.make_sfbk()?.write(stream)?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,395 @@
+/**
+ * 샘플 데이터를 필요할 때 불러오는 기능(큰 사운드뱅크용)
+ *
+ * 사운드뱅크 파일을 메모리 맵으로 열어두고, 샘플마다 앞부분(attack)만 미리 읽어둠
+ * 나머지는 note on으로 샘플이 쓰이기 시작하면 백그라운드 스레드가 읽어옴
+ * 한동안 쓰이지 않은 샘플은 다시 앞부분만 남기고 버림
+ *
+ * 오디오 스레드는 락을 기다리거나 파일을 읽지 않음(try_lock과 lock-free 큐만 씀)
+ * 나머지를 다 읽기 전에 앞부분을 넘어서 재생하면 그 부분은 무음이 되고 misses로 셈
+ */
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::{ Arc, Mutex, Weak, OnceLock };
+use std::sync::atomic::{ AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering };
+use std::thread::Thread;
+use std::time::{ Duration, Instant };
+use anyhow::Context;
+use crossbeam_queue::ArrayQueue;
+use memmap2::Mmap;
+
+// 한 번에 쌓아둘 수 있는 불러오기 요청 수
+const LOAD_QUEUE_CAPACITY: usize = 1024;
+
+// 쓰이지 않는 샘플을 찾는 간격
+const EVICT_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+// 불러오기 상태
+const STATE_UNLOADED: u8 = 0;
+const STATE_REQUESTED: u8 = 1;
+const STATE_LOADED: u8 = 2;
+
+#[derive(Clone, Debug)]
+pub struct StreamOptions {
+    // 미리 읽어둘 앞부분 길이(샘플 단위). 백그라운드 스레드가 나머지를 읽는 동안 재생할 수 있는 길이임
+    pub(crate) preload_frames: usize, // 1024 이상 (기본값 = 16384)
+
+    // 이 시간(초) 동안 쓰이지 않은 샘플은 앞부분만 남기고 버림
+    pub(crate) evict_after_secs: u32 // 1 이상 (기본값 = 10)
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        return Self {
+            preload_frames: 16384,
+            evict_after_secs: 10
+        };
+    }
+}
+
+impl StreamOptions {
+    pub fn new() -> Self {
+        return Default::default();
+    }
+
+    pub fn set_preload_frames(&mut self, val: usize) {
+        self.preload_frames = val.max(1024);
+    }
+
+    pub fn set_evict_after_secs(&mut self, val: u32) {
+        self.evict_after_secs = val.max(1);
+    }
+}
+
+/**
+ * 파일 안에서 샘플 데이터가 있는 위치(바이트 단위)
+ * 불러온 데이터는 항상 wsbk 샘플 데이터 형식(채널이 L R 순서로 섞인 little endian)으로 바꿈
+ */
+#[derive(Clone, Copy, Debug)]
+pub enum SampleLayout {
+    // wsbk: 재생 형식 그대로 들어 있음
+    Interleaved { offset: usize, len: usize },
+
+    // sf2: 16비트 샘플이 채널별로 따로 들어 있음(right가 None이면 mono)
+    // 24비트면 left_low, right_low에 채널별 하위 8비트(sm24 청크)가 있음
+    Pcm16 { left: usize, right: Option<usize>, left_low: Option<usize>, right_low: Option<usize>, frame_count: usize }
+}
+
+struct LoaderShared {
+    map: Mmap,
+    options: StreamOptions,
+
+    // 불러오기를 기다리는 샘플
+    queue: ArrayQueue<Arc<SampleStream>>,
+
+    // 만든 샘플 전체(안 쓰는 샘플을 버릴 때 씀)
+    streams: Mutex<Vec<Weak<SampleStream>>>,
+
+    // 백그라운드 스레드(요청이 들어오면 깨움)
+    thread: OnceLock<Thread>,
+
+    // 다 불러오기 전에 재생하려고 해서 무음이 된 샘플 수
+    misses: AtomicU64,
+
+    // 로더 시작 후 지난 시간(초 단위). 백그라운드 스레드가 깨어날 때마다 고침
+    now_secs: AtomicU32
+}
+
+/**
+ * 사운드뱅크 파일 하나를 맡는 로더
+ * clone해도 같은 파일과 백그라운드 스레드를 씀. 마지막 샘플과 로더가 없어지면 스레드도 끝남
+ */
+#[derive(Clone)]
+pub struct SampleLoader {
+    shared: Arc<LoaderShared>
+}
+
+impl SampleLoader {
+    pub fn open<P: AsRef<Path>>(path: P, options: &StreamOptions) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open soundbank: {}", path.display()))?;
+        // 파일이 바뀌지 않는다고 가정함(재생 중에 파일을 고치면 안 됨)
+        let map = unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map soundbank: {}", path.display()))?;
+
+        let shared = Arc::new(LoaderShared {
+            map,
+            options: options.clone(),
+            queue: ArrayQueue::new(LOAD_QUEUE_CAPACITY),
+            streams: Mutex::new(vec![]),
+            thread: OnceLock::new(),
+            misses: AtomicU64::new(0),
+            now_secs: AtomicU32::new(0)
+        });
+        let weak = Arc::downgrade(&shared);
+        let thread = std::thread::Builder::new().name("whitesynth-sample-loader".into()).spawn(move || {
+            run_loader(weak);
+        })?;
+        let _ = shared.thread.set(thread.thread().clone());
+        return Ok(Self { shared });
+    }
+
+    // 파일 전체(riff 청크를 읽을 때 씀)
+    pub fn bytes(&self) -> &[u8] {
+        return &self.shared.map;
+    }
+
+    pub fn misses(&self) -> u64 {
+        return self.shared.misses.load(Ordering::Relaxed);
+    }
+
+    // 불러온 데이터가 메모리에 있는 샘플 수
+    pub fn loaded_streams(&self) -> usize {
+        let streams = self.shared.streams.lock().unwrap();
+        return streams.iter()
+            .filter_map(|stream| stream.upgrade())
+            .filter(|stream| stream.is_fully_loaded())
+            .count();
+    }
+
+    /**
+     * 샘플을 만들고 앞부분을 읽어둠
+     * 파일 범위를 벗어나는 부분은 잘라냄
+     */
+    pub fn create_stream(&self, layout: SampleLayout, bit_depth: u16, channels: usize) -> Arc<SampleStream> {
+        let map_len = self.shared.map.len();
+        let bytes_per_sample = (bit_depth as usize / 8).max(1);
+        let (layout, frame_count) = match layout {
+            SampleLayout::Interleaved { offset, len } => {
+                let offset = offset.min(map_len);
+                let len = len.min(map_len - offset);
+                (SampleLayout::Interleaved { offset, len }, len / (bytes_per_sample * channels))
+            },
+            SampleLayout::Pcm16 { left, right, left_low, right_low, frame_count } => {
+                let mut frame_count = frame_count.min(map_len.saturating_sub(left) / 2);
+                if let Some(right) = right {
+                    frame_count = frame_count.min(map_len.saturating_sub(right) / 2);
+                }
+                for low in [left_low, right_low].into_iter().flatten() {
+                    frame_count = frame_count.min(map_len.saturating_sub(low));
+                }
+                (SampleLayout::Pcm16 { left, right, left_low, right_low, frame_count }, frame_count)
+            }
+        };
+
+        let attack_frames = frame_count.min(self.shared.options.preload_frames);
+        let mut stream = SampleStream {
+            loader: self.shared.clone(),
+            layout,
+            bit_depth,
+            channels,
+            frame_count,
+            attack_frames,
+            attack: Arc::new(vec![]),
+            full: Mutex::new(None),
+            state: AtomicU8::new(STATE_UNLOADED),
+            users: AtomicUsize::new(0),
+            idle_since: AtomicU32::new(self.shared.now_secs.load(Ordering::Relaxed))
+        };
+        stream.attack = Arc::new(stream.read_frames(0, attack_frames));
+        // 앞부분이 곧 전체면 더 불러올 필요 없음
+        if attack_frames == frame_count {
+            *stream.full.lock().unwrap() = Some(stream.attack.clone());
+            stream.state.store(STATE_LOADED, Ordering::Release);
+        }
+
+        let stream = Arc::new(stream);
+        self.shared.streams.lock().unwrap().push(Arc::downgrade(&stream));
+        return stream;
+    }
+}
+
+fn run_loader(shared: Weak<LoaderShared>) {
+    let started = Instant::now();
+    let mut last_scan = Instant::now();
+    loop {
+        let Some(shared) = shared.upgrade() else {
+            break;
+        };
+        shared.now_secs.store(started.elapsed().as_secs() as u32, Ordering::Relaxed);
+        while let Some(stream) = shared.queue.pop() {
+            stream.load();
+        }
+        if last_scan.elapsed() >= EVICT_SCAN_INTERVAL {
+            last_scan = Instant::now();
+            evict_idle_streams(&shared);
+        }
+        drop(shared);
+        std::thread::park_timeout(Duration::from_millis(100));
+    }
+}
+
+// 한동안 쓰이지 않은 샘플을 앞부분만 남기고 버림
+fn evict_idle_streams(shared: &LoaderShared) {
+    let now_secs = shared.now_secs.load(Ordering::Relaxed);
+    let mut streams = shared.streams.lock().unwrap();
+    streams.retain(|stream| {
+        let Some(stream) = stream.upgrade() else {
+            return false;
+        };
+        if stream.attack_frames == stream.frame_count || stream.users.load(Ordering::Acquire) > 0 {
+            stream.idle_since.store(now_secs, Ordering::Relaxed);
+            return true;
+        }
+        let idle_secs = now_secs.saturating_sub(stream.idle_since.load(Ordering::Relaxed));
+        if idle_secs < shared.options.evict_after_secs || stream.state.load(Ordering::Acquire) != STATE_LOADED {
+            return true;
+        }
+        // 락을 잡은 다음 사용 수를 다시 확인함. 그 사이에 재생을 시작한 voice가 데이터를 가져갔으면 버리지 않음
+        // voice가 가진 데이터가 마지막 참조가 되면 오디오 스레드에서 메모리를 해제하게 됨
+        let mut full = stream.full.lock().unwrap();
+        if stream.users.load(Ordering::Acquire) == 0 {
+            *full = None;
+            stream.state.store(STATE_UNLOADED, Ordering::Release);
+        }
+        return true;
+    });
+}
+
+/**
+ * 필요할 때 불러오는 샘플 1개
+ * 불러온 데이터 형식은 wsbk 샘플 데이터와 같음(bit_depth, 채널 수 참고)
+ */
+pub struct SampleStream {
+    loader: Arc<LoaderShared>,
+    layout: SampleLayout,
+    bit_depth: u16,
+    channels: usize,
+
+    // 전체 길이와 미리 읽어둔 앞부분 길이(프레임 단위)
+    frame_count: usize,
+    attack_frames: usize,
+
+    attack: Arc<Vec<u8>>,
+
+    // 전체 데이터(불러온 경우에만 있음)
+    // 오디오 스레드는 try_lock으로만 가져감
+    // 이 참조는 재생 중인 voice가 없을 때 로더 스레드에서만 놓으므로 데이터는 항상 로더 스레드에서 해제됨
+    full: Mutex<Option<Arc<Vec<u8>>>>,
+
+    state: AtomicU8,
+
+    // 이 샘플을 재생 중인 voice 수
+    users: AtomicUsize,
+
+    // 마지막으로 쓰인 시간(로더 시작 후 초 단위)
+    // voice가 재생을 시작하고 끝날 때, 그리고 재생 중이면 주기적으로 고침
+    idle_since: AtomicU32
+}
+
+impl SampleStream {
+    pub fn bit_depth(&self) -> u16 {
+        return self.bit_depth;
+    }
+
+    pub fn channels(&self) -> usize {
+        return self.channels;
+    }
+
+    pub fn frame_count(&self) -> usize {
+        return self.frame_count;
+    }
+
+    pub fn attack_frames(&self) -> usize {
+        return self.attack_frames;
+    }
+
+    pub fn attack(&self) -> &Arc<Vec<u8>> {
+        return &self.attack;
+    }
+
+    pub fn is_fully_loaded(&self) -> bool {
+        return self.state.load(Ordering::Acquire) == STATE_LOADED;
+    }
+
+    // 파일에서 프레임 start부터 count개를 읽어서 wsbk 샘플 데이터 형식으로 바꿈
+    fn read_frames(&self, start: usize, count: usize) -> Vec<u8> {
+        let map = &self.loader.map;
+        return match self.layout {
+            SampleLayout::Interleaved { offset, .. } => {
+                let frame_len = (self.bit_depth as usize / 8).max(1) * self.channels;
+                let begin = offset + start * frame_len;
+                map[begin..(begin + count * frame_len)].to_vec()
+            },
+            SampleLayout::Pcm16 { left, right, left_low, right_low, .. } => {
+                let mut data = Vec::with_capacity(count * 3 * self.channels);
+                for i in start..(start + count) {
+                    for (high, low) in [(Some(left), left_low), (right, right_low)] {
+                        let Some(high) = high else {
+                            continue;
+                        };
+                        if let Some(low) = low {
+                            data.push(map[low + i]);
+                        }
+                        data.extend_from_slice(&map[(high + i * 2)..(high + i * 2 + 2)]);
+                    }
+                }
+                data
+            }
+        };
+    }
+
+    // 전체 데이터를 바로 읽음(파일로 저장할 때 등. 오디오 스레드에서 쓰면 안 됨)
+    pub fn read_all(&self) -> Vec<u8> {
+        return self.read_frames(0, self.frame_count);
+    }
+
+    // 백그라운드 스레드에서 전체 데이터를 읽음
+    fn load(&self) {
+        if self.state.load(Ordering::Acquire) != STATE_REQUESTED {
+            return;
+        }
+        let data = Arc::new(self.read_all());
+        *self.full.lock().unwrap() = Some(data);
+        self.state.store(STATE_LOADED, Ordering::Release);
+    }
+
+    // 불러오기 요청(오디오 스레드에서 불러도 됨)
+    pub(crate) fn request(self: &Arc<Self>) {
+        if self.state.compare_exchange(STATE_UNLOADED, STATE_REQUESTED, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return;
+        }
+        if self.loader.queue.push(self.clone()).is_err() {
+            // 큐가 꽉 찼으면 다음에 다시 요청함
+            self.state.store(STATE_UNLOADED, Ordering::Release);
+            return;
+        }
+        if let Some(thread) = self.loader.thread.get() {
+            thread.unpark();
+        }
+    }
+
+    // voice가 재생을 시작할 때 부름
+    pub(crate) fn acquire(self: &Arc<Self>) {
+        self.touch();
+        self.users.fetch_add(1, Ordering::AcqRel);
+        self.request();
+    }
+
+    // voice가 없어질 때 부름
+    pub(crate) fn release(&self) {
+        self.touch();
+        self.users.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    // 지금 쓰였다고 기록함(검사 주기보다 짧게 재생된 샘플도 바로 버리지 않도록)
+    fn touch(&self) {
+        self.idle_since.store(self.loader.now_secs.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    // 전체 데이터를 기다리지 않고 가져옴. 아직 없거나 다른 스레드가 쓰는 중이면 None
+    pub(crate) fn try_full(self: &Arc<Self>) -> Option<Arc<Vec<u8>>> {
+        if !self.is_fully_loaded() {
+            self.request();
+            return None;
+        }
+        return match self.full.try_lock() {
+            Ok(full) => full.clone(),
+            Err(_) => None
+        };
+    }
+
+    pub(crate) fn add_miss(&self) {
+        self.loader.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -10,6 +10,8 @@ pub mod artc_src { // source, control
     pub const CHANNEL_AFTERTOUCH: u32 = 0x0007;
     pub const MODULATION_LFO: u32 = 0x0008;
     pub const VIBRATO_LFO: u32 = 0x0009;
+    // 샘플의 기본 key(0 - 127)
+    pub const ROOT_KEY: u32 = 0x000a;
 
     // midi control change: 0x00010000 - 0x000100ff
     pub const MIDI_CONTROL_CHANGE: u32 = 0x00010000;
@@ -76,6 +78,7 @@ pub mod artc_dest { // destination
     pub const MODULATION_LFO_START_DELAY: u32 = 0x0101;
     pub const VIBRATO_LFO_FREQUENCY: u32 = 0x0110;
     pub const VIBRATO_LFO_START_DELAY: u32 = 0x0111;
+    pub const VIBRATO_LFO_TO_PITCH: u32 = 0x0112;
 
     pub const VOLUME_ENV_DELAY: u32 = 0x0200;
     pub const VOLUME_ENV_ATTACK: u32 = 0x0201;
@@ -110,11 +113,21 @@ pub mod artc_transform {
 
     #[inline]
     pub const fn is_inverted(transform: u8) -> bool {
-        return (transform & 0xf0) == INVERTED;
+        return (transform & INVERTED) != 0;
     }
 
     #[inline]
     pub const fn is_bipolar(transform: u8) -> bool {
-        return (transform & 0xf0) == BIPOLAR;
+        return (transform & BIPOLAR) != 0;
     }
+}
+
+// region generator(articulator로 나타낼 수 없는 region 설정)
+pub mod region_gen {
+    // 0이 아니면 같은 채널에서 같은 값의 소리가 새로 나올 때 이전 소리를 끊음(하이햇 등)
+    pub const EXCLUSIVE_CLASS: u16 = 0x0001;
+    // 누른 key 대신 이 key 번호로 소리를 냄(0 - 127)
+    pub const KEY_NUMBER: u16 = 0x0002;
+    // 누른 velocity 대신 이 velocity로 소리를 냄(1 - 127)
+    pub const VELOCITY: u16 = 0x0003;
 }
\ No newline at end of file
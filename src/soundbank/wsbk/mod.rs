@@ -10,6 +10,7 @@ use anyhow::bail;
 use riff::{ Chunk, ChunkContents };
 
 use crate::util;
+use crate::soundbank::stream::{ SampleLoader, SampleLayout, SampleStream };
 
 pub mod consts;
 pub mod fourcc;
@@ -19,6 +20,7 @@ fn make_name(name: &str) -> ChunkContents {
 }
 
 // 해당 샘플이 mono인지 stereo인지 정의함
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum SampleType {
     Mono, Stereo
 }
@@ -41,6 +43,7 @@ impl SampleType {
 }
 
 // 해당 샘플의 루프 방식을 정의함
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum LoopType {
     NoLoop, Infinite, UntilReleased
 }
@@ -93,7 +96,10 @@ pub struct Sample {
 
     // 샘플 데이터
     // sample_type이 Stereo인 경우에는 L R L R 순서로 들어감
-    pub data: Arc<Vec<u8>>
+    pub data: Arc<Vec<u8>>,
+
+    // 필요할 때 불러오는 샘플이면 data 대신 이걸 씀(data는 비어 있음)
+    pub stream: Option<Arc<SampleStream>>
 }
 
 impl Sample {
@@ -110,21 +116,23 @@ impl Sample {
             loop_type: LoopType::NoLoop,
             base_key: 60,
             cent_correction: 0,
-            data: Arc::new(vec![])
+            data: Arc::new(vec![]),
+            stream: None
         };
     }
 
-    fn parse_smls<T: Read + Seek>(list: &Chunk, stream: &mut T) -> anyhow::Result<Vec<Self>> {
+    // loader가 있으면 샘플 데이터를 읽지 않고 필요할 때 불러오도록 함
+    fn parse_smls<T: Read + Seek>(list: &Chunk, stream: &mut T, loader: Option<&SampleLoader>) -> anyhow::Result<Vec<Self>> {
         let mut samples = vec![];
         for chunk in util::unwrap_result_iter(list.iter(stream))? {
             if chunk.read_type(stream)? == fourcc::SMPL {
-                samples.push(Self::parse_smpl(&chunk, stream)?);
+                samples.push(Self::parse_smpl(&chunk, stream, loader)?);
             }
         }
         return Ok(samples);
     }
 
-    fn parse_smpl<T: Read + Seek>(list: &Chunk, stream: &mut T) -> anyhow::Result<Self> {
+    fn parse_smpl<T: Read + Seek>(list: &Chunk, stream: &mut T, loader: Option<&SampleLoader>) -> anyhow::Result<Self> {
         let mut name = String::new();
         let mut bit_depth = 32;
         let mut sample_rate = 48000;
@@ -135,6 +143,7 @@ impl Sample {
         let mut base_key = 60;
         let mut cent_correction = 0;
         let mut data = Arc::new(vec![]);
+        let mut data_layout = None;
 
         for chunk in util::unwrap_result_iter(list.iter(stream))? {
             let chunk_id = chunk.id();
@@ -154,17 +163,34 @@ impl Sample {
                 base_key = contents[16];
                 cent_correction = i8::from_le_bytes(contents[17..18].try_into()?);
             } else if chunk_id == fourcc::SMDT {
-                let contents = chunk.read_contents(stream)?;
-                data = Arc::new(contents);
+                if loader.is_some() {
+                    data_layout = Some(SampleLayout::Interleaved {
+                        offset: chunk.offset() as usize + 8,
+                        len: chunk.len() as usize
+                    });
+                } else {
+                    data = Arc::new(chunk.read_contents(stream)?);
+                }
             }
         }
 
+        // 샘플 형식은 smhd까지 읽어야 알 수 있음
+        let channels = match sample_type {
+            SampleType::Mono => 1,
+            SampleType::Stereo => 2
+        };
+        let stream = match (loader, data_layout) {
+            (Some(loader), Some(layout)) => Some(loader.create_stream(layout, bit_depth, channels)),
+            _ => None
+        };
+
         return Ok(Self {
             name, bit_depth,
             sample_rate, sample_type,
             loop_start, loop_end,
             loop_type, base_key,
-            cent_correction, data
+            cent_correction, data,
+            stream
         });
     }
 
@@ -182,7 +208,10 @@ impl Sample {
     }
 
     fn make_smdt(&self) -> ChunkContents {
-        return ChunkContents::Data(fourcc::SMDT, Vec::clone(&self.data));
+        return match &self.stream {
+            Some(stream) => ChunkContents::Data(fourcc::SMDT, stream.read_all()),
+            None => ChunkContents::Data(fourcc::SMDT, Vec::clone(&self.data))
+        };
     }
 
     fn to_smpl(&self) -> anyhow::Result<ChunkContents> {
@@ -203,6 +232,7 @@ impl Sample {
     }
 }
 
+#[derive(Clone)]
 pub struct Articulator {
     pub src: u32,
     pub src_transform: u8,
@@ -299,6 +329,12 @@ pub struct Region {
 impl Region {
     const RGNH_LEN: u32 = 8;
 
+    // 이 region이 해당 key와 velocity에서 소리를 내는지 확인
+    pub fn contains(&self, key: u8, velocity: u8) -> bool {
+        return self.key_range.0 <= key && key <= self.key_range.1
+            && self.velocity_range.0 <= velocity && velocity <= self.velocity_range.1;
+    }
+
     fn parse_lrgn<T: Read + Seek>(list: &Chunk, stream: &mut T) -> anyhow::Result<Vec<Self>> {
         let mut regions = vec![];
         for chunk in util::unwrap_result_iter(list.iter(stream))? {
@@ -313,6 +349,7 @@ impl Region {
         let mut key_range = (0, 127);
         let mut velocity_range = (0, 127);
         let mut target_index = 0;
+        let mut generators = HashMap::new();
         let mut articulators = vec![];
 
         for chunk in util::unwrap_result_iter(list.iter(stream))? {
@@ -327,6 +364,8 @@ impl Region {
                 velocity_range.0 = contents[2];
                 velocity_range.1 = contents[3];
                 target_index = u32::from_le_bytes(contents[4..8].try_into()?);
+            } else if chunk_id == fourcc::LGEN {
+                generators.extend(Self::parse_lgen(chunk.read_contents(stream)?)?);
             } else if chunk_id == fourcc::ARTC {
                 articulators.append(&mut Articulator::parse_artc(chunk.read_contents(stream)?)?);
             }
@@ -335,10 +374,43 @@ impl Region {
         return Ok(Self {
             key_range, velocity_range,
             target_index,
-            generators: HashMap::new(), articulators
+            generators, articulators
         });
     }
 
+    // 개수(u32) 다음에 (종류(u16), 값(i32))가 이어짐
+    fn parse_lgen(content: Vec<u8>) -> anyhow::Result<Vec<(u16, i32)>> {
+        let mut stream = Cursor::new(content);
+
+        let mut count_bytes = [0; 4];
+        stream.read_exact(&mut count_bytes)?;
+        let count = u32::from_le_bytes(count_bytes);
+
+        let mut generators = vec![];
+        for _ in 0..count {
+            let mut gen_bytes = [0; 2];
+            stream.read_exact(&mut gen_bytes)?;
+            let mut value_bytes = [0; 4];
+            stream.read_exact(&mut value_bytes)?;
+            generators.push((u16::from_le_bytes(gen_bytes), i32::from_le_bytes(value_bytes)));
+        }
+        return Ok(generators);
+    }
+
+    fn make_lgen(&self) -> anyhow::Result<ChunkContents> {
+        // 항상 같은 파일이 나오도록 종류 순서대로 씀
+        let mut generators: Vec<(&u16, &i32)> = self.generators.iter().collect();
+        generators.sort();
+
+        let mut stream = Cursor::new(vec![]);
+        stream.write_all(&u32::to_le_bytes(generators.len() as u32))?;
+        for (gen, value) in generators {
+            stream.write_all(&gen.to_le_bytes())?;
+            stream.write_all(&value.to_le_bytes())?;
+        }
+        return Ok(ChunkContents::Data(fourcc::LGEN, stream.into_inner()));
+    }
+
     fn make_rgnh(&self) -> anyhow::Result<ChunkContents> {
         let mut stream = Cursor::new(vec![]);
         stream.write_all(&[
@@ -352,10 +424,12 @@ impl Region {
     }
 
     fn to_rgni(&self) -> anyhow::Result<ChunkContents> {
-        let chunks = vec![
-            self.make_rgnh()?,
-            Articulator::make_artc(&self.articulators)?
-        ];
+        let mut chunks = vec![self.make_rgnh()?];
+        // generator가 없으면 lgen 청크를 쓰지 않음
+        if !self.generators.is_empty() {
+            chunks.push(self.make_lgen()?);
+        }
+        chunks.push(Articulator::make_artc(&self.articulators)?);
         return Ok(ChunkContents::Children(riff::LIST_ID, fourcc::RGNI, chunks));
     }
 
@@ -419,6 +493,7 @@ impl Instrument {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum PresetType {
     Melodic, Drum
 }
@@ -542,6 +617,18 @@ impl WSBK {
     }
 
     pub fn read<T: Read + Seek>(stream: &mut T) -> anyhow::Result<Self> {
+        return Self::read_with_loader(stream, None);
+    }
+
+    /**
+     * 샘플 데이터를 필요할 때 불러오도록 읽음(큰 사운드뱅크용)
+     * 샘플마다 앞부분만 메모리에 올리고 나머지는 재생할 때 백그라운드 스레드가 불러옴
+     */
+    pub fn read_streamed(loader: &SampleLoader) -> anyhow::Result<Self> {
+        return Self::read_with_loader(&mut Cursor::new(loader.bytes()), Some(loader));
+    }
+
+    fn read_with_loader<T: Read + Seek>(stream: &mut T, loader: Option<&SampleLoader>) -> anyhow::Result<Self> {
         let wsbk = Chunk::read(stream, 0)?;
         let mut samples = vec![];
         let mut instruments = vec![];
@@ -553,7 +640,7 @@ impl WSBK {
 
         for chunk in util::unwrap_result_iter(wsbk.iter(stream))? {
             match chunk.read_type(stream)? {
-                fourcc::SMLS => samples.append(&mut Sample::parse_smls(&chunk, stream)?),
+                fourcc::SMLS => samples.append(&mut Sample::parse_smls(&chunk, stream, loader)?),
                 fourcc::LINS => instruments.append(&mut Instrument::parse_lins(&chunk, stream)?),
                 fourcc::LPRS => presets.append(&mut Preset::parse_lprs(&chunk, stream)?),
                 _ => {}
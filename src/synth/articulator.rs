@@ -6,11 +6,14 @@ use crate::soundbank::wsbk::Articulator;
 fn normalize(val: f64, val_type: u32) -> f64 {
     return if
         val_type == artc_src::PITCH_WHEEL
+        || artc_src::is_midi_rpn(val_type)
+        || artc_src::is_midi_nrpn(val_type)
     { // 0 - 16383
         val / 16383.0
     } else if
         val_type == artc_src::NOTE_ON_VELOCITY
         || val_type == artc_src::NOTE_NUMBER
+        || val_type == artc_src::ROOT_KEY
         || val_type == artc_src::NOTE_AFTERTOUCH
         || val_type == artc_src::CHANNEL_AFTERTOUCH
         || artc_src::is_midi_cc(val_type)
@@ -26,6 +29,8 @@ fn normalize(val: f64, val_type: u32) -> f64 {
         || val_type == artc_src::VIBRATO_LFO
     { // -1.0 - 1.0
         (val + 1.0) / 2.0
+    } else { // NONE 등(항상 최대값)
+        1.0
     };
 }
 
@@ -33,16 +38,16 @@ fn do_process_transform(val: f64, transform_type: u8) -> f64 {
     return if transform_type == artc_transform::LINEAR {
         val
     } else if transform_type == artc_transform::CONCAVE {
-        if val > 1.0 - 10.0.powf(-12.0 / 5.0) {
+        if val > 1.0 - 10.0_f64.powf(-12.0 / 5.0) {
             1.0
         } else {
             (-5.0 / 12.0) * (1.0 - val).log10()
         }
     } else if transform_type == artc_transform::CONVEX {
-        if val > 1.0 - 10.0.powf(-12.0 / 5.0) {
-            1.0
+        if val < 10.0_f64.powf(-12.0 / 5.0) {
+            0.0
         } else {
-            (-5.0 / 12.0) * (1.0 - val).log10()
+            1.0 + (5.0 / 12.0) * val.log10()
         }
     } else if transform_type == artc_transform::SWITCH {
         if val >= 0.5 {
@@ -50,6 +55,8 @@ fn do_process_transform(val: f64, transform_type: u8) -> f64 {
         } else {
             0.0
         }
+    } else {
+        val
     };
 }
 
@@ -62,14 +69,14 @@ fn process_transform(val: f64, transform: u8) -> f64 {
     let val = if inverted { 1.0 - val } else { val };
     return if bipolar {
         let val = val * 2.0 - 1.0;
-        val.sgn() * do_process_transform(val.abs())
+        val.signum() * do_process_transform(val.abs(), transform_type)
     } else {
-        do_process_transform(val)
+        do_process_transform(val, transform_type)
     };
 }
 
 // 여기서 나온 값을 dest val에 더하게 됨
-fn process_articulation(articulator: Articulator, src_val: f64, control_val: f64) -> f64 {
+fn process_articulation(articulator: &Articulator, src_val: f64, control_val: f64) -> f64 {
     /* 0.0 - 1.0 범위 또는 -1.0 - 1.0 범위로 변환 */
 
     // 1차적으로 0.0 - 1.0 범위로 변환
@@ -80,7 +87,17 @@ fn process_articulation(articulator: Articulator, src_val: f64, control_val: f64
     let src_normalized = process_transform(src_normalized, articulator.src_transform);
     let control_normalized = process_transform(control_normalized, articulator.control_transform);
 
-    return process_transform(src_normalized * control_normalized * articulator.scale, articulator.main_transform);
+    // scale은 변환 함수를 적용한 다음에 곱해야 함(미리 곱하면 0.0 - 1.0 범위를 벗어나 버림)
+    return process_transform(src_normalized * control_normalized, articulator.main_transform) * articulator.scale;
+}
+
+// note on 이후로 값이 바뀌지 않는 source인지 확인
+// 이런 source만 쓰는 articulator는 note on 시점에 한 번만 계산하면 됨
+fn is_static_src(src: u32) -> bool {
+    return src == artc_src::NONE
+        || src == artc_src::NOTE_ON_VELOCITY
+        || src == artc_src::NOTE_NUMBER
+        || src == artc_src::ROOT_KEY;
 }
 
 pub struct ArticulationUnit {
@@ -88,9 +105,39 @@ pub struct ArticulationUnit {
 }
 
 impl ArticulationUnit {
-    pub fn process(artc_val: &mut AriculationValues) {}
+    pub fn new(articulators: Vec<Articulator>) -> Self {
+        return Self { articulators };
+    }
+
+    // (note on 시점에만 계산하면 되는 unit, 계속 다시 계산해야 하는 unit)으로 나눔
+    pub fn split_static(articulators: Vec<Articulator>) -> (Self, Self) {
+        let (static_artc, dynamic_artc) = articulators.into_iter().partition(|artc: &Articulator| {
+            is_static_src(artc.src) && is_static_src(artc.control)
+        });
+        return (Self::new(static_artc), Self::new(dynamic_artc));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.articulators.is_empty();
+    }
+
+    // get_src_val: source 종류를 받아서 그 source의 현재 값을 반환하는 함수
+    pub fn process<F: Fn(u32) -> f64>(&self, artc_val: &mut AriculationValues, get_src_val: F) {
+        for articulator in self.articulators.iter() {
+            let val = process_articulation(
+                articulator,
+                get_src_val(articulator.src),
+                get_src_val(articulator.control)
+            );
+            artc_val.add(articulator.destination, val);
+        }
+    }
 }
 
+// 시간/Hz 단위에서 "거의 0"을 나타내는 기본값(약 0.00005ms, 0.00005Hz)
+// articulator 값은 기본값에 더해지므로 i32 최솟값을 기본값으로 쓰면 양수 값을 만들 수 없어서 이 값을 씀
+pub const NEAR_ZERO_VALUE: i32 = -143000;
+
 /**
  * Hz 단위: 값 자체는 int의 범위(-2147483648 - 2147483647)와 같으며,
  * 실제로 적용할 때는 이 값을 10000으로 나눈 다음 2의 지수로 집어넣어서 나온 결과값을 Hz 단위로 적용함
@@ -106,6 +153,7 @@ impl ArticulationUnit {
  * 따라서 실제 초 단위 값의 범위는 거의 0에 가까운 값에서 시작해서 우리가 일반적으로는 세지 못할 정도로 커짐
  * 그러므로 실질적으로는 "값 자체"를 -2147483648 - 180000 범위 내에서 사용할 것을 권장함. 이렇게 하면 밀리초 값의 범위가 약 0ms - 262144ms(262.144초)가 됨
  */
+#[derive(Clone, Copy)]
 pub struct AriculationValues {
     // 게인: 0.01dBFS 단위
     // 즉 -14400 = -144dBFS가 되는 거임
@@ -130,6 +178,10 @@ pub struct AriculationValues {
     pub vibrato_lfo_freq: i32,
     pub vibrato_lfo_start_delay: i32,
 
+    // 비브라토 lfo가 피치에 주는 영향: 0.1cent 단위
+    // articulator로 거는 비브라토와는 별개로 더해짐
+    pub vibrato_lfo_pitch: i32,
+
     // sustain = 0 - 10000 (0.01% 단위)
     // 나머지 = 시간 단위
     pub volume_env_delay: i32,
@@ -167,28 +219,136 @@ impl AriculationValues {
             chorus_send_coeff: 10000,
 
             modulation_lfo_freq: 0,
-            modulation_lfo_start_delay: -2147483648,
+            modulation_lfo_start_delay: NEAR_ZERO_VALUE,
             vibrato_lfo_freq: 0,
-            vibrato_lfo_start_delay: -2147483648,
+            vibrato_lfo_start_delay: NEAR_ZERO_VALUE,
+            vibrato_lfo_pitch: 0,
 
-            volume_env_delay: -2147483648,
-            volume_env_attack: -2147483648,
-            volume_env_hold: -2147483648,
-            volume_env_decay: -2147483648,
+            volume_env_delay: NEAR_ZERO_VALUE,
+            volume_env_attack: NEAR_ZERO_VALUE,
+            volume_env_hold: NEAR_ZERO_VALUE,
+            volume_env_decay: NEAR_ZERO_VALUE,
             volume_env_sustain: 10000,
-            volume_env_release: -2147483648,
+            volume_env_release: NEAR_ZERO_VALUE,
 
-            modulation_env_delay: -2147483648,
-            modulation_env_attack: -2147483648,
-            modulation_env_hold: -2147483648,
-            modulation_env_decay: -2147483648,
+            modulation_env_delay: NEAR_ZERO_VALUE,
+            modulation_env_attack: NEAR_ZERO_VALUE,
+            modulation_env_hold: NEAR_ZERO_VALUE,
+            modulation_env_decay: NEAR_ZERO_VALUE,
             modulation_env_sustain: 10000,
-            modulation_env_release: -2147483648,
+            modulation_env_release: NEAR_ZERO_VALUE,
 
             lpf_cutoff: 143000,
             lpf_q: 0,
-            hpf_cutoff: -2147483648,
+            hpf_cutoff: NEAR_ZERO_VALUE,
             hpf_q: 0
         };
     }
+
+    // 모든 값이 0인 개체를 생성
+    // 다른 AriculationValues에 더할 변화량을 담는 용도
+    pub fn zero() -> Self {
+        return Self {
+            gain: 0,
+            pitch: 0,
+            pan: 0,
+            reverb_send_coeff: 0,
+            chorus_send_coeff: 0,
+            modulation_lfo_freq: 0,
+            modulation_lfo_start_delay: 0,
+            vibrato_lfo_freq: 0,
+            vibrato_lfo_start_delay: 0,
+            vibrato_lfo_pitch: 0,
+            volume_env_delay: 0,
+            volume_env_attack: 0,
+            volume_env_hold: 0,
+            volume_env_decay: 0,
+            volume_env_sustain: 0,
+            volume_env_release: 0,
+            modulation_env_delay: 0,
+            modulation_env_attack: 0,
+            modulation_env_hold: 0,
+            modulation_env_decay: 0,
+            modulation_env_sustain: 0,
+            modulation_env_release: 0,
+            lpf_cutoff: 0,
+            lpf_q: 0,
+            hpf_cutoff: 0,
+            hpf_q: 0
+        };
+    }
+
+    // 변화량을 모두 더함
+    pub fn add_values(&mut self, offsets: &Self) {
+        self.gain = self.gain.saturating_add(offsets.gain);
+        self.pitch = self.pitch.saturating_add(offsets.pitch);
+        self.pan = self.pan.saturating_add(offsets.pan);
+        self.reverb_send_coeff = self.reverb_send_coeff.saturating_add(offsets.reverb_send_coeff);
+        self.chorus_send_coeff = self.chorus_send_coeff.saturating_add(offsets.chorus_send_coeff);
+        self.modulation_lfo_freq = self.modulation_lfo_freq.saturating_add(offsets.modulation_lfo_freq);
+        self.modulation_lfo_start_delay = self.modulation_lfo_start_delay.saturating_add(offsets.modulation_lfo_start_delay);
+        self.vibrato_lfo_freq = self.vibrato_lfo_freq.saturating_add(offsets.vibrato_lfo_freq);
+        self.vibrato_lfo_start_delay = self.vibrato_lfo_start_delay.saturating_add(offsets.vibrato_lfo_start_delay);
+        self.vibrato_lfo_pitch = self.vibrato_lfo_pitch.saturating_add(offsets.vibrato_lfo_pitch);
+        self.volume_env_delay = self.volume_env_delay.saturating_add(offsets.volume_env_delay);
+        self.volume_env_attack = self.volume_env_attack.saturating_add(offsets.volume_env_attack);
+        self.volume_env_hold = self.volume_env_hold.saturating_add(offsets.volume_env_hold);
+        self.volume_env_decay = self.volume_env_decay.saturating_add(offsets.volume_env_decay);
+        self.volume_env_sustain = self.volume_env_sustain.saturating_add(offsets.volume_env_sustain);
+        self.volume_env_release = self.volume_env_release.saturating_add(offsets.volume_env_release);
+        self.modulation_env_delay = self.modulation_env_delay.saturating_add(offsets.modulation_env_delay);
+        self.modulation_env_attack = self.modulation_env_attack.saturating_add(offsets.modulation_env_attack);
+        self.modulation_env_hold = self.modulation_env_hold.saturating_add(offsets.modulation_env_hold);
+        self.modulation_env_decay = self.modulation_env_decay.saturating_add(offsets.modulation_env_decay);
+        self.modulation_env_sustain = self.modulation_env_sustain.saturating_add(offsets.modulation_env_sustain);
+        self.modulation_env_release = self.modulation_env_release.saturating_add(offsets.modulation_env_release);
+        self.lpf_cutoff = self.lpf_cutoff.saturating_add(offsets.lpf_cutoff);
+        self.lpf_q = self.lpf_q.saturating_add(offsets.lpf_q);
+        self.hpf_cutoff = self.hpf_cutoff.saturating_add(offsets.hpf_cutoff);
+        self.hpf_q = self.hpf_q.saturating_add(offsets.hpf_q);
+    }
+
+    // destination에 해당하는 값에 val을 더함
+    pub fn add(&mut self, dest: u32, val: f64) {
+        let target = match dest {
+            artc_dest::GAIN => &mut self.gain,
+            artc_dest::PITCH => &mut self.pitch,
+            artc_dest::PAN => &mut self.pan,
+            artc_dest::REVERB_SEND_COEFF => &mut self.reverb_send_coeff,
+            artc_dest::CHORUS_SEND_COEFF => &mut self.chorus_send_coeff,
+            artc_dest::MODULATION_LFO_FREQUENCY => &mut self.modulation_lfo_freq,
+            artc_dest::MODULATION_LFO_START_DELAY => &mut self.modulation_lfo_start_delay,
+            artc_dest::VIBRATO_LFO_FREQUENCY => &mut self.vibrato_lfo_freq,
+            artc_dest::VIBRATO_LFO_START_DELAY => &mut self.vibrato_lfo_start_delay,
+            artc_dest::VIBRATO_LFO_TO_PITCH => &mut self.vibrato_lfo_pitch,
+            artc_dest::VOLUME_ENV_DELAY => &mut self.volume_env_delay,
+            artc_dest::VOLUME_ENV_ATTACK => &mut self.volume_env_attack,
+            artc_dest::VOLUME_ENV_HOLD => &mut self.volume_env_hold,
+            artc_dest::VOLUME_ENV_DECAY => &mut self.volume_env_decay,
+            artc_dest::VOLUME_ENV_SUSTAIN => &mut self.volume_env_sustain,
+            artc_dest::VOLUME_ENV_RELEASE => &mut self.volume_env_release,
+            artc_dest::MODULATION_ENV_DELAY => &mut self.modulation_env_delay,
+            artc_dest::MODULATION_ENV_ATTACK => &mut self.modulation_env_attack,
+            artc_dest::MODULATION_ENV_HOLD => &mut self.modulation_env_hold,
+            artc_dest::MODULATION_ENV_DECAY => &mut self.modulation_env_decay,
+            artc_dest::MODULATION_ENV_SUSTAIN => &mut self.modulation_env_sustain,
+            artc_dest::MODULATION_ENV_RELEASE => &mut self.modulation_env_release,
+            artc_dest::LPF_CUTOFF => &mut self.lpf_cutoff,
+            artc_dest::LPF_Q => &mut self.lpf_q,
+            artc_dest::HPF_CUTOFF => &mut self.hpf_cutoff,
+            artc_dest::HPF_Q => &mut self.hpf_q,
+            _ => return
+        };
+        *target = target.saturating_add(val.round() as i32);
+    }
+}
+
+/** Hz 단위 값 => 실제 Hz */
+pub fn hz_from_value(val: i32) -> f64 {
+    return 2.0_f64.powf(val as f64 / 10000.0);
+}
+
+/** 시간 단위 값 => 실제 밀리초 */
+pub fn ms_from_value(val: i32) -> f64 {
+    return 2.0_f64.powf(val as f64 / 10000.0);
 }
\ No newline at end of file
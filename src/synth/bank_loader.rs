@@ -0,0 +1,140 @@
+/**
+ * wsn sysex로 요청한 사운드뱅크를 백그라운드 스레드에서 불러옴
+ * 사운드뱅크 불러오기 함수는 파일을 읽으므로 오디오 스레드(render)에서 부르면 안 됨
+ * 오디오 스레드는 lock-free 큐로 요청만 넣고, 다 불러온 사운드뱅크는 render할 때 블록 경계에서 꺼내감
+ * 뺀 사운드뱅크도 오디오 스레드에서 메모리를 해제하지 않도록 이 스레드로 돌려보내서 해제함
+ */
+
+use std::sync::{ Arc, Weak };
+use std::thread::Thread;
+use std::time::Duration;
+use crossbeam_queue::ArrayQueue;
+use crate::soundbank::Soundbank;
+use crate::soundbank::wsbk::WSBK;
+
+// 한 번에 쌓아둘 수 있는 불러오기 요청 수
+// 모두 빼기 전까지 요청할 수 있는 사운드뱅크 수도 같음
+const REQUEST_QUEUE_CAPACITY: usize = 64;
+
+// 번호로 사운드뱅크를 불러오는 함수
+pub(crate) type SoundbankLoader = Box<dyn FnMut(u16) -> Option<WSBK> + Send>;
+
+// 요청: (사운드뱅크 번호, 요청할 때의 세대), 결과: (세대, 번호, 불러온 사운드뱅크)
+// 세대는 "사운드뱅크 모두 빼기" 때마다 바뀜. 그 전에 요청한 사운드뱅크는 다 불러와도 버림
+// discarded: 오디오 스레드에서 뺀 사운드뱅크(불러오기 스레드에서 해제함)
+struct Shared {
+    requests: ArrayQueue<(u16, u32)>,
+    loaded: ArrayQueue<(u32, u16, Option<WSBK>)>,
+    discarded: ArrayQueue<Soundbank>
+}
+
+pub(crate) struct BankLoader {
+    shared: Arc<Shared>,
+    thread: Thread,
+    generation: u32,
+
+    // 마지막으로 모두 뺀 다음에 불러왔거나 불러오는 중인 사운드뱅크 번호
+    // 같은 번호를 또 요청하면 무시함(미디 파일 재생 위치를 옮길 때 등)
+    // 오디오 스레드에서 메모리를 할당하지 않도록 REQUEST_QUEUE_CAPACITY개까지만 넣음
+    requested: Vec<u16>
+}
+
+impl BankLoader {
+    pub fn new(loader: SoundbankLoader) -> anyhow::Result<Self> {
+        let shared = Arc::new(Shared {
+            requests: ArrayQueue::new(REQUEST_QUEUE_CAPACITY),
+            loaded: ArrayQueue::new(REQUEST_QUEUE_CAPACITY),
+            discarded: ArrayQueue::new(REQUEST_QUEUE_CAPACITY)
+        });
+        let weak = Arc::downgrade(&shared);
+        let thread = std::thread::Builder::new().name("whitesynth-soundbank-loader".into()).spawn(move || {
+            run_loader(weak, loader);
+        })?;
+        return Ok(Self {
+            shared,
+            thread: thread.thread().clone(),
+            generation: 0,
+            requested: Vec::with_capacity(REQUEST_QUEUE_CAPACITY)
+        });
+    }
+
+    // 불러오기 요청(오디오 스레드에서 불러도 됨)
+    pub fn request(&mut self, index: u16) {
+        if self.requested.contains(&index) {
+            return;
+        }
+        if self.requested.len() >= REQUEST_QUEUE_CAPACITY {
+            log::error!("Too many soundbanks requested, ignoring soundbank #{}", index);
+            return;
+        }
+        if self.shared.requests.push((index, self.generation)).is_err() {
+            log::error!("Soundbank load request queue is full, ignoring soundbank #{}", index);
+            return;
+        }
+        self.requested.push(index);
+        self.thread.unpark();
+    }
+
+    pub fn requested(&self) -> &[u16] {
+        return &self.requested;
+    }
+
+    // 지금까지 요청한 사운드뱅크는 다 불러와도 추가하지 않음
+    pub fn cancel_pending(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.requested.clear();
+    }
+
+    // 다 불러온 사운드뱅크를 하나 꺼냄. 없으면 None
+    // 불러오지 못한 번호는 다시 요청할 수 있게 함
+    pub fn pop_loaded(&mut self) -> Option<WSBK> {
+        while let Some((generation, index, soundbank)) = self.shared.loaded.pop() {
+            if generation != self.generation {
+                if let Some(soundbank) = soundbank {
+                    self.discard(soundbank.into());
+                }
+                continue;
+            }
+            match soundbank {
+                Some(soundbank) => return Some(soundbank),
+                None => self.requested.retain(|requested| *requested != index)
+            }
+        }
+        return None;
+    }
+
+    // 뺀 사운드뱅크를 불러오기 스레드에서 해제하도록 넘김
+    // 큐가 가득 차 있으면 어쩔 수 없이 여기서 해제함
+    pub fn discard(&mut self, soundbank: Soundbank) {
+        if self.shared.discarded.push(soundbank).is_err() {
+            log::warn!("Soundbank discard queue is full, freeing soundbank on the calling thread");
+        }
+        self.thread.unpark();
+    }
+}
+
+fn run_loader(shared: Weak<Shared>, mut loader: SoundbankLoader) {
+    loop {
+        let Some(shared) = shared.upgrade() else {
+            break;
+        };
+        while shared.discarded.pop().is_some() {}
+        while let Some((index, generation)) = shared.requests.pop() {
+            let soundbank = loader(index);
+            if soundbank.is_none() {
+                log::error!("Failed to load soundbank #{}", index);
+            }
+            // 오디오 스레드가 꺼내갈 때까지 기다림(synth가 없어지면 그만둠)
+            let mut result = (generation, index, soundbank);
+            while let Err(rejected) = shared.loaded.push(result) {
+                if Arc::strong_count(&shared) == 1 {
+                    return;
+                }
+                result = rejected;
+                std::thread::park_timeout(Duration::from_millis(10));
+            }
+        }
+        drop(shared);
+        std::thread::park_timeout(Duration::from_millis(100));
+    }
+}
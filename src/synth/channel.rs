@@ -0,0 +1,724 @@
+/**
+ * midi 채널 1개의 상태
+ * sc-8820처럼 포트 2개(A, B) x 16채널 = 32채널을 씀
+ * 채널 번호는 (포트 번호 * 16 + 포트 안에서의 채널 번호)로 매김
+ */
+
+use std::collections::HashMap;
+use crate::soundbank::wsbk::consts::artc_src;
+use crate::synth::articulator::AriculationValues;
+use crate::util::to_dbfs;
+use crate::util::midi::{ cc_ids, cc_ids_i, get_initial_cc };
+
+// 포트 수
+pub const MIDI_PORTS: usize = 2;
+
+// 포트 1개당 채널 수
+pub const CHANNELS_PER_PORT: usize = 16;
+
+// 전체 채널 수
+pub const MIDI_CHANNELS: usize = MIDI_PORTS * CHANNELS_PER_PORT;
+
+// data entry로 바꿀 파라미터가 rpn인지 nrpn인지
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ParamSelect {
+    None, Rpn, Nrpn
+}
+
+// 표준 rpn 번호(msb, lsb)
+pub mod rpn_ids {
+    pub const PITCH_BEND_SENSITIVITY: (u8, u8) = (0x00, 0x00);
+    pub const FINE_TUNING: (u8, u8) = (0x00, 0x01);
+    pub const COARSE_TUNING: (u8, u8) = (0x00, 0x02);
+    pub const MODULATION_DEPTH_RANGE: (u8, u8) = (0x00, 0x05);
+    pub const NULL: (u8, u8) = (0x7f, 0x7f);
+}
+
+/**
+ * gs nrpn 번호(msb, lsb)
+ * 파트 파라미터는 data entry msb 40h가 기준(변화 없음)이고 0Eh - 72h 범위에서 -64 - +63만큼 바뀜
+ * 드럼 파라미터는 lsb가 note 번호
+ */
+pub mod gs_nrpn_ids {
+    pub const VIBRATO_RATE: (u8, u8) = (0x01, 0x08);
+    pub const VIBRATO_DEPTH: (u8, u8) = (0x01, 0x09);
+    pub const VIBRATO_DELAY: (u8, u8) = (0x01, 0x0a);
+    pub const TVF_CUTOFF: (u8, u8) = (0x01, 0x20);
+    pub const TVF_RESONANCE: (u8, u8) = (0x01, 0x21);
+    pub const TVA_ENV_ATTACK: (u8, u8) = (0x01, 0x63);
+    pub const TVA_ENV_DECAY: (u8, u8) = (0x01, 0x64);
+    pub const TVA_ENV_RELEASE: (u8, u8) = (0x01, 0x66);
+
+    // 파트 파라미터의 msb
+    pub const PART_PARAM_MSB: u8 = 0x01;
+
+    // 드럼 파라미터의 msb
+    pub const DRUM_PITCH_COARSE: u8 = 0x18;
+    pub const DRUM_TVA_LEVEL: u8 = 0x1a;
+    pub const DRUM_PANPOT: u8 = 0x1c;
+    pub const DRUM_REVERB_SEND: u8 = 0x1d;
+    pub const DRUM_CHORUS_SEND: u8 = 0x1e;
+}
+
+/**
+ * gs 컨트롤러 destination(sysex 40 2x yy)의 source 번호
+ * yy의 상위 4비트가 source, 하위 4비트가 destination
+ */
+pub mod gs_ctrl_src {
+    pub const MOD: usize = 0; // modulation wheel(cc 1)
+    pub const BEND: usize = 1; // pitch bend
+    pub const CAF: usize = 2; // channel aftertouch
+    pub const PAF: usize = 3; // polyphonic aftertouch
+    pub const CC1: usize = 4; // 임의로 지정한 cc 1(기본값 = cc 16)
+    pub const CC2: usize = 5; // 임의로 지정한 cc 2(기본값 = cc 17)
+
+    pub const COUNT: usize = 6;
+}
+
+// gs 컨트롤러 destination 번호
+pub mod gs_ctrl_dest {
+    pub const PITCH: usize = 0; // 28h - 58h => -24 - +24키
+    pub const TVF_CUTOFF: usize = 1; // 00h - 7Fh => -9600 - +9450cent
+    pub const AMPLITUDE: usize = 2; // 00h - 7Fh => -100 - +100%
+    pub const LFO1_RATE: usize = 3; // 00h - 7Fh => -10 - +10Hz
+    pub const LFO1_PITCH: usize = 4; // 00h - 7Fh => 0 - 600cent
+    pub const LFO1_TVF: usize = 5; // 00h - 7Fh => 0 - 2400cent
+    pub const LFO1_TVA: usize = 6; // 00h - 7Fh => 0 - 100%
+    pub const LFO2_RATE: usize = 7;
+    pub const LFO2_PITCH: usize = 8;
+    pub const LFO2_TVF: usize = 9;
+    pub const LFO2_TVA: usize = 10;
+
+    pub const COUNT: usize = 11;
+}
+
+// 컨트롤러 destination의 기본값
+// MOD => LFO1 pitch와 BEND => pitch는 각각 rpn 5(modulation depth range)와 rpn 0(pitch bend sensitivity)에 저장됨
+const GS_CTRL_DEST_DEFAULT: [u8; gs_ctrl_dest::COUNT] = [0x40, 0x40, 0x40, 0x40, 0, 0, 0, 0x40, 0, 0, 0];
+
+/**
+ * gs 컨트롤러 destination을 모두 합친 변화량
+ * lfo1은 vibrato lfo, lfo2는 modulation lfo에 해당함
+ */
+#[derive(Clone, Copy, Default)]
+pub struct ControllerModulation {
+    pub pitch_cent: f64,
+    pub tvf_cent: f64,
+
+    // 0.0 = 원래 음량. 실제 게인은 (1.0 + amplitude)
+    pub amplitude: f64,
+
+    pub lfo1_rate_hz: f64,
+    pub lfo1_pitch_cent: f64,
+    pub lfo1_tvf_cent: f64,
+    pub lfo1_tva: f64,
+    pub lfo2_rate_hz: f64,
+    pub lfo2_pitch_cent: f64,
+    pub lfo2_tvf_cent: f64,
+    pub lfo2_tva: f64
+}
+
+// data entry로 값이 바뀐 파라미터
+pub struct ParamChange {
+    pub select: ParamSelect,
+    pub msb: u8,
+    pub lsb: u8,
+
+    // 14비트 값(data entry msb << 7 | data entry lsb)
+    pub value: u16
+}
+
+#[inline]
+fn param_key(msb: u8, lsb: u8) -> u16 {
+    return ((msb as u16) << 8) | lsb as u16;
+}
+
+pub struct Channel {
+    // control change 값
+    pub(crate) cc: [u8; 128],
+
+    // 현재 적용된 bank select 값
+    // bank select cc는 program change가 들어와야 적용되므로 cc 값과는 따로 저장함
+    pub(crate) bank_msb: u8,
+    pub(crate) bank_lsb: u8,
+
+    pub(crate) program: u8,
+
+    // 0 - 8192(가운데) - 16383
+    pub(crate) pitch_bend: u16,
+
+    pub(crate) channel_aftertouch: u8,
+    pub(crate) note_aftertouch: [u8; 128],
+
+    // 선택된 rpn/nrpn 번호(msb, lsb)
+    pub(crate) param_select: ParamSelect,
+    pub(crate) rpn: (u8, u8),
+    pub(crate) nrpn: (u8, u8),
+
+    // rpn/nrpn 값(14비트)
+    // key = msb << 8 | lsb
+    pub(crate) rpn_values: HashMap<u16, u16>,
+    pub(crate) nrpn_values: HashMap<u16, u16>,
+
+    // gs nrpn 파트 파라미터를 변화량으로 바꿔 놓은 것
+    pub(crate) part_offsets: AriculationValues,
+
+    // 드럼 채널 여부
+    pub(crate) is_drum: bool,
+
+    /* gs sysex로 바꾸는 파트 파라미터 */
+
+    // 이 파트가 받는 채널(포트 안에서의 채널 번호). None이면 아무것도 받지 않음
+    pub(crate) rx_channel: Option<u8>,
+
+    // -24 - +24키
+    pub(crate) key_shift: i8,
+
+    // velocity sense depth/offset. 둘 다 64가 기준(변화 없음)
+    pub(crate) velocity_sense_depth: u8,
+    pub(crate) velocity_sense_offset: u8,
+
+    // 소리를 낼 note 범위(low, high)
+    pub(crate) key_range: (u8, u8),
+
+    // 음이름(C - B)별 피치 조정(cent 단위. -100 - +100)
+    pub(crate) scale_tuning: [f64; 12],
+
+    // 컨트롤러 destination의 CC1, CC2로 쓸 cc 번호
+    pub(crate) ctrl_cc_numbers: [u8; 2],
+
+    // 컨트롤러 destination 값([source][destination])
+    pub(crate) ctrl_dests: [[u8; gs_ctrl_dest::COUNT]; gs_ctrl_src::COUNT]
+}
+
+impl Channel {
+    pub fn new(channel_no: u8) -> Self {
+        let mut this = Self {
+            cc: get_initial_cc(),
+            bank_msb: 0,
+            bank_lsb: 0,
+            program: 0,
+            pitch_bend: 8192,
+            channel_aftertouch: 0,
+            note_aftertouch: [0; 128],
+            param_select: ParamSelect::None,
+            rpn: rpn_ids::NULL,
+            nrpn: rpn_ids::NULL,
+            rpn_values: HashMap::new(),
+            nrpn_values: HashMap::new(),
+            part_offsets: AriculationValues::zero(),
+            is_drum: false,
+            rx_channel: None,
+            key_shift: 0,
+            velocity_sense_depth: 64,
+            velocity_sense_offset: 64,
+            key_range: (0, 127),
+            scale_tuning: [0.0; 12],
+            ctrl_cc_numbers: [cc_ids::GENERAL_PURPOSE_1, cc_ids::GENERAL_PURPOSE_2],
+            ctrl_dests: [GS_CTRL_DEST_DEFAULT; gs_ctrl_src::COUNT]
+        };
+        this.reset(channel_no);
+        return this;
+    }
+
+    // 전원을 켰을 때의 상태로 되돌림
+    // 각 포트의 10번 채널(channel_no % 16 == 9)이 드럼 채널
+    pub fn reset(&mut self, channel_no: u8) {
+        self.cc = get_initial_cc();
+        self.bank_msb = 0;
+        self.bank_lsb = 0;
+        self.program = 0;
+        self.is_drum = channel_no as usize % CHANNELS_PER_PORT == 9;
+        self.reset_controllers();
+
+        self.nrpn_values.clear();
+        self.rpn_values.clear();
+        self.set_rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY, 2 << 7); // 2키
+        self.set_rpn_value(rpn_ids::FINE_TUNING, 8192);
+        self.set_rpn_value(rpn_ids::COARSE_TUNING, 64 << 7);
+        self.set_rpn_value(rpn_ids::MODULATION_DEPTH_RANGE, 0x40); // 50cent
+        self.update_part_offsets();
+
+        self.rx_channel = Some((channel_no as usize % CHANNELS_PER_PORT) as u8);
+        self.key_shift = 0;
+        self.velocity_sense_depth = 64;
+        self.velocity_sense_offset = 64;
+        self.key_range = (0, 127);
+        self.scale_tuning = [0.0; 12];
+        self.ctrl_cc_numbers = [cc_ids::GENERAL_PURPOSE_1, cc_ids::GENERAL_PURPOSE_2];
+        self.ctrl_dests = [GS_CTRL_DEST_DEFAULT; gs_ctrl_src::COUNT];
+    }
+
+    // reset all controllers(cc 121)
+    // volume, pan, bank select, program 등은 건드리지 않음(RP-015 참조)
+    pub fn reset_controllers(&mut self) {
+        self.cc[cc_ids_i::MODULATION] = 0;
+        self.cc[cc_ids_i::EXPRESSION] = 127;
+        self.cc[cc_ids_i::SUSTAIN_ONOFF] = 0;
+        self.cc[cc_ids_i::PORTAMENTO_ONOFF] = 0;
+        self.cc[cc_ids_i::SOSTENUTO_ONOFF] = 0;
+        self.cc[cc_ids_i::SOFT_PEDAL_ONOFF] = 0;
+        self.pitch_bend = 8192;
+        self.channel_aftertouch = 0;
+        self.note_aftertouch = [0; 128];
+        self.param_select = ParamSelect::None;
+        self.rpn = rpn_ids::NULL;
+        self.nrpn = rpn_ids::NULL;
+    }
+
+    // cc 값을 저장하고 rpn/nrpn 선택 상태를 갱신
+    // data entry로 rpn/nrpn 값이 바뀌었으면 바뀐 파라미터를 반환함
+    pub fn control_change(&mut self, cc: u8, val: u8) -> Option<ParamChange> {
+        let cc = cc & 0x7f;
+        let val = val & 0x7f;
+        self.cc[cc as usize] = val;
+
+        match cc {
+            cc_ids::RPN_MSB => {
+                self.rpn.0 = val;
+                self.param_select = ParamSelect::Rpn;
+            },
+            cc_ids::RPN_LSB => {
+                self.rpn.1 = val;
+                self.param_select = ParamSelect::Rpn;
+            },
+            cc_ids::NRPN_MSB => {
+                self.nrpn.0 = val;
+                self.param_select = ParamSelect::Nrpn;
+            },
+            cc_ids::NRPN_LSB => {
+                self.nrpn.1 = val;
+                self.param_select = ParamSelect::Nrpn;
+            },
+            cc_ids::DATA_ENTRY_MSB
+            | cc_ids::DATA_ENTRY_LSB
+            | cc_ids::DATA_INCREMENT
+            | cc_ids::DATA_DECREMENT => return self.data_entry(cc, val),
+            _ => {}
+        }
+        return None;
+    }
+
+    /**
+     * data entry 처리
+     * - msb: 값의 상위 7비트를 바꾸고 하위 7비트는 0으로 만듦
+     * - lsb: 값의 하위 7비트만 바꿈
+     * - increment/decrement: 값을 1씩 올리거나 내림(cc 값은 무시)
+     * null rpn/nrpn(7F/7F)이 선택되어 있으면 아무것도 하지 않음
+     */
+    fn data_entry(&mut self, cc: u8, val: u8) -> Option<ParamChange> {
+        let (msb, lsb) = match self.param_select {
+            ParamSelect::Rpn => self.rpn,
+            ParamSelect::Nrpn => self.nrpn,
+            ParamSelect::None => return None
+        };
+        if (msb, lsb) == rpn_ids::NULL {
+            return None;
+        }
+
+        let values = match self.param_select {
+            ParamSelect::Nrpn => &mut self.nrpn_values,
+            _ => &mut self.rpn_values
+        };
+        let current = values.get(&param_key(msb, lsb)).copied().unwrap_or(0);
+        let value = match cc {
+            cc_ids::DATA_ENTRY_MSB => (val as u16) << 7,
+            cc_ids::DATA_ENTRY_LSB => (current & 0x3f80) | val as u16,
+            cc_ids::DATA_INCREMENT => (current + 1).min(16383),
+            _ => current.saturating_sub(1)
+        };
+        values.insert(param_key(msb, lsb), value);
+
+        if self.param_select == ParamSelect::Nrpn && msb == gs_nrpn_ids::PART_PARAM_MSB {
+            self.update_part_offsets();
+        }
+
+        return Some(ParamChange {
+            select: self.param_select,
+            msb, lsb, value
+        });
+    }
+
+    pub fn set_rpn_value(&mut self, param: (u8, u8), value: u16) {
+        self.rpn_values.insert(param_key(param.0, param.1), value.min(16383));
+    }
+
+    pub fn set_nrpn_value(&mut self, param: (u8, u8), value: u16) {
+        self.nrpn_values.insert(param_key(param.0, param.1), value.min(16383));
+        if param.0 == gs_nrpn_ids::PART_PARAM_MSB {
+            self.update_part_offsets();
+        }
+    }
+
+    // nrpn의 data entry msb 값
+    fn nrpn_msb_value(&self, param: (u8, u8)) -> Option<u8> {
+        return self.nrpn_value(param).map(|value| (value >> 7) as u8);
+    }
+
+    // 40h를 기준으로 한 파트 파라미터 값(-64 - +63)
+    // 0Eh - 72h 범위를 벗어나면 범위 안으로 맞춤
+    fn part_param_relative(&self, param: (u8, u8)) -> f64 {
+        return match self.nrpn_msb_value(param) {
+            Some(val) => val.max(0x0e).min(0x72) as f64 - 64.0,
+            None => 0.0
+        };
+    }
+
+    /**
+     * gs nrpn 파트 파라미터를 AriculationValues 변화량으로 바꿈
+     * 변화량의 크기는 sc-8820을 들어보고 대강 맞춘 값
+     * - vibrato rate/delay, tvf cutoff, tva env: +-64 => 2^(+-4)배 (16배)
+     * - vibrato depth: +-64 => +-100cent
+     * - tvf resonance: +-64 => +-20dB
+     */
+    fn update_part_offsets(&mut self) {
+        let mut offsets = AriculationValues::zero();
+        let scale_4oct = |val: f64| (val / 64.0 * 40000.0) as i32;
+
+        offsets.vibrato_lfo_freq = (self.part_param_relative(gs_nrpn_ids::VIBRATO_RATE) / 64.0 * 20000.0) as i32;
+        offsets.vibrato_lfo_pitch = (self.part_param_relative(gs_nrpn_ids::VIBRATO_DEPTH) / 64.0 * 1000.0) as i32;
+        offsets.vibrato_lfo_start_delay = scale_4oct(self.part_param_relative(gs_nrpn_ids::VIBRATO_DELAY));
+        offsets.lpf_cutoff = scale_4oct(self.part_param_relative(gs_nrpn_ids::TVF_CUTOFF));
+        offsets.lpf_q = (self.part_param_relative(gs_nrpn_ids::TVF_RESONANCE) / 64.0 * 2000.0) as i32;
+        offsets.volume_env_attack = scale_4oct(self.part_param_relative(gs_nrpn_ids::TVA_ENV_ATTACK));
+        offsets.volume_env_decay = scale_4oct(self.part_param_relative(gs_nrpn_ids::TVA_ENV_DECAY));
+        offsets.volume_env_release = scale_4oct(self.part_param_relative(gs_nrpn_ids::TVA_ENV_RELEASE));
+
+        self.part_offsets = offsets;
+    }
+
+    // gs nrpn 파트 파라미터의 변화량
+    pub fn get_part_offsets(&self) -> &AriculationValues {
+        return &self.part_offsets;
+    }
+
+    /**
+     * gs nrpn 드럼 파라미터의 변화량(드럼 채널일 때만)
+     * random: 0.0 - 1.0 범위의 난수. 드럼 panpot 값이 0(random)일 때 씀
+     */
+    pub fn get_drum_offsets(&self, note: u8, random: f64) -> AriculationValues {
+        let mut offsets = AriculationValues::zero();
+        if !self.is_drum {
+            return offsets;
+        }
+
+        // pitch coarse: 40h 기준 1키 단위
+        if let Some(val) = self.nrpn_msb_value((gs_nrpn_ids::DRUM_PITCH_COARSE, note)) {
+            offsets.pitch = (val as i32 - 64) * 1000;
+        }
+
+        // level: 127이 원래 음량
+        if let Some(val) = self.nrpn_msb_value((gs_nrpn_ids::DRUM_TVA_LEVEL, note)) {
+            let level = val as f64 / 127.0;
+            offsets.gain = if val == 0 { -14400 } else { (to_dbfs(level * level) * 100.0) as i32 };
+        }
+
+        // panpot: 0 = random, 1 - 64(가운데) - 127
+        if let Some(val) = self.nrpn_msb_value((gs_nrpn_ids::DRUM_PANPOT, note)) {
+            offsets.pan = if val == 0 {
+                ((random * 2.0 - 1.0) * 10000.0) as i32
+            } else {
+                ((val as f64 - 64.0) / 63.0 * 10000.0) as i32
+            };
+        }
+
+        // reverb/chorus send: 127이 원래 send level
+        if let Some(val) = self.nrpn_msb_value((gs_nrpn_ids::DRUM_REVERB_SEND, note)) {
+            offsets.reverb_send_coeff = (val as f64 / 127.0 * 10000.0) as i32 - 10000;
+        }
+        if let Some(val) = self.nrpn_msb_value((gs_nrpn_ids::DRUM_CHORUS_SEND, note)) {
+            offsets.chorus_send_coeff = (val as f64 / 127.0 * 10000.0) as i32 - 10000;
+        }
+
+        return offsets;
+    }
+
+    pub fn rpn_value(&self, param: (u8, u8)) -> Option<u16> {
+        return self.rpn_values.get(&param_key(param.0, param.1)).copied();
+    }
+
+    pub fn nrpn_value(&self, param: (u8, u8)) -> Option<u16> {
+        return self.nrpn_values.get(&param_key(param.0, param.1)).copied();
+    }
+
+    // pitch bend sensitivity(cent 단위)
+    // msb = 키, lsb = cent
+    pub fn get_pitch_bend_sensitivity(&self) -> f64 {
+        let value = self.rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY).unwrap_or(2 << 7);
+        return (value >> 7) as f64 * 100.0 + ((value & 0x7f) as f64).min(99.0);
+    }
+
+    // pitch bend, fine tuning, coarse tuning을 합친 피치 변화량(cent 단위)
+    pub fn get_pitch_offset_cent(&self) -> f64 {
+        let bend = (self.pitch_bend as f64 - 8192.0) / 8192.0 * self.get_pitch_bend_sensitivity();
+
+        // fine tuning: 8192 = 0cent, 0 = -100cent, 16383 = +99.99cent
+        let fine = self.rpn_value(rpn_ids::FINE_TUNING).unwrap_or(8192);
+        let fine = (fine as f64 - 8192.0) / 8192.0 * 100.0;
+
+        // coarse tuning: msb만 씀. 64 = 0키
+        let coarse = self.rpn_value(rpn_ids::COARSE_TUNING).unwrap_or(64 << 7);
+        let coarse = ((coarse >> 7) as f64 - 64.0) * 100.0;
+
+        return bend + fine + coarse;
+    }
+
+    // modulation wheel(cc 1)로 거는 비브라토의 깊이(cent 단위)
+    // modulation depth range는 gm2와 같이 msb = 키, lsb = 100/128cent 단위
+    pub fn get_modulation_depth_cent(&self) -> f64 {
+        let range = self.rpn_value(rpn_ids::MODULATION_DEPTH_RANGE).unwrap_or(0x40);
+        let range = (range >> 7) as f64 * 100.0 + (range & 0x7f) as f64 * 100.0 / 128.0;
+        return self.cc[cc_ids_i::MODULATION] as f64 / 127.0 * range;
+    }
+
+    // bank select cc에 저장된 값이 여기서 적용됨
+    pub fn program_change(&mut self, program_no: u8) {
+        self.program = program_no & 0x7f;
+        self.bank_msb = self.cc[cc_ids_i::BANK_SELECT];
+        self.bank_lsb = self.cc[cc_ids_i::BANK_SELECT_LSB];
+    }
+
+    pub fn cc(&self, cc: u8) -> u8 {
+        return self.cc[(cc & 0x7f) as usize];
+    }
+
+    pub fn program(&self) -> u8 {
+        return self.program;
+    }
+
+    // (msb, lsb)
+    pub fn bank(&self) -> (u8, u8) {
+        return (self.bank_msb, self.bank_lsb);
+    }
+
+    pub fn pitch_bend(&self) -> u16 {
+        return self.pitch_bend;
+    }
+
+    pub fn channel_aftertouch(&self) -> u8 {
+        return self.channel_aftertouch;
+    }
+
+    pub fn note_aftertouch(&self, note: u8) -> u8 {
+        return self.note_aftertouch[(note & 0x7f) as usize];
+    }
+
+    pub fn param_select(&self) -> ParamSelect {
+        return self.param_select;
+    }
+
+    pub fn is_drum(&self) -> bool {
+        return self.is_drum;
+    }
+
+    pub fn set_drum(&mut self, is_drum: bool) {
+        self.is_drum = is_drum;
+    }
+
+    pub fn is_sustain_on(&self) -> bool {
+        return self.cc[cc_ids_i::SUSTAIN_ONOFF] >= 64;
+    }
+
+    pub fn is_sostenuto_on(&self) -> bool {
+        return self.cc[cc_ids_i::SOSTENUTO_ONOFF] >= 64;
+    }
+
+    // volume(cc 7)과 expression(cc 11)을 합친 게인
+    // gs와 마찬가지로 40 * log10(val / 127) dB의 곡선을 씀
+    pub fn get_gain(&self) -> f64 {
+        let volume = self.cc[cc_ids_i::CHANNEL_VOLUME] as f64 / 127.0;
+        let expression = self.cc[cc_ids_i::EXPRESSION] as f64 / 127.0;
+        return (volume * volume) * (expression * expression);
+    }
+
+    // -1.0(왼쪽) - 0.0(가운데) - 1.0(오른쪽)
+    pub fn get_pan(&self) -> f64 {
+        return ((self.cc[cc_ids_i::PAN] as f64 - 64.0) / 63.0).max(-1.0).min(1.0);
+    }
+
+    // 채널 단위로 관리되는 articulation source의 현재 값
+    // 채널과 관계 없는 source면 None
+    pub fn get_src_val(&self, src: u32) -> Option<f64> {
+        return if src == artc_src::PITCH_WHEEL {
+            Some(self.pitch_bend as f64)
+        } else if src == artc_src::CHANNEL_AFTERTOUCH {
+            Some(self.channel_aftertouch as f64)
+        } else if artc_src::is_midi_cc(src) {
+            Some(self.cc[(src & 0x7f) as usize] as f64)
+        } else if artc_src::is_midi_rpn(src) {
+            Some(self.rpn_values.get(&((src & 0xffff) as u16)).copied().unwrap_or(0) as f64)
+        } else if artc_src::is_midi_nrpn(src) {
+            Some(self.nrpn_values.get(&((src & 0xffff) as u16)).copied().unwrap_or(0) as f64)
+        } else {
+            None
+        };
+    }
+
+    /**
+     * gs 파트 파라미터
+     */
+    pub fn rx_channel(&self) -> Option<u8> {
+        return self.rx_channel;
+    }
+
+    // 16 이상이면 아무것도 받지 않음
+    pub fn set_rx_channel(&mut self, channel: u8) {
+        self.rx_channel = if (channel as usize) < CHANNELS_PER_PORT { Some(channel) } else { None };
+    }
+
+    pub fn key_shift(&self) -> i8 {
+        return self.key_shift;
+    }
+
+    pub fn set_key_shift(&mut self, key: i8) {
+        self.key_shift = key.max(-24).min(24);
+    }
+
+    pub fn set_velocity_sense(&mut self, depth: u8, offset: u8) {
+        self.velocity_sense_depth = depth & 0x7f;
+        self.velocity_sense_offset = offset & 0x7f;
+    }
+
+    // velocity sense를 적용한 velocity(1 - 127)
+    pub fn apply_velocity_sense(&self, velocity: u8) -> u8 {
+        let velocity = velocity as i32 * self.velocity_sense_depth as i32 / 64
+            + self.velocity_sense_offset as i32 - 64;
+        return velocity.max(1).min(127) as u8;
+    }
+
+    pub fn set_key_range(&mut self, low: u8, high: u8) {
+        self.key_range = (low & 0x7f, high & 0x7f);
+    }
+
+    pub fn is_in_key_range(&self, note: u8) -> bool {
+        return self.key_range.0 <= note && note <= self.key_range.1;
+    }
+
+    // note: 0 = C, 1 = C#, ..., 11 = B
+    pub fn set_scale_tuning(&mut self, note: usize, cent: f64) {
+        self.scale_tuning[note % 12] = cent.max(-100.0).min(100.0);
+    }
+
+    // 해당 note의 scale tuning 값(cent 단위)
+    pub fn get_scale_tuning_cent(&self, note: u8) -> f64 {
+        return self.scale_tuning[note as usize % 12];
+    }
+
+    /**
+     * 컨트롤러 destination 값
+     * MOD => LFO1 pitch와 BEND => pitch는 rpn 값으로 바꿔서 읽고 씀
+     */
+    pub fn ctrl_dest(&self, src: usize, dest: usize) -> u8 {
+        if src == gs_ctrl_src::MOD && dest == gs_ctrl_dest::LFO1_PITCH {
+            // 100/128cent 단위 => 00h - 7Fh(0 - 600cent)
+            let range = self.rpn_value(rpn_ids::MODULATION_DEPTH_RANGE).unwrap_or(0x40);
+            let cent = range as f64 * 100.0 / 128.0;
+            return (cent / 600.0 * 127.0).round().min(127.0) as u8;
+        }
+        if src == gs_ctrl_src::BEND && dest == gs_ctrl_dest::PITCH {
+            let range = self.rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY).unwrap_or(2 << 7) >> 7;
+            return (range + 0x40).min(0x58) as u8;
+        }
+        return match self.ctrl_dests.get(src).and_then(|dests| dests.get(dest)) {
+            Some(val) => *val,
+            None => 0
+        };
+    }
+
+    pub fn set_ctrl_dest(&mut self, src: usize, dest: usize, val: u8) {
+        let val = val & 0x7f;
+        if src == gs_ctrl_src::MOD && dest == gs_ctrl_dest::LFO1_PITCH {
+            let cent = val as f64 / 127.0 * 600.0;
+            self.set_rpn_value(rpn_ids::MODULATION_DEPTH_RANGE, (cent * 128.0 / 100.0).round() as u16);
+            return;
+        }
+        if src == gs_ctrl_src::BEND && dest == gs_ctrl_dest::PITCH {
+            // 40h - 58h => 0 - 24키(gs에서 음수는 무시함)
+            let range = val.max(0x40).min(0x58) - 0x40;
+            self.set_rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY, (range as u16) << 7);
+            return;
+        }
+        if let Some(slot) = self.ctrl_dests.get_mut(src).and_then(|dests| dests.get_mut(dest)) {
+            *slot = val;
+        }
+    }
+
+    pub fn ctrl_cc_number(&self, index: usize) -> u8 {
+        return self.ctrl_cc_numbers[index.min(1)];
+    }
+
+    pub fn set_ctrl_cc_number(&mut self, index: usize, cc: u8) {
+        self.ctrl_cc_numbers[index.min(1)] = cc & 0x7f;
+    }
+
+    /**
+     * 임의의 cc를 컨트롤러 destination의 source로 쓸 때 쓸 source 번호
+     * cc 1은 MOD, CC1/CC2에 이미 지정된 cc면 그걸 씀
+     * 아니면 값이 기본값 그대로인 CC1/CC2에 지정하고, 둘 다 쓰고 있으면 CC2를 덮어씀
+     */
+    pub fn assign_ctrl_cc(&mut self, cc: u8) -> usize {
+        let cc = cc & 0x7f;
+        if cc == cc_ids::MODULATION {
+            return gs_ctrl_src::MOD;
+        }
+        for (i, src) in [gs_ctrl_src::CC1, gs_ctrl_src::CC2].into_iter().enumerate() {
+            if self.ctrl_cc_numbers[i] == cc {
+                return src;
+            }
+        }
+        for (i, src) in [gs_ctrl_src::CC1, gs_ctrl_src::CC2].into_iter().enumerate() {
+            if self.ctrl_dests[src] == GS_CTRL_DEST_DEFAULT {
+                self.ctrl_cc_numbers[i] = cc;
+                return src;
+            }
+        }
+        log::warn!("No free controller destination slot for cc {}, overwriting CC2", cc);
+        self.ctrl_cc_numbers[1] = cc;
+        self.ctrl_dests[gs_ctrl_src::CC2] = GS_CTRL_DEST_DEFAULT;
+        return gs_ctrl_src::CC2;
+    }
+
+    /**
+     * 각 source의 현재 값에 따라 컨트롤러 destination 변화량을 합침
+     * note_aftertouch: PAf source로 쓸 값(voice마다 다름)
+     * MOD => LFO1 pitch와 BEND => pitch는 get_modulation_depth_cent, get_pitch_offset_cent에서 처리하므로 여기서는 뺌
+     */
+    pub fn get_controller_modulation(&self, note_aftertouch: u8) -> ControllerModulation {
+        let mut m = ControllerModulation::default();
+        let sources = [
+            (gs_ctrl_src::MOD, self.cc[cc_ids_i::MODULATION] as f64 / 127.0),
+            (gs_ctrl_src::BEND, (self.pitch_bend as f64 - 8192.0) / 8192.0),
+            (gs_ctrl_src::CAF, self.channel_aftertouch as f64 / 127.0),
+            (gs_ctrl_src::PAF, note_aftertouch as f64 / 127.0),
+            (gs_ctrl_src::CC1, self.cc[self.ctrl_cc_numbers[0] as usize] as f64 / 127.0),
+            (gs_ctrl_src::CC2, self.cc[self.ctrl_cc_numbers[1] as usize] as f64 / 127.0)
+        ];
+
+        for (src, x) in sources {
+            if x == 0.0 {
+                continue;
+            }
+            let dests = &self.ctrl_dests[src];
+            let relative = |dest: usize| dests[dest] as f64 - 64.0;
+            let depth = |dest: usize| dests[dest] as f64 / 127.0;
+
+            if src != gs_ctrl_src::BEND {
+                m.pitch_cent += x * relative(gs_ctrl_dest::PITCH).max(-24.0).min(24.0) * 100.0;
+            }
+            m.tvf_cent += x * relative(gs_ctrl_dest::TVF_CUTOFF) * 150.0;
+            m.amplitude += x * relative(gs_ctrl_dest::AMPLITUDE) / 64.0;
+            m.lfo1_rate_hz += x * relative(gs_ctrl_dest::LFO1_RATE) / 64.0 * 10.0;
+            if src != gs_ctrl_src::MOD {
+                m.lfo1_pitch_cent += x * depth(gs_ctrl_dest::LFO1_PITCH) * 600.0;
+            }
+            m.lfo1_tvf_cent += x * depth(gs_ctrl_dest::LFO1_TVF) * 2400.0;
+            m.lfo1_tva += x * depth(gs_ctrl_dest::LFO1_TVA);
+            m.lfo2_rate_hz += x * relative(gs_ctrl_dest::LFO2_RATE) / 64.0 * 10.0;
+            m.lfo2_pitch_cent += x * depth(gs_ctrl_dest::LFO2_PITCH) * 600.0;
+            m.lfo2_tvf_cent += x * depth(gs_ctrl_dest::LFO2_TVF) * 2400.0;
+            m.lfo2_tva += x * depth(gs_ctrl_dest::LFO2_TVA);
+        }
+
+        return m;
+    }
+}
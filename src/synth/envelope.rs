@@ -195,6 +195,15 @@ impl Envelope {
         }
     }
 
+    // release가 끝나서 더 이상 소리가 나지 않는 상태인지 확인
+    pub fn is_finished(&self) -> bool {
+        return self.status == EnvelopeStatus::Finished;
+    }
+
+    pub fn is_released(&self) -> bool {
+        return self.status == EnvelopeStatus::Released || self.status == EnvelopeStatus::Finished;
+    }
+
     pub fn attack(&mut self) {
         self.current_tick = -self.delay_tick;
         self.status = EnvelopeStatus::Delay;
@@ -0,0 +1,539 @@
+/**
+ * roland gs sysex 처리
+ * 주소의 각 바이트는 7비트만 쓰므로 데이터가 여러 바이트면 주소도 7비트 단위로 올라감
+ * 메세지 형식: F0 41 [장치 ID] [모델 ID] [명령 ID] [주소 3바이트] [데이터...] [체크섬] F7
+ * - 모델 ID: 42h = gs, 45h = sc 화면 표시
+ * - 명령 ID: 12h = DT1(데이터 보내기), 11h = RQ1(데이터 요청)
+ * - 체크섬: 주소 + 데이터 + 체크섬을 모두 더한 값의 하위 7비트가 0이 되어야 함
+ */
+
+use super::Synth;
+use super::channel::{ Channel, gs_ctrl_src, gs_nrpn_ids, CHANNELS_PER_PORT, MIDI_PORTS };
+use crate::util::midi::cc_ids_i;
+
+pub const MODEL_GS: u8 = 0x42;
+pub const MODEL_SC_DISPLAY: u8 = 0x45;
+
+pub const CMD_RQ1: u8 = 0x11;
+pub const CMD_DT1: u8 = 0x12;
+
+// RQ1에 한 번에 답장하는 최대 바이트 수
+pub(super) const MAX_REPLY_SIZE: usize = 128;
+
+// 주소 3바이트 <=> 21비트 정수
+#[inline]
+pub(super) fn to_addr(bytes: &[u8]) -> u32 {
+    return ((bytes[0] as u32 & 0x7f) << 14) | ((bytes[1] as u32 & 0x7f) << 7) | (bytes[2] as u32 & 0x7f);
+}
+
+#[inline]
+pub(super) fn split_addr(addr: u32) -> (u8, u8, u8) {
+    return (((addr >> 14) & 0x7f) as u8, ((addr >> 7) & 0x7f) as u8, (addr & 0x7f) as u8);
+}
+
+// 체크섬 계산(주소와 데이터를 넣으면 됨)
+pub fn checksum(bytes: &[u8]) -> u8 {
+    let sum: u32 = bytes.iter().map(|b| *b as u32).sum();
+    return ((128 - sum % 128) % 128) as u8;
+}
+
+// RQ1에 대한 답장(DT1) 메세지: F0 [vendor] [장치 ID] [모델 ID] 12 [주소 3바이트] [데이터...] [체크섬] F7
+pub(super) fn build_dt1_reply(vendor: u8, model: u8, device_id: u8, addr: u32, data: &[u8]) -> Vec<u8> {
+    let (a, b, c) = split_addr(addr);
+    let mut body = vec![a, b, c];
+    body.extend_from_slice(data);
+    let sum = checksum(&body);
+
+    let mut reply = vec![0xf0, vendor, device_id, model, CMD_DT1];
+    reply.extend(body);
+    reply.push(sum);
+    reply.push(0xf7);
+    return reply;
+}
+
+// 파트 블록 번호(주소 두번째 바이트의 하위 4비트) => 포트 안에서의 채널 번호
+// 0 = 10번 파트, 1 - 9 = 1 - 9번 파트, A - F = 11 - 16번 파트
+fn block_to_channel(block: u8) -> usize {
+    return match block {
+        0 => 9,
+        1..=9 => block as usize - 1,
+        _ => block as usize
+    };
+}
+
+// tone modify 파라미터(40 1x 30 - 37) 순서대로 대응하는 nrpn
+const TONE_MODIFY_NRPNS: [(u8, u8); 8] = [
+    gs_nrpn_ids::VIBRATO_RATE,
+    gs_nrpn_ids::VIBRATO_DEPTH,
+    gs_nrpn_ids::TVF_CUTOFF,
+    gs_nrpn_ids::TVF_RESONANCE,
+    gs_nrpn_ids::TVA_ENV_ATTACK,
+    gs_nrpn_ids::TVA_ENV_DECAY,
+    gs_nrpn_ids::TVA_ENV_RELEASE,
+    gs_nrpn_ids::VIBRATO_DELAY
+];
+
+// reverb macro별 값(character, pre-lpf, level, time, delay feedback)
+const REVERB_MACROS: [[u8; 5]; 8] = [
+    [0, 3, 64, 80, 0], // room 1
+    [1, 4, 64, 56, 0], // room 2
+    [2, 0, 64, 64, 0], // room 3
+    [3, 4, 64, 72, 0], // hall 1
+    [4, 0, 64, 64, 0], // hall 2
+    [5, 0, 64, 88, 0], // plate
+    [6, 0, 64, 32, 40], // delay
+    [7, 0, 64, 64, 32] // panning delay
+];
+
+// chorus macro별 값(pre-lpf, level, feedback, delay, rate, depth, send to reverb)
+const CHORUS_MACROS: [[u8; 7]; 8] = [
+    [0, 64, 0, 112, 3, 5, 0], // chorus 1
+    [0, 64, 5, 80, 9, 19, 0], // chorus 2
+    [0, 64, 8, 80, 3, 19, 0], // chorus 3
+    [0, 64, 16, 64, 9, 16, 0], // chorus 4
+    [0, 64, 64, 127, 2, 24, 0], // feedback chorus
+    [0, 64, 112, 127, 1, 5, 0], // flanger
+    [0, 64, 0, 127, 0, 127, 0], // short delay
+    [0, 64, 80, 127, 0, 127, 0] // short delay(feedback)
+];
+
+/**
+ * gs patch common 파라미터 중 reverb/chorus 관련 값(40 01 30 - 3F)
+ * 값은 전부 0 - 127 범위이고 gs 주소표에 적힌 값을 그대로 저장함
+ */
+pub struct GSEffectParams {
+    pub reverb_macro: u8,
+    pub reverb_character: u8,
+    pub reverb_pre_lpf: u8,
+    pub reverb_level: u8,
+    pub reverb_time: u8,
+    pub reverb_delay_feedback: u8,
+    pub reverb_predelay_time: u8,
+
+    pub chorus_macro: u8,
+    pub chorus_pre_lpf: u8,
+    pub chorus_level: u8,
+    pub chorus_feedback: u8,
+    pub chorus_delay: u8,
+    pub chorus_rate: u8,
+    pub chorus_depth: u8,
+    pub chorus_send_to_reverb: u8
+}
+
+impl GSEffectParams {
+    // gs reset 이후의 값(reverb = hall 2, chorus = chorus 3)
+    pub fn new() -> Self {
+        let mut this = Self {
+            reverb_macro: 0,
+            reverb_character: 0,
+            reverb_pre_lpf: 0,
+            reverb_level: 0,
+            reverb_time: 0,
+            reverb_delay_feedback: 0,
+            reverb_predelay_time: 0,
+            chorus_macro: 0,
+            chorus_pre_lpf: 0,
+            chorus_level: 0,
+            chorus_feedback: 0,
+            chorus_delay: 0,
+            chorus_rate: 0,
+            chorus_depth: 0,
+            chorus_send_to_reverb: 0
+        };
+        this.set_reverb_macro(4);
+        this.set_chorus_macro(2);
+        return this;
+    }
+
+    // macro를 바꾸면 나머지 값도 그 macro의 값으로 바뀜
+    pub fn set_reverb_macro(&mut self, val: u8) {
+        let val = val.min(7);
+        let [character, pre_lpf, level, time, delay_feedback] = REVERB_MACROS[val as usize];
+        self.reverb_macro = val;
+        self.reverb_character = character;
+        self.reverb_pre_lpf = pre_lpf;
+        self.reverb_level = level;
+        self.reverb_time = time;
+        self.reverb_delay_feedback = delay_feedback;
+        self.reverb_predelay_time = 0;
+    }
+
+    pub fn set_chorus_macro(&mut self, val: u8) {
+        let val = val.min(7);
+        let [pre_lpf, level, feedback, delay, rate, depth, send_to_reverb] = CHORUS_MACROS[val as usize];
+        self.chorus_macro = val;
+        self.chorus_pre_lpf = pre_lpf;
+        self.chorus_level = level;
+        self.chorus_feedback = feedback;
+        self.chorus_delay = delay;
+        self.chorus_rate = rate;
+        self.chorus_depth = depth;
+        self.chorus_send_to_reverb = send_to_reverb;
+    }
+}
+
+impl Default for GSEffectParams {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/**
+ * sc 시리즈 화면에 표시하는 내용(모델 ID 45h)
+ * 10 00 00 - 1F: 글자(최대 32자)
+ * 10 01 00 - 3F: 16x16 도트 그림(1바이트에 5도트씩 64바이트)
+ */
+pub struct SCDisplay {
+    pub text: [u8; 32],
+    pub text_len: usize,
+    pub bitmap: [u8; 64]
+}
+
+impl SCDisplay {
+    pub fn new() -> Self {
+        return Self {
+            text: [0x20; 32],
+            text_len: 0,
+            bitmap: [0; 64]
+        };
+    }
+
+    pub fn text(&self) -> String {
+        return String::from_utf8_lossy(&self.text[0..self.text_len]).to_string();
+    }
+}
+
+impl Default for SCDisplay {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Synth {
+    /**
+     * roland sysex 메세지를 해석함
+     * port: 메세지가 들어온 포트. 파트 파라미터의 주소가 40h로 시작하면 이 포트의 파트를 바꿈
+     * (sc-88 이후 기종처럼 50h로 시작하는 주소는 포트 B의 파트를 바꿈)
+     */
+    pub(crate) fn process_roland_sysex(&mut self, port: u8, msg: &[u8]) {
+        // F0 41 dev model cmd addr addr addr (데이터 최소 1바이트) sum F7
+        if msg.len() < 11 || msg[0] != 0xf0 || msg[msg.len() - 1] != 0xf7 {
+            log::error!("Malformed roland sysex message");
+            return;
+        }
+
+        let device_id = msg[2];
+        if device_id != 0x7f && device_id != self.settings.device_id {
+            return;
+        }
+
+        let model_id = msg[3];
+        let command_id = msg[4];
+        let body = &msg[5..(msg.len() - 2)];
+        let sum = msg[msg.len() - 2];
+        if body.iter().chain(std::iter::once(&sum)).any(|b| *b >= 0x80) {
+            log::error!("Malformed roland sysex message");
+            return;
+        }
+        if checksum(body) != sum {
+            log::error!("Roland sysex checksum mismatch (expected {:02X}h, got {:02X}h)", checksum(body), sum);
+            return;
+        }
+
+        let addr = to_addr(&body[0..3]);
+        let data = &body[3..];
+        match (model_id, command_id) {
+            (MODEL_GS, CMD_DT1) => {
+                for (i, val) in data.iter().enumerate() {
+                    self.write_gs_param(port, addr + i as u32, *val);
+                }
+            },
+            (MODEL_GS, CMD_RQ1) => {
+                if data.len() != 3 {
+                    log::error!("Malformed roland RQ1 message");
+                    return;
+                }
+                self.reply_gs_params(port, device_id, addr, to_addr(data) as usize);
+            },
+            (MODEL_SC_DISPLAY, CMD_DT1) => {
+                for (i, val) in data.iter().enumerate() {
+                    self.write_sc_display(addr + i as u32, *val);
+                }
+            },
+            _ => log::warn!("Unsupported roland sysex (model {:02X}h, command {:02X}h)", model_id, command_id)
+        }
+    }
+
+    // 주소에 해당하는 파트의 채널 번호
+    fn gs_part_channel_no(port: u8, addr: (u8, u8, u8)) -> usize {
+        let port = if addr.0 == 0x50 { 1 } else { port as usize };
+        return port.min(MIDI_PORTS - 1) * CHANNELS_PER_PORT + block_to_channel(addr.1 & 0x0f);
+    }
+
+    fn write_gs_param(&mut self, port: u8, addr: u32, val: u8) {
+        let (a, b, c) = split_addr(addr);
+        match (a, b, c) {
+            // mode set(sc-88 이후 기종). single/double 모두 gs reset으로 취급
+            (0x00, 0x00, 0x7f) => self.gs_reset(),
+
+            /* system parameter */
+            // master tune: 4바이트에 4비트씩 나눠서 보냄. 0400h = 0cent, 0.1cent 단위
+            (0x40, 0x00, 0x00..=0x03) => {
+                let shift = (3 - c) * 4;
+                let value = self.gs_master_tune_value() & !(0xf << shift) | ((val as u16 & 0xf) << shift);
+                self.set_master_fine_tuning((value.max(0x18).min(0x7e8) as f64 - 1024.0) / 10.0);
+            },
+            (0x40, 0x00, 0x04) => self.set_master_volume(val as i32 * 16383 / 127),
+            (0x40, 0x00, 0x05) => self.set_master_coarse_tuning(val.max(0x28).min(0x58) as i32 - 64),
+            (0x40, 0x00, 0x06) => self.set_master_pan((val.max(1) as f64 - 64.0) / 63.0),
+            (0x40, 0x00, 0x7f) => self.gs_reset(),
+
+            /* patch common parameter(reverb/chorus) */
+            (0x40, 0x01, 0x30) => self.gs_effects.set_reverb_macro(val),
+            (0x40, 0x01, 0x31) => self.gs_effects.reverb_character = val.min(7),
+            (0x40, 0x01, 0x32) => self.gs_effects.reverb_pre_lpf = val.min(7),
+            (0x40, 0x01, 0x33) => self.gs_effects.reverb_level = val,
+            (0x40, 0x01, 0x34) => self.gs_effects.reverb_time = val,
+            (0x40, 0x01, 0x35) => self.gs_effects.reverb_delay_feedback = val,
+            (0x40, 0x01, 0x37) => self.gs_effects.reverb_predelay_time = val,
+            (0x40, 0x01, 0x38) => self.gs_effects.set_chorus_macro(val),
+            (0x40, 0x01, 0x39) => self.gs_effects.chorus_pre_lpf = val.min(7),
+            (0x40, 0x01, 0x3a) => self.gs_effects.chorus_level = val,
+            (0x40, 0x01, 0x3b) => self.gs_effects.chorus_feedback = val,
+            (0x40, 0x01, 0x3c) => self.gs_effects.chorus_delay = val,
+            (0x40, 0x01, 0x3d) => self.gs_effects.chorus_rate = val,
+            (0x40, 0x01, 0x3e) => self.gs_effects.chorus_depth = val,
+            (0x40, 0x01, 0x3f) => self.gs_effects.chorus_send_to_reverb = val,
+
+            /* patch part parameter */
+            (0x40 | 0x50, 0x10..=0x1f, _) => {
+                let channel_no = Self::gs_part_channel_no(port, (a, b, c));
+                self.write_gs_part_param(channel_no, c, val);
+            },
+            (0x40 | 0x50, 0x20..=0x2f, _) => {
+                let channel_no = Self::gs_part_channel_no(port, (a, b, c));
+                let (src, dest) = ((c >> 4) as usize, (c & 0x0f) as usize);
+                if src < gs_ctrl_src::COUNT {
+                    self.channels[channel_no].set_ctrl_dest(src, dest, val);
+                }
+            },
+
+            _ => log::warn!("Unsupported gs parameter address: {:02X} {:02X} {:02X}", a, b, c)
+        }
+    }
+
+    fn write_gs_part_param(&mut self, channel_no: usize, offset: u8, val: u8) {
+        let channel = &mut self.channels[channel_no];
+        match offset {
+            // tone number(bank select msb, program)
+            0x00 => channel.cc[cc_ids_i::BANK_SELECT] = val,
+            0x01 => channel.program_change(val),
+            0x02 => channel.set_rx_channel(val),
+            0x15 => {
+                // use for rhythm part: 0 = off, 1 = map 1, 2 = map 2
+                channel.set_drum(val != 0);
+                self.voices.kill_all(channel_no as u8);
+            },
+            0x16 => channel.set_key_shift(val.max(0x28).min(0x58) as i8 - 64),
+            0x19 => channel.cc[cc_ids_i::CHANNEL_VOLUME] = val,
+            0x1a => channel.velocity_sense_depth = val,
+            0x1b => channel.velocity_sense_offset = val,
+            // 0 = random이지만 여기서는 가운데로 취급함
+            0x1c => channel.cc[cc_ids_i::PAN] = if val == 0 { 64 } else { val },
+            0x1d => channel.key_range.0 = val,
+            0x1e => channel.key_range.1 = val,
+            0x1f => channel.set_ctrl_cc_number(0, val),
+            0x20 => channel.set_ctrl_cc_number(1, val),
+            0x21 => channel.cc[cc_ids_i::CHORUS_SEND_LEVEL] = val,
+            0x22 => channel.cc[cc_ids_i::REVERB_SEND_LEVEL] = val,
+            0x30..=0x37 => channel.set_nrpn_value(TONE_MODIFY_NRPNS[(offset - 0x30) as usize], (val as u16) << 7),
+            0x40..=0x4b => channel.set_scale_tuning((offset - 0x40) as usize, val as f64 - 64.0),
+            _ => log::warn!("Unsupported gs part parameter: {:02X}h", offset)
+        }
+    }
+
+    // 주소에 해당하는 현재 값. 지원하지 않는 주소면 None
+    fn read_gs_param(&self, port: u8, addr: u32) -> Option<u8> {
+        let (a, b, c) = split_addr(addr);
+        let effects = &self.gs_effects;
+        return match (a, b, c) {
+            (0x40, 0x00, 0x00..=0x03) => Some(((self.gs_master_tune_value() >> ((3 - c) * 4)) & 0xf) as u8),
+            (0x40, 0x00, 0x04) => Some((self.master_volume as u32 * 127 / 16383) as u8),
+            (0x40, 0x00, 0x05) => Some((self.master_coarse_tuning.max(-24).min(24) + 64) as u8),
+            (0x40, 0x00, 0x06) => Some((self.master_pan * 63.0 + 64.0).round().max(1.0).min(127.0) as u8),
+
+            (0x40, 0x01, 0x30) => Some(effects.reverb_macro),
+            (0x40, 0x01, 0x31) => Some(effects.reverb_character),
+            (0x40, 0x01, 0x32) => Some(effects.reverb_pre_lpf),
+            (0x40, 0x01, 0x33) => Some(effects.reverb_level),
+            (0x40, 0x01, 0x34) => Some(effects.reverb_time),
+            (0x40, 0x01, 0x35) => Some(effects.reverb_delay_feedback),
+            (0x40, 0x01, 0x37) => Some(effects.reverb_predelay_time),
+            (0x40, 0x01, 0x38) => Some(effects.chorus_macro),
+            (0x40, 0x01, 0x39) => Some(effects.chorus_pre_lpf),
+            (0x40, 0x01, 0x3a) => Some(effects.chorus_level),
+            (0x40, 0x01, 0x3b) => Some(effects.chorus_feedback),
+            (0x40, 0x01, 0x3c) => Some(effects.chorus_delay),
+            (0x40, 0x01, 0x3d) => Some(effects.chorus_rate),
+            (0x40, 0x01, 0x3e) => Some(effects.chorus_depth),
+            (0x40, 0x01, 0x3f) => Some(effects.chorus_send_to_reverb),
+
+            (0x40 | 0x50, 0x10..=0x1f, _) => {
+                let channel = &self.channels[Self::gs_part_channel_no(port, (a, b, c))];
+                Self::read_gs_part_param(channel, c)
+            },
+            (0x40 | 0x50, 0x20..=0x2f, _) => {
+                let channel = &self.channels[Self::gs_part_channel_no(port, (a, b, c))];
+                let (src, dest) = ((c >> 4) as usize, (c & 0x0f) as usize);
+                if src < gs_ctrl_src::COUNT { Some(channel.ctrl_dest(src, dest)) } else { None }
+            },
+            _ => None
+        };
+    }
+
+    fn read_gs_part_param(channel: &Channel, offset: u8) -> Option<u8> {
+        return match offset {
+            0x00 => Some(channel.bank_msb),
+            0x01 => Some(channel.program),
+            0x02 => Some(channel.rx_channel.unwrap_or(0x10)),
+            0x15 => Some(channel.is_drum as u8),
+            0x16 => Some((channel.key_shift + 64) as u8),
+            0x19 => Some(channel.cc[cc_ids_i::CHANNEL_VOLUME]),
+            0x1a => Some(channel.velocity_sense_depth),
+            0x1b => Some(channel.velocity_sense_offset),
+            0x1c => Some(channel.cc[cc_ids_i::PAN]),
+            0x1d => Some(channel.key_range.0),
+            0x1e => Some(channel.key_range.1),
+            0x1f => Some(channel.ctrl_cc_number(0)),
+            0x20 => Some(channel.ctrl_cc_number(1)),
+            0x21 => Some(channel.cc[cc_ids_i::CHORUS_SEND_LEVEL]),
+            0x22 => Some(channel.cc[cc_ids_i::REVERB_SEND_LEVEL]),
+            0x30..=0x37 => {
+                let value = channel.nrpn_value(TONE_MODIFY_NRPNS[(offset - 0x30) as usize]).unwrap_or(0x40 << 7);
+                Some((value >> 7) as u8)
+            },
+            0x40..=0x4b => {
+                let cent = channel.scale_tuning[(offset - 0x40) as usize];
+                Some((cent.round() + 64.0).max(0.0).min(127.0) as u8)
+            },
+            _ => None
+        };
+    }
+
+    // RQ1에 대한 답장(DT1)을 보냄
+    // 요청한 범위 중 앞에서부터 읽을 수 있는 데까지만 보냄
+    fn reply_gs_params(&mut self, port: u8, device_id: u8, addr: u32, size: usize) {
+        let mut data = vec![];
+        for i in 0..size.min(MAX_REPLY_SIZE) {
+            match self.read_gs_param(port, addr + i as u32) {
+                Some(val) => data.push(val),
+                None => break
+            }
+        }
+        if data.is_empty() {
+            log::warn!("Unsupported gs parameter address for RQ1: {:06X}h", addr);
+            return;
+        }
+
+        // 장치 ID가 7Fh(broadcast)인 요청에도 자기 장치 ID로 답장함
+        let device_id = if device_id == 0x7f { self.settings.device_id } else { device_id };
+        let reply = build_dt1_reply(0x41, MODEL_GS, device_id, addr, &data);
+        self.send_sysex_reply(&reply);
+    }
+
+    fn write_sc_display(&mut self, addr: u32, val: u8) {
+        let (a, b, c) = split_addr(addr);
+        match (a, b, c) {
+            (0x10, 0x00, 0x00..=0x1f) => {
+                // 글자를 보내면 그 뒤쪽은 지워진 것으로 취급함
+                self.sc_display.text[c as usize] = val;
+                self.sc_display.text_len = c as usize + 1;
+            },
+            (0x10, 0x01, 0x00..=0x3f) => self.sc_display.bitmap[c as usize] = val,
+            _ => log::warn!("Unsupported sc display address: {:02X} {:02X} {:02X}", a, b, c)
+        }
+    }
+
+    // master fine tuning을 gs master tune 값(0018h - 07E8h, 0400h = 0cent)으로 바꿈
+    fn gs_master_tune_value(&self) -> u16 {
+        return (self.master_fine_tuning * 10.0 + 1024.0).round().max(0x18 as f64).min(0x7e8 as f64) as u16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{ Arc, Mutex };
+    use super::*;
+    use crate::synth::settings::SynthCreateSettings;
+
+    fn gs_message(device_id: u8, command_id: u8, body: &[u8]) -> Vec<u8> {
+        let mut msg = vec![0xf0, 0x41, device_id, MODEL_GS, command_id];
+        msg.extend_from_slice(body);
+        msg.push(checksum(body));
+        msg.push(0xf7);
+        return msg;
+    }
+
+    fn dt1(addr: [u8; 3], data: &[u8]) -> Vec<u8> {
+        return gs_message(0x10, CMD_DT1, &[&addr[..], data].concat());
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        let mut msg = dt1([0x40, 0x00, 0x04], &[0x40]);
+        let sum_pos = msg.len() - 2;
+        msg[sum_pos] = (msg[sum_pos] + 1) & 0x7f;
+        synth.handle_sysex(&msg);
+        assert_eq!(synth.master_volume, 16383);
+
+        synth.handle_sysex(&dt1([0x40, 0x00, 0x04], &[0x40]));
+        assert_eq!(synth.master_volume as i32, 0x40 * 16383 / 127);
+    }
+
+    #[test]
+    fn multi_byte_data_carries_address() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        // 40 11 7F 다음 주소는 40 12 00(2번 파트의 tone number)
+        synth.handle_sysex(&dt1([0x40, 0x11, 0x7f], &[0x00, 0x05, 0x07]));
+        let channel = synth.channel(1).unwrap();
+        assert_eq!(channel.cc(0), 5);
+        assert_eq!(channel.program(), 7);
+        assert_eq!(channel.bank(), (5, 0));
+    }
+
+    #[test]
+    fn rq1_is_answered_through_reply_callback() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        let replies = Arc::new(Mutex::new(vec![]));
+        let sink = replies.clone();
+        synth.set_sysex_reply_callback(move |msg| sink.lock().unwrap().push(msg.to_vec()));
+
+        synth.handle_sysex(&dt1([0x40, 0x00, 0x05], &[0x42]));
+        // master volume, master key shift, master pan 3바이트를 요청함
+        synth.handle_sysex(&gs_message(0x7f, CMD_RQ1, &[0x40, 0x00, 0x04, 0x00, 0x00, 0x03]));
+        // 지원하지 않는 주소에는 답장하지 않음
+        synth.handle_sysex(&gs_message(0x10, CMD_RQ1, &[0x41, 0x00, 0x00, 0x00, 0x00, 0x01]));
+
+        let replies = replies.lock().unwrap();
+        assert_eq!(replies.len(), 1);
+        // broadcast 요청에는 자기 장치 ID(10h)로 답장함
+        assert_eq!(replies[0], dt1([0x40, 0x00, 0x04], &[127, 0x42, 64]));
+    }
+
+    #[test]
+    fn address_50_selects_port_b() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        synth.handle_port_sysex(0, &dt1([0x50, 0x11, 0x19], &[60]));
+        synth.handle_port_sysex(0, &dt1([0x40, 0x12, 0x19], &[70]));
+        synth.handle_port_sysex(1, &dt1([0x40, 0x13, 0x19], &[80]));
+        let volumes: Vec<u8> = [0, 1, 2, 16, 17, 18].iter().map(|ch| synth.channel(*ch).unwrap().cc(7)).collect();
+        // 기본값은 100
+        assert_eq!(volumes, vec![100, 70, 100, 60, 100, 80]);
+
+        // 10번 파트(블록 0)도 포트 B로 감
+        synth.handle_port_sysex(0, &dt1([0x50, 0x10, 0x15], &[0]));
+        assert!(!synth.channel(25).unwrap().is_drum());
+        assert!(synth.channel(9).unwrap().is_drum());
+    }
+}
@@ -41,6 +41,11 @@ impl LFO {
         return self.tick;
     }
 
+    // 값을 계산하지 않고 샘플 count개분의 시간만큼 진행
+    pub fn advance(&mut self, count: usize) {
+        self.tick += count as f64;
+    }
+
     #[inline]
     pub fn sine(&mut self) -> f64 {
         return (2.0 * PI * self.next_tick() / self.period).sin();
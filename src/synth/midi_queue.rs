@@ -0,0 +1,98 @@
+/**
+ * 다른 스레드에서 synth로 미디 메세지를 보내기 위한 대기열
+ * 미디 입력 스레드 등에서 MidiMessageSender로 메세지를 넣으면 오디오 스레드가 render할 때 꺼내서 적용함
+ * 락을 쓰지 않으므로 오디오 스레드가 기다리는 일이 없음
+ */
+
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use crossbeam_queue::ArrayQueue;
+
+// 대기열에 한 번에 넣어둘 수 있는 최대 메세지 수
+pub const MIDI_QUEUE_CAPACITY: usize = 4096;
+
+/**
+ * 미디 메세지 내용
+ * 3바이트 이하인 메세지는 따로 메모리를 할당하지 않고 그대로 들고 있음(sysex만 할당함)
+ */
+#[derive(Clone)]
+pub(crate) enum MidiBytes {
+    Short([u8; 3], u8),
+    Long(Box<[u8]>)
+}
+
+impl MidiBytes {
+    pub fn new(msg: &[u8]) -> Self {
+        if msg.len() <= 3 {
+            let mut bytes = [0; 3];
+            bytes[0..msg.len()].copy_from_slice(msg);
+            return Self::Short(bytes, msg.len() as u8);
+        }
+        return Self::Long(msg.into());
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        return match self {
+            Self::Short(bytes, len) => &bytes[0..(*len as usize)],
+            Self::Long(bytes) => bytes
+        };
+    }
+}
+
+pub(crate) struct QueuedMidiMessage {
+    // 메세지를 넣은 다음 처음 호출되는 render의 시작 위치 기준 시간(샘플 단위)
+    pub frame_offset: u32,
+    pub port: u8,
+    pub data: MidiBytes
+}
+
+/**
+ * synth로 미디 메세지를 보내는 핸들(Synth::midi_message_sender로 만듦)
+ * 복제해서 여러 스레드에서 같이 써도 됨
+ */
+#[derive(Clone)]
+pub struct MidiMessageSender {
+    queue: Arc<ArrayQueue<QueuedMidiMessage>>,
+    frame_position: Arc<AtomicU64>
+}
+
+impl MidiMessageSender {
+    pub(crate) fn new(queue: Arc<ArrayQueue<QueuedMidiMessage>>, frame_position: Arc<AtomicU64>) -> Self {
+        return Self {
+            queue,
+            frame_position
+        };
+    }
+
+    // 포트 A로 들어온 메세지로 취급함
+    pub fn queue_midi_message(&self, frame_offset: u32, msg: &[u8]) -> bool {
+        return self.queue_port_midi_message(frame_offset, 0, msg);
+    }
+
+    /**
+     * 메세지를 대기열에 넣음
+     * frame_offset: 다음 render 호출에서 내보내는 첫 샘플 기준 위치
+     * 대기열이 꽉 차서 못 넣었으면 false
+     */
+    pub fn queue_port_midi_message(&self, frame_offset: u32, port: u8, msg: &[u8]) -> bool {
+        if msg.is_empty() {
+            return true;
+        }
+
+        let message = QueuedMidiMessage {
+            frame_offset,
+            port,
+            data: MidiBytes::new(msg)
+        };
+        if self.queue.push(message).is_err() {
+            log::warn!("Midi message queue is full");
+            return false;
+        }
+        return true;
+    }
+
+    // 오디오 스레드가 지금까지 render로 내보낸 샘플 수(Synth::frame_position과 같음)
+    pub fn frame_position(&self) -> u64 {
+        return self.frame_position.load(Ordering::Acquire);
+    }
+}
@@ -4,53 +4,454 @@ pub mod effects;
 pub mod settings;
 pub mod vendors;
 pub mod param_smoother;
-
-use crate::soundbank::wsbk::WSBK;
+pub mod articulator;
+pub mod oscillator;
+pub mod voice;
+pub mod channel;
+pub mod gs;
+pub mod universal;
+pub mod wsn;
+pub mod midi_queue;
+pub mod bank_loader;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use crossbeam_queue::ArrayQueue;
+use crate::soundbank::Soundbank;
+use crate::soundbank::wsbk::{ WSBK, Preset, PresetType };
+use crate::soundbank::wsbk::consts::region_gen;
+use crate::synth::oscillator::SampleOscillator;
+use crate::util::midi::cc_ids;
 use vendors::VendorId;
-use settings::SynthCreateSettings;
+use settings::{ SynthCreateSettings, SynthSettings };
+use voice::{ Voice, VoiceManager, VoiceSample, VoiceStats };
+use channel::{ Channel, MIDI_PORTS, MIDI_CHANNELS, CHANNELS_PER_PORT };
+use gs::{ GSEffectParams, SCDisplay };
+use midi_queue::{ MidiBytes, MidiMessageSender, QueuedMidiMessage, MIDI_QUEUE_CAPACITY };
+use bank_loader::BankLoader;
+
+// sysex 답장을 받는 함수
+type SysexReplyCallback = Box<dyn FnMut(&[u8]) + Send>;
+
+/**
+ * 마지막으로 받은 reset 메세지에 따른 동작 모드
+ * - GS: 기본값. bank select를 그대로 씀
+ * - GM: bank select를 무시함
+ * - GM2: bank select msb 78h = 드럼, 79h = 멜로디
+ */
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SynthMode {
+    GS, GM, GM2
+}
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct FXType(pub u8, pub u8, pub u8);
 
+// 이펙트 유닛 1개당 파라미터 수
+pub const FX_PARAMS: usize = 32;
+
+// variation effect 유닛 수(포트 1개당 16개)
+pub const VARIATION_FX_UNITS: usize = MIDI_PORTS * 16;
+
+// 채널 1개당 multi effect 유닛 수
+pub const MFX_UNITS_PER_CHANNEL: usize = 16;
+
+/**
+ * 이펙트 유닛 1개의 설정
+ * FXType(0, 0, 0)은 이펙트 없음
+ */
+pub struct FXUnit {
+    pub fx_type: FXType,
+    pub params: [i32; FX_PARAMS]
+}
+
+impl FXUnit {
+    pub fn new() -> Self {
+        return Self {
+            fx_type: FXType(0, 0, 0),
+            params: [0; FX_PARAMS]
+        };
+    }
+}
+
+impl Default for FXUnit {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+// 적용할 시간이 정해진 미디 메세지
+struct ScheduledMidiMessage {
+    // 내부 렌더링 기준 시간(샘플 단위)
+    frame: u64,
+    port: u8,
+    data: MidiBytes
+}
+
 pub struct Synth {
-    soundbanks: Vec<WSBK>,
-    buffer_left: Vec<u8>,
-    buffer_right: Vec<u8>
+    create_settings: SynthCreateSettings,
+    settings: SynthSettings,
+
+    // (사운드뱅크, wsn sysex로 불러온 것인지)
+    // wsn sysex로 모두 뺄 때는 wsn sysex로 불러온 것만 뺌
+    soundbanks: Vec<(Soundbank, bool)>,
+    channels: Vec<Channel>,
+    voices: VoiceManager,
+
+    // 난수 생성용(xorshift)
+    random_state: u32,
+
+    mode: SynthMode,
+
+    // master volume(0 - 16383)
+    master_volume: u16,
+
+    // master fine tuning(cent 단위. -100 - +100)
+    master_fine_tuning: f64,
+
+    // master coarse tuning(키 단위. -64 - +63). 드럼 채널에는 적용하지 않음
+    master_coarse_tuning: i32,
+
+    // 조옮김(키 단위. -24 - +24). master coarse tuning에 더해서 적용함
+    // 곡에서 보내는 reset/sysex로는 바뀌지 않음(미디 파일 재생기 등에서 씀)
+    transpose: i32,
+
+    // -1.0(왼쪽) - 0.0(가운데) - 1.0(오른쪽)
+    master_pan: f64,
+
+    // gs reverb/chorus 설정
+    gs_effects: GSEffectParams,
+
+    // sc 화면 표시 내용
+    sc_display: SCDisplay,
+
+    // sysex 요청에 대한 답장을 보낼 곳
+    sysex_reply: Option<SysexReplyCallback>,
+
+    // wsn sysex로 사운드뱅크를 불러올 때 쓰는 백그라운드 스레드
+    soundbank_loader: Option<BankLoader>,
+
+    // variation effect/multi effect 설정
+    // multi effect는 채널 번호 * MFX_UNITS_PER_CHANNEL + 유닛 번호 순서
+    variation_fx: Vec<FXUnit>,
+    mfx: Vec<FXUnit>,
+
+    // 내부 렌더링 버퍼(render_buffer_size 크기)
+    // buffer_pos부터 끝까지가 아직 내보내지 않은 부분임
+    buffer_left: Vec<f64>,
+    buffer_right: Vec<f64>,
+    buffer_pos: usize,
+
+    // 지금까지 render로 내보낸 샘플 수
+    output_frame: u64,
+
+    // 지금까지 내부 버퍼에 렌더링한 샘플 수(= 다음 블록의 시작 시간)
+    render_frame: u64,
+
+    // 시간 순서로 정렬된 미디 메세지 대기열
+    scheduled_messages: VecDeque<ScheduledMidiMessage>,
+
+    // 다른 스레드에서 MidiMessageSender로 보낸 메세지(render할 때 scheduled_messages로 옮김)
+    midi_queue: Arc<ArrayQueue<QueuedMidiMessage>>,
+
+    // MidiMessageSender에서 읽을 수 있게 공유하는 output_frame
+    shared_frame_position: Arc<AtomicU64>
 }
 
 #[allow(unused)] // 모든 기능이 완성될 즈음에 제거 예정
 impl Synth {
     pub fn new(settings: SynthCreateSettings) -> Self {
+        let buffer_size = settings.render_buffer_size;
         return Self {
+            voices: VoiceManager::new(settings.polyphony, settings.max_worker_threads),
+            create_settings: settings,
+            settings: SynthSettings::new(),
             soundbanks: vec![],
-            buffer_left: vec![],
-            buffer_right: vec![]
+            channels: (0..MIDI_CHANNELS).map(|i| Channel::new(i as u8)).collect(),
+            random_state: 0x2545f491,
+            mode: SynthMode::GS,
+            master_volume: 16383,
+            master_fine_tuning: 0.0,
+            master_coarse_tuning: 0,
+            transpose: 0,
+            master_pan: 0.0,
+            gs_effects: GSEffectParams::new(),
+            sc_display: SCDisplay::new(),
+            sysex_reply: None,
+            soundbank_loader: None,
+            variation_fx: (0..VARIATION_FX_UNITS).map(|_| FXUnit::new()).collect(),
+            mfx: (0..(MIDI_CHANNELS * MFX_UNITS_PER_CHANNEL)).map(|_| FXUnit::new()).collect(),
+            buffer_left: vec![0.0; buffer_size],
+            buffer_right: vec![0.0; buffer_size],
+            buffer_pos: buffer_size,
+            output_frame: 0,
+            render_frame: 0,
+            scheduled_messages: VecDeque::with_capacity(MIDI_QUEUE_CAPACITY),
+            midi_queue: Arc::new(ArrayQueue::new(MIDI_QUEUE_CAPACITY)),
+            shared_frame_position: Arc::new(AtomicU64::new(0))
         };
     }
 
+    pub fn sample_rate(&self) -> u32 {
+        return self.create_settings.sample_rate;
+    }
+
+    pub fn render_buffer_size(&self) -> usize {
+        return self.create_settings.render_buffer_size;
+    }
+
+    pub fn settings(&self) -> &SynthSettings {
+        return &self.settings;
+    }
+
+    pub fn settings_mut(&mut self) -> &mut SynthSettings {
+        return &mut self.settings;
+    }
+
+    // 사운드뱅크 추가(WSBK 또는 SF2Bank. &SF2를 넣으면 SF2Bank로 바꿔서 추가함)
+    // 나중에 추가한 사운드뱅크에 있는 preset이 우선순위가 높음
+    pub fn add_soundbank<S: Into<Soundbank>>(&mut self, soundbank: S) {
+        self.soundbanks.push((soundbank.into(), false));
+    }
+
+    pub fn active_voices(&self) -> usize {
+        return self.voices.active_voices();
+    }
+
+    // 동시 발음 수 관련 통계
+    pub fn voice_stats(&self) -> VoiceStats {
+        return *self.voices.stats();
+    }
+
+    pub fn reset_voice_stats(&mut self) {
+        self.voices.reset_stats();
+    }
+
+    // channel_no = 포트 번호 * 16 + 포트 안에서의 채널 번호
+    pub fn channel(&self, channel_no: u8) -> Option<&Channel> {
+        return self.channels.get(channel_no as usize);
+    }
+
+    pub fn mode(&self) -> SynthMode {
+        return self.mode;
+    }
+
+    pub fn gs_effects(&self) -> &GSEffectParams {
+        return &self.gs_effects;
+    }
+
+    pub fn sc_display(&self) -> &SCDisplay {
+        return &self.sc_display;
+    }
+
+    // RQ1 등 답장이 필요한 sysex를 받았을 때 답장(F0 ... F7)을 넘겨받을 함수 설정
+    pub fn set_sysex_reply_callback<F: FnMut(&[u8]) + Send + 'static>(&mut self, callback: F) {
+        self.sysex_reply = Some(Box::new(callback));
+    }
+
+    // wsn sysex로 사운드뱅크 불러오기 요청을 받았을 때 쓸 함수 설정
+    // 번호에 맞는 사운드뱅크를 반환하면 됨(add_soundbank로 추가한 것과 같이 취급함)
+    // 함수는 백그라운드 스레드에서 불리고, 불러온 사운드뱅크는 그 다음 블록부터 쓰임
+    pub fn set_soundbank_loader<F: FnMut(u16) -> Option<WSBK> + Send + 'static>(&mut self, loader: F) {
+        match BankLoader::new(Box::new(loader)) {
+            Ok(loader) => self.soundbank_loader = Some(loader),
+            Err(err) => log::error!("Failed to start soundbank loader thread: {}", err)
+        }
+    }
+
+    pub fn variation_fx(&self, unit: u8) -> Option<&FXUnit> {
+        return self.variation_fx.get(unit as usize);
+    }
+
+    pub fn mfx(&self, channel_no: u8, unit: u8) -> Option<&FXUnit> {
+        if unit as usize >= MFX_UNITS_PER_CHANNEL {
+            return None;
+        }
+        return self.mfx.get(channel_no as usize * MFX_UNITS_PER_CHANNEL + unit as usize);
+    }
+
+    fn send_sysex_reply(&mut self, msg: &[u8]) {
+        if let Some(callback) = self.sysex_reply.as_mut() {
+            callback(msg);
+        }
+    }
+
+    // 0.0 - 1.0 범위의 난수
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random_state = x;
+        return x as f64 / u32::MAX as f64;
+    }
+
+    // 채널에 맞는 preset을 찾음
+    // 정확히 일치하는 게 없으면 bank select lsb => msb 순서로 0으로 바꿔 가면서 다시 찾음
+    fn find_preset(&self, channel: &Channel) -> Option<(&Soundbank, &Preset)> {
+        let (type_flag, candidates) = if channel.is_drum {
+            (PresetType::Drum, [
+                (channel.bank_msb, channel.bank_lsb, channel.program),
+                (0, 0, channel.program),
+                (0, 0, 0)
+            ])
+        } else {
+            (PresetType::Melodic, [
+                (channel.bank_msb, channel.bank_lsb, channel.program),
+                (channel.bank_msb, 0, channel.program),
+                (0, 0, channel.program)
+            ])
+        };
+
+        for (bank_msb, bank_lsb, program) in candidates {
+            for (soundbank, _) in self.soundbanks.iter().rev() {
+                if let Some(preset) = soundbank.find_preset(bank_msb, bank_lsb, program as u16, type_flag) {
+                    return Some((soundbank, preset));
+                }
+            }
+        }
+        return None;
+    }
+
     // midi 기본기능
+    // 포트 A로 들어온 메세지로 취급함
     pub fn handle_midi_message(&mut self, msg: &[u8]) {
+        self.handle_port_midi_message(0, msg);
+    }
+
+    /**
+     * 지정한 시간에 미디 메세지를 적용하도록 예약함
+     * frame: render로 내보낸 샘플 수 기준 시간(frame_position 참고)
+     *
+     * render는 내부 버퍼를 미리 렌더링해 두기 때문에 예약한 메세지는 항상 render_buffer_size만큼 늦게 적용됨
+     * 늦어지는 정도가 항상 같으므로 메세지 사이의 간격은 샘플 단위로 정확하게 유지됨
+     * 이미 지나간 시간이면 다음 블록 시작 때 적용함
+     */
+    pub fn schedule_midi_message(&mut self, frame: u64, msg: &[u8]) {
+        self.schedule_port_midi_message(frame, 0, msg);
+    }
+
+    pub fn schedule_port_midi_message(&mut self, frame: u64, port: u8, msg: &[u8]) {
+        if msg.is_empty() {
+            return;
+        }
+
+        self.schedule_message(frame, port, MidiBytes::new(msg));
+    }
+
+    /**
+     * 다음 render 호출 기준 위치에 미디 메세지를 적용하도록 예약함
+     * frame_offset: 다음 render 호출에서 내보내는 첫 샘플 기준 위치
+     * 다른 스레드에서 보내려면 midi_message_sender를 씀
+     */
+    pub fn queue_midi_message(&mut self, frame_offset: u32, msg: &[u8]) {
+        self.queue_port_midi_message(frame_offset, 0, msg);
+    }
+
+    pub fn queue_port_midi_message(&mut self, frame_offset: u32, port: u8, msg: &[u8]) {
+        self.schedule_port_midi_message(self.output_frame + frame_offset as u64, port, msg);
+    }
+
+    // 다른 스레드(미디 입력 등)에서 메세지를 보낼 때 쓰는 핸들을 만듦
+    pub fn midi_message_sender(&self) -> MidiMessageSender {
+        return MidiMessageSender::new(self.midi_queue.clone(), self.shared_frame_position.clone());
+    }
+
+    fn schedule_message(&mut self, frame: u64, port: u8, data: MidiBytes) {
+        let frame = frame + self.create_settings.render_buffer_size as u64;
+        // 시간이 같으면 먼저 예약한 메세지가 먼저 적용됨
+        let index = self.scheduled_messages.partition_point(|message| message.frame <= frame);
+        self.scheduled_messages.insert(index, ScheduledMidiMessage {
+            frame,
+            port,
+            data
+        });
+    }
+
+    // MidiMessageSender로 들어온 메세지를 모두 꺼내서 예약함
+    fn receive_queued_messages(&mut self) {
+        while let Some(message) = self.midi_queue.pop() {
+            let frame = self.output_frame + message.frame_offset as u64;
+            self.schedule_message(frame, message.port, message.data);
+        }
+    }
+
+    // 예약해 두고 아직 적용하지 않은 메세지를 모두 버림(미디 파일 재생 위치를 옮길 때 등)
+    pub fn clear_scheduled_messages(&mut self) {
+        self.scheduled_messages.clear();
+    }
+
+    // 지금까지 render로 내보낸 샘플 수
+    pub fn frame_position(&self) -> u64 {
+        return self.output_frame;
+    }
+
+    // port: 0 = 포트 A, 1 = 포트 B
+    pub fn handle_port_midi_message(&mut self, port: u8, msg: &[u8]) {
+        if msg.is_empty() {
+            return;
+        }
+        if port as usize >= MIDI_PORTS {
+            log::error!("Invalid midi port: {}", port);
+            return;
+        }
+
         let msg_category = msg[0] >> 4;
         let channel = msg[0] - (msg_category << 4);
-        match msg_category {
-            0x8 => self.note_off(channel, msg[1] as i32, msg[2] as i32),
-            0x9 => self.note_on(channel, msg[1] as i32, msg[2] as i32),
-            0xa => self.note_aftertouch(channel, msg[1] as i32, msg[2] as i32),
-            0xb => self.control_change(channel, msg[1], msg[2]),
-            0xc => self.program_change(channel, msg[1] as i32),
-            0xd => self.channel_aftertouch(channel, msg[1] as i32),
-            0xe => self.pitch_bend(channel, (msg[1] as i32) + ((msg[2] as i32) << 7)),
-            0xf => {
-                if channel == 0x0 {
-                    self.handle_sysex(msg);
-                }
-            },
-            _ => {}
+        let expected_len = match msg_category {
+            0xc | 0xd => 2,
+            0x8..=0xe => 3,
+            _ => 1
+        };
+        if msg.len() < expected_len {
+            log::error!("Midi message too short");
+            return;
+        }
+
+        if msg_category == 0xf {
+            if channel == 0x0 {
+                self.handle_port_sysex(port, msg);
+            }
+            return;
+        }
+
+        // 해당 채널을 받도록 설정된(gs rx channel) 파트에 모두 보냄
+        let first_part = port as usize * CHANNELS_PER_PORT;
+        for part in first_part..(first_part + CHANNELS_PER_PORT) {
+            if self.channels[part].rx_channel != Some(channel) {
+                continue;
+            }
+            let part = part as u8;
+            match msg_category {
+                0x8 => self.note_off(part, msg[1] as i32, msg[2] as i32),
+                0x9 => self.note_on(part, msg[1] as i32, msg[2] as i32),
+                0xa => self.note_aftertouch(part, msg[1] as i32, msg[2] as i32),
+                0xb => self.control_change(part, msg[1], msg[2]),
+                0xc => self.program_change(part, msg[1] as i32),
+                0xd => self.channel_aftertouch(part, msg[1] as i32),
+                0xe => self.pitch_bend(part, (msg[1] as i32) + ((msg[2] as i32) << 7)),
+                _ => {}
+            }
         }
     }
 
     // sysex 메세지를 해석해 그에 해당하는 기능 수행
+    // 포트 A로 들어온 메세지로 취급함
     pub fn handle_sysex(&mut self, msg: &[u8]) {
+        self.handle_port_sysex(0, msg);
+    }
+
+    pub fn handle_port_sysex(&mut self, port: u8, msg: &[u8]) {
+        if msg.len() < 4 {
+            log::error!("Sysex message too short");
+            return;
+        }
+        if port as usize >= MIDI_PORTS {
+            log::error!("Invalid midi port: {}", port);
+            return;
+        }
+
         let vendor_id: VendorId = if msg[1] == 0x00 {
             VendorId::Extended(msg[2], msg[3])
         } else {
@@ -58,9 +459,9 @@ impl Synth {
         };
 
         match vendor_id {
-            vendors::STD_NON_REALTIME => self.handle_gm_non_realtime_sysex(msg),
-            vendors::STD_REALTIME => self.handle_gm_realtime_sysex(msg),
-            vendors::ROLAND => self.handle_gs_sysex(msg),
+            vendors::STD_NON_REALTIME => self.handle_gm_non_realtime_sysex(port, msg),
+            vendors::STD_REALTIME => self.handle_gm_realtime_sysex(port, msg),
+            vendors::ROLAND => self.handle_gs_sysex(port, msg),
             vendors::YJ => self.handle_wsn_sysex(msg),
             _ => log::error!("Invalid sysex vendor id")
         }
@@ -68,107 +469,429 @@ impl Synth {
 
     pub fn note_on(&mut self, channel_no: u8, note: i32, velocity: i32) {
         if velocity <= 0 { return self.note_off(channel_no, note, velocity); }
-        
-    //
+
+        if channel_no as usize >= self.channels.len() {
+            return;
+        }
+        let random = self.next_random();
+        let channel = &self.channels[channel_no as usize];
+        let key = note.max(0).min(127) as u8;
+        if !channel.is_in_key_range(key) {
+            return;
+        }
+        let velocity = channel.apply_velocity_sense(velocity.min(127) as u8);
+
+        // key shift는 드럼 채널에는 적용하지 않음
+        let note = if channel.is_drum {
+            key
+        } else {
+            (key as i32 + channel.key_shift as i32 + self.coarse_tuning()).max(0).min(127) as u8
+        };
+        let mut offsets = channel.get_drum_offsets(note, random);
+        offsets.pitch += (channel.get_scale_tuning_cent(note) * 10.0) as i32;
+
+        let mut new_voices = vec![];
+        if let Some((soundbank, preset)) = self.find_preset(channel) {
+            // preset region => instrument region => sample 순서로 찾아감
+            for preset_region in preset.regions.iter().filter(|rgn| rgn.contains(note, velocity)) {
+                let instrument = match soundbank.instruments().get(preset_region.target_index as usize) {
+                    Some(instrument) => instrument,
+                    None => continue
+                };
+                for inst_region in instrument.regions.iter().filter(|rgn| rgn.contains(note, velocity)) {
+                    let sample_index = inst_region.target_index as usize;
+                    let sample = match soundbank {
+                        Soundbank::WSBK(bank) => bank.samples.get(sample_index).map(VoiceSample::from_wsbk),
+                        Soundbank::SF2(bank) => bank.samples.get(sample_index).map(|sample| VoiceSample {
+                            oscillator: match &sample.stream {
+                                Some(stream) => SampleOscillator::from_stream(
+                                    stream, sample.loop_type, sample.loop_start, sample.loop_end
+                                ),
+                                None => SampleOscillator::from_pcm16(
+                                    bank.sample_data(), bank.sm24_data(),
+                                    sample.start, sample.right_start, sample.frame_count,
+                                    sample.loop_type, sample.loop_start, sample.loop_end
+                                )
+                            },
+                            base_key: sample.base_key,
+                            cent_correction: sample.cent_correction,
+                            sample_rate: sample.sample_rate
+                        })
+                    };
+                    let sample = match sample {
+                        Some(sample) => sample,
+                        None => continue
+                    };
+
+                    // preset 쪽 articulator는 instrument 쪽 articulator에 더해지는 방식
+                    let mut articulators = inst_region.articulators.clone();
+                    articulators.extend(preset_region.articulators.iter().cloned());
+
+                    // region에 고정된 key/velocity가 있으면 그 값으로 소리를 냄
+                    let generator = |gen: u16| inst_region.generators.get(&gen).copied();
+                    let voice_note = generator(region_gen::KEY_NUMBER).map(|key| key.max(0).min(127) as u8).unwrap_or(note);
+                    let voice_velocity = generator(region_gen::VELOCITY).map(|vel| vel.max(1).min(127) as u8).unwrap_or(velocity);
+
+                    let mut voice = Voice::new(
+                        sample, articulators, &offsets,
+                        channel, channel_no, key, voice_note, voice_velocity,
+                        &self.create_settings
+                    );
+                    voice.exclusive_class = generator(region_gen::EXCLUSIVE_CLASS).unwrap_or(0);
+                    new_voices.push(voice);
+                }
+            }
+        }
+
+        // 새 voice끼리는 끊지 않도록 먼저 이전 voice를 다 끊고 추가함
+        for voice in new_voices.iter().filter(|voice| voice.exclusive_class != 0) {
+            self.voices.kill_exclusive(channel_no, voice.exclusive_class);
+        }
+        for voice in new_voices {
+            self.voices.start_voice(voice, &self.channels, &self.settings.overflow);
+        }
     }
 
     pub fn note_off(&mut self, channel_no: u8, note: i32, _velocity: i32) {
-        //
+        let channel = match self.channels.get(channel_no as usize) {
+            Some(channel) => channel,
+            None => return
+        };
+        self.voices.key_off(channel_no, note.max(0).min(127) as u8, channel);
     }
 
     pub fn note_aftertouch(&mut self, channel_no: u8, note: i32, pressure: i32) {
-        //
+        let channel = match self.channels.get_mut(channel_no as usize) {
+            Some(channel) => channel,
+            None => return
+        };
+        let note = note.max(0).min(127) as u8;
+        let pressure = pressure.max(0).min(127) as u8;
+        channel.note_aftertouch[note as usize] = pressure;
+        self.voices.note_aftertouch(channel_no, note, pressure);
     }
 
     pub fn control_change(&mut self, channel_no: u8, cc: u8, val: u8) {
-        //
+        let channel = match self.channels.get_mut(channel_no as usize) {
+            Some(channel) => channel,
+            None => return
+        };
+        let was_sostenuto_on = channel.is_sostenuto_on();
+        channel.control_change(cc, val);
+
+        let channel = &self.channels[channel_no as usize];
+        match cc {
+            cc_ids::SUSTAIN_ONOFF => self.voices.update_pedal(channel_no, channel),
+            cc_ids::SOSTENUTO_ONOFF => {
+                if !was_sostenuto_on && channel.is_sostenuto_on() {
+                    self.voices.sostenuto_on(channel_no);
+                } else if was_sostenuto_on && !channel.is_sostenuto_on() {
+                    self.voices.sostenuto_off(channel_no, channel);
+                }
+            },
+            cc_ids::ALL_SOUND_OFF => self.voices.kill_all(channel_no),
+            cc_ids::RESET_ALL_CONTROLLERS => {
+                self.channels[channel_no as usize].reset_controllers();
+                let channel = &self.channels[channel_no as usize];
+                self.voices.sostenuto_off(channel_no, channel);
+            },
+            cc_ids::ALL_NOTES_OFF
+            | cc_ids::OMNI_OFF
+            | cc_ids::OMNI_ON
+            | cc_ids::MONO_ON
+            | cc_ids::POLY_ON => self.voices.key_off_all(channel_no, channel),
+            _ => {}
+        }
     }
 
     pub fn program_change(&mut self, channel_no: u8, program_no: i32) {
-        //
+        let channel = match self.channels.get_mut(channel_no as usize) {
+            Some(channel) => channel,
+            None => return
+        };
+        channel.program_change(program_no.max(0).min(127) as u8);
+
+        match self.mode {
+            SynthMode::GS => {},
+            SynthMode::GM => {
+                channel.bank_msb = 0;
+                channel.bank_lsb = 0;
+            },
+            SynthMode::GM2 => {
+                // 78h/79h는 사운드뱅크에서는 0번 bank로 찾음
+                match channel.bank_msb {
+                    0x78 => channel.set_drum(true),
+                    0x79 => channel.set_drum(false),
+                    _ => return
+                }
+                channel.bank_msb = 0;
+            }
+        }
     }
 
     pub fn channel_aftertouch(&mut self, channel_no: u8, pressure: i32) {
-        //
+        if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+            channel.channel_aftertouch = pressure.max(0).min(127) as u8;
+        }
     }
 
     // offset => 0 - 16383
     pub fn pitch_bend(&mut self, channel_no: u8, offset: i32) {
-        //
+        if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+            channel.pitch_bend = offset.max(0).min(16383) as u16;
+        }
     }
 
     // 어떤 reset 메세지가 들어와도 공통으로 수행하는 reset
-    pub fn system_reset(&mut self) {}
+    pub fn system_reset(&mut self) {
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            channel.reset(i as u8);
+            self.voices.kill_all(i as u8);
+        }
+    }
 
     /**
      * 표준 sysex로 제어하는 기능
+     * port: 메세지가 들어온 포트(0 = 포트 A, 1 = 포트 B)
      */
-    pub fn handle_gm_non_realtime_sysex(&mut self, msg: &[u8]) {
-        //
+    pub fn handle_gm_non_realtime_sysex(&mut self, port: u8, msg: &[u8]) {
+        self.process_non_realtime_sysex(port, msg);
     }
 
-    pub fn handle_gm_realtime_sysex(&mut self, msg: &[u8]) {
-        //
+    pub fn handle_gm_realtime_sysex(&mut self, port: u8, msg: &[u8]) {
+        self.process_realtime_sysex(port, msg);
     }
 
-    pub fn gm_reset(&mut self) {}
+    pub fn gm_reset(&mut self) {
+        self.system_reset();
+        self.reset_master();
+        self.gs_effects = GSEffectParams::new();
+        self.mode = SynthMode::GM;
+    }
+
+    pub fn gm2_reset(&mut self) {
+        self.system_reset();
+        self.reset_master();
+        self.gs_effects = GSEffectParams::new();
+        self.mode = SynthMode::GM2;
+    }
 
+    // volume: 0 - 16383
     pub fn set_master_volume(&mut self, volume: i32) {
-        //
+        self.master_volume = volume.max(0).min(16383) as u16;
     }
 
+    // cent: -100 - +100
     pub fn set_master_fine_tuning(&mut self, cent: f64) {
-        //
+        self.master_fine_tuning = cent.max(-100.0).min(100.0);
     }
 
+    // key: -64 - +63
+    // 이미 소리를 내고 있는 note에는 적용되지 않음
     pub fn set_master_coarse_tuning(&mut self, key: i32) {
-        //
+        self.master_coarse_tuning = key.max(-64).min(63);
+    }
+
+    pub fn transpose(&self) -> i32 {
+        return self.transpose;
+    }
+
+    // key: -24 - +24
+    // 이미 소리를 내고 있는 note에는 적용되지 않음
+    pub fn set_transpose(&mut self, key: i32) {
+        self.transpose = key.max(-24).min(24);
+    }
+
+    // 조옮김을 적용한 master coarse tuning(-64 - +63)
+    // 조옮김은 곡에서 보내는 master coarse tuning과 따로 저장함
+    // set_master_coarse_tuning으로 바꾸면 gs reset이나 곡의 sysex에 덮어씌워지므로 합쳐서 적용함
+    fn coarse_tuning(&self) -> i32 {
+        return (self.master_coarse_tuning + self.transpose).max(-64).min(63);
+    }
+
+    // master volume/tuning/pan을 기본값으로 되돌림
+    fn reset_master(&mut self) {
+        self.master_volume = 16383;
+        self.master_fine_tuning = 0.0;
+        self.master_coarse_tuning = 0;
+        self.master_pan = 0.0;
     }
 
     /**
      * gs 확장 sysex로 제어하는 기능
+     * port: 메세지가 들어온 포트(0 = 포트 A, 1 = 포트 B)
      */
-    pub fn handle_gs_sysex(&mut self, msg: &[u8]) {
-        //
+    pub fn handle_gs_sysex(&mut self, port: u8, msg: &[u8]) {
+        self.process_roland_sysex(port, msg);
     }
 
-    pub fn gs_reset(&mut self) {}
+    pub fn gs_reset(&mut self) {
+        self.system_reset();
+        self.reset_master();
+        self.gs_effects = GSEffectParams::new();
+        self.mode = SynthMode::GS;
+    }
 
     /**
      * 자체 확장 sysex로 제어하는 기능
      */
     pub fn handle_wsn_sysex(&mut self, msg: &[u8]) {
-        //
+        self.process_wsn_sysex(msg);
     }
 
-    pub fn wsn_reset(&mut self) {}
+    // 이펙트 설정을 모두 없앰
+    // 사운드뱅크와 SynthSettings는 그대로 둠
+    pub fn wsn_reset(&mut self) {
+        for fx in self.variation_fx.iter_mut().chain(self.mfx.iter_mut()) {
+            *fx = FXUnit::new();
+        }
+    }
 
     // -1.0(왼쪽) - 0.0(가운데) - 1.0(오른쪽)
     pub fn set_master_pan(&mut self, pan: f64) {
-        //
+        self.master_pan = pan.max(-1.0).min(1.0);
     }
 
     // multi effect(이하 mfx) 관련 기능
+    // 이펙트 종류를 바꾸면 파라미터는 0으로 초기화됨
     pub fn set_mfx_type(&mut self, channel_no: u8, unit: u8, mfx_type: FXType) {
-        //
+        if channel_no as usize >= MIDI_CHANNELS || unit as usize >= MFX_UNITS_PER_CHANNEL {
+            log::error!("Invalid mfx unit: channel {}, unit {}", channel_no, unit);
+            return;
+        }
+        let fx = &mut self.mfx[channel_no as usize * MFX_UNITS_PER_CHANNEL + unit as usize];
+        fx.fx_type = mfx_type;
+        fx.params = [0; FX_PARAMS];
     }
 
     pub fn set_mfx_parameter(&mut self, channel_no: u8, unit: u8, param_no: i32, val: i32) {
-        //
+        if channel_no as usize >= MIDI_CHANNELS || unit as usize >= MFX_UNITS_PER_CHANNEL {
+            log::error!("Invalid mfx unit: channel {}, unit {}", channel_no, unit);
+            return;
+        }
+        let fx = &mut self.mfx[channel_no as usize * MFX_UNITS_PER_CHANNEL + unit as usize];
+        match fx.params.get_mut(param_no as usize) {
+            Some(param) => *param = val,
+            None => log::error!("Invalid mfx parameter number: {}", param_no)
+        }
     }
 
     // variation effect 관련 기능
     pub fn set_variation_fx_type(&mut self, unit: u8, vfx_type: FXType) {
-        //
+        let fx = match self.variation_fx.get_mut(unit as usize) {
+            Some(fx) => fx,
+            None => {
+                log::error!("Invalid variation effect unit: {}", unit);
+                return;
+            }
+        };
+        fx.fx_type = vfx_type;
+        fx.params = [0; FX_PARAMS];
     }
 
     pub fn set_variation_fx_parameter(&mut self, unit: u8, param_no: i32, val: i32) {
-        //
+        let fx = match self.variation_fx.get_mut(unit as usize) {
+            Some(fx) => fx,
+            None => {
+                log::error!("Invalid variation effect unit: {}", unit);
+                return;
+            }
+        };
+        match fx.params.get_mut(param_no as usize) {
+            Some(param) => *param = val,
+            None => log::error!("Invalid variation effect parameter number: {}", param_no)
+        }
     }
 
-    pub fn render(&mut self, left: &mut [f64], right: &mut [f64]) {}
+    /**
+     * left/right 버퍼를 채움(원래 있던 값은 지워짐)
+     * 길이는 아무래도 상관 없음. 내부적으로는 render_buffer_size 단위로 렌더링하고 남은 부분은 다음 호출 때 내보냄
+     */
+    pub fn render(&mut self, left: &mut [f64], right: &mut [f64]) {
+        self.receive_queued_messages();
+
+        let len = left.len().min(right.len());
+        let mut pos = 0;
+        while pos < len {
+            let (start, count) = self.next_buffered_frames(len - pos);
+            left[pos..(pos + count)].copy_from_slice(&self.buffer_left[start..(start + count)]);
+            right[pos..(pos + count)].copy_from_slice(&self.buffer_right[start..(start + count)]);
+            pos += count;
+        }
+        self.shared_frame_position.store(self.output_frame, Ordering::Release);
+    }
 
-    pub fn render_as_one_array(&mut self, left: &mut [f64], right: &mut [f64]) {}
+    // left, right, left, right, ... 순서로 된 배열 하나를 채움
+    pub fn render_as_one_array(&mut self, output: &mut [f64]) {
+        self.receive_queued_messages();
+
+        let len = output.len() / 2;
+        let mut pos = 0;
+        while pos < len {
+            let (start, count) = self.next_buffered_frames(len - pos);
+            let frames = output[(pos * 2)..((pos + count) * 2)].chunks_exact_mut(2);
+            for (i, frame) in frames.enumerate() {
+                frame[0] = self.buffer_left[start + i];
+                frame[1] = self.buffer_right[start + i];
+            }
+            pos += count;
+        }
+        self.shared_frame_position.store(self.output_frame, Ordering::Release);
+    }
+
+    // 내부 버퍼에서 최대 max개의 샘플을 꺼냄(버퍼가 비었으면 새로 렌더링함)
+    // 꺼낸 부분의 (시작 위치, 샘플 수)를 돌려줌
+    fn next_buffered_frames(&mut self, max: usize) -> (usize, usize) {
+        if self.buffer_pos >= self.buffer_left.len() {
+            self.render_buffer();
+        }
+        let start = self.buffer_pos;
+        let count = (self.buffer_left.len() - start).min(max);
+        self.buffer_pos += count;
+        self.output_frame += count as u64;
+        return (start, count);
+    }
 
-    fn render_buffer(&mut self) {}
-}
\ No newline at end of file
+    /**
+     * 내부 버퍼를 블록 1개만큼 새로 렌더링함
+     * 예약된 미디 메세지가 있으면 그 위치에서 블록을 나눠서, 메세지가 정확한 샘플 위치부터 적용되게 함
+     */
+    fn render_buffer(&mut self) {
+        self.install_loaded_soundbanks();
+
+        let size = self.buffer_left.len();
+        self.buffer_left.fill(0.0);
+        self.buffer_right.fill(0.0);
+
+        let mut pos = 0;
+        while pos < size {
+            let now = self.render_frame + pos as u64;
+            while self.scheduled_messages.front().is_some_and(|message| message.frame <= now) {
+                let message = self.scheduled_messages.pop_front().unwrap();
+                self.handle_port_midi_message(message.port, message.data.as_slice());
+            }
+
+            let end = match self.scheduled_messages.front() {
+                Some(message) => ((message.frame - self.render_frame) as usize).min(size),
+                None => size
+            };
+            let left = &mut self.buffer_left[pos..end];
+            let right = &mut self.buffer_right[pos..end];
+            self.voices.render(left, right, &self.channels, self.master_fine_tuning);
+
+            // master volume은 cc 7과 같은 곡선을 씀. master pan은 balance 방식
+            let volume = self.master_volume as f64 / 16383.0;
+            let gain = self.settings.output_gain * volume * volume;
+            let gain_left = gain * (1.0 - self.master_pan).min(1.0);
+            let gain_right = gain * (1.0 + self.master_pan).min(1.0);
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                *l *= gain_left;
+                *r *= gain_right;
+            }
+            pos = end;
+        }
+
+        self.render_frame += size as u64;
+        self.buffer_pos = 0;
+    }
+}
@@ -1 +1,329 @@
-pub struct Oscillator {}
\ No newline at end of file
+/**
+ * 샘플 재생기
+ * wsbk/sf2 샘플 데이터를 원하는 속도(=피치)로 읽어들임
+ * 샘플 사이의 값은 라그랑주 3차다항식 보간으로 구함
+ */
+
+use std::sync::Arc;
+use crate::soundbank::wsbk::{ Sample, SampleType, LoopType };
+use crate::soundbank::stream::SampleStream;
+use crate::util::interpolation::interpolate_cubic;
+
+// 오실레이터가 읽는 샘플 데이터(사운드뱅크와 공유함)
+enum SampleData {
+    // wsbk 샘플 데이터
+    // bytes_per_sample: 샘플 1개가 차지하는 바이트 수
+    // bit_depth: 16, 24 = 정수 / 32, 64 = 부동소수점
+    Bytes {
+        data: Arc<Vec<u8>>,
+        bytes_per_sample: usize,
+        bit_depth: u16
+    },
+
+    // sf2 샘플 데이터 전체에서 왼쪽(mono면 유일한) 채널과 오른쪽 채널이 시작하는 위치
+    // 24비트 샘플이면 low에 하위 8비트가 있음(위치는 data와 같음)
+    Pcm16 {
+        data: Arc<Vec<i16>>,
+        low: Option<Arc<Vec<u8>>>,
+        left_start: usize,
+        right_start: usize
+    },
+
+    // 필요할 때 불러오는 샘플(wsbk 샘플 데이터 형식)
+    // 앞부분(attack)은 항상 있고 나머지(full)는 백그라운드 스레드가 불러오면 가져옴
+    Streamed {
+        stream: Arc<SampleStream>,
+        attack: Arc<Vec<u8>>,
+        full: Option<Arc<Vec<u8>>>,
+        bytes_per_sample: usize,
+        bit_depth: u16,
+
+        // 불러오기 전에 재생하려고 했는지 여부(한 번만 셈)
+        missed: bool
+    }
+}
+
+impl Drop for SampleData {
+    fn drop(&mut self) {
+        // 전체 데이터를 먼저 놓고 사용 수를 줄임
+        // 반대로 하면 그 사이에 로더가 샘플을 버려서 이 스레드(오디오 스레드)에서 메모리를 해제하게 될 수 있음
+        if let Self::Streamed { stream, full, .. } = self {
+            drop(full.take());
+            stream.release();
+        }
+    }
+}
+
+// 샘플 1개를 -1.0 - 1.0 범위로 변환
+#[inline]
+fn decode(bytes: &[u8], bit_depth: u16) -> f64 {
+    return match bit_depth {
+        16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32768.0,
+        24 => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f64 / 8388608.0,
+        32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        64 => f64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3],
+            bytes[4], bytes[5], bytes[6], bytes[7]
+        ]),
+        _ => 0.0
+    };
+}
+
+fn bytes_per_sample(bit_depth: u16) -> usize {
+    return match bit_depth {
+        16 => 2,
+        24 => 3,
+        32 => 4,
+        64 => 8,
+        _ => 0
+    };
+}
+
+pub struct SampleOscillator {
+    data: SampleData,
+
+    // 1 = mono, 2 = stereo
+    channels: usize,
+
+    // 전체 길이(프레임 단위. 프레임 1개 = 채널 수만큼의 샘플)
+    frame_count: usize,
+
+    // 루프 관련
+    loop_type: LoopType,
+    loop_start: usize,
+    loop_end: usize,
+
+    // 지금 루프를 돌고 있는지 여부
+    // UntilReleased의 경우 release 이후에는 루프를 빠져나와 끝까지 재생함
+    looping: bool,
+
+    // 현재 위치와 샘플 1개당 진행하는 양(프레임 단위)
+    position: f64,
+    increment: f64,
+
+    // 샘플 끝에 도달했는지 여부
+    finished: bool
+}
+
+impl SampleOscillator {
+    pub fn new(sample: &Sample) -> Self {
+        if let Some(stream) = &sample.stream {
+            return Self::from_stream(stream, sample.loop_type, sample.loop_start, sample.loop_end);
+        }
+        let bytes_per_sample = bytes_per_sample(sample.bit_depth);
+        let channels = match sample.sample_type {
+            SampleType::Mono => 1,
+            SampleType::Stereo => 2
+        };
+        let frame_count = if bytes_per_sample == 0 {
+            log::error!("Unsupported sample bit depth: {}", sample.bit_depth);
+            0
+        } else {
+            sample.data.len() / (bytes_per_sample * channels)
+        };
+
+        let data = SampleData::Bytes {
+            data: Arc::clone(&sample.data),
+            bytes_per_sample,
+            bit_depth: sample.bit_depth
+        };
+        return Self::with_data(data, channels, frame_count, sample.loop_type, sample.loop_start, sample.loop_end);
+    }
+
+    /**
+     * sf2 샘플 데이터를 복사하지 않고 재생함
+     * low: 24비트 샘플의 하위 8비트(sm24). 16비트 샘플이면 None
+     * left_start, right_start: 데이터에서 각 채널이 시작하는 위치(right_start가 None이면 mono)
+     * 데이터 범위를 벗어나는 부분은 잘라냄
+     */
+    pub fn from_pcm16(
+        data: &Arc<Vec<i16>>, low: Option<&Arc<Vec<u8>>>,
+        left_start: usize, right_start: Option<usize>, frame_count: usize,
+        loop_type: LoopType, loop_start: u32, loop_end: u32
+    ) -> Self {
+        // 하위 8비트 데이터는 길이가 같아야 함
+        let low = low.filter(|low| low.len() == data.len());
+        let mut frame_count = frame_count.min(data.len().saturating_sub(left_start));
+        if let Some(right_start) = right_start {
+            frame_count = frame_count.min(data.len().saturating_sub(right_start));
+        }
+        let data = SampleData::Pcm16 {
+            data: Arc::clone(data),
+            low: low.cloned(),
+            left_start,
+            right_start: right_start.unwrap_or(left_start)
+        };
+        let channels = if right_start.is_some() { 2 } else { 1 };
+        return Self::with_data(data, channels, frame_count, loop_type, loop_start, loop_end);
+    }
+
+    // 필요할 때 불러오는 샘플을 재생함. 재생하는 동안 샘플이 버려지지 않음
+    pub fn from_stream(stream: &Arc<SampleStream>, loop_type: LoopType, loop_start: u32, loop_end: u32) -> Self {
+        stream.acquire();
+        let bytes_per_sample = bytes_per_sample(stream.bit_depth());
+        let frame_count = if bytes_per_sample == 0 {
+            log::error!("Unsupported sample bit depth: {}", stream.bit_depth());
+            0
+        } else {
+            stream.frame_count()
+        };
+        let data = SampleData::Streamed {
+            stream: Arc::clone(stream),
+            attack: Arc::clone(stream.attack()),
+            full: stream.try_full(),
+            bytes_per_sample,
+            bit_depth: stream.bit_depth(),
+            missed: false
+        };
+        return Self::with_data(data, stream.channels(), frame_count, loop_type, loop_start, loop_end);
+    }
+
+    fn with_data(data: SampleData, channels: usize, frame_count: usize, loop_type: LoopType, loop_start: u32, loop_end: u32) -> Self {
+        // 루프 지점이 이상하면 루프 없이 재생함
+        let loop_start = loop_start as usize;
+        let loop_end = (loop_end as usize).min(frame_count);
+        let loop_type = if loop_start < loop_end {
+            loop_type
+        } else {
+            LoopType::NoLoop
+        };
+
+        return Self {
+            data,
+            channels,
+            frame_count,
+            loop_type,
+            loop_start,
+            loop_end,
+            looping: loop_type != LoopType::NoLoop,
+            position: 0.0,
+            increment: 1.0,
+            finished: frame_count == 0
+        };
+    }
+
+    pub fn is_stereo(&self) -> bool {
+        return self.channels == 2;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.finished;
+    }
+
+    // 샘플 1개당 진행하는 양(1.0 = 원래 속도)
+    pub fn set_increment(&mut self, increment: f64) {
+        self.increment = increment.max(0.0);
+    }
+
+    // note off가 들어왔을 때 호출
+    pub fn release(&mut self) {
+        if self.loop_type == LoopType::UntilReleased {
+            self.looping = false;
+        }
+    }
+
+    // 샘플 데이터에서 값 1개를 읽어 -1.0 - 1.0 범위로 변환
+    #[inline]
+    fn read_raw(&self, frame: usize, channel: usize) -> f64 {
+        let (data, bytes_per_sample, bit_depth) = match &self.data {
+            SampleData::Bytes { data, bytes_per_sample, bit_depth } => (data, *bytes_per_sample, *bit_depth),
+            SampleData::Pcm16 { data, low, left_start, right_start } => {
+                let index = frame + if channel == 0 { *left_start } else { *right_start };
+                return match low {
+                    Some(low) => ((data[index] as i32) << 8 | low[index] as i32) as f64 / 8388608.0,
+                    None => data[index] as f64 / 32768.0
+                };
+            },
+            SampleData::Streamed { stream, attack, full, bytes_per_sample, bit_depth, .. } => {
+                let data = match full {
+                    Some(full) => full,
+                    None if frame < stream.attack_frames() => attack,
+                    // 아직 불러오지 못한 부분
+                    None => return 0.0
+                };
+                (data, *bytes_per_sample, *bit_depth)
+            }
+        };
+        let i = (frame * self.channels + channel) * bytes_per_sample;
+        return decode(&data[i..(i + bytes_per_sample)], bit_depth);
+    }
+
+    /**
+     * 필요할 때 불러오는 샘플이면 전체 데이터를 불러왔는지 확인해서 가져옴(기다리지 않음)
+     * 블록마다 next_frame을 부르기 전에 한 번 부름. len: 이번 블록에서 읽을 프레임 수
+     */
+    pub fn update_stream(&mut self, len: usize) {
+        let SampleData::Streamed { stream, full, missed, .. } = &mut self.data else {
+            return;
+        };
+        if full.is_some() || self.finished {
+            return;
+        }
+        *full = stream.try_full();
+        // 이번 블록에서 앞부분을 넘어가는데 아직 못 불러왔으면 그 부분은 무음이 됨
+        let end = self.position + self.increment * len as f64 + 2.0;
+        if full.is_none() && !*missed && end >= stream.attack_frames() as f64 {
+            *missed = true;
+            stream.add_miss();
+        }
+    }
+
+    // 루프를 고려해서 프레임 번호를 실제 위치로 바꾼 다음 값을 읽음
+    // 범위를 벗어나면 0.0
+    #[inline]
+    fn fetch(&self, frame: isize, channel: usize) -> f64 {
+        let mut frame = frame;
+        if self.looping {
+            let loop_len = (self.loop_end - self.loop_start) as isize;
+            while frame >= self.loop_end as isize {
+                frame -= loop_len;
+            }
+        }
+        if frame < 0 || frame as usize >= self.frame_count {
+            return 0.0;
+        }
+        return self.read_raw(frame as usize, channel);
+    }
+
+    #[inline]
+    fn interpolate(&self, index: isize, t: f64, channel: usize) -> f64 {
+        return interpolate_cubic(
+            t,
+            self.fetch(index - 1, channel),
+            self.fetch(index, channel),
+            self.fetch(index + 1, channel),
+            self.fetch(index + 2, channel)
+        );
+    }
+
+    // 다음 값(왼쪽, 오른쪽)을 반환. mono 샘플은 둘 다 같은 값
+    #[inline]
+    pub fn next_frame(&mut self) -> (f64, f64) {
+        if self.finished {
+            return (0.0, 0.0);
+        }
+
+        let index = self.position.floor();
+        let t = self.position - index;
+        let index = index as isize;
+
+        let left = self.interpolate(index, t, 0);
+        let right = if self.channels == 2 {
+            self.interpolate(index, t, 1)
+        } else {
+            left
+        };
+
+        self.position += self.increment;
+        if self.looping {
+            let loop_len = (self.loop_end - self.loop_start) as f64;
+            while self.position >= self.loop_end as f64 {
+                self.position -= loop_len;
+            }
+        } else if self.position >= self.frame_count as f64 {
+            self.finished = true;
+        }
+
+        return (left, right);
+    }
+}
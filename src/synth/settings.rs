@@ -7,8 +7,13 @@
  * 이걸 다르게 하려면 무조건 Synth 개체를 다시 만들어야 함
  */
 pub struct SynthCreateSettings {
+    // 초당 샘플 수
+    pub(crate) sample_rate: u32, // 8000 - 192000 (기본값 = 48000)
+
     // 생성 가능한 최대 보조 스레드 수 설정
-    pub(crate) max_worker_threads: usize, // 최소 1 (기본값 = 1)
+    // 2 이상이면 voice 렌더링을 이 수만큼의 스레드에 나눠서 처리함(1이면 렌더링 스레드에서 전부 처리)
+    // 스레드 수와 상관없이 렌더링 결과는 같음
+    pub(crate) max_worker_threads: usize, // 1 - 64 (기본값 = 1)
 
     // 노트 1개의 최소 길이
     // note on 하자마자 바로 note off를 시전해도 최소한 이만큼은 소리가 유지됨
@@ -28,6 +33,7 @@ pub struct SynthCreateSettings {
 impl Default for SynthCreateSettings {
     fn default() -> Self {
         return Self {
+            sample_rate: 48000,
             max_worker_threads: 1,
             min_note_length: 10,
             polyphony: 384,
@@ -41,8 +47,12 @@ impl SynthCreateSettings {
         return Default::default();
     }
 
+    pub fn set_sample_rate(&mut self, val: u32) {
+        self.sample_rate = val.max(8000).min(192000);
+    }
+
     pub fn set_max_worker_threads(&mut self, val: usize) {
-        self.max_worker_threads = val.min(1);
+        self.max_worker_threads = val.max(1).min(64);
     }
 
     pub fn set_min_note_length(&mut self, val: i32) {
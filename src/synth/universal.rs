@@ -0,0 +1,377 @@
+/**
+ * 표준(universal) sysex 처리
+ * 메세지 형식: F0 [7Eh(non-realtime) 또는 7Fh(realtime)] [장치 ID] [sub ID 1] [sub ID 2] [데이터...] F7
+ * 장치 ID가 7Fh면 장치 ID 설정과 관계없이 처리함
+ */
+
+use super::Synth;
+use super::channel::{ gs_ctrl_src, gs_ctrl_dest, gs_nrpn_ids, CHANNELS_PER_PORT, MIDI_PORTS };
+use crate::util::midi::cc_ids;
+
+// sub ID 1
+pub mod sub_ids {
+    pub const DEVICE_CONTROL: u8 = 0x04; // realtime
+    pub const MIDI_TUNING: u8 = 0x08; // realtime/non-realtime
+    pub const GENERAL_MIDI: u8 = 0x09; // non-realtime
+    pub const CONTROLLER_DESTINATION: u8 = 0x09; // realtime
+    pub const KEY_BASED_INSTRUMENT_CONTROL: u8 = 0x0a; // realtime
+}
+
+// device control(04h)의 sub ID 2
+pub mod device_control_ids {
+    pub const MASTER_VOLUME: u8 = 0x01;
+    pub const MASTER_BALANCE: u8 = 0x02;
+    pub const MASTER_FINE_TUNING: u8 = 0x03;
+    pub const MASTER_COARSE_TUNING: u8 = 0x04;
+    pub const GLOBAL_PARAMETER_CONTROL: u8 = 0x05;
+}
+
+// general midi(09h)의 sub ID 2
+pub mod general_midi_ids {
+    pub const GM_SYSTEM_ON: u8 = 0x01;
+    pub const GM_SYSTEM_OFF: u8 = 0x02;
+    pub const GM2_SYSTEM_ON: u8 = 0x03;
+}
+
+// midi tuning standard(08h)의 sub ID 2
+pub mod midi_tuning_ids {
+    pub const SCALE_OCTAVE_TUNING_1BYTE: u8 = 0x08;
+    pub const SCALE_OCTAVE_TUNING_2BYTE: u8 = 0x09;
+}
+
+// controller destination setting(09h)의 sub ID 2
+pub mod controller_destination_ids {
+    pub const CHANNEL_PRESSURE: u8 = 0x01;
+    pub const POLY_PRESSURE: u8 = 0x02;
+    pub const CONTROLLER: u8 = 0x03;
+}
+
+// global parameter control의 slot path
+const SLOT_REVERB: (u8, u8) = (0x01, 0x01);
+const SLOT_CHORUS: (u8, u8) = (0x01, 0x02);
+
+// gm2 reverb type => gs reverb macro
+// 0 = small room, 1 = medium room, 2 = large room, 3 = medium hall, 4 = large hall, 8 = plate
+fn gm2_reverb_type_to_gs_macro(reverb_type: u8) -> Option<u8> {
+    return match reverb_type {
+        0..=4 => Some(reverb_type),
+        8 => Some(5),
+        _ => None
+    };
+}
+
+// gm2 controller destination 파라미터 번호 => gs 컨트롤러 destination 번호
+// 값의 범위도 gs와 같음(pitch: 28h - 58h, 나머지는 00h - 7Fh)
+fn gm2_ctrl_dest(param: u8) -> Option<usize> {
+    return match param {
+        0x00 => Some(gs_ctrl_dest::PITCH),
+        0x01 => Some(gs_ctrl_dest::TVF_CUTOFF),
+        0x02 => Some(gs_ctrl_dest::AMPLITUDE),
+        0x03 => Some(gs_ctrl_dest::LFO1_PITCH),
+        0x04 => Some(gs_ctrl_dest::LFO1_TVF),
+        0x05 => Some(gs_ctrl_dest::LFO1_TVA),
+        _ => None
+    };
+}
+
+// key-based instrument control에서 쓰는 cc 번호 => gs 드럼 nrpn msb
+fn key_based_nrpn_msb(cc: u8) -> Option<u8> {
+    return match cc {
+        cc_ids::CHANNEL_VOLUME => Some(gs_nrpn_ids::DRUM_TVA_LEVEL),
+        cc_ids::PAN => Some(gs_nrpn_ids::DRUM_PANPOT),
+        cc_ids::REVERB_SEND_LEVEL => Some(gs_nrpn_ids::DRUM_REVERB_SEND),
+        cc_ids::CHORUS_SEND_LEVEL => Some(gs_nrpn_ids::DRUM_CHORUS_SEND),
+        _ => None
+    };
+}
+
+// 7비트씩 나뉜 값을 합침(lsb가 먼저 옴)
+fn combine_lsb_first(bytes: &[u8]) -> u32 {
+    return bytes.iter().rev().fold(0, |acc, b| (acc << 7) | (*b as u32 & 0x7f));
+}
+
+impl Synth {
+    // 포트, 메세지 형식과 장치 ID를 확인하고 sub ID 1, 2와 데이터 부분(F7 제외)을 반환함
+    fn parse_universal_sysex<'a>(&self, port: u8, msg: &'a [u8]) -> Option<(u8, u8, &'a [u8])> {
+        if port as usize >= MIDI_PORTS {
+            log::error!("Invalid midi port: {}", port);
+            return None;
+        }
+        if msg.len() < 6 || msg[0] != 0xf0 || msg[msg.len() - 1] != 0xf7 {
+            log::error!("Malformed universal sysex message");
+            return None;
+        }
+        if msg[1..(msg.len() - 1)].iter().any(|b| *b >= 0x80) {
+            log::error!("Malformed universal sysex message");
+            return None;
+        }
+
+        let device_id = msg[2];
+        if device_id != 0x7f && device_id != self.settings.device_id {
+            return None;
+        }
+        return Some((msg[3], msg[4], &msg[5..(msg.len() - 1)]));
+    }
+
+    pub(crate) fn process_non_realtime_sysex(&mut self, port: u8, msg: &[u8]) {
+        let (sub_id1, sub_id2, data) = match self.parse_universal_sysex(port, msg) {
+            Some(parsed) => parsed,
+            None => return
+        };
+
+        match (sub_id1, sub_id2) {
+            (sub_ids::GENERAL_MIDI, general_midi_ids::GM_SYSTEM_ON) => self.gm_reset(),
+            (sub_ids::GENERAL_MIDI, general_midi_ids::GM2_SYSTEM_ON) => self.gm2_reset(),
+            // gm system off를 받으면 원래 모드(gs)로 돌아감
+            (sub_ids::GENERAL_MIDI, general_midi_ids::GM_SYSTEM_OFF) => self.gs_reset(),
+            (sub_ids::MIDI_TUNING, _) => self.process_midi_tuning(port, sub_id2, data),
+            _ => log::warn!("Unsupported non-realtime sysex ({:02X}h {:02X}h)", sub_id1, sub_id2)
+        }
+    }
+
+    pub(crate) fn process_realtime_sysex(&mut self, port: u8, msg: &[u8]) {
+        let (sub_id1, sub_id2, data) = match self.parse_universal_sysex(port, msg) {
+            Some(parsed) => parsed,
+            None => return
+        };
+
+        match (sub_id1, sub_id2) {
+            (sub_ids::DEVICE_CONTROL, device_control_ids::GLOBAL_PARAMETER_CONTROL) => {
+                self.process_global_parameter_control(data);
+            },
+            (sub_ids::DEVICE_CONTROL, _) => {
+                // lsb, msb 순서의 14비트 값
+                if data.len() < 2 {
+                    log::error!("Malformed device control message");
+                    return;
+                }
+                let value = combine_lsb_first(&data[0..2]) as i32;
+                match sub_id2 {
+                    device_control_ids::MASTER_VOLUME => self.set_master_volume(value),
+                    device_control_ids::MASTER_BALANCE => self.set_master_pan((value as f64 - 8192.0) / 8192.0),
+                    device_control_ids::MASTER_FINE_TUNING => {
+                        self.set_master_fine_tuning((value as f64 - 8192.0) / 8192.0 * 100.0);
+                    },
+                    // msb만 씀. 40h = 0키
+                    device_control_ids::MASTER_COARSE_TUNING => self.set_master_coarse_tuning((value >> 7) - 64),
+                    _ => log::warn!("Unsupported device control message ({:02X}h)", sub_id2)
+                }
+            },
+            (sub_ids::MIDI_TUNING, _) => self.process_midi_tuning(port, sub_id2, data),
+            (sub_ids::CONTROLLER_DESTINATION, _) => self.process_controller_destination(port, sub_id2, data),
+            (sub_ids::KEY_BASED_INSTRUMENT_CONTROL, 0x01) => self.process_key_based_instrument_control(port, data),
+            _ => log::warn!("Unsupported realtime sysex ({:02X}h {:02X}h)", sub_id1, sub_id2)
+        }
+    }
+
+    /**
+     * global parameter control
+     * 데이터: [slot path 길이] [파라미터 번호 길이] [값 길이] [slot path...] [파라미터 번호, 값]...
+     * reverb(01 01)와 chorus(01 02) slot만 지원함
+     */
+    fn process_global_parameter_control(&mut self, data: &[u8]) {
+        if data.len() < 3 {
+            log::error!("Malformed global parameter control message");
+            return;
+        }
+        let slot_path_len = data[0] as usize;
+        let param_width = data[1] as usize;
+        let value_width = data[2] as usize;
+        let body = &data[3..];
+        if slot_path_len != 1 || !(1..=4).contains(&param_width) || !(1..=4).contains(&value_width) || body.len() < 2 {
+            log::warn!("Unsupported global parameter control slot path");
+            return;
+        }
+
+        let slot = (body[0], body[1]);
+        for pair in body[2..].chunks_exact(param_width + value_width) {
+            // 파라미터 번호는 msb가 먼저 옴
+            let param = pair[0..param_width].iter().fold(0, |acc, b| (acc << 7) | *b as u32);
+            let value = combine_lsb_first(&pair[param_width..]).min(127) as u8;
+            let effects = &mut self.gs_effects;
+            match (slot, param) {
+                (SLOT_REVERB, 0x00) => match gm2_reverb_type_to_gs_macro(value) {
+                    Some(reverb_macro) => effects.set_reverb_macro(reverb_macro),
+                    None => log::warn!("Unsupported reverb type: {}", value)
+                },
+                (SLOT_REVERB, 0x01) => effects.reverb_time = value,
+                (SLOT_CHORUS, 0x00) => effects.set_chorus_macro(value.min(5)),
+                (SLOT_CHORUS, 0x01) => effects.chorus_rate = value,
+                (SLOT_CHORUS, 0x02) => effects.chorus_depth = value,
+                (SLOT_CHORUS, 0x03) => effects.chorus_feedback = value,
+                (SLOT_CHORUS, 0x04) => effects.chorus_send_to_reverb = value,
+                _ => log::warn!("Unsupported global parameter ({:02X} {:02X}, {:02X}h)", slot.0, slot.1, param)
+            }
+        }
+    }
+
+    /**
+     * scale/octave tuning
+     * 데이터: [채널 비트맵 3바이트] [C - B 12개 값(1바이트 형식) 또는 24개 값(2바이트 형식)]
+     * 비트맵: 첫번째 바이트 bit 0 - 1 = 15 - 16번 채널, 두번째 = 8 - 14번, 세번째 = 1 - 7번
+     * 1바이트 형식: 00h - 40h - 7Fh = -64 - 0 - +63cent
+     * 2바이트 형식: msb, lsb 순서의 14비트 값. 0000h - 2000h - 3FFFh = -100 - 0 - +100cent
+     */
+    fn process_midi_tuning(&mut self, port: u8, sub_id2: u8, data: &[u8]) {
+        let bytes_per_note = match sub_id2 {
+            midi_tuning_ids::SCALE_OCTAVE_TUNING_1BYTE => 1,
+            midi_tuning_ids::SCALE_OCTAVE_TUNING_2BYTE => 2,
+            _ => {
+                log::warn!("Unsupported midi tuning message ({:02X}h)", sub_id2);
+                return;
+            }
+        };
+        if data.len() != 3 + 12 * bytes_per_note {
+            log::error!("Malformed scale/octave tuning message");
+            return;
+        }
+
+        let channel_mask = ((data[0] as u32 & 0x03) << 14) | ((data[1] as u32) << 7) | data[2] as u32;
+        let cents: Vec<f64> = data[3..].chunks_exact(bytes_per_note).map(|val| {
+            return if bytes_per_note == 1 {
+                val[0] as f64 - 64.0
+            } else {
+                let val = ((val[0] as u32) << 7) | val[1] as u32;
+                (val as f64 - 8192.0) / 8192.0 * 100.0
+            };
+        }).collect();
+
+        for ch in 0..CHANNELS_PER_PORT {
+            if channel_mask & (1 << ch) == 0 {
+                continue;
+            }
+            let channel = &mut self.channels[port as usize * CHANNELS_PER_PORT + ch];
+            for (note, cent) in cents.iter().enumerate() {
+                channel.set_scale_tuning(note, *cent);
+            }
+        }
+    }
+
+    /**
+     * channel pressure/polyphonic key pressure/controller destination setting
+     * 데이터: 0n [cc 번호(controller일 때만)] [파라미터, 값]...
+     */
+    fn process_controller_destination(&mut self, port: u8, sub_id2: u8, data: &[u8]) {
+        let header_len = if sub_id2 == controller_destination_ids::CONTROLLER { 2 } else { 1 };
+        if data.len() < header_len || data[0] as usize >= CHANNELS_PER_PORT {
+            log::error!("Malformed controller destination message");
+            return;
+        }
+        let channel = &mut self.channels[port as usize * CHANNELS_PER_PORT + data[0] as usize];
+
+        let src = match sub_id2 {
+            controller_destination_ids::CHANNEL_PRESSURE => gs_ctrl_src::CAF,
+            controller_destination_ids::POLY_PRESSURE => gs_ctrl_src::PAF,
+            controller_destination_ids::CONTROLLER => {
+                // 01h - 1Fh, 40h - 5Fh만 쓸 수 있음
+                let cc = data[1];
+                if !matches!(cc, 0x01..=0x1f | 0x40..=0x5f) {
+                    log::warn!("Invalid controller for controller destination: {}", cc);
+                    return;
+                }
+                channel.assign_ctrl_cc(cc)
+            },
+            _ => {
+                log::warn!("Unsupported controller destination message ({:02X}h)", sub_id2);
+                return;
+            }
+        };
+
+        for pair in data[header_len..].chunks_exact(2) {
+            match gm2_ctrl_dest(pair[0]) {
+                Some(dest) => channel.set_ctrl_dest(src, dest, pair[1]),
+                None => log::warn!("Unsupported controller destination parameter: {:02X}h", pair[0])
+            }
+        }
+    }
+
+    /**
+     * key-based instrument control
+     * 데이터: 0n [note 번호] [cc 번호, 값]...
+     * gs 드럼 nrpn과 같은 값으로 저장하므로 드럼 채널에서만 효과가 있음
+     */
+    fn process_key_based_instrument_control(&mut self, port: u8, data: &[u8]) {
+        if data.len() < 2 || data[0] as usize >= CHANNELS_PER_PORT {
+            log::error!("Malformed key-based instrument control message");
+            return;
+        }
+        let channel = &mut self.channels[port as usize * CHANNELS_PER_PORT + data[0] as usize];
+        let note = data[1];
+
+        for pair in data[2..].chunks_exact(2) {
+            match key_based_nrpn_msb(pair[0]) {
+                Some(msb) => channel.set_nrpn_value((msb, note), (pair[1] as u16) << 7),
+                None => log::warn!("Unsupported key-based instrument controller: {}", pair[0])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::SynthMode;
+    use crate::synth::settings::SynthCreateSettings;
+
+    #[test]
+    fn invalid_port_is_ignored() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        synth.handle_port_sysex(2, &[0xf0, 0x7f, 0x7f, 0x09, 0x01, 0x00, 0x00, 0x40, 0xf7]);
+        synth.handle_port_sysex(2, &[0xf0, 0x7f, 0x7f, 0x0a, 0x01, 0x00, 0x24, 0x07, 0x00, 0xf7]);
+        synth.handle_gm_realtime_sysex(2, &[0xf0, 0x7f, 0x7f, 0x09, 0x01, 0x00, 0x00, 0x40, 0xf7]);
+        synth.handle_gm_non_realtime_sysex(5, &[0xf0, 0x7e, 0x7f, 0x08, 0x08, 0x03, 0x7f, 0x7f, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xf7]);
+        assert!((0..32).all(|ch| synth.channel(ch).unwrap().get_scale_tuning_cent(60) == 0.0));
+    }
+
+    #[test]
+    fn device_id_must_match_or_be_broadcast() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        let master_volume = |device_id: u8, msb: u8| [0xf0, 0x7f, device_id, 0x04, 0x01, 0x00, msb, 0xf7];
+
+        // 기본 장치 ID는 10h
+        synth.handle_sysex(&master_volume(0x11, 0x10));
+        assert_eq!(synth.master_volume, 16383);
+        synth.handle_sysex(&master_volume(0x10, 0x20));
+        assert_eq!(synth.master_volume, 0x20 << 7);
+        synth.handle_sysex(&master_volume(0x7f, 0x30));
+        assert_eq!(synth.master_volume, 0x30 << 7);
+
+        // gm system on도 broadcast로 받음
+        synth.handle_sysex(&[0xf0, 0x7e, 0x7f, 0x09, 0x01, 0xf7]);
+        assert_eq!(synth.mode(), SynthMode::GM);
+    }
+
+    #[test]
+    fn scale_octave_tuning() {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        // 1바이트 형식: 포트 B의 1번, 16번 채널. C = -64cent, D = +63cent
+        let mut msg = vec![0xf0, 0x7e, 0x7f, 0x08, 0x08, 0x02, 0x00, 0x01];
+        msg.extend([0x00, 0x40, 0x7f, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40]);
+        msg.push(0xf7);
+        synth.handle_port_sysex(1, &msg);
+        for ch in [16, 31] {
+            let channel = synth.channel(ch).unwrap();
+            assert_eq!(channel.get_scale_tuning_cent(60), -64.0);
+            assert_eq!(channel.get_scale_tuning_cent(74), 63.0);
+            assert_eq!(channel.get_scale_tuning_cent(61), 0.0);
+        }
+        assert_eq!(synth.channel(0).unwrap().get_scale_tuning_cent(60), 0.0);
+        assert_eq!(synth.channel(17).unwrap().get_scale_tuning_cent(60), 0.0);
+
+        // 2바이트 형식(realtime): 포트 A의 8번 채널. C = -100cent, C# = +50cent
+        let mut msg = vec![0xf0, 0x7f, 0x7f, 0x08, 0x09, 0x00, 0x01, 0x00, 0x00, 0x00, 0x60, 0x00];
+        msg.extend([0x40, 0x00].repeat(10));
+        msg.push(0xf7);
+        synth.handle_sysex(&msg);
+        let channel = synth.channel(7).unwrap();
+        assert_eq!(channel.get_scale_tuning_cent(48), -100.0);
+        assert_eq!(channel.get_scale_tuning_cent(49), 50.0);
+        assert_eq!(channel.get_scale_tuning_cent(50), 0.0);
+
+        // 길이가 맞지 않으면 무시함
+        msg[8] = 0x7f;
+        msg.drain((msg.len() - 3)..(msg.len() - 1));
+        synth.handle_sysex(&msg);
+        assert_eq!(synth.channel(7).unwrap().get_scale_tuning_cent(48), -100.0);
+    }
+}
@@ -1,3 +1,718 @@
-pub struct Voice {}
+/**
+ * 발음(voice) 처리
+ * note on 1개가 region 여러 개에 걸쳐 있으면 그 개수만큼 voice가 생김
+ */
 
-pub struct VoiceManager {}
\ No newline at end of file
+use std::f64::consts::{ PI, FRAC_1_SQRT_2 };
+use rayon::prelude::*;
+use rayon::{ ThreadPool, ThreadPoolBuilder };
+use crate::soundbank::wsbk::{ Sample, Articulator };
+use crate::soundbank::wsbk::consts::artc_src;
+use crate::synth::articulator::{ ArticulationUnit, AriculationValues, hz_from_value, ms_from_value };
+use crate::synth::oscillator::SampleOscillator;
+use crate::synth::envelope::{ Envelope, EnvelopeMode };
+use crate::synth::lfo::LFO;
+use crate::synth::effects::filter::Filter;
+use crate::synth::channel::{ Channel, ControllerModulation, MIDI_CHANNELS };
+use crate::synth::settings::{ SynthCreateSettings, VoiceOverflowPriorityScoreSettings };
+use crate::util::from_dbfs;
+
+// 강제로 없앨 때 소리를 줄이는 시간(밀리초)
+// 바로 끊으면 딸깍 소리가 나므로 짧게 페이드 아웃 함
+const KILL_FADE_MS: f64 = 5.0;
+
+// voice가 이보다 적으면 스레드로 나누지 않음(나누는 비용이 더 큼)
+const MIN_VOICES_FOR_PARALLEL: usize = 16;
+
+// 스레드 하나가 한 번에 가져가는 최소 voice 수
+const MIN_VOICES_PER_TASK: usize = 4;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum VoiceStatus {
+    Playing,
+    Released, // note off 이후 release 단계
+    Finished // 소리가 완전히 끝남(곧 제거됨)
+}
+
+/**
+ * voice가 재생할 샘플
+ * 샘플 데이터를 읽는 오실레이터와 재생 속도 계산에 필요한 정보
+ */
+pub struct VoiceSample {
+    pub oscillator: SampleOscillator,
+    pub base_key: u8,
+    pub cent_correction: i8,
+    pub sample_rate: u32
+}
+
+impl VoiceSample {
+    pub fn from_wsbk(sample: &Sample) -> Self {
+        return Self {
+            oscillator: SampleOscillator::new(sample),
+            base_key: sample.base_key,
+            cent_correction: sample.cent_correction,
+            sample_rate: sample.sample_rate
+        };
+    }
+}
+
+pub struct Voice {
+    // 이 voice를 만든 채널과 건반 번호(note off를 받을 때 씀)
+    pub(crate) channel_no: u8,
+    pub(crate) key: u8,
+
+    // key shift 등을 반영해서 실제로 소리를 내는 note 번호
+    pub(crate) note: u8,
+    velocity: u8,
+
+    // 샘플의 기본 key
+    root_key: u8,
+
+    // 0이 아니면 같은 채널에서 같은 값의 voice가 새로 시작할 때 없앰
+    pub(crate) exclusive_class: i32,
+
+    // 폴리포닉 애프터터치 값
+    note_aftertouch: u8,
+
+    status: VoiceStatus,
+
+    // note off를 받았는지 여부
+    // 서스테인/소스테누토 페달 때문에 note off를 받고도 Playing 상태일 수 있음
+    key_released: bool,
+
+    // 소스테누토 페달을 밟았을 때 눌려 있던 voice인지 여부
+    sostenuto_held: bool,
+
+    // 최소 길이(샘플 단위)
+    // 이보다 일찍 note off를 받으면 release를 미뤄 뒀다가 이 시간이 지나면 release함
+    min_length: u64,
+    release_pending: bool,
+
+    // 강제로 없애는 중인지 여부와 페이드 아웃 진행 상태(1.0 => 0.0)
+    killed: bool,
+    kill_fade_level: f64,
+
+    // 가장 최근에 렌더링한 샘플의 음량(0.0 - 1.0)
+    current_level: f64,
+
+    // 초당 샘플 수
+    sample_rate: f64,
+
+    // 재생이 시작된 후 지난 시간(샘플 단위)
+    age: u64,
+
+    oscillator: SampleOscillator,
+
+    // 샘플의 기본 키와 피치 보정값을 반영한, 재생 속도 계산의 기준이 되는 값
+    // (note 번호 - 기본 키) * 100 + 피치 보정(cent 단위)
+    base_pitch_cent: f64,
+
+    // 샘플의 초당 샘플 수 / 출력 초당 샘플 수
+    sample_rate_ratio: f64,
+
+    // note on 시점에 계산이 끝난 값
+    base_values: AriculationValues,
+
+    // 렌더링할 때마다 다시 계산해야 하는 articulator
+    dynamic_unit: ArticulationUnit,
+
+    // 가장 최근에 계산된 값
+    current_values: AriculationValues,
+
+    volume_env: Envelope,
+    modulation_env: Envelope,
+
+    modulation_lfo: LFO,
+    vibrato_lfo: LFO,
+
+    // lfo가 시작되기까지 남은 시간(샘플 단위)
+    modulation_lfo_delay: f64,
+    vibrato_lfo_delay: f64,
+
+    // 가장 최근의 lfo 값
+    modulation_lfo_val: f64,
+    vibrato_lfo_val: f64,
+
+    lpf_left: Filter,
+    lpf_right: Filter,
+    hpf_left: Filter,
+    hpf_right: Filter,
+
+    // 렌더링 작업용 버퍼
+    buf_left: Vec<f64>,
+    buf_right: Vec<f64>,
+    rendered_len: usize // 마지막 render에서 버퍼에 채운 샘플 수
+}
+
+impl Voice {
+    pub fn new(
+        sample: VoiceSample,
+        articulators: Vec<Articulator>,
+        offsets: &AriculationValues,
+        channel: &Channel,
+        channel_no: u8, key: u8, note: u8, velocity: u8,
+        settings: &SynthCreateSettings
+    ) -> Self {
+        let sample_rate = settings.sample_rate as f64;
+        let buffer_size = settings.render_buffer_size;
+        let (static_unit, dynamic_unit) = ArticulationUnit::split_static(articulators);
+
+        let mut base_values = AriculationValues::new();
+        static_unit.process(&mut base_values, |src| {
+            return match src {
+                artc_src::NOTE_ON_VELOCITY => velocity as f64,
+                artc_src::NOTE_NUMBER => note as f64,
+                artc_src::ROOT_KEY => sample.base_key as f64,
+                _ => 0.0
+            };
+        });
+        base_values.add_values(offsets);
+
+        let mut this = Self {
+            channel_no, key, note, velocity,
+            root_key: sample.base_key,
+            exclusive_class: 0,
+            note_aftertouch: 0,
+            status: VoiceStatus::Playing,
+            key_released: false,
+            sostenuto_held: false,
+            min_length: (settings.min_note_length as f64 / 1000.0 * sample_rate) as u64,
+            release_pending: false,
+            killed: false,
+            kill_fade_level: 1.0,
+            current_level: 0.0,
+            sample_rate,
+            age: 0,
+            oscillator: sample.oscillator,
+            base_pitch_cent: (note as f64 - sample.base_key as f64) * 100.0 + sample.cent_correction as f64,
+            sample_rate_ratio: sample.sample_rate as f64 / sample_rate,
+            base_values,
+            dynamic_unit,
+            current_values: base_values,
+            volume_env: Envelope::new(sample_rate, EnvelopeMode::DLS),
+            modulation_env: Envelope::new(sample_rate, EnvelopeMode::DLS),
+            modulation_lfo: LFO::new(sample_rate),
+            vibrato_lfo: LFO::new(sample_rate),
+            modulation_lfo_delay: 0.0,
+            vibrato_lfo_delay: 0.0,
+            modulation_lfo_val: 0.0,
+            vibrato_lfo_val: 0.0,
+            lpf_left: Filter::new(sample_rate),
+            lpf_right: Filter::new(sample_rate),
+            hpf_left: Filter::new(sample_rate),
+            hpf_right: Filter::new(sample_rate),
+            buf_left: vec![0.0; buffer_size],
+            buf_right: vec![0.0; buffer_size],
+            rendered_len: 0
+        };
+
+        // envelope/lfo 설정은 note on 시점의 값으로 고정
+        this.update_values(channel);
+        let values = this.current_values;
+
+        this.volume_env.set_delay_time(ms_from_value(values.volume_env_delay));
+        this.volume_env.set_attack_time(ms_from_value(values.volume_env_attack));
+        this.volume_env.set_hold_time(ms_from_value(values.volume_env_hold));
+        this.volume_env.set_decay_time(ms_from_value(values.volume_env_decay));
+        this.volume_env.set_sustain_level(values.volume_env_sustain as f64 / 10000.0);
+        this.volume_env.set_release_time(ms_from_value(values.volume_env_release));
+
+        this.modulation_env.set_delay_time(ms_from_value(values.modulation_env_delay));
+        this.modulation_env.set_attack_time(ms_from_value(values.modulation_env_attack));
+        this.modulation_env.set_hold_time(ms_from_value(values.modulation_env_hold));
+        this.modulation_env.set_decay_time(ms_from_value(values.modulation_env_decay));
+        this.modulation_env.set_sustain_level(values.modulation_env_sustain as f64 / 10000.0);
+        this.modulation_env.set_release_time(ms_from_value(values.modulation_env_release));
+
+        this.modulation_lfo_delay = ms_from_value(values.modulation_lfo_start_delay) / 1000.0 * sample_rate;
+        this.vibrato_lfo_delay = ms_from_value(values.vibrato_lfo_start_delay) / 1000.0 * sample_rate;
+
+        this.volume_env.attack();
+        this.modulation_env.attack();
+
+        return this;
+    }
+
+    pub fn status(&self) -> VoiceStatus {
+        return self.status;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.status == VoiceStatus::Finished;
+    }
+
+    // 재생된 시간(초 단위)
+    pub fn age_secs(&self) -> f64 {
+        return self.age as f64 / self.sample_rate;
+    }
+
+    pub fn set_note_aftertouch(&mut self, pressure: u8) {
+        self.note_aftertouch = pressure;
+    }
+
+    // note off를 받았지만 페달 때문에 소리가 유지되고 있는지 여부
+    pub fn is_sustained(&self) -> bool {
+        return self.key_released && !self.release_pending && self.status == VoiceStatus::Playing;
+    }
+
+    // note off를 받았을 때 호출
+    // 페달이 눌려 있으면 note off를 받았다는 것만 기록해 둠
+    pub fn key_off(&mut self, sustain: bool) {
+        self.key_released = true;
+        if !sustain && !self.sostenuto_held {
+            self.release_after_min_length();
+        }
+    }
+
+    // 서스테인/소스테누토 페달 상태가 바뀌었을 때 호출
+    pub fn update_pedal(&mut self, sustain: bool) {
+        if self.key_released && !sustain && !self.sostenuto_held {
+            self.release_after_min_length();
+        }
+    }
+
+    // 최소 길이가 지났으면 바로 release, 아니면 render에서 최소 길이가 지났을 때 release함
+    fn release_after_min_length(&mut self) {
+        if self.age < self.min_length {
+            self.release_pending = true;
+        } else {
+            self.release();
+        }
+    }
+
+    pub fn set_sostenuto_held(&mut self, held: bool) {
+        self.sostenuto_held = held;
+    }
+
+    // 지금 음량(0.0 - 1.0)
+    pub fn current_level(&self) -> f64 {
+        return self.current_level;
+    }
+
+    pub fn is_killed(&self) -> bool {
+        return self.killed;
+    }
+
+    // 짧게 페이드 아웃 한 다음 없앰
+    pub fn kill(&mut self) {
+        self.killed = true;
+    }
+
+    // 페달과 관계없이 바로 release
+    pub fn release(&mut self) {
+        if self.status != VoiceStatus::Playing {
+            return;
+        }
+        self.volume_env.release();
+        self.modulation_env.release();
+        self.oscillator.release();
+        self.status = VoiceStatus::Released;
+    }
+
+    // source의 현재 값
+    fn get_src_val(&self, src: u32, channel: &Channel) -> f64 {
+        if let Some(val) = channel.get_src_val(src) {
+            return val;
+        }
+        return match src {
+            artc_src::NOTE_ON_VELOCITY => self.velocity as f64,
+            artc_src::NOTE_NUMBER => self.note as f64,
+            artc_src::ROOT_KEY => self.root_key as f64,
+            artc_src::VOLUME_ENV => self.volume_env.get_level(),
+            artc_src::MODULATION_ENV => self.modulation_env.get_level(),
+            artc_src::NOTE_AFTERTOUCH => self.note_aftertouch as f64,
+            artc_src::MODULATION_LFO => self.modulation_lfo_val,
+            artc_src::VIBRATO_LFO => self.vibrato_lfo_val,
+            _ => 0.0
+        };
+    }
+
+    // 매번 바뀔 수 있는 articulator를 다시 계산
+    fn update_values(&mut self, channel: &Channel) {
+        let mut values = self.base_values;
+        if !self.dynamic_unit.is_empty() {
+            self.dynamic_unit.process(&mut values, |src| self.get_src_val(src, channel));
+        }
+        values.add_values(channel.get_part_offsets());
+        self.current_values = values;
+    }
+
+    // lfo를 count개 샘플만큼 진행시키고 값을 갱신
+    fn process_lfo(&mut self, count: usize, ctrl: &ControllerModulation) {
+        let values = self.current_values;
+
+        if self.modulation_lfo_delay > 0.0 {
+            self.modulation_lfo_delay -= count as f64;
+        } else {
+            self.modulation_lfo.set_frequency((hz_from_value(values.modulation_lfo_freq) + ctrl.lfo2_rate_hz).max(0.0));
+            self.modulation_lfo_val = self.modulation_lfo.triangle() / (PI / 2.0);
+            self.modulation_lfo.advance(count - 1);
+        }
+
+        if self.vibrato_lfo_delay > 0.0 {
+            self.vibrato_lfo_delay -= count as f64;
+        } else {
+            self.vibrato_lfo.set_frequency((hz_from_value(values.vibrato_lfo_freq) + ctrl.lfo1_rate_hz).max(0.0));
+            self.vibrato_lfo_val = self.vibrato_lfo.triangle() / (PI / 2.0);
+            self.vibrato_lfo.advance(count - 1);
+        }
+    }
+
+    // tvf_cent: 컨트롤러 destination 등으로 바뀐 lpf cutoff 변화량(cent 단위)
+    fn update_filters(&mut self, tvf_cent: f64) {
+        let values = self.current_values;
+
+        let lpf_freq = hz_from_value(values.lpf_cutoff) * 2.0_f64.powf(tvf_cent / 1200.0);
+        if lpf_freq >= self.sample_rate * 0.45 {
+            self.lpf_left.clear();
+            self.lpf_right.clear();
+        } else {
+            let q = from_dbfs(values.lpf_q as f64 / 100.0) * FRAC_1_SQRT_2;
+            self.lpf_left.low_pass(lpf_freq, q);
+            self.lpf_right.low_pass(lpf_freq, q);
+        }
+
+        let hpf_freq = hz_from_value(values.hpf_cutoff);
+        if hpf_freq < 10.0 {
+            self.hpf_left.clear();
+            self.hpf_right.clear();
+        } else {
+            let q = from_dbfs(values.hpf_q as f64 / 100.0) * FRAC_1_SQRT_2;
+            self.hpf_left.high_pass(hpf_freq.min(self.sample_rate * 0.45), q);
+            self.hpf_right.high_pass(hpf_freq.min(self.sample_rate * 0.45), q);
+        }
+    }
+
+    /**
+     * 이 voice의 소리를 len 샘플만큼 자체 버퍼에 렌더링함(출력 버퍼에 더하는 건 mix에서 함)
+     * 다른 voice와 상관없이 처리되므로 여러 스레드에서 voice별로 나눠서 돌려도 됨
+     * 버퍼 전체를 하나의 블록으로 보고 피치/필터 등은 블록 시작 시점의 값을 사용함
+     * master_pitch_cent: master tuning 등 모든 voice에 공통으로 적용되는 피치 변화량
+     */
+    pub fn render(&mut self, len: usize, channel: &Channel, master_pitch_cent: f64) {
+        self.rendered_len = 0;
+        if self.status == VoiceStatus::Finished || len == 0 {
+            return;
+        }
+        if self.buf_left.len() < len {
+            self.buf_left.resize(len, 0.0);
+            self.buf_right.resize(len, 0.0);
+        }
+
+        // 미뤄 둔 release 처리(블록 단위로 처리하므로 최대 블록 길이만큼 늦어질 수 있음)
+        // 그 사이에 페달을 밟았으면 페달을 뗄 때 release함
+        if self.release_pending && self.age >= self.min_length {
+            self.release_pending = false;
+            if !channel.is_sustain_on() && !self.sostenuto_held {
+                self.release();
+            }
+        }
+
+        let ctrl = channel.get_controller_modulation(self.note_aftertouch);
+        self.process_lfo(len, &ctrl);
+        self.update_values(channel);
+        self.update_filters(ctrl.tvf_cent
+            + self.vibrato_lfo_val * ctrl.lfo1_tvf_cent
+            + self.modulation_lfo_val * ctrl.lfo2_tvf_cent);
+
+        let values = self.current_values;
+        let pitch_cent = self.base_pitch_cent + values.pitch as f64 / 10.0
+            + channel.get_pitch_offset_cent() + master_pitch_cent + ctrl.pitch_cent
+            + self.vibrato_lfo_val * (
+                values.vibrato_lfo_pitch as f64 / 10.0 + channel.get_modulation_depth_cent() + ctrl.lfo1_pitch_cent
+            )
+            + self.modulation_lfo_val * ctrl.lfo2_pitch_cent;
+        self.oscillator.set_increment(2.0_f64.powf(pitch_cent / 1200.0) * self.sample_rate_ratio);
+        self.oscillator.update_stream(len);
+
+        // lfo tva depth: lfo 값이 -1.0일 때 depth만큼 음량이 줄어듦
+        let tremolo = (1.0 - ctrl.lfo1_tva * (1.0 - self.vibrato_lfo_val) / 2.0)
+            * (1.0 - ctrl.lfo2_tva * (1.0 - self.modulation_lfo_val) / 2.0);
+        let gain = from_dbfs(values.gain as f64 / 100.0) * channel.get_gain()
+            * (1.0 + ctrl.amplitude).max(0.0) * tremolo.max(0.0);
+        let kill_fade_step = 1000.0 / (KILL_FADE_MS * self.sample_rate);
+        let mut amp = 0.0;
+        for i in 0..len {
+            let (l, r) = self.oscillator.next_frame();
+            self.volume_env.process(1);
+            self.modulation_env.process(1);
+            amp = self.volume_env.get_log_scale_level() * gain;
+            if self.killed {
+                self.kill_fade_level = (self.kill_fade_level - kill_fade_step).max(0.0);
+                amp *= self.kill_fade_level;
+            }
+            self.buf_left[i] = l * amp;
+            self.buf_right[i] = r * amp;
+        }
+        self.current_level = amp;
+
+        let buf_left = &mut self.buf_left[0..len];
+        let buf_right = &mut self.buf_right[0..len];
+        self.lpf_left.process(buf_left);
+        self.hpf_left.process(buf_left);
+        if self.oscillator.is_stereo() {
+            self.lpf_right.process(buf_right);
+            self.hpf_right.process(buf_right);
+        }
+
+        let pan = (values.pan as f64 / 10000.0 + channel.get_pan()).max(-1.0).min(1.0);
+        if self.oscillator.is_stereo() {
+            // stereo 샘플은 balance 방식으로 처리
+            let pan_left = (1.0 - pan).min(1.0);
+            let pan_right = (1.0 + pan).min(1.0);
+            for i in 0..len {
+                buf_left[i] *= pan_left;
+                buf_right[i] *= pan_right;
+            }
+        } else {
+            // mono 샘플은 constant power 방식으로 처리
+            let angle = (pan + 1.0) / 2.0 * (PI / 2.0);
+            let pan_left = angle.cos();
+            let pan_right = angle.sin();
+            for i in 0..len {
+                buf_right[i] = buf_left[i] * pan_right;
+                buf_left[i] *= pan_left;
+            }
+        }
+
+        self.rendered_len = len;
+        self.age += len as u64;
+        if self.volume_env.is_finished() || self.oscillator.is_finished() || self.kill_fade_level <= 0.0 {
+            self.status = VoiceStatus::Finished;
+        }
+    }
+
+    // 마지막으로 render한 결과를 left/right 버퍼에 더함
+    pub fn mix(&self, left: &mut [f64], right: &mut [f64]) {
+        let len = self.rendered_len.min(left.len()).min(right.len());
+        for i in 0..len {
+            left[i] += self.buf_left[i];
+            right[i] += self.buf_right[i];
+        }
+    }
+}
+
+/**
+ * 동시 발음 수 관련 통계
+ * 라즈베리파이 같은 환경에서 최대 동시 발음 수를 정할 때 참고용
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct VoiceStats {
+    // 최대 동시 발음 수를 넘어서 강제로 없앤 voice 수
+    pub steal_count: u64,
+
+    // 채널별로 강제로 없앤 voice 수
+    pub steal_count_per_channel: [u64; MIDI_CHANNELS],
+
+    // 가장 많았던 동시 발음 수
+    pub peak_voices: usize
+}
+
+impl VoiceStats {
+    pub fn new() -> Self {
+        return Self {
+            steal_count: 0,
+            steal_count_per_channel: [0; MIDI_CHANNELS],
+            peak_voices: 0
+        };
+    }
+}
+
+impl Default for VoiceStats {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/**
+ * 최대 동시 발음 수를 초과했을 때 없앨 voice를 고르기 위한 우선순위 점수
+ * 계산 방법은 VoiceOverflowPriorityScoreSettings 참조
+ */
+pub fn get_priority_score(voice: &Voice, is_drum: bool, settings: &VoiceOverflowPriorityScoreSettings) -> f64 {
+    // 막 시작한 voice는 시간이 0초이므로 1ms로 취급함
+    let mut score = settings.age / voice.age_secs().max(0.001);
+    score += settings.volume * voice.current_level();
+    if is_drum {
+        score += settings.percussion;
+    }
+    if voice.is_sustained() {
+        score += settings.sustained;
+    } else if voice.status() == VoiceStatus::Released {
+        score += settings.released;
+    }
+    return score;
+}
+
+/**
+ * voice 목록 관리
+ */
+pub struct VoiceManager {
+    voices: Vec<Voice>,
+
+    // 최대 동시 발음 수
+    polyphony: usize,
+
+    stats: VoiceStats,
+
+    // voice 렌더링을 나눠서 처리할 스레드 풀(max_worker_threads가 1이면 없음)
+    worker_pool: Option<ThreadPool>
+}
+
+impl VoiceManager {
+    pub fn new(polyphony: usize, max_worker_threads: usize) -> Self {
+        let worker_pool = if max_worker_threads > 1 {
+            ThreadPoolBuilder::new()
+                .num_threads(max_worker_threads)
+                .thread_name(|i| format!("whitesynth-worker-{}", i))
+                .build()
+                .map_err(|e| log::error!("Failed to create worker threads: {}", e))
+                .ok()
+        } else {
+            None
+        };
+
+        return Self {
+            voices: Vec::with_capacity(polyphony),
+            polyphony,
+            stats: VoiceStats::new(),
+            worker_pool
+        };
+    }
+
+    pub fn active_voices(&self) -> usize {
+        return self.voices.len();
+    }
+
+    pub fn stats(&self) -> &VoiceStats {
+        return &self.stats;
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = VoiceStats::new();
+    }
+
+    /**
+     * voice를 추가함
+     * 최대 동시 발음 수를 넘으면 우선순위 점수가 가장 낮은 voice를 없앰
+     * 없애는 중인(페이드 아웃 중인) voice는 동시 발음 수에 넣지 않음
+     */
+    pub fn start_voice(&mut self, voice: Voice, channels: &[Channel], settings: &VoiceOverflowPriorityScoreSettings) {
+        let mut playing = self.voices.iter().filter(|voice| !voice.is_killed()).count();
+        while playing >= self.polyphony {
+            let victim = self.voices.iter_mut()
+                .filter(|voice| !voice.is_killed())
+                .map(|voice| {
+                    let is_drum = channels[voice.channel_no as usize].is_drum;
+                    let score = get_priority_score(voice, is_drum, settings);
+                    (voice, score)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let victim = match victim {
+                Some((victim, _)) => victim,
+                None => break
+            };
+
+            victim.kill();
+            self.stats.steal_count += 1;
+            self.stats.steal_count_per_channel[victim.channel_no as usize] += 1;
+            playing -= 1;
+        }
+
+        self.voices.push(voice);
+        self.stats.peak_voices = self.stats.peak_voices.max(playing + 1);
+    }
+
+    pub fn key_off(&mut self, channel_no: u8, note: u8, channel: &Channel) {
+        let sustain = channel.is_sustain_on();
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no && voice.key == note && !voice.key_released {
+                voice.key_off(sustain);
+            }
+        }
+    }
+
+    // 해당 채널의 모든 note에 note off를 보냄(all notes off)
+    pub fn key_off_all(&mut self, channel_no: u8, channel: &Channel) {
+        let sustain = channel.is_sustain_on();
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no && !voice.key_released {
+                voice.key_off(sustain);
+            }
+        }
+    }
+
+    // 해당 채널에서 exclusive class가 같은 voice를 짧게 페이드 아웃 하고 없앰
+    pub fn kill_exclusive(&mut self, channel_no: u8, exclusive_class: i32) {
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no && voice.exclusive_class == exclusive_class {
+                voice.kill();
+            }
+        }
+    }
+
+    // 해당 채널의 모든 소리를 즉시 없앰(all sound off)
+    pub fn kill_all(&mut self, channel_no: u8) {
+        self.voices.retain(|voice| voice.channel_no != channel_no);
+    }
+
+    // 서스테인 페달을 뗐을 때 등
+    pub fn update_pedal(&mut self, channel_no: u8, channel: &Channel) {
+        let sustain = channel.is_sustain_on();
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no {
+                voice.update_pedal(sustain);
+            }
+        }
+    }
+
+    // 소스테누토 페달을 밟으면 그 순간 건반이 눌려 있는 voice만 유지됨
+    pub fn sostenuto_on(&mut self, channel_no: u8) {
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no && !voice.key_released && voice.status == VoiceStatus::Playing {
+                voice.set_sostenuto_held(true);
+            }
+        }
+    }
+
+    pub fn sostenuto_off(&mut self, channel_no: u8, channel: &Channel) {
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no {
+                voice.set_sostenuto_held(false);
+            }
+        }
+        self.update_pedal(channel_no, channel);
+    }
+
+    pub fn note_aftertouch(&mut self, channel_no: u8, note: u8, pressure: u8) {
+        for voice in self.voices.iter_mut() {
+            if voice.channel_no == channel_no && voice.key == note {
+                voice.set_note_aftertouch(pressure);
+            }
+        }
+    }
+
+    /**
+     * 모든 voice를 렌더링해서 left/right 버퍼에 더함
+     * voice별 렌더링은 스레드 풀에 나눠서 처리하고, 더하는 건 항상 voice 순서대로 이 스레드에서 함
+     * 그래서 스레드 수와 상관없이 결과가 비트 단위로 같음
+     */
+    pub fn render(&mut self, left: &mut [f64], right: &mut [f64], channels: &[Channel], master_pitch_cent: f64) {
+        let len = left.len().min(right.len());
+        let render_voice = |voice: &mut Voice| {
+            voice.render(len, &channels[voice.channel_no as usize], master_pitch_cent);
+        };
+
+        match &self.worker_pool {
+            Some(pool) if self.voices.len() >= MIN_VOICES_FOR_PARALLEL => {
+                let voices = &mut self.voices;
+                pool.install(|| voices.par_iter_mut().with_min_len(MIN_VOICES_PER_TASK).for_each(render_voice));
+            },
+            _ => self.voices.iter_mut().for_each(render_voice)
+        }
+
+        for voice in self.voices.iter() {
+            voice.mix(left, right);
+        }
+        self.voices.retain(|voice| !voice.is_finished());
+    }
+}
@@ -0,0 +1,286 @@
+/**
+ * 자체 확장(wsn) sysex 처리
+ * 미디 파일 안에서 whitesynth에만 있는 기능을 제어하기 위한 것
+ * 형식은 roland gs sysex를 따라함(체크섬 계산 방법도 같음)
+ *
+ * 메세지 형식: F0 7D [장치 ID] 57 [명령 ID] [주소 3바이트] [데이터...] [체크섬] F7
+ * - 57h: 모델 ID('W')
+ * - 명령 ID: 12h = DT1(데이터 보내기), 11h = RQ1(데이터 요청. 데이터 = 크기 3바이트)
+ *   RQ1을 받으면 sysex 답장 함수(set_sysex_reply_callback)로 DT1 메세지를 보냄
+ * - 여러 바이트로 된 값은 7비트씩 나눠서 상위 바이트부터 보냄
+ *   파라미터 1개는 항상 한꺼번에 보내야 함(중간부터 보내거나 일부만 보내면 무시함)
+ *
+ * 주소표
+ * | 주소       | 크기 | 내용                                                                 |
+ * |------------|------|----------------------------------------------------------------------|
+ * | 00 00 00   | 1    | wsn reset(데이터 00)                                                 |
+ * | 00 01 00   | 3    | 출력 게인(0.001 단위. 0 - 20000)                                     |
+ * | 00 01 03   | 1    | 장치 ID(0 - 126)                                                     |
+ * | 00 02 00   | 3    | 동시 발음 수 초과 시 점수: age (아래는 모두 100000h를 0으로 하는 정수) |
+ * | 00 02 03   | 3    | 〃 volume                                                            |
+ * | 00 02 06   | 3    | 〃 percussion                                                        |
+ * | 00 02 09   | 3    | 〃 released                                                          |
+ * | 00 02 0C   | 3    | 〃 sustained                                                         |
+ * | 00 03 00   | 2    | 사운드뱅크 불러오기(번호. 쓰기 전용. 다 불러온 다음 블록부터 쓰임)   |
+ * | 00 03 7F   | 1    | 불러온 사운드뱅크 모두 빼기(데이터 00. 쓰기 전용)                    |
+ * | 10 uu 00   | 3    | variation effect uu번(0 - 31)의 FXType                               |
+ * | 10 uu 10+2n| 2    | variation effect uu번의 n번 파라미터(n = 0 - 31, 0 - 16383)          |
+ * | cc uu 00   | 3    | 채널(cc = 20h + 채널 번호 0 - 31)의 multi effect uu번(0 - 15) FXType  |
+ * | cc uu 10+2n| 2    | 채널의 multi effect uu번의 n번 파라미터                              |
+ */
+
+use super::{ Synth, FXType, FX_PARAMS, VARIATION_FX_UNITS, MFX_UNITS_PER_CHANNEL };
+use super::gs::{ build_dt1_reply, checksum, split_addr, to_addr, CMD_DT1, CMD_RQ1, MAX_REPLY_SIZE };
+
+pub const MODEL_WSN: u8 = 0x57;
+
+// 3바이트 정수 값에서 0으로 취급하는 값
+const SIGNED_OFFSET: i32 = 0x100000;
+
+// 7비트씩 나뉜 값을 합침(msb가 먼저 옴)
+fn decode_value(bytes: &[u8]) -> i32 {
+    return bytes.iter().fold(0, |acc, b| (acc << 7) | (*b as i32 & 0x7f));
+}
+
+fn encode_value(value: i32, width: usize) -> Vec<u8> {
+    return (0..width).rev().map(|i| ((value >> (i * 7)) & 0x7f) as u8).collect();
+}
+
+// 이펙트 유닛 안에서의 주소(세번째 바이트)에 해당하는 파라미터 크기
+fn fx_param_width(offset: u8) -> Option<usize> {
+    return match offset {
+        0x00 => Some(3),
+        0x10..=0x4f if offset & 1 == 0 => Some(2),
+        _ => None
+    };
+}
+
+// 주소에 해당하는 파라미터 크기. 파라미터의 시작 주소가 아니면 None
+fn param_width(addr: (u8, u8, u8)) -> Option<usize> {
+    return match addr {
+        (0x00, 0x00, 0x00) => Some(1),
+        (0x00, 0x01, 0x00) => Some(3),
+        (0x00, 0x01, 0x03) => Some(1),
+        (0x00, 0x02, 0x00 | 0x03 | 0x06 | 0x09 | 0x0c) => Some(3),
+        (0x00, 0x03, 0x00) => Some(2),
+        (0x00, 0x03, 0x7f) => Some(1),
+        (0x10, unit, offset) if (unit as usize) < VARIATION_FX_UNITS => fx_param_width(offset),
+        (0x20..=0x3f, unit, offset) if (unit as usize) < MFX_UNITS_PER_CHANNEL => fx_param_width(offset),
+        _ => None
+    };
+}
+
+// 사운드뱅크를 불러오거나(번호) 모두 빼는 메세지
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SoundbankMessage {
+    Load(u16),
+    Clear
+}
+
+/**
+ * 사운드뱅크 불러오기(00 03 00)/모두 빼기(00 03 7F) DT1 메세지인지 확인
+ * 미디 파일 재생 위치를 옮길 때 이 메세지를 그대로 다시 보내지 않기 위해 씀(체크섬은 확인하지 않음)
+ */
+pub fn soundbank_message(msg: &[u8]) -> Option<SoundbankMessage> {
+    if msg.len() < 11 || msg[1] != 0x7d || msg[3] != MODEL_WSN || msg[4] != CMD_DT1 || msg[5..7] != [0x00, 0x03] {
+        return None;
+    }
+    return match msg[7] {
+        0x00 if msg.len() >= 12 => Some(SoundbankMessage::Load(decode_value(&msg[8..10]) as u16)),
+        0x7f => Some(SoundbankMessage::Clear),
+        _ => None
+    };
+}
+
+impl Synth {
+    pub(crate) fn process_wsn_sysex(&mut self, msg: &[u8]) {
+        // F0 7D dev model cmd addr addr addr (데이터 최소 1바이트) sum F7
+        if msg.len() < 11 || msg[0] != 0xf0 || msg[msg.len() - 1] != 0xf7 {
+            log::error!("Malformed wsn sysex message");
+            return;
+        }
+
+        let device_id = msg[2];
+        if device_id != 0x7f && device_id != self.settings.device_id {
+            return;
+        }
+        if msg[3] != MODEL_WSN {
+            log::warn!("Unsupported wsn sysex model id: {:02X}h", msg[3]);
+            return;
+        }
+
+        let command_id = msg[4];
+        let body = &msg[5..(msg.len() - 2)];
+        let sum = msg[msg.len() - 2];
+        if body.iter().chain(std::iter::once(&sum)).any(|b| *b >= 0x80) {
+            log::error!("Malformed wsn sysex message");
+            return;
+        }
+        if checksum(body) != sum {
+            log::error!("Wsn sysex checksum mismatch (expected {:02X}h, got {:02X}h)", checksum(body), sum);
+            return;
+        }
+
+        let addr = to_addr(&body[0..3]);
+        let data = &body[3..];
+        match command_id {
+            CMD_DT1 => {
+                let mut pos = 0;
+                while pos < data.len() {
+                    let param_addr = addr + pos as u32;
+                    let width = match param_width(split_addr(param_addr)) {
+                        Some(width) if pos + width <= data.len() => width,
+                        _ => {
+                            log::error!("Invalid wsn parameter address or size: {:06X}h", param_addr);
+                            return;
+                        }
+                    };
+                    self.write_wsn_param(split_addr(param_addr), &data[pos..(pos + width)]);
+                    pos += width;
+                }
+            },
+            CMD_RQ1 => {
+                if data.len() != 3 {
+                    log::error!("Malformed wsn RQ1 message");
+                    return;
+                }
+                self.reply_wsn_params(device_id, addr, to_addr(data) as usize);
+            },
+            _ => log::warn!("Unsupported wsn sysex command: {:02X}h", command_id)
+        }
+    }
+
+    fn write_wsn_param(&mut self, addr: (u8, u8, u8), data: &[u8]) {
+        let value = decode_value(data);
+        match addr {
+            (0x00, 0x00, 0x00) => self.wsn_reset(),
+            (0x00, 0x01, 0x00) => self.settings.set_output_gain(value as f64 / 1000.0),
+            (0x00, 0x01, 0x03) => self.settings.set_device_id(value as u8),
+            (0x00, 0x02, offset) => {
+                let score = (value - SIGNED_OFFSET) as f64;
+                let overflow = &mut self.settings.overflow;
+                match offset {
+                    0x00 => overflow.age = score,
+                    0x03 => overflow.volume = score,
+                    0x06 => overflow.percussion = score,
+                    0x09 => overflow.released = score,
+                    _ => overflow.sustained = score
+                }
+            },
+            (0x00, 0x03, 0x00) => self.load_soundbank(value as u16),
+            (0x00, 0x03, 0x7f) => self.clear_soundbanks(),
+            (0x10, unit, offset) => {
+                if offset == 0x00 {
+                    self.set_variation_fx_type(unit, FXType(data[0], data[1], data[2]));
+                } else {
+                    self.set_variation_fx_parameter(unit, (offset as i32 - 0x10) / 2, value);
+                }
+            },
+            (channel, unit, offset) => {
+                let channel_no = channel - 0x20;
+                if offset == 0x00 {
+                    self.set_mfx_type(channel_no, unit, FXType(data[0], data[1], data[2]));
+                } else {
+                    self.set_mfx_parameter(channel_no, unit, (offset as i32 - 0x10) / 2, value);
+                }
+            }
+        }
+    }
+
+    // 주소에 해당하는 현재 값. 읽을 수 없는 주소면 None
+    fn read_wsn_param(&self, addr: (u8, u8, u8)) -> Option<Vec<u8>> {
+        let width = param_width(addr)?;
+        let fx_value = |fx_type: &FXType, params: &[i32; FX_PARAMS], offset: u8| {
+            return if offset == 0x00 {
+                vec![fx_type.0, fx_type.1, fx_type.2]
+            } else {
+                encode_value(params[(offset as usize - 0x10) / 2], width)
+            };
+        };
+
+        let overflow = &self.settings.overflow;
+        let value = match addr {
+            (0x00, 0x01, 0x00) => (self.settings.output_gain * 1000.0).round() as i32,
+            (0x00, 0x01, 0x03) => self.settings.device_id as i32,
+            (0x00, 0x02, 0x00) => overflow.age.round() as i32 + SIGNED_OFFSET,
+            (0x00, 0x02, 0x03) => overflow.volume.round() as i32 + SIGNED_OFFSET,
+            (0x00, 0x02, 0x06) => overflow.percussion.round() as i32 + SIGNED_OFFSET,
+            (0x00, 0x02, 0x09) => overflow.released.round() as i32 + SIGNED_OFFSET,
+            (0x00, 0x02, 0x0c) => overflow.sustained.round() as i32 + SIGNED_OFFSET,
+            (0x10, unit, offset) => {
+                let fx = &self.variation_fx[unit as usize];
+                return Some(fx_value(&fx.fx_type, &fx.params, offset));
+            },
+            (channel @ 0x20..=0x3f, unit, offset) => {
+                let fx = &self.mfx[(channel - 0x20) as usize * MFX_UNITS_PER_CHANNEL + unit as usize];
+                return Some(fx_value(&fx.fx_type, &fx.params, offset));
+            },
+            // 쓰기 전용
+            _ => return None
+        };
+        return Some(encode_value(value.max(0).min((1 << (width * 7)) - 1), width));
+    }
+
+    // RQ1에 대한 답장(DT1)을 보냄
+    // 요청한 범위 중 앞에서부터 읽을 수 있는 데까지만 보냄
+    fn reply_wsn_params(&mut self, device_id: u8, addr: u32, size: usize) {
+        let mut data = vec![];
+        while data.len() < size.min(MAX_REPLY_SIZE) {
+            match self.read_wsn_param(split_addr(addr + data.len() as u32)) {
+                Some(value) => data.extend(value),
+                None => break
+            }
+        }
+        if data.is_empty() {
+            log::warn!("Unsupported wsn parameter address for RQ1: {:06X}h", addr);
+            return;
+        }
+
+        let device_id = if device_id == 0x7f { self.settings.device_id } else { device_id };
+        let reply = build_dt1_reply(0x7d, MODEL_WSN, device_id, addr, &data);
+        self.send_sysex_reply(&reply);
+    }
+
+    // 사운드뱅크 불러오기 함수(set_soundbank_loader)로 사운드뱅크를 불러오도록 요청함
+    // 파일을 읽는 동안 기다리지 않도록 백그라운드 스레드에서 불러오고, render할 때 블록 경계에서 추가함
+    // 이미 불러왔거나 불러오는 중인 번호면 무시함
+    fn load_soundbank(&mut self, index: u16) {
+        match self.soundbank_loader.as_mut() {
+            Some(loader) => loader.request(index),
+            None => log::warn!("Soundbank loader is not set")
+        }
+    }
+
+    // wsn sysex로 마지막으로 모두 뺀 다음에 불러왔거나 불러오는 중인 사운드뱅크 번호(요청한 순서)
+    pub fn requested_soundbanks(&self) -> &[u16] {
+        return self.soundbank_loader.as_ref().map_or(&[], |loader| loader.requested());
+    }
+
+    // wsn sysex로 불러온 사운드뱅크만 뺌(add_soundbank로 추가한 것은 그대로 둠)
+    // 아직 불러오는 중인 사운드뱅크도 추가하지 않음
+    fn clear_soundbanks(&mut self) {
+        let Some(loader) = self.soundbank_loader.as_mut() else {
+            return;
+        };
+        loader.cancel_pending();
+        let mut i = 0;
+        while i < self.soundbanks.len() {
+            if self.soundbanks[i].1 {
+                let (soundbank, _) = self.soundbanks.remove(i);
+                loader.discard(soundbank);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    // 다 불러온 사운드뱅크를 추가함(블록을 렌더링하기 전에 부름)
+    pub(crate) fn install_loaded_soundbanks(&mut self) {
+        let Some(loader) = self.soundbank_loader.as_mut() else {
+            return;
+        };
+        while let Some(soundbank) = loader.pop_loaded() {
+            self.soundbanks.push((soundbank.into(), true));
+        }
+    }
+}
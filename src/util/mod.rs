@@ -1,4 +1,5 @@
 pub mod interpolation;
+pub mod midi;
 
 /** dBFS => 원래 값으로 변환 */
 pub fn from_dbfs(dbfs: f64) -> f64 {
@@ -0,0 +1,189 @@
+/**
+ * 채널 상태(cc, program, rpn/nrpn 등) 확인
+ */
+
+use whitesynth::synth::Synth;
+use whitesynth::synth::channel::{ gs_nrpn_ids, rpn_ids, ParamSelect };
+use whitesynth::synth::settings::SynthCreateSettings;
+
+fn new_synth() -> Synth {
+    return Synth::new(SynthCreateSettings::new());
+}
+
+#[test]
+fn messages_are_routed_by_port() {
+    let mut synth = new_synth();
+    synth.handle_port_midi_message(0, &[0xb2, 7, 50]);
+    synth.handle_port_midi_message(1, &[0xb2, 7, 90]);
+    synth.handle_port_midi_message(1, &[0xb0, 0, 8]);
+    synth.handle_port_midi_message(1, &[0xc0, 5]);
+    synth.handle_port_midi_message(1, &[0xe0, 0x00, 0x60]);
+
+    // 포트 B의 채널 n은 n + 16번 채널
+    assert_eq!(synth.channel(2).unwrap().cc(7), 50);
+    assert_eq!(synth.channel(18).unwrap().cc(7), 90);
+    assert_eq!(synth.channel(16).unwrap().program(), 5);
+    assert_eq!(synth.channel(16).unwrap().bank(), (8, 0));
+    assert_eq!(synth.channel(16).unwrap().pitch_bend(), 0x60 << 7);
+    assert_eq!(synth.channel(0).unwrap().program(), 0);
+
+    // 없는 포트로 들어온 메세지는 무시함
+    synth.handle_port_midi_message(2, &[0xb0, 7, 1]);
+    assert!((0..32).all(|ch| synth.channel(ch).unwrap().cc(7) != 1));
+    assert!(synth.channel(32).is_none());
+}
+
+#[test]
+fn tenth_channel_of_each_port_is_drum() {
+    let synth = new_synth();
+    let drums: Vec<u8> = (0..32).filter(|ch| synth.channel(*ch).unwrap().is_drum()).collect();
+    assert_eq!(drums, vec![9, 25]);
+}
+
+#[test]
+fn bank_select_applies_on_program_change() {
+    let mut synth = new_synth();
+    synth.handle_midi_message(&[0xb0, 0, 8]);
+    synth.handle_midi_message(&[0xb0, 32, 2]);
+    assert_eq!(synth.channel(0).unwrap().bank(), (0, 0));
+    synth.handle_midi_message(&[0xc0, 10]);
+    assert_eq!(synth.channel(0).unwrap().bank(), (8, 2));
+}
+
+#[test]
+fn rpn_data_entry() {
+    let mut synth = new_synth();
+    // rpn 0(pitch bend sensitivity) = 12키 50cent
+    synth.handle_midi_message(&[0xb0, 101, 0]);
+    synth.handle_midi_message(&[0xb0, 100, 0]);
+    synth.handle_midi_message(&[0xb0, 6, 12]);
+    synth.handle_midi_message(&[0xb0, 38, 50]);
+    let channel = synth.channel(0).unwrap();
+    assert_eq!(channel.param_select(), ParamSelect::Rpn);
+    assert_eq!(channel.rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY), Some((12 << 7) | 50));
+    assert_eq!(channel.get_pitch_bend_sensitivity(), 1250.0);
+
+    // increment/decrement는 값을 1씩 바꿈
+    synth.handle_midi_message(&[0xb0, 96, 0]);
+    synth.handle_midi_message(&[0xb0, 96, 0]);
+    synth.handle_midi_message(&[0xb0, 97, 0]);
+    assert_eq!(synth.channel(0).unwrap().rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY), Some((12 << 7) | 51));
+
+    // data entry msb는 lsb를 0으로 만듦
+    synth.handle_midi_message(&[0xb0, 6, 2]);
+    assert_eq!(synth.channel(0).unwrap().get_pitch_bend_sensitivity(), 200.0);
+}
+
+#[test]
+fn null_rpn_ignores_data_entry() {
+    let mut synth = new_synth();
+    synth.handle_midi_message(&[0xb0, 101, 0]);
+    synth.handle_midi_message(&[0xb0, 100, 0]);
+    synth.handle_midi_message(&[0xb0, 101, 0x7f]);
+    synth.handle_midi_message(&[0xb0, 100, 0x7f]);
+    synth.handle_midi_message(&[0xb0, 6, 24]);
+    synth.handle_midi_message(&[0xb0, 96, 0]);
+    let channel = synth.channel(0).unwrap();
+    assert_eq!(channel.rpn_value(rpn_ids::PITCH_BEND_SENSITIVITY), Some(2 << 7));
+    assert_eq!(channel.rpn_value(rpn_ids::NULL), None);
+
+    // 아무것도 선택하지 않은 상태(reset all controllers 뒤)에서도 마찬가지
+    synth.handle_midi_message(&[0xb0, 121, 0]);
+    synth.handle_midi_message(&[0xb0, 6, 24]);
+    assert_eq!(synth.channel(0).unwrap().param_select(), ParamSelect::None);
+    assert_eq!(synth.channel(0).unwrap().get_pitch_bend_sensitivity(), 200.0);
+}
+
+#[test]
+fn rpn_and_nrpn_are_selected_separately() {
+    let mut synth = new_synth();
+    synth.handle_midi_message(&[0xb0, 101, 0]);
+    synth.handle_midi_message(&[0xb0, 100, 2]);
+    // nrpn을 선택하면 data entry는 nrpn으로 감
+    synth.handle_midi_message(&[0xb0, 99, 0x30]);
+    synth.handle_midi_message(&[0xb0, 98, 0x10]);
+    synth.handle_midi_message(&[0xb0, 6, 70]);
+    // rpn lsb만 다시 보내도 이전에 보낸 rpn msb와 합쳐서 선택됨
+    synth.handle_midi_message(&[0xb0, 100, 2]);
+    synth.handle_midi_message(&[0xb0, 6, 66]);
+
+    let channel = synth.channel(0).unwrap();
+    assert_eq!(channel.nrpn_value((0x30, 0x10)), Some(70 << 7));
+    assert_eq!(channel.rpn_value(rpn_ids::COARSE_TUNING), Some(66 << 7));
+    assert_eq!(channel.get_pitch_offset_cent(), 200.0);
+}
+
+#[test]
+fn standard_rpns_change_pitch_and_modulation() {
+    let mut synth = new_synth();
+    let rpn = |synth: &mut Synth, param: (u8, u8), msb: u8, lsb: u8| {
+        synth.handle_midi_message(&[0xb0, 101, param.0]);
+        synth.handle_midi_message(&[0xb0, 100, param.1]);
+        synth.handle_midi_message(&[0xb0, 6, msb]);
+        synth.handle_midi_message(&[0xb0, 38, lsb]);
+    };
+    rpn(&mut synth, rpn_ids::FINE_TUNING, 0x50, 0);
+    rpn(&mut synth, rpn_ids::COARSE_TUNING, 62, 0);
+    rpn(&mut synth, rpn_ids::MODULATION_DEPTH_RANGE, 1, 64);
+    synth.handle_midi_message(&[0xe0, 0x7f, 0x7f]);
+    synth.handle_midi_message(&[0xb0, 1, 127]);
+
+    // bend +200cent(약간 모자람), fine +25cent, coarse -200cent
+    let channel = synth.channel(0).unwrap();
+    let expected = 8191.0 / 8192.0 * 200.0 + 25.0 - 200.0;
+    assert!((channel.get_pitch_offset_cent() - expected).abs() < 1e-9);
+    assert_eq!(channel.get_modulation_depth_cent(), 150.0);
+}
+
+fn nrpn(synth: &mut Synth, channel: u8, param: (u8, u8), msb: u8) {
+    synth.handle_midi_message(&[0xb0 | channel, 99, param.0]);
+    synth.handle_midi_message(&[0xb0 | channel, 98, param.1]);
+    synth.handle_midi_message(&[0xb0 | channel, 6, msb]);
+}
+
+#[test]
+fn gs_nrpn_part_offsets() {
+    let mut synth = new_synth();
+    nrpn(&mut synth, 0, gs_nrpn_ids::VIBRATO_RATE, 0x50);
+    nrpn(&mut synth, 0, gs_nrpn_ids::VIBRATO_DEPTH, 0x20);
+    // 0Eh - 72h를 벗어나는 값은 범위 안으로 맞춤
+    nrpn(&mut synth, 0, gs_nrpn_ids::TVF_CUTOFF, 0x7f);
+    nrpn(&mut synth, 0, gs_nrpn_ids::TVA_ENV_ATTACK, 0x00);
+    nrpn(&mut synth, 0, gs_nrpn_ids::TVA_ENV_RELEASE, 0x40);
+
+    let offsets = synth.channel(0).unwrap().get_part_offsets();
+    assert_eq!(offsets.vibrato_lfo_freq, 5000);
+    assert_eq!(offsets.vibrato_lfo_pitch, -500);
+    assert_eq!(offsets.lpf_cutoff, 31250);
+    assert_eq!(offsets.volume_env_attack, -31250);
+    assert_eq!(offsets.volume_env_release, 0);
+    assert_eq!(offsets.volume_env_decay, 0);
+
+    // 다른 채널에는 영향이 없음
+    assert_eq!(synth.channel(1).unwrap().get_part_offsets().lpf_cutoff, 0);
+}
+
+#[test]
+fn gs_nrpn_drum_offsets() {
+    let mut synth = new_synth();
+    nrpn(&mut synth, 9, (gs_nrpn_ids::DRUM_PITCH_COARSE, 36), 0x44);
+    nrpn(&mut synth, 9, (gs_nrpn_ids::DRUM_TVA_LEVEL, 36), 0);
+    nrpn(&mut synth, 9, (gs_nrpn_ids::DRUM_PANPOT, 36), 0);
+    nrpn(&mut synth, 9, (gs_nrpn_ids::DRUM_PANPOT, 38), 127);
+    nrpn(&mut synth, 9, (gs_nrpn_ids::DRUM_REVERB_SEND, 38), 0);
+
+    let channel = synth.channel(9).unwrap();
+    let kick = channel.get_drum_offsets(36, 0.75);
+    assert_eq!(kick.pitch, 4000);
+    assert_eq!(kick.gain, -14400);
+    // panpot 0은 random
+    assert_eq!(kick.pan, 5000);
+    let snare = channel.get_drum_offsets(38, 0.75);
+    assert_eq!((snare.pitch, snare.gain, snare.pan), (0, 0, 10000));
+    assert_eq!(snare.reverb_send_coeff, -10000);
+    assert_eq!(channel.get_drum_offsets(40, 0.75).pan, 0);
+
+    // 드럼 채널이 아니면 드럼 파라미터는 쓰지 않음
+    nrpn(&mut synth, 0, (gs_nrpn_ids::DRUM_PITCH_COARSE, 36), 0x44);
+    assert_eq!(synth.channel(0).unwrap().get_drum_offsets(36, 0.75).pitch, 0);
+}
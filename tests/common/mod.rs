@@ -0,0 +1,218 @@
+#![allow(dead_code)]
+/**
+ * 테스트용 sf2 파일을 메모리에서 만듦
+ * SF2::write가 쓰는 것과 같은 순서와 형식으로 만들기 때문에 읽고 다시 저장하면 바이트 단위로 같아야 함
+ */
+
+use whitesynth::soundbank::sf2::gen_ids::*;
+
+// 샘플 종류(sfSampleType)
+pub const MONO: u16 = 0x0001;
+pub const RIGHT: u16 = 0x0002;
+pub const LEFT: u16 = 0x0004;
+
+pub struct TestSample {
+    pub name: &'static str,
+    pub data: Vec<i16>,
+    // 샘플 시작 기준 루프 위치
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub root_key: u8,
+    pub linked_sample_index: u16,
+    pub sample_type: u16
+}
+
+#[derive(Clone, Default)]
+pub struct TestZone {
+    // (operator, amount)
+    pub generators: Vec<(u16, i16)>,
+    // (src, dest, amount, amount src, transform)
+    pub modulators: Vec<(u16, u16, i16, u16, u16)>
+}
+
+pub struct TestBank {
+    pub samples: Vec<TestSample>,
+    pub instruments: Vec<(&'static str, Vec<TestZone>)>,
+    // (이름, program, bank, zone)
+    pub presets: Vec<(&'static str, u16, u16, Vec<TestZone>)>,
+    // 있으면 sm24 청크를 만듦(샘플마다 하위 8비트를 이 함수로 정함)
+    pub sm24: Option<fn(usize) -> u8>
+}
+
+// key/velocity 범위 generator 값
+pub fn range(low: u8, high: u8) -> i16 {
+    return i16::from_le_bytes([low, high]);
+}
+
+pub fn zone(generators: &[(u16, i16)]) -> TestZone {
+    return TestZone { generators: generators.to_vec(), modulators: vec![] };
+}
+
+// 샘플 rate 48000에서 주파수 freq인 사인파
+pub fn sine(len: usize, freq: f64, amplitude: f64) -> Vec<i16> {
+    return (0..len).map(|i| ((i as f64 * freq * 2.0 * std::f64::consts::PI / 48000.0).sin() * amplitude) as i16).collect();
+}
+
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut result = id.to_vec();
+    result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    result.extend_from_slice(data);
+    if data.len() % 2 != 0 {
+        result.push(0);
+    }
+    return result;
+}
+
+fn list(id: &[u8; 4], list_type: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = list_type.to_vec();
+    for child in chunks {
+        data.extend_from_slice(child);
+    }
+    return chunk(id, &data);
+}
+
+fn name(text: &str) -> Vec<u8> {
+    let mut result = text.as_bytes().to_vec();
+    result.resize(20, 0);
+    return result;
+}
+
+// zone 목록을 bag, generator, modulator 레코드로 바꿈(끝을 나타내는 레코드 포함)
+// 헤더마다 첫 bag 위치를 돌려줌(마지막은 끝을 나타내는 헤더용)
+fn zone_records(headers: &[&[TestZone]]) -> (Vec<u16>, Vec<u8>, Vec<u8>, Vec<u8>) {
+    let (mut bag_indexes, mut bags, mut gens, mut mods) = (vec![], vec![], vec![], vec![]);
+    let push_bag = |bags: &mut Vec<u8>, gens: &[u8], mods: &[u8]| {
+        bags.extend_from_slice(&((gens.len() / 4) as u16).to_le_bytes());
+        bags.extend_from_slice(&((mods.len() / 10) as u16).to_le_bytes());
+    };
+    for zones in headers {
+        bag_indexes.push((bags.len() / 4) as u16);
+        for zone in zones.iter() {
+            push_bag(&mut bags, &gens, &mods);
+            for (operator, amount) in zone.generators.iter() {
+                gens.extend_from_slice(&operator.to_le_bytes());
+                gens.extend_from_slice(&amount.to_le_bytes());
+            }
+            for (src, dest, amount, amount_src, transform) in zone.modulators.iter() {
+                mods.extend_from_slice(&src.to_le_bytes());
+                mods.extend_from_slice(&dest.to_le_bytes());
+                mods.extend_from_slice(&amount.to_le_bytes());
+                mods.extend_from_slice(&amount_src.to_le_bytes());
+                mods.extend_from_slice(&transform.to_le_bytes());
+            }
+        }
+    }
+    bag_indexes.push((bags.len() / 4) as u16);
+    push_bag(&mut bags, &gens, &mods);
+    gens.extend_from_slice(&[0; 4]);
+    mods.extend_from_slice(&[0; 10]);
+    return (bag_indexes, bags, gens, mods);
+}
+
+impl TestBank {
+    pub fn build(&self) -> Vec<u8> {
+        // 샘플마다 뒤에 무음 46개를 붙임
+        let mut smpl: Vec<i16> = vec![];
+        let mut shdr = vec![];
+        for sample in self.samples.iter() {
+            let start = smpl.len() as u32;
+            smpl.extend_from_slice(&sample.data);
+            smpl.extend_from_slice(&[0; 46]);
+            shdr.extend(name(sample.name));
+            for val in [start, start + sample.data.len() as u32, start + sample.loop_start, start + sample.loop_end, 48000] {
+                shdr.extend_from_slice(&val.to_le_bytes());
+            }
+            shdr.push(sample.root_key);
+            shdr.push(0);
+            shdr.extend_from_slice(&sample.linked_sample_index.to_le_bytes());
+            shdr.extend_from_slice(&sample.sample_type.to_le_bytes());
+        }
+        shdr.extend(name("EOS"));
+        shdr.extend_from_slice(&[0; 26]);
+
+        let instrument_zones: Vec<&[TestZone]> = self.instruments.iter().map(|(_, zones)| zones.as_slice()).collect();
+        let (ibag_indexes, ibag, igen, imod) = zone_records(&instrument_zones);
+        let mut inst = vec![];
+        for (i, instrument_name) in self.instruments.iter().map(|(name, _)| *name).chain(["EOI"]).enumerate() {
+            inst.extend(name(instrument_name));
+            inst.extend_from_slice(&ibag_indexes[i].to_le_bytes());
+        }
+
+        let preset_zones: Vec<&[TestZone]> = self.presets.iter().map(|(_, _, _, zones)| zones.as_slice()).collect();
+        let (pbag_indexes, pbag, pgen, pmod) = zone_records(&preset_zones);
+        let mut phdr = vec![];
+        let headers = self.presets.iter().map(|(name, program, bank, _)| (*name, *program, *bank)).chain([("EOP", 0, 0)]);
+        for (i, (preset_name, program, bank)) in headers.enumerate() {
+            phdr.extend(name(preset_name));
+            phdr.extend_from_slice(&program.to_le_bytes());
+            phdr.extend_from_slice(&bank.to_le_bytes());
+            phdr.extend_from_slice(&pbag_indexes[i].to_le_bytes());
+            phdr.extend_from_slice(&[0; 12]);
+        }
+
+        let version: [u16; 2] = if self.sm24.is_some() { [2, 4] } else { [2, 1] };
+        let ifil: Vec<u8> = version.iter().flat_map(|val| val.to_le_bytes()).collect();
+        let info = list(b"LIST", b"INFO", &[
+            chunk(b"ifil", &ifil),
+            chunk(b"isng", b"EMU8000\0"),
+            chunk(b"INAM", b"Test\0\0")
+        ]);
+
+        let smpl_bytes: Vec<u8> = smpl.iter().flat_map(|val| val.to_le_bytes()).collect();
+        let mut sdta_chunks = vec![chunk(b"smpl", &smpl_bytes)];
+        if let Some(low) = self.sm24 {
+            let mut sm24: Vec<u8> = (0..smpl.len()).map(low).collect();
+            if sm24.len() % 2 != 0 {
+                sm24.push(0);
+            }
+            sdta_chunks.push(chunk(b"sm24", &sm24));
+        }
+        let sdta = list(b"LIST", b"sdta", &sdta_chunks);
+
+        let pdta = list(b"LIST", b"pdta", &[
+            chunk(b"phdr", &phdr),
+            chunk(b"pbag", &pbag),
+            chunk(b"pmod", &pmod),
+            chunk(b"pgen", &pgen),
+            chunk(b"inst", &inst),
+            chunk(b"ibag", &ibag),
+            chunk(b"imod", &imod),
+            chunk(b"igen", &igen),
+            chunk(b"shdr", &shdr)
+        ]);
+        return list(b"RIFF", b"sfbk", &[info, sdta, pdta]);
+    }
+}
+
+/**
+ * 여러 테스트에서 같이 쓰는 사운드뱅크
+ * - preset 0: mono 사인파(루프), preset 1: stereo(왼쪽/오른쪽 zone), bank 128 preset 0: 드럼
+ */
+pub fn basic_bank() -> TestBank {
+    return TestBank {
+        samples: vec![
+            TestSample { name: "Sine", data: sine(4800, 440.0, 12000.0), loop_start: 0, loop_end: 4800 - 4800 % 109, root_key: 69, linked_sample_index: 0, sample_type: MONO },
+            TestSample { name: "Left", data: sine(2400, 440.0, 10000.0), loop_start: 0, loop_end: 0, root_key: 69, linked_sample_index: 2, sample_type: LEFT },
+            TestSample { name: "Right", data: sine(2400, 660.0, 6000.0), loop_start: 0, loop_end: 0, root_key: 69, linked_sample_index: 1, sample_type: RIGHT }
+        ],
+        instruments: vec![
+            ("Sine", vec![
+                zone(&[(ATTACK_VOL_ENV, -7200), (RELEASE_VOL_ENV, -2400)]),
+                zone(&[(SAMPLE_MODES, 1), (SAMPLE_ID, 0)])
+            ]),
+            ("Stereo", vec![
+                zone(&[(KEY_RANGE, range(0, 127)), (PAN, -500), (SAMPLE_ID, 1)]),
+                zone(&[(KEY_RANGE, range(0, 127)), (PAN, 500), (SAMPLE_ID, 2)])
+            ])
+        ],
+        presets: vec![
+            ("Sine", 0, 0, vec![zone(&[(INSTRUMENT, 0)])]),
+            ("Stereo", 1, 0, vec![
+                TestZone { generators: vec![], modulators: vec![(0x0081, 8, -1200, 0, 0)] },
+                zone(&[(INSTRUMENT, 1)])
+            ]),
+            ("Drums", 0, 128, vec![zone(&[(KEY_RANGE, range(35, 81)), (INSTRUMENT, 0)])])
+        ],
+        sm24: None
+    };
+}
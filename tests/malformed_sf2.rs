@@ -0,0 +1,102 @@
+/**
+ * 잘리거나 망가진 sf2 파일을 읽어도 panic이 나지 않는지 확인
+ * 읽기에 실패하는 건 괜찮음(오류를 돌려주면 됨)
+ */
+
+mod common;
+
+use std::io::Cursor;
+use std::panic::{ catch_unwind, AssertUnwindSafe };
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::soundbank::sf2::bank::SF2Bank;
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+// 항상 같은 순서로 나오는 난수(xorshift)
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0 as usize;
+    }
+}
+
+fn fixtures() -> Vec<Vec<u8>> {
+    let mut sm24 = common::basic_bank();
+    sm24.sm24 = Some(|i| (i * 37 % 256) as u8);
+    return vec![common::basic_bank().build(), sm24.build()];
+}
+
+// 읽기, 변환, 저장, 연주를 모두 해 봄
+fn exercise(bytes: &[u8]) {
+    let Ok(sf2) = SF2::new(&mut Cursor::new(bytes.to_vec())) else {
+        return;
+    };
+    let wsbk = sf2.to_wsbk();
+    let bank = SF2Bank::new(&sf2);
+    let _ = sf2.write(&mut Cursor::new(vec![]));
+
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(wsbk);
+    synth.add_soundbank(bank);
+    let mut left = vec![0.0; 256];
+    let mut right = vec![0.0; 256];
+    for (channel, program) in [(0, 0), (0, 1), (9, 0)] {
+        synth.handle_midi_message(&[0xc0 | channel, program]);
+        synth.handle_midi_message(&[0x90 | channel, 60, 100]);
+        synth.render(&mut left, &mut right);
+    }
+}
+
+fn check(bytes: &[u8], what: &str) {
+    let result = catch_unwind(AssertUnwindSafe(|| exercise(bytes)));
+    assert!(result.is_ok(), "panicked on {}", what);
+}
+
+#[test]
+fn truncated_files_do_not_panic() {
+    for (i, fixture) in fixtures().iter().enumerate() {
+        // pdta 청크(파일 끝부분)는 모든 위치에서, 나머지는 띄엄띄엄 자름
+        let pdta = fixture.windows(4).position(|id| id == b"pdta").unwrap();
+        for len in (0..pdta).step_by(97).chain(pdta..fixture.len()) {
+            check(&fixture[0..len], &format!("fixture {} truncated to {} bytes", i, len));
+        }
+    }
+}
+
+#[test]
+fn mutated_files_do_not_panic() {
+    let mut random = Random(0x5eed_1234_abcd_0001);
+    for (i, fixture) in fixtures().iter().enumerate() {
+        // 바꾸는 위치는 대부분 pdta 청크 안에서 고름(샘플 데이터를 바꿔서는 찾을 게 없음)
+        let pdta = fixture.windows(4).position(|id| id == b"pdta").unwrap();
+        for iteration in 0..1500 {
+            let mut bytes = fixture.clone();
+            for _ in 0..(1 + random.next() % 8) {
+                let pos = if random.next() % 4 == 0 {
+                    random.next() % bytes.len()
+                } else {
+                    pdta + random.next() % (bytes.len() - pdta)
+                };
+                match random.next() % 4 {
+                    0 => bytes[pos] = random.next() as u8,
+                    1 => bytes[pos] ^= 1 << (random.next() % 8),
+                    2 => bytes[pos] = [0x00, 0xff, 0x7f, 0x80][random.next() % 4],
+                    _ => {
+                        // 16비트 값(index, 개수 등)을 아무 값으로 바꿈
+                        let val = random.next() as u16;
+                        for (k, byte) in val.to_le_bytes().into_iter().enumerate() {
+                            if let Some(target) = bytes.get_mut(pos + k) {
+                                *target = byte;
+                            }
+                        }
+                    }
+                }
+            }
+            check(&bytes, &format!("fixture {} mutation {}", i, iteration));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/**
+ * 시간이 지정된 미디 메세지 대기열 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use std::thread;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::synth::Synth;
+use whitesynth::synth::midi_queue::MIDI_QUEUE_CAPACITY;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+fn new_synth() -> Synth {
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(&SF2::new(&mut Cursor::new(common::basic_bank().build())).unwrap());
+    return synth;
+}
+
+fn render(synth: &mut Synth, len: usize) -> Vec<f64> {
+    let mut left = vec![0.0; len];
+    let mut right = vec![0.0; len];
+    synth.render(&mut left, &mut right);
+    return left;
+}
+
+// 300샘플 지점부터 350, 1234샘플 지점에 note on을 예약한 결과
+fn expected_output() -> Vec<f64> {
+    let mut synth = new_synth();
+    synth.schedule_midi_message(350, &[0x90, 69, 127]);
+    synth.schedule_port_midi_message(1234, 1, &[0x91, 60, 100]);
+    render(&mut synth, 300);
+    return render(&mut synth, 2000);
+}
+
+#[test]
+fn queued_messages_use_next_render_position() {
+    let mut synth = new_synth();
+    render(&mut synth, 300);
+    synth.queue_midi_message(50, &[0x90, 69, 127]);
+    synth.queue_port_midi_message(934, 1, &[0x91, 60, 100]);
+    assert!(render(&mut synth, 2000) == expected_output());
+}
+
+#[test]
+fn messages_from_other_thread_are_applied_at_frame_offset() {
+    let mut synth = new_synth();
+    render(&mut synth, 300);
+    let sender = synth.midi_message_sender();
+    let handle = thread::spawn(move || {
+        assert_eq!(sender.frame_position(), 300);
+        assert!(sender.queue_midi_message(50, &[0x90, 69, 127]));
+        assert!(sender.queue_port_midi_message(934, 1, &[0x91, 60, 100]));
+    });
+    handle.join().unwrap();
+
+    let output = render(&mut synth, 2000);
+    assert!(output.iter().any(|val| *val != 0.0));
+    assert!(output == expected_output());
+    assert_eq!(synth.midi_message_sender().frame_position(), 2300);
+}
+
+#[test]
+fn full_queue_rejects_messages() {
+    let mut synth = new_synth();
+    let sender = synth.midi_message_sender();
+    for i in 0..MIDI_QUEUE_CAPACITY {
+        assert!(sender.queue_midi_message(i as u32, &[0xb0, 7, 100]), "message {}", i);
+    }
+    assert!(!sender.queue_midi_message(0, &[0x90, 60, 100]));
+    assert!(!sender.clone().queue_port_midi_message(0, 1, &[0x90, 60, 100]));
+
+    // render에서 대기열을 비우면 다시 넣을 수 있음
+    render(&mut synth, 64);
+    assert!(sender.queue_midi_message(0, &[0x90, 60, 100]));
+}
@@ -0,0 +1,208 @@
+/**
+ * 미디 파일 재생기의 조옮김, 위치 옮기기, 템포 배율, 구간 반복 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use whitesynth::midi::player::{ LoopPoints, Player, PlayerState };
+use whitesynth::midi::smf::MidiFile;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+const SAMPLE_RATE: usize = 48000;
+
+// 4분음표 = 96틱, 기본 템포(120bpm)에서 1틱 = 1/192초
+const DIVISION: u16 = 96;
+
+const GS_RESET: [u8; 12] = [0xf0, 0x0a, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7f, 0x00, 0x41, 0xf7];
+
+fn var_len(mut val: u32) -> Vec<u8> {
+    let mut bytes = vec![(val & 0x7f) as u8];
+    val >>= 7;
+    while val > 0 {
+        bytes.insert(0, (val & 0x7f) as u8 | 0x80);
+        val >>= 7;
+    }
+    return bytes;
+}
+
+// (틱, 이벤트) 목록으로 트랙 1개짜리 format 0 미디 파일을 만듦. end_tick에 end of track을 넣음
+fn smf(events: &[(u32, &[u8])], end_tick: u32) -> MidiFile {
+    let mut track = vec![];
+    let mut tick = 0;
+    for (event_tick, event) in events {
+        track.extend(var_len(event_tick - tick));
+        track.extend_from_slice(event);
+        tick = *event_tick;
+    }
+    track.extend(var_len(end_tick - tick));
+    track.extend([0xff, 0x2f, 0x00]);
+
+    let mut data = b"MThd".to_vec();
+    data.extend(6_u32.to_be_bytes());
+    data.extend([0, 0, 0, 1]);
+    data.extend(DIVISION.to_be_bytes());
+    data.extend(b"MTrk");
+    data.extend((track.len() as u32).to_be_bytes());
+    data.extend(track);
+    return MidiFile::from_bytes(&data).unwrap();
+}
+
+fn new_player(midi_file: &MidiFile) -> Player {
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(&SF2::new(&mut Cursor::new(common::basic_bank().build())).unwrap());
+    let mut player = Player::new(synth);
+    player.load(midi_file);
+    return player;
+}
+
+// 480샘플씩 frames만큼 렌더링한 왼쪽 채널
+fn render(player: &mut Player, frames: usize) -> Vec<f64> {
+    let mut output = vec![];
+    let mut left = vec![0.0; 480];
+    let mut right = vec![0.0; 480];
+    for _ in 0..(frames / 480) {
+        player.render(&mut left, &mut right);
+        output.extend_from_slice(&left);
+    }
+    return output;
+}
+
+// 처음으로 0이 아닌 샘플의 위치
+fn onset(output: &[f64]) -> usize {
+    return output.iter().position(|val| *val != 0.0).unwrap();
+}
+
+// 1초 지점에서 note를 누르는 곡. 곡 처음에 gs reset을 보내고 master coarse tuning을 coarse만큼 바꿈
+fn song_with_note(note: u8, coarse: i8) -> MidiFile {
+    let coarse_tuning = [0xf0, 0x07, 0x7f, 0x7f, 0x04, 0x04, 0x00, (coarse + 64) as u8, 0xf7];
+    let note_on = [0x90, note, 100];
+    let note_off = [0x80, note, 0];
+    return smf(&[(0, &GS_RESET), (0, &coarse_tuning), (192, &note_on), (288, &note_off)], 384);
+}
+
+// 0.5초 지점으로 옮긴 다음 1.5초 렌더링한 결과
+fn play_from_half_second(player: &mut Player) -> Vec<f64> {
+    player.play();
+    render(player, 4800);
+    player.seek(0.5);
+    return render(player, SAMPLE_RATE * 3 / 2);
+}
+
+#[test]
+fn transpose_survives_gs_reset_and_seek() {
+    let mut expected = new_player(&song_with_note(71, 0));
+    let expected = play_from_half_second(&mut expected);
+    assert!(expected.iter().any(|val| *val != 0.0));
+
+    // 곡의 gs reset과 seek할 때 하는 gs reset은 조옮김을 되돌리지 않음
+    let mut transposed = new_player(&song_with_note(69, 0));
+    transposed.set_transpose(2);
+    assert!(play_from_half_second(&mut transposed) == expected);
+    assert_eq!(transposed.transpose(), 2);
+
+    // 곡에서 설정한 master coarse tuning에 더해짐
+    let mut combined = new_player(&song_with_note(68, 1));
+    combined.set_transpose(2);
+    assert!(play_from_half_second(&mut combined) == expected);
+
+    // 더한 값은 master coarse tuning 범위(-64 - +63) 안에서 자름
+    let mut wide = new_player(&song_with_note(10, 60));
+    wide.set_transpose(1);
+    assert!(play_from_half_second(&mut wide) == expected);
+}
+
+#[test]
+fn seek_chases_controllers_and_program() {
+    let song = smf(&[
+        (0, &[0xb0, 7, 40]),
+        (0, &[0xb1, 0, 8]),
+        (0, &[0xc1, 5]),
+        (96, &[0x90, 69, 100]),
+        (192, &[0xb0, 7, 90]),
+        (192, &[0xe1, 0x00, 0x50]),
+        (288, &[0x80, 69, 0])
+    ], 384);
+    let mut player = new_player(&song);
+
+    // 0.75초 = 144틱
+    player.seek(0.75);
+    assert_eq!(player.synth().channel(0).unwrap().cc(7), 40);
+    assert_eq!(player.synth().channel(1).unwrap().program(), 5);
+    assert_eq!(player.synth().channel(1).unwrap().bank(), (8, 0));
+    assert_eq!(player.synth().channel(1).unwrap().pitch_bend(), 0x2000);
+    // note on은 다시 보내지 않음
+    assert_eq!(player.synth().active_voices(), 0);
+
+    player.seek(1.25);
+    assert_eq!(player.synth().channel(0).unwrap().cc(7), 90);
+    assert_eq!(player.synth().channel(1).unwrap().pitch_bend(), 0x50 << 7);
+
+    // 앞으로 돌아가면 그 위치의 값으로 되돌림
+    player.seek(0.25);
+    assert_eq!(player.synth().channel(0).unwrap().cc(7), 40);
+    assert_eq!(player.synth().channel(1).unwrap().pitch_bend(), 0x2000);
+    assert_eq!(player.position(), 0.25);
+}
+
+#[test]
+fn tempo_scale_changes_event_timing() {
+    let song = smf(&[(192, &[0x90, 69, 100])], 384);
+    let mut normal = new_player(&song);
+    normal.play();
+    let normal_onset = onset(&render(&mut normal, SAMPLE_RATE * 2));
+
+    let mut fast = new_player(&song);
+    fast.set_tempo_scale(2.0);
+    fast.play();
+    let output = render(&mut fast, SAMPLE_RATE / 2);
+    // 2배 빠르면 0.5초 동안 곡 시간 1초만큼 진행함
+    assert!((fast.position() - 1.0).abs() < 1e-9, "{}", fast.position());
+    assert!(output.iter().all(|val| *val == 0.0));
+    let fast_onset = SAMPLE_RATE / 2 + onset(&render(&mut fast, SAMPLE_RATE / 2));
+    assert_eq!(normal_onset - SAMPLE_RATE, fast_onset - SAMPLE_RATE / 2);
+
+    // 곡 길이를 다 재생하면 멈춤
+    render(&mut fast, SAMPLE_RATE / 2);
+    assert_eq!(fast.state(), PlayerState::Stopped);
+}
+
+#[test]
+fn cc111_loops_back_to_loop_start() {
+    // 0.5초에 cc111, 1초에 누른 note는 끝(2초)까지 떼지 않음
+    // 리버브/코러스 꼬리가 남지 않게 send를 0으로 함
+    let song = smf(&[
+        (0, &[0xb0, 91, 0]),
+        (0, &[0xb0, 93, 0]),
+        (96, &[0xb0, 111, 0]),
+        (192, &[0x90, 69, 100])
+    ], 384);
+    let mut player = new_player(&song);
+    assert_eq!(player.loop_points(), Some(LoopPoints { start_tick: 96, end_tick: 384 }));
+    assert!(player.is_looping());
+
+    player.play();
+    let first = render(&mut player, SAMPLE_RATE * 2);
+    let first_onset = onset(&first);
+
+    // 반복 지점으로 돌아갈 때 누르고 있던 note를 뗌(release 0.25초)
+    let gap = render(&mut player, SAMPLE_RATE * 2 / 5);
+    assert!(gap[(SAMPLE_RATE * 3 / 10)..].iter().all(|val| *val == 0.0));
+    assert_eq!(player.synth().active_voices(), 0);
+    assert!((player.position() - 0.9).abs() < 1e-9, "{}", player.position());
+    assert_eq!(player.state(), PlayerState::Playing);
+
+    // 반복 구간 길이(1.5초)만큼 지나서 같은 소리가 다시 남
+    let second = render(&mut player, SAMPLE_RATE * 3 / 5);
+    let second_onset = onset(&second);
+    assert_eq!(SAMPLE_RATE * 12 / 5 + second_onset, first_onset + SAMPLE_RATE * 3 / 2);
+    let len = SAMPLE_RATE / 10;
+    assert!(first[first_onset..(first_onset + len)] == second[second_onset..(second_onset + len)]);
+
+    // 반복을 끄면 곡 끝에서 멈춤
+    player.set_looping(false);
+    render(&mut player, SAMPLE_RATE * 2);
+    assert_eq!(player.state(), PlayerState::Stopped);
+}
@@ -0,0 +1,103 @@
+/**
+ * render 길이와 상관없이 결과가 같은지, 예약한 메세지가 정확한 위치에 적용되는지 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+// render_buffer_size 기본값
+const BUFFER_SIZE: usize = 128;
+
+fn new_synth() -> Synth {
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(&SF2::new(&mut Cursor::new(common::basic_bank().build())).unwrap());
+    return synth;
+}
+
+// lengths 길이로 차례대로 나눠서 렌더링한 결과(왼쪽, 오른쪽을 이어 붙임)
+fn render_in_pieces(synth: &mut Synth, lengths: &[usize]) -> (Vec<f64>, Vec<f64>) {
+    let (mut output_left, mut output_right) = (vec![], vec![]);
+    for len in lengths {
+        let mut left = vec![0.0; *len];
+        let mut right = vec![0.0; *len];
+        synth.render(&mut left, &mut right);
+        output_left.extend(left);
+        output_right.extend(right);
+    }
+    return (output_left, output_right);
+}
+
+fn schedule_notes(synth: &mut Synth) {
+    synth.schedule_midi_message(100, &[0x90, 60, 100]);
+    synth.schedule_midi_message(1000, &[0xc1, 1]);
+    synth.schedule_midi_message(1001, &[0x91, 64, 90]);
+    synth.schedule_midi_message(3000, &[0xe0, 0x00, 0x50]);
+    synth.schedule_midi_message(5555, &[0x80, 60, 0]);
+    synth.schedule_midi_message(7000, &[0x99, 38, 127]);
+}
+
+#[test]
+fn output_does_not_depend_on_render_length() {
+    let mut lengths = vec![];
+    while lengths.iter().sum::<usize>() < 12000 {
+        lengths.extend([64, 441, 1024, 1, 441, 64]);
+    }
+    let total: usize = lengths.iter().sum();
+
+    let mut whole = new_synth();
+    schedule_notes(&mut whole);
+    let expected = render_in_pieces(&mut whole, &[total]);
+    assert!(expected.0.iter().any(|val| *val != 0.0));
+
+    let mut pieces = new_synth();
+    schedule_notes(&mut pieces);
+    let actual = render_in_pieces(&mut pieces, &lengths);
+    assert!(actual.0 == expected.0 && actual.1 == expected.1);
+    assert_eq!(pieces.frame_position(), total as u64);
+}
+
+// 처음으로 0이 아닌 샘플의 위치
+fn onset(output: &[f64]) -> usize {
+    return output.iter().position(|val| *val != 0.0).unwrap();
+}
+
+#[test]
+fn scheduled_note_on_lands_on_exact_frame() {
+    let mut onsets = vec![];
+    let mut first_samples = vec![];
+    for frame in [1000, 1037, 1100] {
+        let mut synth = new_synth();
+        synth.schedule_midi_message(frame, &[0x90, 69, 127]);
+        let (left, _) = render_in_pieces(&mut synth, &[441, 441, 441, 441]);
+        let start = onset(&left);
+        onsets.push(start - frame as usize);
+        first_samples.push(left[start..(start + 16)].to_vec());
+    }
+
+    // 항상 render_buffer_size만큼 늦게 소리가 나고, 메세지 위치와 상관없이 똑같이 시작함
+    assert!(onsets.iter().all(|offset| *offset == onsets[0]), "{:?}", onsets);
+    assert!((BUFFER_SIZE..(BUFFER_SIZE + 4)).contains(&onsets[0]), "{:?}", onsets);
+    assert!(first_samples.iter().all(|samples| *samples == first_samples[0]));
+}
+
+#[test]
+fn interleaved_output_matches_render() {
+    let mut planar = new_synth();
+    schedule_notes(&mut planar);
+    let (left, right) = render_in_pieces(&mut planar, &[441, 1024, 64]);
+
+    let mut interleaved = new_synth();
+    schedule_notes(&mut interleaved);
+    let mut output = vec![];
+    for len in [64, 1024, 441] {
+        let mut buffer = vec![0.0; len * 2];
+        interleaved.render_as_one_array(&mut buffer);
+        output.extend(buffer);
+    }
+    let expected: Vec<f64> = left.iter().zip(right.iter()).flat_map(|(l, r)| [*l, *r]).collect();
+    assert!(output == expected);
+}
@@ -0,0 +1,147 @@
+/**
+ * SF2::write로 저장한 파일이 읽은 파일과 같은지 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::soundbank::sf2::bank::SF2Bank;
+
+fn read(bytes: &[u8]) -> SF2 {
+    return SF2::new(&mut Cursor::new(bytes.to_vec())).unwrap();
+}
+
+fn write(sf2: &SF2) -> Vec<u8> {
+    let mut stream = Cursor::new(vec![]);
+    sf2.write(&mut stream).unwrap();
+    return stream.into_inner();
+}
+
+fn assert_same_structure(a: &SF2, b: &SF2) {
+    assert_eq!(a.info, b.info);
+    assert_eq!(a.sample_headers, b.sample_headers);
+    assert_eq!(a.instruments, b.instruments);
+    assert_eq!(a.presets, b.presets);
+    assert_eq!(a.is_24bit(), b.is_24bit());
+    let (a_bank, b_bank) = (SF2Bank::new(a), SF2Bank::new(b));
+    assert_eq!(a_bank.sample_data(), b_bank.sample_data());
+    assert_eq!(a_bank.sm24_data(), b_bank.sm24_data());
+}
+
+// 첫 번째 name_chunk 레코드의 이름을 바꿈
+fn set_first_name(bytes: &mut [u8], name_chunk: &[u8; 4], name: &[u8]) {
+    let offset = bytes.windows(4).position(|id| id == name_chunk).unwrap() + 8;
+    bytes[offset..(offset + 20)].fill(0);
+    bytes[offset..(offset + name.len())].copy_from_slice(name);
+}
+
+#[test]
+fn roundtrip_is_byte_identical() {
+    let mut sm24 = common::basic_bank();
+    sm24.sm24 = Some(|i| (i * 37 % 256) as u8);
+    for bank in [common::basic_bank(), sm24] {
+        let bytes = bank.build();
+        let sf2 = read(&bytes);
+        let written = write(&sf2);
+        assert!(written == bytes, "written file differs ({} => {} bytes)", bytes.len(), written.len());
+        assert_same_structure(&sf2, &read(&written));
+    }
+}
+
+#[test]
+fn non_utf8_names_are_kept() {
+    let mut bytes = common::basic_bank().build();
+    // EUC-KR "피아노", Shift_JIS "ピアノ", 20바이트를 다 쓰는 이름
+    let euc_kr: &[u8] = &[0xc7, 0xc7, 0xbe, 0xc6, 0xb3, 0xeb];
+    let shift_jis: &[u8] = &[0x83, 0x73, 0x83, 0x41, 0x83, 0x6d];
+    set_first_name(&mut bytes, b"phdr", euc_kr);
+    set_first_name(&mut bytes, b"inst", shift_jis);
+    set_first_name(&mut bytes, b"shdr", b"TwentyCharacterName!");
+
+    let sf2 = read(&bytes);
+    assert!(sf2.presets[0].name.contains('\u{fffd}'));
+    assert_eq!(sf2.sample_headers[0].name, "TwentyCharacterName!");
+    assert!(write(&sf2) == bytes);
+}
+
+#[test]
+fn edited_names_are_cut_at_char_boundary() {
+    let mut sf2 = read(&common::basic_bank().build());
+    // 한 글자가 3바이트라서 7글자(21바이트)는 6글자(18바이트)만 들어감
+    sf2.presets[0].name = "가나다라마바사".to_owned();
+    sf2.instruments[0].name = "Renamed".to_owned();
+    let reread = read(&write(&sf2));
+    assert_eq!(reread.presets[0].name, "가나다라마바");
+    assert_eq!(reread.instruments[0].name, "Renamed");
+    assert_eq!(reread.presets[1].name, "Stereo");
+}
+
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut result = id.to_vec();
+    result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    result.extend_from_slice(data);
+    if data.len() % 2 != 0 {
+        result.push(0);
+    }
+    return result;
+}
+
+// INFO 리스트를 chunks로 바꿈(common::TestBank::build로 만든 파일은 INFO가 첫 번째 리스트)
+fn replace_info(bytes: &[u8], chunks: &[Vec<u8>]) -> Vec<u8> {
+    let info_len = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+    let mut info = b"INFO".to_vec();
+    for child in chunks {
+        info.extend_from_slice(child);
+    }
+    let mut body = b"sfbk".to_vec();
+    body.extend(chunk(b"LIST", &info));
+    body.extend_from_slice(&bytes[(20 + info_len)..]);
+    return chunk(b"RIFF", &body);
+}
+
+#[test]
+fn roundtrip_keeps_structure_of_other_layouts() {
+    // SF2::write와 순서가 다르고, 길이가 홀수인 문자열(패딩 바이트가 붙음)이 있는 INFO
+    let mut sm24 = common::basic_bank();
+    sm24.sm24 = Some(|i| (i * 37 % 256) as u8);
+    for bank in [common::basic_bank(), sm24] {
+        let version: &[u8] = if bank.sm24.is_some() { &[2, 0, 4, 0] } else { &[2, 0, 1, 0] };
+        let bytes = replace_info(&bank.build(), &[
+            chunk(b"INAM", b"Hand made\0"),
+            chunk(b"ICMT", b"Odd length\0"),
+            chunk(b"ISFT", b"Editor 1.0\0\0"),
+            chunk(b"isng", b"EMU8000\0"),
+            chunk(b"ifil", version)
+        ]);
+        let sf2 = read(&bytes);
+        assert_eq!(sf2.info.bank_name, "Hand made");
+        assert_eq!(sf2.info.comments, "Odd length");
+        assert_eq!(sf2.info.created_software, "Editor 1.0");
+
+        let written = write(&sf2);
+        assert!(written != bytes);
+        let reread = read(&written);
+        assert_same_structure(&sf2, &reread);
+        assert!(write(&reread) == written);
+    }
+}
+
+#[test]
+fn roundtrip_keeps_structure_of_odd_length_sm24() {
+    // smpl 샘플 수가 홀수라서 sm24 청크 길이도 홀수(뒤에 패딩 바이트)
+    let mut bank = common::basic_bank();
+    bank.samples[0].data.truncate(4799);
+    bank.sm24 = Some(|i| (i * 37 % 256) as u8);
+    let mut bytes = bank.build();
+    let sample_count: usize = bank.samples.iter().map(|sample| sample.data.len() + 46).sum();
+    assert_eq!(sample_count % 2, 1);
+    let sm24 = bytes.windows(4).position(|id| id == b"sm24").unwrap();
+    bytes[(sm24 + 4)..(sm24 + 8)].copy_from_slice(&(sample_count as u32).to_le_bytes());
+
+    let sf2 = read(&bytes);
+    assert!(sf2.is_24bit());
+    assert_eq!(SF2Bank::new(&sf2).sm24_data().unwrap().len(), sample_count);
+    let reread = read(&write(&sf2));
+    assert_same_structure(&sf2, &reread);
+}
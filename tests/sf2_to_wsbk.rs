@@ -0,0 +1,266 @@
+/**
+ * sf2 => wsbk 변환 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use common::{ zone, TestBank, TestSample, TestZone, LEFT, RIGHT };
+use whitesynth::soundbank::Soundbank;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::soundbank::sf2::bank::SF2Bank;
+use whitesynth::soundbank::sf2::gen_ids::*;
+use whitesynth::soundbank::wsbk::{ Region, SampleType, WSBK };
+use whitesynth::soundbank::wsbk::consts::region_gen;
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+fn read(bank: &TestBank) -> SF2 {
+    return SF2::new(&mut Cursor::new(bank.build())).unwrap();
+}
+
+// instrument 0 하나만 쓰는 preset 0
+fn bank_with_instrument(zones: Vec<TestZone>) -> TestBank {
+    let mut bank = common::basic_bank();
+    bank.instruments = vec![("Test", zones)];
+    bank.presets = vec![("Test", 0, 0, vec![zone(&[(INSTRUMENT, 0)])])];
+    return bank;
+}
+
+fn render(synth: &mut Synth, blocks: usize) -> Vec<f64> {
+    let mut output = vec![];
+    let mut left = vec![0.0; 256];
+    let mut right = vec![0.0; 256];
+    for _ in 0..blocks {
+        synth.render(&mut left, &mut right);
+        output.extend_from_slice(&left);
+        output.extend_from_slice(&right);
+    }
+    return output;
+}
+
+#[test]
+fn stereo_zones_merge_in_either_order() {
+    let left_first = common::basic_bank();
+    let mut right_first = common::basic_bank();
+    right_first.instruments[1].1.reverse();
+
+    for bank in [left_first, right_first] {
+        let wsbk = read(&bank).to_wsbk();
+        let regions = &wsbk.instruments[1].regions;
+        assert_eq!(regions.len(), 1);
+        let sample = &wsbk.samples[regions[0].target_index as usize];
+        assert!(sample.sample_type == SampleType::Stereo);
+        // 왼쪽 샘플의 데이터가 앞에 옴
+        assert_eq!(&sample.data[0..4], &[bank.samples[1].data[0].to_le_bytes(), bank.samples[2].data[0].to_le_bytes()].concat()[..]);
+    }
+}
+
+#[test]
+fn stereo_pair_with_right_sample_listed_first() {
+    let mut bank = bank_with_instrument(vec![
+        zone(&[(PAN, 500), (SAMPLE_ID, 0)]),
+        zone(&[(PAN, -500), (SAMPLE_ID, 1)])
+    ]);
+    bank.samples = vec![
+        TestSample { name: "R", data: common::sine(1000, 660.0, 6000.0), loop_start: 0, loop_end: 0, root_key: 60, linked_sample_index: 1, sample_type: RIGHT },
+        TestSample { name: "L", data: common::sine(1000, 440.0, 6000.0), loop_start: 0, loop_end: 0, root_key: 60, linked_sample_index: 0, sample_type: LEFT }
+    ];
+    let wsbk = read(&bank).to_wsbk();
+    assert_eq!(wsbk.instruments[0].regions.len(), 1);
+    assert_eq!(wsbk.samples.len(), 1);
+    assert!(wsbk.samples[0].sample_type == SampleType::Stereo);
+}
+
+#[test]
+fn region_generators_are_kept() {
+    let bank = bank_with_instrument(vec![
+        zone(&[(EXCLUSIVE_CLASS, 3), (KEYNUM, 64), (VELOCITY, 90), (SAMPLE_ID, 0)])
+    ]);
+    let wsbk = read(&bank).to_wsbk();
+    let generators = &wsbk.instruments[0].regions[0].generators;
+    assert_eq!(generators.get(&region_gen::EXCLUSIVE_CLASS), Some(&3));
+    assert_eq!(generators.get(&region_gen::KEY_NUMBER), Some(&64));
+    assert_eq!(generators.get(&region_gen::VELOCITY), Some(&90));
+
+    // wsbk 파일로 저장했다가 읽어도 남아 있어야 함
+    let mut file = Cursor::new(vec![]);
+    wsbk.write(&mut file).unwrap();
+    file.set_position(0);
+    let reread = WSBK::read(&mut file).unwrap();
+    assert_eq!(&reread.instruments[0].regions[0].generators, generators);
+}
+
+#[test]
+fn exclusive_class_cuts_previous_note() {
+    for (class, expected_voices) in [(0, 2), (1, 1)] {
+        let bank = bank_with_instrument(vec![
+            zone(&[(KEY_RANGE, common::range(0, 59)), (EXCLUSIVE_CLASS, class), (SAMPLE_MODES, 1), (SAMPLE_ID, 0)]),
+            zone(&[(KEY_RANGE, common::range(60, 127)), (EXCLUSIVE_CLASS, class), (SAMPLE_MODES, 1), (SAMPLE_ID, 0)])
+        ]);
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        synth.add_soundbank(&read(&bank));
+        synth.handle_midi_message(&[0x90, 50, 100]);
+        render(&mut synth, 1);
+        synth.handle_midi_message(&[0x90, 70, 100]);
+        render(&mut synth, 20);
+        assert_eq!(synth.active_voices(), expected_voices, "exclusive class {}", class);
+    }
+}
+
+#[test]
+fn preset_scale_tuning_uses_sample_root_key() {
+    // 기본 key(69)에서는 scale tuning과 상관없이 같은 높이로 소리가 나야 함
+    let mut bank = common::basic_bank();
+    bank.presets = vec![
+        ("Plain", 0, 0, vec![zone(&[(INSTRUMENT, 0)])]),
+        ("Scaled", 1, 0, vec![zone(&[(SCALE_TUNING, 50), (INSTRUMENT, 0)])])
+    ];
+    let sf2 = read(&bank);
+    let outputs: Vec<Vec<f64>> = [0u8, 1].iter().map(|program| {
+        let mut synth = Synth::new(SynthCreateSettings::new());
+        synth.add_soundbank(&sf2);
+        synth.handle_midi_message(&[0xc0, *program]);
+        synth.handle_midi_message(&[0x90, 69, 100]);
+        return render(&mut synth, 8);
+    }).collect();
+    assert!(outputs[0].iter().any(|val| *val != 0.0));
+    let max_diff = outputs[0].iter().zip(outputs[1].iter()).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+    assert!(max_diff < 1e-6, "max diff {}", max_diff);
+}
+
+fn assert_same_regions(sf2_regions: &[Region], wsbk_regions: &[Region]) {
+    assert_eq!(sf2_regions.len(), wsbk_regions.len());
+    for (a, b) in sf2_regions.iter().zip(wsbk_regions.iter()) {
+        assert_eq!((a.key_range, a.velocity_range, a.target_index), (b.key_range, b.velocity_range, b.target_index));
+        assert_eq!(a.generators, b.generators);
+        let articulators = |region: &Region| -> Vec<(u32, u32, u32, f64)> {
+            return region.articulators.iter().map(|art| (art.src, art.control, art.destination, art.scale)).collect();
+        };
+        assert_eq!(articulators(a), articulators(b));
+    }
+}
+
+// 건반 하나를 누르고 렌더링한 결과
+fn play(soundbank: Soundbank, program: u8, key: u8, velocity: u8) -> Vec<f64> {
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(soundbank);
+    synth.handle_midi_message(&[0xc0, program]);
+    synth.handle_midi_message(&[0x90, key, velocity]);
+    return render(&mut synth, 8);
+}
+
+fn max_diff(a: &[f64], b: &[f64]) -> f64 {
+    return a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+}
+
+#[test]
+fn sf2_bank_matches_wsbk_conversion() {
+    let mut bank = common::basic_bank();
+    bank.instruments.push(("Tuned", vec![
+        // global zone: 다른 zone의 기본값
+        zone(&[(ATTACK_VOL_ENV, -7200), (RELEASE_VOL_ENV, -2400), (COARSE_TUNE, -12)]),
+        zone(&[(KEY_RANGE, common::range(0, 63)), (SAMPLE_MODES, 1), (SAMPLE_ID, 0)]),
+        // zone에 있는 값이 global zone보다 우선함
+        zone(&[(KEY_RANGE, common::range(64, 127)), (COARSE_TUNE, 0), (SAMPLE_MODES, 1), (SAMPLE_ID, 0)])
+    ]));
+    // preset generator는 instrument generator에 더함(preset의 global zone도 마찬가지)
+    bank.presets.push(("Tuned", 2, 0, vec![zone(&[(COARSE_TUNE, 12)]), zone(&[(INSTRUMENT, 2)])]));
+    let sf2 = read(&bank);
+
+    let sf2_bank = SF2Bank::new(&sf2);
+    let wsbk = sf2.to_wsbk();
+    assert_eq!(sf2_bank.instruments.len(), wsbk.instruments.len());
+    for (a, b) in sf2_bank.instruments.iter().zip(wsbk.instruments.iter()) {
+        assert_eq!(a.name, b.name);
+        assert_same_regions(&a.regions, &b.regions);
+    }
+    assert_eq!(sf2_bank.presets.len(), wsbk.presets.len());
+    for (a, b) in sf2_bank.presets.iter().zip(wsbk.presets.iter()) {
+        assert_eq!((&a.name, a.program_no, a.bank_msb, a.bank_lsb), (&b.name, b.program_no, b.bank_msb, b.bank_lsb));
+        assert_same_regions(&a.regions, &b.regions);
+    }
+    for (a, b) in sf2_bank.samples.iter().zip(wsbk.samples.iter()) {
+        assert_eq!((a.loop_start, a.loop_end, a.base_key, a.sample_rate), (b.loop_start, b.loop_end, b.base_key, b.sample_rate));
+    }
+
+    // 두 사운드뱅크로 재생한 소리가 같아야 함
+    for (program, key, velocity) in [(0, 60, 127), (0, 60, 40), (1, 69, 100), (2, 60, 127), (2, 70, 127)] {
+        let direct = play(SF2Bank::new(&sf2).into(), program, key, velocity);
+        let converted = play(sf2.to_wsbk().into(), program, key, velocity);
+        assert!(direct.iter().any(|val| *val != 0.0));
+        assert!(direct == converted, "program {} key {} velocity {}", program, key, velocity);
+    }
+
+    // global zone -12 + preset +12 => 원래 높이, zone 0 + preset +12 => 한 옥타브 위
+    let plain = |key| play(SF2Bank::new(&sf2).into(), 0, key, 127);
+    let tuned = |key| play(SF2Bank::new(&sf2).into(), 2, key, 127);
+    assert!(max_diff(&tuned(60), &plain(60)) < 1e-6);
+    assert!(max_diff(&tuned(70), &plain(82)) < 1e-6);
+    assert!(max_diff(&tuned(70), &plain(70)) > 0.01);
+
+    // 기본 modulator(velocity => 음량)
+    let peak = |output: Vec<f64>| output.iter().fold(0.0, |peak: f64, val| peak.max(val.abs()));
+    let loud = peak(play(SF2Bank::new(&sf2).into(), 0, 60, 127));
+    let quiet = peak(play(SF2Bank::new(&sf2).into(), 0, 60, 40));
+    assert!(quiet < loud * 0.5, "{} vs {}", quiet, loud);
+}
+
+// sm24 청크의 하위 8비트(smpl 청크 안의 위치로 정함)
+fn sm24_low(i: usize) -> u8 {
+    return (i * 37 % 256) as u8;
+}
+
+#[test]
+fn sm24_samples_become_24bit() {
+    let mut bank = common::basic_bank();
+    bank.sm24 = Some(sm24_low);
+    let wsbk = read(&bank).to_wsbk();
+
+    // 샘플마다 뒤에 무음 46개가 붙어 있음
+    let left_start = bank.samples[0].data.len() + 46;
+    let right_start = left_start + bank.samples[1].data.len() + 46;
+    let sample_24bit = |low: u8, high: i16| [&[low][..], &high.to_le_bytes()].concat();
+
+    let mono = &wsbk.samples[0];
+    assert_eq!(mono.bit_depth, 24);
+    assert_eq!(mono.data.len(), bank.samples[0].data.len() * 3);
+    for i in [0, 1, 1000] {
+        assert_eq!(&mono.data[(i * 3)..(i * 3 + 3)], &sample_24bit(sm24_low(i), bank.samples[0].data[i])[..]);
+    }
+
+    // stereo는 프레임마다 왼쪽, 오른쪽 순서
+    let stereo = &wsbk.samples[1];
+    assert_eq!(stereo.bit_depth, 24);
+    assert_eq!(stereo.data.len(), bank.samples[1].data.len() * 6);
+    for i in [0, 7] {
+        let frame = [
+            sample_24bit(sm24_low(left_start + i), bank.samples[1].data[i]),
+            sample_24bit(sm24_low(right_start + i), bank.samples[2].data[i])
+        ].concat();
+        assert_eq!(&stereo.data[(i * 6)..(i * 6 + 6)], &frame[..]);
+    }
+
+    // sm24가 없으면 16비트 그대로
+    assert_eq!(read(&common::basic_bank()).to_wsbk().samples[0].bit_depth, 16);
+}
+
+#[test]
+fn sf2_bank_plays_sm24_low_byte() {
+    let mut bank = common::basic_bank();
+    let plain = read(&bank);
+    bank.sm24 = Some(sm24_low);
+    let sf2 = read(&bank);
+
+    for program in [0, 1] {
+        let direct = play(SF2Bank::new(&sf2).into(), program, 69, 127);
+        let converted = play(sf2.to_wsbk().into(), program, 69, 127);
+        let plain_output = play(SF2Bank::new(&plain).into(), program, 69, 127);
+        assert!(direct.iter().any(|val| *val != 0.0));
+        assert!(direct == converted, "program {}", program);
+
+        // 하위 8비트만큼(16비트 샘플 1단계 미만) 차이가 남
+        let diff = max_diff(&direct, &plain_output);
+        assert!(diff > 0.0 && diff < 1.0 / 32768.0, "program {}: {}", program, diff);
+    }
+}
@@ -0,0 +1,143 @@
+/**
+ * sf3(압축된 샘플) 불러오기 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use common::{ zone, TestSample, MONO };
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::soundbank::sf2::bank::SF2Bank;
+use whitesynth::soundbank::sf2::gen_ids::*;
+use whitesynth::soundbank::sf2::vorbis::{ self, SAMPLE_TYPE_VORBIS };
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+// mono 48000Hz Ogg Vorbis 파일. 주기가 128샘플인 코사인파(최대 16383) 12800샘플
+const TONE_OGG: &[u8] = include_bytes!("data/tone.ogg");
+const TONE_LEN: usize = 12800;
+
+// 샘플 헤더 index번의 sample_type에 flags를 더함
+fn set_sample_type_flags(bytes: &mut [u8], index: usize, flags: u16) {
+    let shdr = bytes.windows(4).position(|id| id == b"shdr").unwrap() + 8;
+    let offset = shdr + index * 46 + 44;
+    let sample_type = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) | flags;
+    bytes[offset..(offset + 2)].copy_from_slice(&sample_type.to_le_bytes());
+}
+
+// 샘플 헤더 index번의 start, end, loop start, loop end를 바꿈
+fn set_sample_range(bytes: &mut [u8], index: usize, range: [u32; 4]) {
+    let shdr = bytes.windows(4).position(|id| id == b"shdr").unwrap() + 8;
+    let offset = shdr + index * 46 + 20;
+    let data: Vec<u8> = range.iter().flat_map(|val| val.to_le_bytes()).collect();
+    bytes[offset..(offset + 16)].copy_from_slice(&data);
+}
+
+/**
+ * 기본 사운드뱅크 뒤에 ogg 샘플(3번)과 그 샘플을 쓰는 preset(program 2)을 붙인 sf3
+ * 압축된 샘플의 start, end는 smpl 청크 안의 바이트 위치, 루프는 압축을 푼 샘플의 시작 기준 위치
+ */
+fn sf3_bank() -> Vec<u8> {
+    let mut bank = common::basic_bank();
+    let start: usize = bank.samples.iter().map(|sample| sample.data.len() + 46).sum();
+    let mut ogg = TONE_OGG.to_vec();
+    ogg.resize(ogg.len() + ogg.len() % 2, 0);
+    bank.samples.push(TestSample {
+        name: "Tone",
+        data: ogg.chunks_exact(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect(),
+        loop_start: 0,
+        loop_end: 0,
+        root_key: 60,
+        linked_sample_index: 0,
+        sample_type: MONO
+    });
+    bank.instruments.push(("Tone", vec![zone(&[(SAMPLE_MODES, 1), (SAMPLE_ID, 3)])]));
+    bank.presets.push(("Tone", 2, 0, vec![zone(&[(INSTRUMENT, 2)])]));
+
+    let mut bytes = bank.build();
+    let byte_start = start as u32 * 2;
+    set_sample_range(&mut bytes, 3, [byte_start, byte_start + TONE_OGG.len() as u32, 128, 12800]);
+    set_sample_type_flags(&mut bytes, 3, SAMPLE_TYPE_VORBIS);
+    return bytes;
+}
+
+#[test]
+fn ogg_samples_are_decoded() {
+    let decoded = vorbis::decode(TONE_OGG).unwrap();
+    assert_eq!(decoded.len(), TONE_LEN);
+    assert_eq!(&decoded[0..4], &[11934, 12595, 13205, 13762]);
+    assert_eq!(decoded[13], 16383);
+    assert!((0..(TONE_LEN - 128)).all(|i| decoded[i] == decoded[i + 128]));
+
+    let sf2 = SF2::new(&mut Cursor::new(sf3_bank())).unwrap();
+    // 압축을 푼 샘플은 압축되지 않은 샘플(사인파, 왼쪽, 오른쪽) 뒤에 놓임
+    let start = 4800 + 2400 * 2 + 46 * 3;
+    let header = &sf2.sample_headers[3];
+    assert_eq!((header.smpl_start, header.smpl_end), (start, start + TONE_LEN as u32));
+    assert_eq!((header.loop_start, header.loop_end), (start + 128, start + 12800));
+    assert_eq!(header.sample_type, MONO);
+
+    let sample_data = SF2Bank::new(&sf2).sample_data().clone();
+    assert_eq!(&sample_data[(start as usize)..(start as usize + TONE_LEN)], &decoded[..]);
+
+    // wsbk는 왼쪽/오른쪽 샘플을 stereo 샘플 하나로 합치므로 2번
+    let wsbk = sf2.to_wsbk();
+    let sample = &wsbk.samples[2];
+    assert_eq!(sample.name, "Tone");
+    assert_eq!((sample.loop_start, sample.loop_end, sample.base_key), (128, 12800, 60));
+    assert_eq!(sample.data.len(), TONE_LEN * 2);
+    assert_eq!(&sample.data[0..2], &decoded[0].to_le_bytes());
+
+    // 루프를 돌면서 계속 소리가 남
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(&sf2);
+    synth.handle_midi_message(&[0xc0, 2]);
+    synth.handle_midi_message(&[0x90, 60, 100]);
+    let mut left = vec![0.0; 4800];
+    let mut right = vec![0.0; 4800];
+    for _ in 0..5 {
+        synth.render(&mut left, &mut right);
+    }
+    assert!(left.iter().any(|val| val.abs() > 0.01));
+}
+
+#[test]
+fn compressed_data_is_not_kept_in_sample_data() {
+    // 첫 샘플을 압축된 샘플로 표시함(ogg 데이터가 아니라서 압축 풀기는 실패함)
+    let bank = common::basic_bank();
+    let mut bytes = bank.build();
+    set_sample_type_flags(&mut bytes, 0, SAMPLE_TYPE_VORBIS);
+    let sf2 = SF2::new(&mut Cursor::new(bytes.clone())).unwrap();
+
+    // 압축되지 않은 샘플(왼쪽, 오른쪽)만 새 위치로 옮겨짐
+    let headers = &sf2.sample_headers;
+    assert_eq!((headers[0].smpl_start, headers[0].smpl_end), (0, 0));
+    assert_eq!((headers[1].smpl_start, headers[1].smpl_end), (0, 2400));
+    assert_eq!((headers[2].smpl_start, headers[2].smpl_end), (2446, 4846));
+    assert!(headers.iter().all(|header| header.sample_type & SAMPLE_TYPE_VORBIS == 0));
+
+    let sample_data = SF2Bank::new(&sf2).sample_data().clone();
+    assert_eq!(sample_data.len(), 2 * (2400 + 46));
+    assert_eq!(&sample_data[0..2400], &bank.samples[1].data[..]);
+    assert_eq!(&sample_data[2446..4846], &bank.samples[2].data[..]);
+
+    // 다시 저장해도 원래 파일보다 커지지 않고, 그 파일을 다시 저장하면 그대로임
+    let mut written = Cursor::new(vec![]);
+    sf2.write(&mut written).unwrap();
+    assert!(written.get_ref().len() < bytes.len());
+    let mut rewritten = Cursor::new(vec![]);
+    SF2::new(&mut Cursor::new(written.get_ref().clone())).unwrap().write(&mut rewritten).unwrap();
+    assert_eq!(rewritten.get_ref(), written.get_ref());
+}
+
+#[test]
+fn loop_points_move_with_uncompressed_samples() {
+    let mut bank = common::basic_bank();
+    bank.samples[2].loop_start = 100;
+    bank.samples[2].loop_end = 2000;
+    let mut bytes = bank.build();
+    set_sample_type_flags(&mut bytes, 0, SAMPLE_TYPE_VORBIS);
+    let sf2 = SF2::new(&mut Cursor::new(bytes)).unwrap();
+    let right = &sf2.sample_headers[2];
+    assert_eq!((right.loop_start, right.loop_end), (right.smpl_start + 100, right.smpl_start + 2000));
+}
@@ -0,0 +1,65 @@
+/**
+ * 필요할 때 불러오는 사운드뱅크 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use std::thread::sleep;
+use std::time::Duration;
+use whitesynth::soundbank::read_soundbank_streamed;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::soundbank::stream::{ SampleLoader, StreamOptions };
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+fn render(synth: &mut Synth, len: usize) -> Vec<f64> {
+    let mut left = vec![0.0; len];
+    let mut right = vec![0.0; len];
+    synth.render(&mut left, &mut right);
+    left.extend(right);
+    return left;
+}
+
+#[test]
+fn streamed_sample_plays_same_as_in_memory() {
+    let data = common::basic_bank().build();
+    let path = std::env::temp_dir().join(format!("whitesynth-streaming-{}.sf2", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+
+    // 사인파 샘플(4800프레임) 중에서 앞부분 1024프레임만 미리 읽음
+    let mut options = StreamOptions::new();
+    options.set_preload_frames(1024);
+    let loader = SampleLoader::open(&path, &options).unwrap();
+    let mut streamed = Synth::new(SynthCreateSettings::new());
+    streamed.add_soundbank(read_soundbank_streamed(&loader).unwrap());
+    assert_eq!(loader.loaded_streams(), 0);
+
+    let mut in_memory = Synth::new(SynthCreateSettings::new());
+    in_memory.add_soundbank(&SF2::new(&mut Cursor::new(data)).unwrap());
+
+    let mut outputs = vec![];
+    for synth in [&mut in_memory, &mut streamed] {
+        synth.handle_midi_message(&[0x90, 69, 100]);
+        outputs.push(render(synth, 64));
+    }
+    // note on으로 불러오기를 요청함. 앞부분을 다 재생하기 전에 불러오면 빠지는 부분이 없음
+    for _ in 0..500 {
+        if loader.loaded_streams() > 0 {
+            break;
+        }
+        sleep(Duration::from_millis(2));
+    }
+    assert_eq!(loader.loaded_streams(), 1);
+    for _ in 0..40 {
+        outputs[0].extend(render(&mut in_memory, 256));
+        outputs[1].extend(render(&mut streamed, 256));
+    }
+    assert!(outputs[0].iter().any(|val| *val != 0.0));
+    assert!(outputs[0] == outputs[1]);
+    assert_eq!(loader.misses(), 0);
+
+    drop(streamed);
+    drop(loader);
+    std::fs::remove_file(&path).unwrap();
+}
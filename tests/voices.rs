@@ -0,0 +1,176 @@
+/**
+ * 최대 동시 발음 수를 넘었을 때 voice를 없애는 순서와 최소 note 길이 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+fn new_synth(polyphony: usize) -> Synth {
+    let mut settings = SynthCreateSettings::new();
+    settings.set_polyphony(polyphony);
+    return new_synth_with(settings);
+}
+
+fn new_synth_with(settings: SynthCreateSettings) -> Synth {
+    let mut synth = Synth::new(settings);
+    synth.add_soundbank(&SF2::new(&mut Cursor::new(common::basic_bank().build())).unwrap());
+    return synth;
+}
+
+// 64샘플씩 frames만큼 렌더링함
+fn render(synth: &mut Synth, frames: usize) {
+    let mut left = vec![0.0; 64];
+    let mut right = vec![0.0; 64];
+    for _ in 0..(frames / 64) {
+        synth.render(&mut left, &mut right);
+    }
+}
+
+// frames만큼 렌더링하고 가장 큰 샘플 값을 반환함
+fn render_peak(synth: &mut Synth, frames: usize) -> f64 {
+    let mut left = vec![0.0; frames];
+    let mut right = vec![0.0; frames];
+    synth.render(&mut left, &mut right);
+    return left.iter().chain(right.iter()).fold(0.0, |peak, val| val.abs().max(peak));
+}
+
+fn synth_with_min_note_length(ms: i32) -> Synth {
+    let mut settings = SynthCreateSettings::new();
+    settings.set_min_note_length(ms);
+    return new_synth_with(settings);
+}
+
+#[test]
+fn oldest_voice_is_stolen_with_kill_fade() {
+    let mut synth = new_synth(4);
+    synth.handle_midi_message(&[0x92, 60, 100]);
+    render(&mut synth, 4800);
+    for note in 61..64 {
+        synth.handle_midi_message(&[0x90, note, 100]);
+    }
+    render(&mut synth, 4800);
+    assert_eq!(synth.voice_stats().steal_count, 0);
+
+    // 가장 오래된 채널 3의 voice를 없앰
+    synth.handle_midi_message(&[0x91, 64, 100]);
+    let stats = synth.voice_stats();
+    assert_eq!(stats.steal_count, 1);
+    assert_eq!(stats.steal_count_per_channel[2], 1);
+    assert_eq!(stats.peak_voices, 4);
+
+    // 없애는 voice는 5ms(240샘플) 동안 페이드 아웃 한 다음 없어짐
+    // 내부에서는 128샘플 블록 단위로 렌더링함
+    render(&mut synth, 64);
+    assert_eq!(synth.active_voices(), 5);
+    render(&mut synth, 192);
+    assert_eq!(synth.active_voices(), 4);
+
+    // 페이드 아웃 중인 voice는 동시 발음 수에 넣지 않으므로 한 번만 없앰
+    synth.handle_midi_message(&[0x91, 65, 100]);
+    synth.handle_midi_message(&[0x91, 66, 100]);
+    let stats = synth.voice_stats();
+    assert_eq!(stats.steal_count, 3);
+    assert_eq!(stats.steal_count_per_channel[0], 2);
+    assert_eq!(synth.active_voices(), 6);
+}
+
+#[test]
+fn released_voices_are_stolen_before_sustained_and_playing() {
+    let mut synth = new_synth(3);
+    // 채널 1은 건반을 누르고 있고, 채널 2는 서스테인 페달로 유지되고, 채널 3은 release 중
+    synth.handle_midi_message(&[0xb1, 64, 127]);
+    for channel in 0..3 {
+        synth.handle_midi_message(&[0x90 | channel, 60, 100]);
+    }
+    render(&mut synth, 960);
+    synth.handle_midi_message(&[0x81, 60, 0]);
+    synth.handle_midi_message(&[0x82, 60, 0]);
+    render(&mut synth, 64);
+
+    synth.handle_midi_message(&[0x93, 60, 100]);
+    assert_eq!(synth.voice_stats().steal_count_per_channel[2], 1);
+    synth.handle_midi_message(&[0x93, 62, 100]);
+    assert_eq!(synth.voice_stats().steal_count_per_channel[1], 1);
+    synth.handle_midi_message(&[0x93, 64, 100]);
+    assert_eq!(synth.voice_stats().steal_count_per_channel[0], 1);
+}
+
+#[test]
+fn drum_voices_are_kept_over_melodic_voices() {
+    let mut synth = new_synth(3);
+    // 같은 때 시작한 voice 중에서는 percussion 점수 때문에 드럼 voice가 남음
+    synth.handle_midi_message(&[0x90, 60, 100]);
+    synth.handle_midi_message(&[0x99, 60, 100]);
+    synth.handle_midi_message(&[0x91, 60, 100]);
+    render(&mut synth, 960);
+    synth.handle_midi_message(&[0x92, 60, 100]);
+    synth.handle_midi_message(&[0x92, 62, 100]);
+
+    let stats = synth.voice_stats();
+    assert_eq!(stats.steal_count, 2);
+    assert_eq!(stats.steal_count_per_channel[9], 0);
+    assert_eq!((stats.steal_count_per_channel[0], stats.steal_count_per_channel[1]), (1, 1));
+}
+
+#[test]
+fn note_off_is_deferred_until_min_note_length() {
+    // 48000Hz에서 100ms = 4800샘플
+    let mut short = synth_with_min_note_length(1);
+    let mut long = synth_with_min_note_length(100);
+    for synth in [&mut short, &mut long] {
+        synth.handle_midi_message(&[0x90, 69, 100]);
+        synth.handle_midi_message(&[0x80, 69, 0]);
+        render(synth, 3840);
+    }
+    // 80ms 지점: 최소 길이가 긴 쪽은 아직 release하지 않음
+    let short_peak = render_peak(&mut short, 256);
+    let long_peak = render_peak(&mut long, 256);
+    assert!(long_peak > short_peak * 4.0, "{} vs {}", long_peak, short_peak);
+
+    // 최소 길이가 지나면 알아서 release함(release 시간 250ms)
+    render(&mut long, 960);
+    assert!(render_peak(&mut long, 256) < long_peak);
+    render(&mut long, 24000);
+    assert_eq!(long.active_voices(), 0);
+}
+
+#[test]
+fn drum_hit_in_same_tick_sounds() {
+    let mut synth = synth_with_min_note_length(50);
+    synth.handle_midi_message(&[0x99, 36, 100]);
+    synth.handle_midi_message(&[0x89, 36, 0]);
+    assert_eq!(synth.active_voices(), 1);
+    render(&mut synth, 1920);
+    assert!(render_peak(&mut synth, 256) > 0.01);
+}
+
+#[test]
+fn pedal_keeps_deferred_note() {
+    let mut synth = synth_with_min_note_length(50);
+    synth.handle_midi_message(&[0x90, 69, 100]);
+    synth.handle_midi_message(&[0x80, 69, 0]);
+    // 최소 길이가 지나기 전에 서스테인 페달을 밟으면 페달을 뗄 때까지 유지됨
+    synth.handle_midi_message(&[0xb0, 64, 127]);
+    render(&mut synth, 48000);
+    assert_eq!(synth.active_voices(), 1);
+    let sustained_peak = render_peak(&mut synth, 256);
+    assert!(sustained_peak > 0.01);
+
+    synth.handle_midi_message(&[0xb0, 64, 0]);
+    render(&mut synth, 24000);
+    assert_eq!(synth.active_voices(), 0);
+
+    // 소스테누토 페달도 마찬가지
+    synth.handle_midi_message(&[0x90, 69, 100]);
+    synth.handle_midi_message(&[0xb0, 66, 127]);
+    synth.handle_midi_message(&[0x80, 69, 0]);
+    render(&mut synth, 48000);
+    assert_eq!(synth.active_voices(), 1);
+    synth.handle_midi_message(&[0xb0, 66, 0]);
+    render(&mut synth, 24000);
+    assert_eq!(synth.active_voices(), 0);
+}
@@ -0,0 +1,44 @@
+/**
+ * wav 파일 출력의 길이 제한 확인
+ */
+
+use std::time::Duration;
+use whitesynth::audio::{ AudioConfig, AudioSink, WavSink };
+use whitesynth::audio::wav::WavFormat;
+
+#[test]
+fn stops_after_max_frames() {
+    let path = std::env::temp_dir().join(format!("whitesynth-wav-sink-{}.wav", std::process::id()));
+    let mut config = AudioConfig::new();
+    config.set_buffer_size(256);
+
+    let mut sink = WavSink::new(&path);
+    sink.set_format(WavFormat::Int16);
+    sink.set_realtime(false);
+    sink.set_max_frames(Some(1000));
+    let mut value = 0.0;
+    sink.start(&config, Box::new(move |left, right| {
+        for i in 0..left.len() {
+            value += 0.0001;
+            left[i] = value;
+            right[i] = -value;
+        }
+    })).unwrap();
+    for _ in 0..500 {
+        if !sink.is_running() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    assert!(!sink.is_running());
+    sink.stop().unwrap();
+
+    // 마지막 버퍼는 잘라서 씀
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.duration(), 1000);
+    let samples: Vec<i16> = reader.samples::<i16>().map(|sample| sample.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(samples.len(), 2000);
+    assert_eq!(&samples[0..4], &[3, -3, 7, -7]);
+    assert_eq!(samples[1998], (0.1 * 32767.0_f64).round() as i16);
+}
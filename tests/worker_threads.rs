@@ -0,0 +1,53 @@
+/**
+ * 보조 스레드 수와 상관없이 렌더링 결과가 비트 단위로 같은지 확인
+ */
+
+mod common;
+
+use std::io::Cursor;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::synth::Synth;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+// voice를 스레드로 나누는 기준(16개)을 넘도록 여러 채널에서 화음을 누름
+fn render(max_worker_threads: usize) -> (Vec<f64>, usize) {
+    let sf2 = SF2::new(&mut Cursor::new(common::basic_bank().build())).unwrap();
+    let mut settings = SynthCreateSettings::new();
+    settings.set_max_worker_threads(max_worker_threads);
+    let mut synth = Synth::new(settings);
+    synth.add_soundbank(&sf2);
+
+    let mut output = vec![];
+    let mut peak_voices = 0;
+    let mut left = vec![0.0; 1000];
+    let mut right = vec![0.0; 1000];
+    for step in 0..40u8 {
+        let channel = step % 4;
+        if step < 32 {
+            synth.handle_midi_message(&[0xc0 | channel, channel % 2]);
+            synth.handle_midi_message(&[0xb0 | channel, 10, step * 4]);
+            synth.handle_midi_message(&[0x90 | channel, 48 + step, 60 + step]);
+            synth.handle_midi_message(&[0x99, 35 + step, 100]);
+        } else {
+            synth.handle_midi_message(&[0xb0 | channel, 123, 0]);
+        }
+        synth.render(&mut left, &mut right);
+        peak_voices = peak_voices.max(synth.active_voices());
+        output.extend_from_slice(&left);
+        output.extend_from_slice(&right);
+    }
+    return (output, peak_voices);
+}
+
+#[test]
+fn worker_threads_render_identically() {
+    let (single, peak_voices) = render(1);
+    assert!(peak_voices >= 32, "only {} voices", peak_voices);
+    assert!(single.iter().any(|val| *val != 0.0));
+    for threads in [2, 4, 8] {
+        let (parallel, _) = render(threads);
+        let first_diff = single.iter().zip(parallel.iter()).position(|(a, b)| a.to_bits() != b.to_bits());
+        assert_eq!(first_diff, None, "{} threads", threads);
+        assert_eq!(single.len(), parallel.len());
+    }
+}
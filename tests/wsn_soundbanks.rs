@@ -0,0 +1,71 @@
+/**
+ * wsn sysex로 사운드뱅크를 불러오고 빼기
+ */
+
+mod common;
+
+use std::io::Cursor;
+use std::thread::sleep;
+use std::time::Duration;
+use whitesynth::soundbank::sf2::SF2;
+use whitesynth::synth::Synth;
+use whitesynth::synth::gs::checksum;
+use whitesynth::synth::settings::SynthCreateSettings;
+
+fn wsn_dt1(addr: [u8; 3], data: &[u8]) -> Vec<u8> {
+    let body = [&addr[..], data].concat();
+    let mut msg = vec![0xf0, 0x7d, 0x10, 0x57, 0x12];
+    msg.extend_from_slice(&body);
+    msg.push(checksum(&body));
+    msg.push(0xf7);
+    return msg;
+}
+
+// program을 누르고 소리가 나는 voice 수
+fn voices_for_program(synth: &mut Synth, program: u8) -> usize {
+    let mut left = vec![0.0; 64];
+    let mut right = vec![0.0; 64];
+    synth.handle_midi_message(&[0xc0, program]);
+    synth.handle_midi_message(&[0x90, 69, 100]);
+    synth.render(&mut left, &mut right);
+    let voices = synth.active_voices();
+    // kill fade가 끝날 때까지 렌더링함
+    synth.handle_midi_message(&[0xb0, 120, 0]);
+    for _ in 0..8 {
+        synth.render(&mut left, &mut right);
+    }
+    return voices;
+}
+
+#[test]
+fn clear_removes_only_wsn_soundbanks() {
+    let sf2 = SF2::new(&mut Cursor::new(common::basic_bank().build())).unwrap();
+    let mut synth = Synth::new(SynthCreateSettings::new());
+    synth.add_soundbank(&sf2);
+
+    // 1번 사운드뱅크는 program 5에 preset이 있음
+    let mut loaded = sf2.to_wsbk();
+    loaded.presets.truncate(1);
+    loaded.presets[0].program_no = 5;
+    let mut loaded = Some(loaded);
+    synth.set_soundbank_loader(move |index| if index == 1 { loaded.take() } else { None });
+
+    synth.handle_sysex(&wsn_dt1([0x00, 0x03, 0x00], &[0x00, 0x01]));
+    assert_eq!(synth.requested_soundbanks(), &[1]);
+    let mut installed = false;
+    for _ in 0..500 {
+        if voices_for_program(&mut synth, 5) > 0 {
+            installed = true;
+            break;
+        }
+        sleep(Duration::from_millis(2));
+    }
+    assert!(installed);
+    assert!(voices_for_program(&mut synth, 0) > 0);
+
+    // add_soundbank로 추가한 사운드뱅크는 그대로 남음
+    synth.handle_sysex(&wsn_dt1([0x00, 0x03, 0x7f], &[0x00]));
+    assert!(synth.requested_soundbanks().is_empty());
+    assert_eq!(voices_for_program(&mut synth, 5), 0);
+    assert!(voices_for_program(&mut synth, 0) > 0);
+}